@@ -9,14 +9,18 @@ pub const OP_ADDQ   : u32 = 0b0101_0000_0000_0000;
 pub const OP_ADDX   : u32 = 0b1101_0001_0000_0000;
 pub const OP_AND    : u32 = 0b1100_0000_0000_0000;
 pub const OP_ANDI   : u32 = 0b0000_0010_0000_0000;
+pub const OP_BITFIELD: u32 = 0b1110_1000_1100_0000; // 020+
 pub const OP_BITOPS : u32 = 0b0000_0000_0000_0000;
 pub const OP_BRANCH : u32 = 0b0110_0000_0000_0000;
+pub const OP_CAS    : u32 = 0b0000_1000_1100_0000; // 020+
 pub const OP_CHK    : u32 = 0b0100_0000_0000_0000;
+pub const OP_CHK2   : u32 = 0b0000_0000_1100_0000; // 020+, also CMP2
 pub const OP_CLR    : u32 = 0b0100_0010_0000_0000;
 pub const OP_CMP    : u32 = 0b1011_0000_0000_0000;
 pub const OP_CMPI   : u32 = 0b0000_1100_0000_0000;
 pub const OP_CMPM   : u32 = 0b1011_0001_0000_0000;
 pub const OP_DBCC   : u32 = 0b0101_0000_1100_1000;
+pub const OP_DIVL   : u32 = 0b0100_1100_0100_0000; // 020+
 pub const OP_DIVS   : u32 = 0b1000_0001_1100_0000;
 pub const OP_DIVU   : u32 = 0b1000_0000_1100_0000;
 pub const OP_EOR    : u32 = 0b1011_0000_0000_0000;
@@ -27,11 +31,15 @@ pub const OP_JMP    : u32 = 0b0100_1110_1100_0000;
 pub const OP_JSR    : u32 = 0b0100_1110_1000_0000;
 pub const OP_LEA    : u32 = 0b0100_0001_1100_0000;
 pub const OP_LINK   : u32 = 0b0100_1110_0101_0000;
+pub const OP_LINK_L : u32 = 0b0100_1000_0000_1000; // 020+
 pub const OP_MOVE   : u32 = 0b0000_0000_0000_0000;
 pub const OP_MOVE2  : u32 = 0b0100_0000_0000_0000;
+pub const OP_MOVEC  : u32 = 0b0100_1110_0111_1010; // 010+
 pub const OP_MOVEM  : u32 = 0b0100_1000_1000_0000;
 pub const OP_MOVEP  : u32 = 0b0000_0000_0000_1000;
 pub const OP_MOVEQ  : u32 = 0b0111_0000_0000_0000;
+pub const OP_MOVES  : u32 = 0b0000_1110_0000_0000; // 010+
+pub const OP_MULL   : u32 = 0b0100_1100_0000_0000; // 020+
 pub const OP_MULS   : u32 = 0b1100_0001_1100_0000;
 pub const OP_MULU   : u32 = 0b1100_0000_1100_0000;
 pub const OP_NBCD   : u32 = 0b0100_1000_0000_0000;
@@ -40,7 +48,9 @@ pub const OP_NEGX   : u32 = 0b0100_0000_0000_0000;
 pub const OP_NOT    : u32 = 0b0100_0110_0000_0000;
 pub const OP_OR     : u32 = 0b1000_0000_0000_0000;
 pub const OP_ORI    : u32 = 0b0000_0000_0000_0000;
+pub const OP_PACK   : u32 = 0b1000_0001_0100_0000; // 020+
 pub const OP_PEA    : u32 = 0b0100_1000_0100_0000;
+pub const OP_RTD    : u32 = 0b0100_1110_0111_0100; // 010+
 pub const OP_RTE    : u32 = 0b0100_1110_0111_0011;
 pub const OP_RTR    : u32 = 0b0100_1110_0111_0111;
 pub const OP_RTS    : u32 = 0b0100_1110_0111_0101;
@@ -53,8 +63,11 @@ pub const OP_SUBQ   : u32 = 0b0101_0001_0000_0000;
 pub const OP_SUBX   : u32 = 0b1001_0001_0000_0000;
 pub const OP_SWAP   : u32 = 0b0100_1000_0000_0000;
 pub const OP_TAS    : u32 = 0b0100_1010_1100_0000;
+pub const OP_TBL    : u32 = 0b1111_1000_0000_0000; // CPU32, also TBLU, TBLSN and TBLUN
+pub const OP_TRAP_CC: u32 = 0b0101_0000_1111_1000; // 020+
 pub const OP_TST    : u32 = 0b0100_1010_0000_0000;
 pub const OP_UNLK   : u32 = 0b0100_1110_0101_1000;
+pub const OP_UNPK   : u32 = 0b1000_0001_1000_0000; // 020+
 
 pub const BYTE_SIZED: u32 = 0x00;
 #[allow(dead_code)]
@@ -126,7 +139,7 @@ pub const ROTX_MEM_SHIFT  : u32 = 0xC0 | (ROTX_REG_SHIFT << 6);
 pub const ROTA_MEM_SHIFT  : u32 = 0xC0 | (ROTA_REG_SHIFT << 6);
 
 pub const MOVE_FROM_SR : u32 = 0x0c0;
-// pub const MOVE_FROM_CCR : u32 = 0x2c0; // Only 010+
+pub const MOVE_FROM_CCR: u32 = 0x2c0; // Only 010+
 pub const MOVE_TO_CCR  : u32 = 0x4c0;
 pub const MOVE_TO_SR   : u32 = 0x6c0;
 
@@ -183,6 +196,10 @@ pub const FROM_AN: u32 = 0x8;
 pub const MOVEP_MEMORY_TO_REGISTER: u32 = 0x100;
 pub const MOVEP_REGISTER_TO_MEMORY: u32 = 0x180;
 
+// MOVEC constants
+pub const CONTROL_TO_REGISTER: u32 = 0x0;
+pub const REGISTER_TO_CONTROL: u32 = 0x1;
+
 // EXG constants
 pub const EXG_DATA_DATA: u32 = 0x40; // Exchange two data registers
 pub const EXG_ADDR_ADDR: u32 = 0x48; // Exchange two address registers
@@ -192,8 +209,34 @@ pub const EXG_DATA_ADDR: u32 = 0x88; // Exchange a data register and an address
 // DEST_AX_LONG, perhaps there's a better common name somewhere)
 pub const BYTE_TO_WORD: u32 = 0x080;
 pub const WORD_TO_LONG: u32 = 0x0C0;
-// const BYTE_TO_LONG: u32 = 0x1C0; // 020+
+pub const BYTE_TO_LONG: u32 = 0x1C0; // 020+
 
 // CHK constants
 pub const WORD_OP: u32 = 0x180;
-// const LONG_OP: u32 = 0x100;  only implemented by MC68020+
+pub const LONG_OP: u32 = 0x100; // 020+
+
+// Bit field constants (020+)
+pub const BF_TST : u32 = 0x000;
+pub const BF_EXTU: u32 = 0x100;
+pub const BF_CHG : u32 = 0x200;
+pub const BF_EXTS: u32 = 0x300;
+pub const BF_CLR : u32 = 0x400;
+pub const BF_FFO : u32 = 0x500;
+pub const BF_SET : u32 = 0x600;
+pub const BF_INS : u32 = 0x700;
+
+// CAS and CAS2 constants (020+), CAS2 uses the immediate operand mode
+pub const CAS_BYTE: u32 = 0x200;
+pub const CAS_WORD: u32 = 0x400;
+pub const CAS_LONG: u32 = 0x600;
+pub const CAS2    : u32 = 0x03c;
+
+// CHK2 and CMP2 constants (020+), bit 11 of the extension word selects CHK2
+pub const CHK2_BYTE: u32 = 0x000;
+pub const CHK2_WORD: u32 = 0x200;
+pub const CHK2_LONG: u32 = 0x400;
+
+// TRAPcc constants (020+), the size of the operand following the opcode
+pub const TRAPCC_WORD: u32 = 0x2;
+pub const TRAPCC_LONG: u32 = 0x3;
+pub const TRAPCC_NONE: u32 = 0x4;
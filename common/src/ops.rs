@@ -5,3 +5,5 @@ pub const OP_RESET  : u32 = 0b0100_1110_0111_0000;
 pub const OP_STOP   : u32 = 0b0100_1110_0111_0010;
 pub const OP_TRAP   : u32 = 0b0100_1110_0100_0000;
 pub const OP_TRAPV  : u32 = 0b0100_1110_0111_0110;
+pub const OP_BGND   : u32 = 0b0100_1010_1111_1010; // CPU32
+pub const OP_LPSTOP : u32 = 0b1111_1000_0000_0000; // CPU32, followed by $01c0
@@ -6,7 +6,7 @@ use test::Bencher;
 
 use r68k_emu::cpu::{ConfiguredCore, Core, ProcessingState, Result, Cycles, Exception, Callbacks};
 use r68k_emu::cpu::ops::opcodes;
-use r68k_emu::ram::PagedMem;
+use r68k_emu::ram::{AddressBus, FlatMem, PagedMem};
 use r68k_emu::interrupts::AutoInterruptController;
 
 struct LogAllExceptions {
@@ -22,12 +22,77 @@ impl Callbacks for LogAllExceptions {
 
 #[bench]
 fn bench_100k_cycles(b: &mut Bencher) {
-    let mut cpu = ConfiguredCore::new_with(0, AutoInterruptController::new(), PagedMem::new(0xAAAAAAAA));
-    let regregops = [opcodes::OP_ADD_16_ER_DN, opcodes::OP_SUB_16_ER_DN, opcodes::OP_AND_16_ER_DN, opcodes::OP_OR_16_ER_DN];
+    bench_100k_cycles_with(b, PagedMem::new(0xAAAAAAAA), Speedup::None);
+}
+
+#[bench]
+fn bench_100k_cycles_flatmem(b: &mut Bencher) {
+    bench_100k_cycles_with(b, FlatMem::new(0xAAAAAAAA), Speedup::None);
+}
+
+#[bench]
+fn bench_100k_cycles_decode_cache(b: &mut Bencher) {
+    bench_100k_cycles_with(b, PagedMem::new(0xAAAAAAAA), Speedup::DecodeCache);
+}
+
+#[bench]
+fn bench_100k_cycles_flatmem_decode_cache(b: &mut Bencher) {
+    bench_100k_cycles_with(b, FlatMem::new(0xAAAAAAAA), Speedup::DecodeCache);
+}
+
+#[cfg(feature = "jit")]
+#[bench]
+fn bench_100k_cycles_jit(b: &mut Bencher) {
+    bench_100k_cycles_with(b, PagedMem::new(0xAAAAAAAA), Speedup::Jit);
+}
+
+#[cfg(feature = "jit")]
+#[bench]
+fn bench_100k_cycles_flatmem_jit(b: &mut Bencher) {
+    bench_100k_cycles_with(b, FlatMem::new(0xAAAAAAAA), Speedup::Jit);
+}
+
+// the same, but of instructions the JIT compiles into code of their own
+// (MOVEQ, MOVE.L Dy,Dx and TST.L Dy, also of 4 cycles each)
+#[bench]
+fn bench_100k_cycles_inlined_decode_cache(b: &mut Bencher) {
+    bench_100k_cycles_of(b, FlatMem::new(0xAAAAAAAA), Speedup::DecodeCache, &INLINED_OPS);
+}
+
+#[cfg(feature = "jit")]
+#[bench]
+fn bench_100k_cycles_inlined_jit(b: &mut Bencher) {
+    bench_100k_cycles_of(b, FlatMem::new(0xAAAAAAAA), Speedup::Jit, &INLINED_OPS);
+}
+
+const REG_REG_OPS: [u32; 4] = [opcodes::OP_ADD_16_ER_DN, opcodes::OP_SUB_16_ER_DN, opcodes::OP_AND_16_ER_DN, opcodes::OP_OR_16_ER_DN];
+const INLINED_OPS: [u32; 4] = [opcodes::OP_MOVEQ_32 | 0x0201, opcodes::OP_MOVE_32_DN_DN | 0x0201, opcodes::OP_TST_32_DN | 0x01, opcodes::OP_MOVE_32_DN_DN | 0x0001];
+
+#[allow(dead_code)]
+enum Speedup {
+    None,
+    DecodeCache,
+    Jit,
+}
+
+fn bench_100k_cycles_with<A: AddressBus>(b: &mut Bencher, mem: A, speedup: Speedup) {
+    bench_100k_cycles_of(b, mem, speedup, &REG_REG_OPS);
+}
+
+fn bench_100k_cycles_of<A: AddressBus>(b: &mut Bencher, mem: A, speedup: Speedup, ops: &[u32]) {
+    let mut cpu = ConfiguredCore::new_with(0, AutoInterruptController::new(), mem);
+    match speedup {
+        Speedup::None => (),
+        Speedup::DecodeCache => cpu.enable_decode_cache(),
+        #[cfg(feature = "jit")]
+        Speedup::Jit => cpu.enable_jit(),
+        #[cfg(not(feature = "jit"))]
+        Speedup::Jit => unreachable!(),
+    }
     let pc_base = 0x1000;
     // write an instruction sequence of simple reg-to-reg operations
     for i in 0..0x10000 {
-        cpu.write_word(pc_base + i*2, regregops[(i % regregops.len() as u32) as usize]).unwrap();
+        cpu.write_word(pc_base + i*2, ops[(i % ops.len() as u32) as usize]).unwrap();
     }
     cpu.write_long(0, 0x100000).unwrap(); // SSP
     cpu.write_long(4, pc_base).unwrap(); // PC
@@ -0,0 +1,44 @@
+extern crate r68k_emu;
+
+use r68k_emu::easy68k::{self, Simulator, Termination};
+use r68k_emu::peripherals::serial::HostPort;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+// Runs a program assembled by EASy68K, with its console on this one:
+//   cargo run --release --example easy68k -- program.S68
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: easy68k <program.S68>");
+            process::exit(2);
+        },
+    };
+    let loaded = File::open(&path).and_then(|file| easy68k::load(&mut BufReader::new(file)));
+    let mut core = match loaded {
+        Ok(core) => core,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        },
+    };
+    let mut simulator = Simulator::new(HostPort::stdio());
+    match easy68k::run(&mut core, &mut simulator) {
+        Termination::Exited(_) => (),
+        Termination::Unsupported(task) => {
+            eprintln!("\nunsupported TRAP #15 task {} at {:08x}", task, core.pc - 2);
+            process::exit(1);
+        },
+        Termination::Unhandled(ex) => {
+            eprintln!("\nunhandled {}", ex);
+            process::exit(1);
+        },
+        termination => {
+            eprintln!("\n{:?} at {:08x}", termination, core.pc);
+            process::exit(1);
+        },
+    }
+}
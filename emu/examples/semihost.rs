@@ -0,0 +1,35 @@
+extern crate r68k_emu;
+
+use r68k_emu::semihosting::{self, Semihost, Termination};
+use std::env;
+use std::fs;
+use std::process;
+
+// Runs a bare-metal program (an ELF file or S-records) using semihosting,
+// and exits with the status it exits with, for running cross-compiled
+// tests in CI:
+//   cargo run --release --example semihost -- test.elf
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: semihost <program.elf|program.S68>");
+            process::exit(2);
+        },
+    };
+    let loaded = fs::read(&path).and_then(|image| semihosting::load(&image));
+    let mut core = match loaded {
+        Ok(core) => core,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        },
+    };
+    let mut semihost = Semihost::stdio();
+    match semihosting::run(&mut core, &mut semihost) {
+        Termination::Exited(status) => process::exit(status),
+        Termination::Unhandled(ex) => eprintln!("unhandled {}", ex),
+        termination => eprintln!("{:?} at {:08x}", termination, core.pc),
+    }
+    process::exit(1);
+}
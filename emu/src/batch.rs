@@ -0,0 +1,342 @@
+// Runs many independent cores, or whole machines, on a pool of threads
+// that lives as long as the Batch, so that running them timeslice after
+// timeslice doesn't start threads each time. Each thread takes the next
+// core that nobody runs yet, so cores that take longer don't hold up the
+// others. A core is Send whenever its interrupt controller and address
+// bus are (see below), and LoggingMem with a SharedOpsLogger lets the
+// host follow a core on another thread.
+use cpu::{ConfiguredCore, Cycles};
+use interrupts::InterruptController;
+use ram::AddressBus;
+use std::any::Any;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// fails to compile should a core ever hold something that isn't Send
+#[allow(dead_code)]
+fn configured_core_is_send<T: InterruptController + Send, A: AddressBus + Send>(core: ConfiguredCore<T, A>) -> impl Send {
+    core
+}
+
+// The work loop of a call of map, for a worker to run until there are no
+// items left. Its lifetime is erased, which is sound as map doesn't
+// return, nor unwind, before every worker it sent the loop to is done
+// with it (see Outstanding).
+struct Job {
+    work: &'static (dyn Fn() + Sync),
+    done: Sender<thread::Result<()>>,
+}
+
+#[derive(Debug)]
+struct Pool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    fn new(threads: usize) -> Pool {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..threads).map(|_| {
+            let queue = queue.clone();
+            thread::spawn(move || Pool::work(&queue))
+        }).collect();
+        Pool { jobs: Some(jobs), workers }
+    }
+
+    fn work(queue: &Mutex<Receiver<Job>>) {
+        loop {
+            let job = match queue.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return, // the Batch is gone
+            };
+            let _ = job.done.send(panic::catch_unwind(AssertUnwindSafe(job.work)));
+        }
+    }
+
+    // Runs work on this many workers at once, and returns once they're
+    // all done, passing on the first panic
+    fn run(&self, workers: usize, work: &(dyn Fn() + Sync)) {
+        let work: &'static (dyn Fn() + Sync) = unsafe { mem::transmute(work) };
+        let (sender, finished) = mpsc::channel();
+        let mut outstanding = Outstanding { finished, sent: 0, panicked: None };
+        // declared after outstanding, so that unwinding drops it first
+        let done = sender;
+        for _ in 0..workers {
+            let job = Job { work, done: done.clone() };
+            self.jobs.as_ref().unwrap().send(job).expect("batch workers are gone");
+            outstanding.sent += 1;
+        }
+        drop(done);
+        outstanding.wait();
+        if let Some(payload) = outstanding.panicked.take() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+// The jobs run has sent, which it waits for even when it unwinds before
+// sending them all, as they borrow its work
+struct Outstanding {
+    finished: Receiver<thread::Result<()>>,
+    sent: usize,
+    panicked: Option<Box<dyn Any + Send>>, // the first panic of a job
+}
+
+impl Outstanding {
+    fn wait(&mut self) {
+        while self.sent > 0 {
+            match self.finished.recv() {
+                Ok(result) => {
+                    if let Err(payload) = result {
+                        self.panicked.get_or_insert(payload);
+                    }
+                    self.sent -= 1;
+                },
+                // every job has been dropped, so none holds the work
+                Err(_) => self.sent = 0,
+            }
+        }
+    }
+}
+
+impl Drop for Outstanding {
+    fn drop(&mut self) {
+        self.wait();
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+// Clones share the pool. The functions passed to map and for_each must
+// not use the same Batch themselves, as they would wait for workers that
+// are busy running them.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    pool: Arc<Pool>,
+}
+
+impl Default for Batch {
+    fn default() -> Batch {
+        Batch::new()
+    }
+}
+
+impl Batch {
+    // as many threads as the host runs at once
+    pub fn new() -> Batch {
+        Batch::with_threads(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn with_threads(threads: usize) -> Batch {
+        assert!(threads > 0);
+        Batch { pool: Arc::new(Pool::new(threads)) }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.workers.len()
+    }
+
+    // Calls f with each item, and returns what it returned, in order
+    pub fn map<C, R, F>(&self, items: &mut [C], f: F) -> Vec<R>
+        where C: Send, R: Send, F: Fn(&mut C) -> R + Sync
+    {
+        let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+        {
+            let workers = self.threads().min(items.len());
+            let work = Mutex::new(items.iter_mut().zip(results.iter_mut()));
+            let run = || loop {
+                let next = work.lock().unwrap().next();
+                match next {
+                    Some((item, result)) => *result = Some(f(item)),
+                    None => break,
+                }
+            };
+            self.pool.run(workers, &run);
+        }
+        results.into_iter().map(|result| result.unwrap()).collect()
+    }
+
+    pub fn for_each<C, F>(&self, items: &mut [C], f: F)
+        where C: Send, F: Fn(&mut C) + Sync
+    {
+        self.map(items, f);
+    }
+
+    // Executes each core for the given number of cycles, and returns the
+    // cycles each used
+    pub fn execute<T, A>(&self, cores: &mut [ConfiguredCore<T, A>], cycles: i32) -> Vec<Cycles>
+        where T: InterruptController + Send, A: AddressBus + Send
+    {
+        self.map(cores, |core| core.execute(cycles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Batch;
+    use cpu::{ConfiguredCore, ProcessingState, TestCore};
+    use interrupts::AutoInterruptController;
+    use ram::{AddressBus, MappedBus, SUPERVISOR_DATA};
+    use ram::loggingmem::{LoggingMem, Operation, SharedOpsLogger};
+    use std::collections::HashSet;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Barrier, Mutex};
+    use std::thread;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn cores_are_send() {
+        assert_send::<TestCore>();
+        assert_send::<ConfiguredCore<AutoInterruptController, MappedBus>>();
+        assert_send::<ConfiguredCore<AutoInterruptController, LoggingMem<SharedOpsLogger>>>();
+    }
+
+    // sums 1..=D0 into D1, then stops
+    // loop: ADD.L D0,D1; SUBQ.L #1,D0; BNE.S loop; STOP #$2700
+    const SUM: &[u8] = &[0xd2, 0x80, 0x53, 0x80, 0x66, 0xfa, 0x4e, 0x72, 0x27, 0x00];
+
+    fn sum_core(n: u32) -> TestCore {
+        let mut core = TestCore::new_mem(0x1000, SUM);
+        core.dar[0] = n;
+        core
+    }
+
+    #[test]
+    fn map_keeps_the_order_of_the_items() {
+        let mut items: Vec<u32> = (0..1000).collect();
+        let doubled = Batch::with_threads(8).map(&mut items, |item| { *item += 1; *item * 2 });
+        assert_eq!((1..1001).map(|i| i * 2).collect::<Vec<u32>>(), doubled);
+        assert_eq!((1..1001).collect::<Vec<u32>>(), items);
+    }
+
+    #[test]
+    fn cores_run_on_many_threads() {
+        let mut cores: Vec<TestCore> = (1..=64).map(sum_core).collect();
+        let threads = Mutex::new(HashSet::new());
+        // the first two cores only run once both have been taken
+        let barrier = Barrier::new(2);
+        Batch::with_threads(8).for_each(&mut cores, |core| {
+            threads.lock().unwrap().insert(thread::current().id());
+            if core.dar[0] <= 2 {
+                barrier.wait();
+            }
+            core.execute(100_000);
+        });
+        for (n, core) in (1..=64).zip(&cores) {
+            assert_eq!(n * (n + 1) / 2, core.dar[1]);
+        }
+        assert!(threads.lock().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn the_threads_are_kept_between_calls() {
+        let batch = Batch::with_threads(4);
+        let threads = Mutex::new(HashSet::new());
+        let mut items = vec![0; 64];
+        for _ in 0..20 {
+            batch.for_each(&mut items, |item| {
+                threads.lock().unwrap().insert(thread::current().id());
+                *item += 1;
+            });
+        }
+        assert!(threads.lock().unwrap().len() <= 4);
+        assert!(items.iter().all(|&item| item == 20));
+    }
+
+    #[test]
+    fn panics_are_passed_on_once_every_item_is_done() {
+        let batch = Batch::with_threads(4);
+        let mut items: Vec<u32> = (0..100).collect();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            batch.for_each(&mut items, |item| if *item == 10 { panic!("item 10") } else { *item += 1000 })
+        }));
+        assert!(result.is_err());
+        assert_eq!(99, items.iter().filter(|&&item| item >= 1000).count());
+        // and the pool still works
+        assert_eq!(vec![2, 4], batch.map(&mut [1, 2], |item| *item * 2));
+    }
+
+    #[test]
+    fn batch_executes_every_core() {
+        let mut cores: Vec<TestCore> = (1..=1000).map(|n| sum_core(n % 50 + 1)).collect();
+        let used = Batch::with_threads(16).execute(&mut cores, 10_000);
+        assert_eq!(1000, used.len());
+        for (n, core) in (1..=1000).zip(&cores) {
+            let n = n % 50 + 1;
+            assert_eq!(n * (n + 1) / 2, core.dar[1]);
+        }
+    }
+
+    #[test]
+    fn shared_logs_follow_cores_on_other_threads() {
+        let loggers: Vec<SharedOpsLogger> = (0..4).map(|_| SharedOpsLogger::new()).collect();
+        let mut cores: Vec<_> = loggers.iter().enumerate().map(|(i, logger)| {
+            let mut mem = LoggingMem::new(0, logger.clone());
+            // MOVE.W D0,$2000
+            for (offset, &byte) in [0x33, 0xc0, 0x00, 0x00, 0x20, 0x00].iter().enumerate() {
+                mem.write_u8(0x1000 + offset as u32, byte);
+            }
+            let mut core = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+            core.processing_state = ProcessingState::Normal;
+            core.dar[0] = i as u32;
+            core
+        }).collect();
+        for logger in &loggers {
+            logger.clear();
+        }
+        Batch::with_threads(4).for_each(&mut cores, |core| { core.execute1(); });
+        for (i, logger) in loggers.iter().enumerate() {
+            assert_eq!(Some(&Operation::WriteWord(SUPERVISOR_DATA, 0x2000, i as u32)), logger.ops().last());
+        }
+        assert_eq!(3, cores[3].mem.read_word(SUPERVISOR_DATA, 0x2000));
+    }
+
+    #[cfg(feature = "cycles")]
+    #[test]
+    fn machines_run_on_many_threads() {
+        use machine::Machine;
+        use ram::mappedbus::SharedRam;
+
+        // core 0: MOVE.W #$1234,$10000; STOP #$2700
+        // core 1: wait: TST.W $20000; BEQ.S wait; MOVE.W $20000,D0; STOP #$2700
+        let programs: [&[u16]; 2] = [
+            &[0x33fc, 0x1234, 0x0001, 0x0000, 0x4e72, 0x2700],
+            &[0x4a79, 0x0002, 0x0000, 0x67f8, 0x3039, 0x0002, 0x0000, 0x4e72, 0x2700],
+        ];
+        let mut machines: Vec<Machine<AutoInterruptController, MappedBus>> = (0..100).map(|_| {
+            let ram = SharedRam::new(0x100);
+            let mut machine = Machine::new(50);
+            for (i, program) in programs.iter().enumerate() {
+                let mut bus = MappedBus::new();
+                bus.map_ram(0, 0x10000);
+                bus.map_shared(0x10000 * (i as u32 + 1), &ram);
+                bus.write_long(SUPERVISOR_DATA, 0, 0x8000);
+                bus.write_long(SUPERVISOR_DATA, 4, 0x1000);
+                for (offset, &word) in program.iter().enumerate() {
+                    bus.write_word(SUPERVISOR_DATA, 0x1000 + 2 * offset as u32, u32::from(word));
+                }
+                let mut core = ConfiguredCore::new_with(0, AutoInterruptController::new(), bus);
+                core.reset();
+                machine.add_core(core);
+            }
+            machine
+        }).collect();
+        Batch::with_threads(8).for_each(&mut machines, |machine| machine.run(1000));
+        for machine in &machines {
+            assert_eq!(1000, machine.clock());
+            assert_eq!(0x1234, machine.core(1).dar[0]);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+// Breakpoints, watchpoints and stepping over subroutine calls. Every core
+// has a Debugger, which its run loop consults between instructions; see
+// ConfiguredCore::run and ConfiguredCore::step_over.
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use cpu::AccessType;
+use ram::AddressSpace;
+
+// Why the run loop returned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    CyclesExhausted,
+    Stopped,            // by STOP, with no interrupt pending
+    Halted,             // by a double fault, and needs a reset
+    Breakpoint(u32),    // before executing the instruction at the breakpoint
+    Watchpoint(WatchHit), // after the instruction that made the access
+    Stepped(u32),       // step_over executed an instruction that was not a call
+    Returned(u32),      // step_over saw the called subroutine return
+    Background(u32),    // by BGND (CPU32), at the instruction after it
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchHit {
+    pub address: u32,
+    pub access_type: AccessType,
+    pub address_space: AddressSpace,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Watch {
+    Read,
+    Write,
+    Access, // read or write
+}
+
+// Watches the bus addresses in range (24-bit ones unless the core has a
+// 32-bit address bus, and logical ones when a 68030 PMMU translates them),
+// in the given address space, or in any of them if None. Instruction
+// fetches are program space reads. The range is inclusive, so that it can
+// end at the top of memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u32>,
+    pub address_space: Option<AddressSpace>,
+    pub watch: Watch,
+}
+
+impl Watchpoint {
+    // panics on an empty range, which would never be hit
+    pub fn new(range: RangeInclusive<u32>, watch: Watch) -> Watchpoint {
+        assert!(!range.is_empty(), "empty watchpoint range {:x?}", range);
+        Watchpoint { range, address_space: None, watch }
+    }
+    pub fn in_address_space(self, address_space: AddressSpace) -> Watchpoint {
+        Watchpoint { address_space: Some(address_space), ..self }
+    }
+    fn matches(&self, address: u32, size: u32, access_type: AccessType, address_space: AddressSpace) -> bool {
+        let watched = matches!((self.watch, access_type),
+            (Watch::Access, _) | (Watch::Read, AccessType::Read) | (Watch::Write, AccessType::Write));
+        watched
+            && self.address_space.is_none_or(|space| space == address_space)
+            && address <= *self.range.end() && u64::from(*self.range.start()) < u64::from(address) + u64::from(size)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Register {
+    D(usize),
+    A(usize),
+    Pc,
+    Sr,
+}
+
+// unsigned comparisons
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+// Compares (register & mask) with value, so conditions can look at parts
+// of a register, like the flags in SR
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u32,
+    pub mask: u32,
+}
+
+impl Condition {
+    pub fn new(register: Register, comparison: Comparison, value: u32) -> Condition {
+        Condition { register, comparison, value, mask: 0xffff_ffff }
+    }
+    pub fn masked(self, mask: u32) -> Condition {
+        Condition { mask, ..self }
+    }
+    pub fn holds(&self, register_value: u32) -> bool {
+        let actual = register_value & self.mask;
+        match self.comparison {
+            Comparison::Equal => actual == self.value,
+            Comparison::NotEqual => actual != self.value,
+            Comparison::Less => actual < self.value,
+            Comparison::LessOrEqual => actual <= self.value,
+            Comparison::Greater => actual > self.value,
+            Comparison::GreaterOrEqual => actual >= self.value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeMap<u32, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    // stopped at this breakpoint, so don't stop there again when resuming
+    resume_from: Option<u32>,
+    // step_over has been asked to execute an instruction
+    stepping: bool,
+    // step_over stepped into a call, returning here with this stack pointer
+    return_to: Option<(u32, u32)>,
+    hit: Option<WatchHit>,
+    // BGND enters background debug mode, rather than being illegal
+    background_mode: bool,
+    entered_background: Option<u32>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u32) {
+        self.breakpoints.insert(pc, None);
+    }
+    // only stops when the condition holds as the instruction at pc is reached
+    pub fn add_conditional_breakpoint(&mut self, pc: u32, condition: Condition) {
+        self.breakpoints.insert(pc, Some(condition));
+    }
+    pub fn remove_breakpoint(&mut self, pc: u32) -> bool {
+        self.breakpoints.remove(&pc).is_some()
+    }
+    pub fn breakpoints(&self) -> impl Iterator<Item=u32> + '_ {
+        self.breakpoints.keys().cloned()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w != watchpoint);
+        self.watchpoints.len() != len
+    }
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // removes all breakpoints and watchpoints, and abandons any step_over
+    pub fn clear(&mut self) {
+        *self = Debugger::new();
+    }
+
+    // like the BKPT/DSCLK pins of a CPU32 held for background debug mode
+    pub fn enable_background_mode(&mut self, enabled: bool) {
+        self.background_mode = enabled;
+    }
+    pub fn is_background_mode_enabled(&self) -> bool {
+        self.background_mode
+    }
+
+    // whether the run loop needs to look at us before each instruction
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty() || self.stepping || self.return_to.is_some()
+    }
+
+    pub(super) fn breakpoint_condition(&self, pc: u32) -> Option<Option<Condition>> {
+        if self.resume_from == Some(pc) {
+            None
+        } else {
+            self.breakpoints.get(&pc).cloned()
+        }
+    }
+    pub(super) fn stopped_at(&mut self, pc: u32) {
+        self.resume_from = Some(pc);
+    }
+    pub(super) fn resumed(&mut self) {
+        self.resume_from = None;
+    }
+
+    pub(super) fn start_step_over(&mut self) {
+        self.stepping = true;
+    }
+    pub(super) fn take_stepping(&mut self) -> bool {
+        ::std::mem::replace(&mut self.stepping, false)
+    }
+    pub(super) fn return_to(&self) -> Option<(u32, u32)> {
+        self.return_to
+    }
+    pub(super) fn set_return_to(&mut self, return_to: Option<(u32, u32)>) {
+        self.return_to = return_to;
+    }
+
+    // called by the core for every memory access it makes, with the
+    // address it puts on the bus
+    pub(super) fn check_access(&mut self, address: u32, size: u32, access_type: AccessType, address_space: AddressSpace) {
+        if self.watchpoints.is_empty() || self.hit.is_some() {
+            return;
+        }
+        if self.watchpoints.iter().any(|w| w.matches(address, size, access_type, address_space)) {
+            self.hit = Some(WatchHit { address, access_type, address_space });
+        }
+    }
+    pub(super) fn take_hit(&mut self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+
+    pub(super) fn enter_background_mode(&mut self, pc: u32) -> bool {
+        if self.background_mode {
+            self.entered_background = Some(pc);
+        }
+        self.background_mode
+    }
+    pub(super) fn take_background(&mut self) -> Option<u32> {
+        self.entered_background.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Condition, Debugger, Register, Watch, WatchHit, Watchpoint};
+    use cpu::AccessType;
+    use ram::{SUPERVISOR_DATA, USER_DATA};
+
+    #[test]
+    fn conditions_compare_masked_values() {
+        let zero_set = Condition::new(Register::Sr, Comparison::Equal, 0b100).masked(0b100);
+        assert!(zero_set.holds(0x2704));
+        assert!(!zero_set.holds(0x2700));
+        assert!(Condition::new(Register::D(0), Comparison::Less, 10).holds(9));
+        assert!(!Condition::new(Register::D(0), Comparison::Less, 10).holds(0xffff_fff0));
+    }
+
+    #[test]
+    fn watchpoints_match_overlapping_accesses() {
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Watchpoint::new(0x100..=0x103, Watch::Write).in_address_space(USER_DATA));
+        debugger.check_access(0x100, 4, AccessType::Read, USER_DATA);
+        debugger.check_access(0x100, 4, AccessType::Write, SUPERVISOR_DATA);
+        debugger.check_access(0x104, 2, AccessType::Write, USER_DATA);
+        assert_eq!(None, debugger.take_hit());
+        debugger.check_access(0xfe, 4, AccessType::Write, USER_DATA);
+        assert_eq!(Some(WatchHit { address: 0xfe, access_type: AccessType::Write, address_space: USER_DATA }), debugger.take_hit());
+        assert_eq!(None, debugger.take_hit());
+    }
+
+    #[test]
+    fn watchpoints_match_accesses_at_the_top_of_memory() {
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Watchpoint::new(0xffff_fff0..=0xffff_ffff, Watch::Access));
+        debugger.check_access(0xffff_fffc, 4, AccessType::Read, USER_DATA);
+        assert_eq!(Some(WatchHit { address: 0xffff_fffc, access_type: AccessType::Read, address_space: USER_DATA }), debugger.take_hit());
+        debugger.check_access(0xffff_ffff, 1, AccessType::Write, USER_DATA);
+        assert_eq!(Some(WatchHit { address: 0xffff_ffff, access_type: AccessType::Write, address_space: USER_DATA }), debugger.take_hit());
+    }
+
+    #[test]
+    #[should_panic(expected = "empty watchpoint range")]
+    fn watchpoints_must_watch_something() {
+        #[allow(clippy::reversed_empty_ranges)]
+        Watchpoint::new(0x101..=0x100, Watch::Access);
+    }
+
+    #[test]
+    fn resuming_skips_the_breakpoint_once() {
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x40);
+        assert_eq!(Some(None), debugger.breakpoint_condition(0x40));
+        debugger.stopped_at(0x40);
+        assert_eq!(None, debugger.breakpoint_condition(0x40));
+        debugger.resumed();
+        assert_eq!(Some(None), debugger.breakpoint_condition(0x40));
+        assert!(debugger.remove_breakpoint(0x40));
+        assert!(!debugger.is_active());
+    }
+}
@@ -0,0 +1,321 @@
+// An optional cache of decoded instructions, for faster interpretation.
+// An entry holds the opcode at a PC along with the extension words the
+// instruction fetched the first time it ran, so running it again reads
+// them from the entry instead of the address bus. Writes by the core
+// invalidate the entries of the pages they touch, by bumping a
+// generation count that entries are checked against.
+//
+// Memory changed behind the core's back (by the host, a device doing DMA
+// or another core sharing RAM) is not noticed, so whoever changes code
+// that way must call ConfiguredCore::invalidate_decode_cache. Program
+// reads answered by the cache don't reach the bus, and so aren't logged
+// by LoggingMem nor seen by devices.
+
+const ENTRIES: usize = 0x8000; // direct mapped by PC, covering 64KB of code
+const WORDS: usize = 11; // any 68020 instruction, full format extensions and all
+const PAGE_SHIFT: u32 = 12;
+const PAGES: usize = 0x1000; // larger addresses fold onto these
+
+#[derive(Clone, Copy)]
+struct Entry {
+    tag: u32, // the PC, with the lowest bit set in supervisor mode
+    generation: u32,
+    len: u8, // no words means no entry
+    words: [u16; WORDS],
+}
+
+const EMPTY_ENTRY: Entry = Entry { tag: 0, generation: 0, len: 0, words: [0; WORDS] };
+
+fn tag(pc: u32, supervisor: bool) -> u32 {
+    pc | supervisor as u32
+}
+
+fn pc(tag: u32) -> u32 {
+    tag & !1
+}
+
+fn page(address: u32) -> usize {
+    (address >> PAGE_SHIFT) as usize & (PAGES - 1)
+}
+
+fn index(pc: u32) -> usize {
+    (pc >> 1) as usize & (ENTRIES - 1)
+}
+
+#[cfg(feature = "jit")]
+pub(super) fn same_page(a: u32, b: u32) -> bool {
+    a >> PAGE_SHIFT == b >> PAGE_SHIFT
+}
+
+#[derive(Clone)]
+pub struct DecodeCache {
+    entries: Box<[Entry]>,
+    generations: Box<[u32]>,
+    hit: Option<usize>, // the entry of the running instruction
+    recording: Option<Entry>, // the running instruction, missed
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for DecodeCache {
+    fn default() -> DecodeCache {
+        DecodeCache::new()
+    }
+}
+
+impl DecodeCache {
+    pub fn new() -> DecodeCache {
+        DecodeCache {
+            entries: vec![EMPTY_ENTRY; ENTRIES].into_boxed_slice(),
+            generations: vec![0; PAGES].into_boxed_slice(),
+            hit: None,
+            recording: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // instructions found in, and missing from, the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn invalidate_all(&mut self) {
+        for entry in self.entries.iter_mut() {
+            entry.len = 0;
+        }
+        self.hit = None;
+        self.recording = None;
+    }
+
+    // An instruction starts at the (masked) PC, which the cache either
+    // has (returning the opcode), or will record as it is fetched
+    pub(super) fn begin(&mut self, pc: u32, supervisor: bool) -> Option<u16> {
+        let index = index(pc);
+        let entry = &self.entries[index];
+        let tag = tag(pc, supervisor);
+        let generation = self.generations[page(pc)];
+        if pc & 1 == 0 && entry.tag == tag && entry.len > 0 && entry.generation == generation {
+            self.hit = Some(index);
+            self.recording = None;
+            self.hits += 1;
+            Some(entry.words[0])
+        } else {
+            self.hit = None;
+            self.recording = Some(Entry { tag, generation, len: 0, words: [0; WORDS] });
+            self.misses += 1;
+            None
+        }
+    }
+
+    // ends the instruction, when the cache isn't to be used for it
+    pub(super) fn bypass(&mut self) {
+        self.hit = None;
+        self.recording = None;
+    }
+
+    // the word at the address, if the running instruction has it cached
+    #[inline]
+    pub(super) fn word(&self, address: u32) -> Option<u16> {
+        self.hit.and_then(|index| {
+            let entry = &self.entries[index];
+            let offset = (address.wrapping_sub(pc(entry.tag)) >> 1) as usize;
+            if offset < entry.len as usize { Some(entry.words[offset]) } else { None }
+        })
+    }
+
+    // a word the running instruction fetched from the bus
+    pub(super) fn fetched(&mut self, address: u32, word: u16) {
+        if let Some(ref mut entry) = self.recording {
+            let len = entry.len as usize;
+            if len < WORDS && address == pc(entry.tag).wrapping_add(2 * len as u32) {
+                entry.words[len] = word;
+                entry.len += 1;
+            }
+        }
+    }
+
+    // Keeps what the instruction fetched, unless it faulted or wrote to
+    // its own page meanwhile. Instructions straddling pages aren't kept.
+    pub(super) fn end(&mut self, completed: bool) {
+        if let Some(entry) = self.recording.take() {
+            let pc = pc(entry.tag);
+            let last = pc.wrapping_add(2 * u32::from(entry.len)).wrapping_sub(1);
+            if completed && entry.len > 0 && page(pc) == page(last) && entry.generation == self.generations[page(pc)] {
+                self.entries[index(pc)] = entry;
+            }
+        }
+        self.hit = None;
+    }
+
+    // the words of the instruction at the PC, should the cache have it
+    #[cfg(feature = "jit")]
+    pub(super) fn instruction(&self, pc: u32, supervisor: bool) -> Option<&[u16]> {
+        let entry = &self.entries[index(pc)];
+        if pc & 1 == 0 && entry.tag == tag(pc, supervisor) && entry.len > 0 && entry.generation == self.generation(pc) {
+            Some(&entry.words[..entry.len as usize])
+        } else {
+            None
+        }
+    }
+
+    // bumped by every write to the page of the address
+    #[cfg(feature = "jit")]
+    pub(super) fn generation(&self, address: u32) -> u32 {
+        self.generations[page(address)]
+    }
+
+    // a write by the core, to the physical address
+    pub(super) fn written(&mut self, address: u32, size: u32) {
+        let first = page(address);
+        let last = page(address.wrapping_add(size - 1));
+        self.generations[first] = self.generations[first].wrapping_add(1);
+        if last != first {
+            self.generations[last] = self.generations[last].wrapping_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecodeCache;
+    use cpu::{Core, CpuType, TestCore};
+    use ram::{AddressBus, SUPERVISOR_DATA, SUPERVISOR_PROGRAM};
+    use ram::loggingmem::Operation;
+
+    #[test]
+    fn entries_keep_the_words_an_instruction_fetched() {
+        let mut cache = DecodeCache::new();
+        assert_eq!(None, cache.begin(0x1000, true));
+        assert_eq!(None, cache.word(0x1000));
+        cache.fetched(0x1000, 0x33fc);
+        cache.fetched(0x1002, 0x1234);
+        // not the next word
+        cache.fetched(0x2000, 0x4e71);
+        cache.end(true);
+        assert_eq!(Some(0x33fc), cache.begin(0x1000, true));
+        assert_eq!((1, 1), (cache.hits(), cache.misses()));
+        assert_eq!(Some(0x1234), cache.word(0x1002));
+        assert_eq!(None, cache.word(0x1004));
+        assert_eq!(None, cache.word(0x0ffe));
+        cache.end(true);
+        // in the other mode, or at an odd address, it's another instruction
+        assert_eq!(None, cache.begin(0x1000, false));
+        cache.end(false);
+        assert_eq!(None, cache.begin(0x1001, false));
+    }
+
+    #[test]
+    fn faulting_instructions_are_not_kept() {
+        let mut cache = DecodeCache::new();
+        cache.begin(0x1000, true);
+        cache.fetched(0x1000, 0x4e71);
+        cache.end(false);
+        assert_eq!(None, cache.begin(0x1000, true));
+    }
+
+    #[test]
+    fn writes_invalidate_their_pages() {
+        let mut cache = DecodeCache::new();
+        for &pc in &[0x1ffe, 0x2000, 0x3000] {
+            cache.begin(pc, true);
+            cache.fetched(pc, 0x4e71);
+            cache.end(true);
+        }
+        // a long straddling two pages
+        cache.written(0x1ffe, 4);
+        for &(pc, cached) in &[(0x1ffe, false), (0x2000, false), (0x3000, true)] {
+            assert_eq!(cached, cache.begin(pc, true).is_some(), "{:04x}", pc);
+            cache.end(true);
+        }
+    }
+
+    // MOVE.L #$11112222,D0; ADD.W $4(A0),D0; BEQ.S +2; ADDQ.L #1,D1;
+    // DBRA D2,$40
+    const PROGRAM: &[u8] = &[0x20, 0x3c, 0x11, 0x11, 0x22, 0x22, 0xd0, 0x68, 0x00, 0x04, 0x67, 0x02, 0x52, 0x81, 0x51, 0xca, 0xff, 0xf0];
+
+    fn core() -> TestCore {
+        let mut core = TestCore::new_mem(0x40, PROGRAM);
+        core.dar[2] = 9;
+        core.dar[8] = 0x100;
+        core
+    }
+
+    #[test]
+    fn runs_like_the_uncached_core() {
+        let mut uncached = core();
+        let mut cached = core();
+        cached.enable_decode_cache();
+        for _ in 0..50 {
+            uncached.execute1();
+            cached.execute1();
+            assert_eq!(uncached.registers(), cached.registers());
+        }
+        let cache = cached.decode_cache.as_ref().unwrap();
+        assert_eq!(5, cache.misses());
+        assert_eq!(45, cache.hits());
+    }
+
+    #[test]
+    fn cached_instructions_take_no_program_reads() {
+        let mut core = core();
+        core.enable_decode_cache();
+        core.execute1();
+        core.mem.logger = Default::default();
+        core.pc = 0x40;
+        core.execute1();
+        assert!(core.mem.logger.is_empty());
+        assert_eq!(0x11112222, core.dar[0]);
+    }
+
+    #[test]
+    fn long_mc68020_instructions_are_cached_whole() {
+        // MOVE.L #$11112222,($2000.L,A0), with a full extension word
+        let mut core = TestCore::new_mem(0x40, &[0x21, 0xbc, 0x11, 0x11, 0x22, 0x22, 0x01, 0x70, 0x00, 0x00, 0x20, 0x00]);
+        core.set_cpu_type(CpuType::M68020);
+        core.enable_decode_cache();
+        core.execute1();
+        core.mem.logger = Default::default();
+        core.pc = 0x40;
+        core.execute1();
+        assert_eq!(vec![Operation::WriteLong(SUPERVISOR_DATA, 0x2000, 0x1111_2222)], core.mem.logger.ops());
+    }
+
+    #[test]
+    fn writes_by_the_core_are_noticed() {
+        // MOVE.W #$7005,$46 (making the next instruction MOVEQ #5,D0);
+        // MOVEQ #1,D0
+        let mut core = TestCore::new_mem(0x40, &[0x31, 0xfc, 0x70, 0x05, 0x00, 0x46, 0x70, 0x01]);
+        core.enable_decode_cache();
+        core.pc = 0x46;
+        core.execute1();
+        assert_eq!(1, core.dar[0]);
+        core.pc = 0x40;
+        core.execute1();
+        core.execute1();
+        assert_eq!(5, core.dar[0]);
+        core.write_word(0x46, 0x7003).unwrap();
+        core.pc = 0x46;
+        core.execute1();
+        assert_eq!(3, core.dar[0]);
+    }
+
+    #[test]
+    fn writes_behind_the_cores_back_need_invalidating() {
+        let mut core = TestCore::new_mem(0x40, &[0x70, 0x01]);
+        core.enable_decode_cache();
+        core.execute1();
+        core.mem.write_word(SUPERVISOR_PROGRAM, 0x40, 0x7005);
+        core.pc = 0x40;
+        core.execute1();
+        assert_eq!(1, core.dar[0]);
+        core.invalidate_decode_cache();
+        core.pc = 0x40;
+        core.execute1();
+        assert_eq!(5, core.dar[0]);
+        assert_eq!(Operation::ReadWord(SUPERVISOR_PROGRAM, 0x40, 0x7005), core.mem.logger.ops()[core.mem.logger.len() - 1]);
+    }
+}
@@ -1,4 +1,5 @@
-use super::{Core, Result};
+use super::{Core, CpuType, Result};
+use super::Exception::IllegalInstruction;
 use std::num::Wrapping;
 
 pub fn absolute_word<T: Core>(core: &mut T) -> Result<u32> {
@@ -132,22 +133,62 @@ pub fn displacement<T: Core>(core: &mut T, reg_val: u32) -> Result<u32> {
 }
 // Brief Extension Word format (see M68000 PRM section 2.1)
 const LONG_INDEX_MASK: u16 = 0x0800;
+// The 68020 adds a scale factor to it, and the Full Extension Word format
+// (of which the CPU32 has only the scale factor)
+const FULL_FORMAT_MASK: u16 = 0x0100;
+const BASE_SUPPRESS_MASK: u16 = 0x0080;
+const INDEX_SUPPRESS_MASK: u16 = 0x0040;
+const POSTINDEX_MASK: u16 = 0x0004;
 fn index<T: Core>(core: &mut T, reg_val: u32) -> Result<u32> {
     let extension = core.read_imm_u16()?;
+    let cpu_type = core.cpu_type();
+    if !cpu_type.is_020() && cpu_type != CpuType::Cpu32 {
+        let xn = index_register(core, extension);
+        let index = extension as i8;
+        return Ok((Wrapping(reg_val) + Wrapping(xn) + Wrapping(index as u32)).0);
+    }
+    let xn = index_register(core, extension) << ((extension >> 9) & 3);
+    if extension & FULL_FORMAT_MASK == 0 {
+        let index = extension as i8;
+        return Ok(reg_val.wrapping_add(xn).wrapping_add(index as u32));
+    }
+    if cpu_type == CpuType::Cpu32 {
+        return Err(IllegalInstruction(ir!(core), core.ppc()));
+    }
+    let base = if extension & BASE_SUPPRESS_MASK > 0 {0} else {reg_val};
+    let xn = if extension & INDEX_SUPPRESS_MASK > 0 {0} else {xn};
+    let base_displacement = extension_displacement(core, extension >> 4)?;
+    // the low three bits select memory indirection, and how
+    if extension & 7 == 0 {
+        return Ok(base.wrapping_add(base_displacement).wrapping_add(xn));
+    }
+    let outer_displacement = extension_displacement(core, extension)?;
+    let ea = if extension & POSTINDEX_MASK > 0 {
+        core.read_long(base.wrapping_add(base_displacement))?.wrapping_add(xn)
+    } else {
+        core.read_long(base.wrapping_add(base_displacement).wrapping_add(xn))?
+    };
+    Ok(ea.wrapping_add(outer_displacement))
+}
+fn index_register<T: Core>(core: &mut T, extension: u16) -> u32 {
     // top four bits = (D/A RRR) matches our register array layout
     let xreg_ndx = (extension>>12) as usize;
     let xn = dar!(core)[xreg_ndx];
-    let xn = if (extension & LONG_INDEX_MASK) > 0 {xn} else {(xn as i16) as u32};
-
-      let index = extension as i8;
-    let ea = (Wrapping(reg_val) + Wrapping(xn) + Wrapping(index as u32)).0;
-    Ok(ea)
+    if (extension & LONG_INDEX_MASK) > 0 {xn} else {(xn as i16) as u32}
+}
+// a null, word or long displacement, as the size in the low two bits says
+fn extension_displacement<T: Core>(core: &mut T, size: u16) -> Result<u32> {
+    match size & 3 {
+        2 => core.read_imm_i16().map(|displacement| displacement as u32),
+        3 => core.read_imm_u32(),
+        _ => Ok(0),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::TestCore;
-    use super::super::effective_address::{predecrement_8, postincrement_8};
+    use super::super::effective_address::{predecrement_8, postincrement_8, index_ay};
 
     #[test]
     fn predecrement_wraps() {
@@ -193,4 +234,27 @@ mod tests {
         assert_eq!(0xFFFFFFFE, ea);
         assert_eq!(0x0, core.dar[8+7]);
     }
+    #[test]
+    fn mc68020_index_scales_the_index_register() {
+        use cpu::CpuType;
+        // 1c08 is the brief extension word of 8(A0, D1.L*4)
+        let mut core = TestCore::new_mem(0x40, &[0x1c, 0x08]);
+        core.set_cpu_type(CpuType::M68020);
+        core.dar[1] = 3;
+        core.dar[8+0] = 0x100;
+        assert_eq!(0x114, index_ay(&mut core).unwrap());
+    }
+    #[test]
+    fn mc68020_index_reads_memory_indirect_addresses() {
+        use cpu::CpuType;
+        use ram::{AddressBus, SUPERVISOR_DATA};
+        // 1d22,0010,0020 is the full extension word of ([$10, A0, D1.L*4], $20)
+        let mut core = TestCore::new_mem(0x40, &[0x1d, 0x22, 0x00, 0x10, 0x00, 0x20]);
+        core.set_cpu_type(CpuType::M68020);
+        core.mem.write_long(SUPERVISOR_DATA, 0x11c, 0x2000);
+        core.dar[1] = 3;
+        core.dar[8+0] = 0x100;
+        assert_eq!(0x2020, index_ay(&mut core).unwrap());
+        assert_eq!(0x46, core.pc);
+    }
 }
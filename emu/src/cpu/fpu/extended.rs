@@ -0,0 +1,872 @@
+// Extended precision floating point, the format of the FPU registers: a
+// sign, a 15-bit exponent biased by 16383 and a 64-bit mantissa with an
+// explicit integer bit. The basic arithmetic is done in software, and
+// rounded as the FPCR asks. The transcendental functions go through f64
+// (like in Musashi), so they are only as precise as a double.
+use std::cmp::Ordering;
+
+// Exception status bits, as in the FPSR
+pub const BSUN: u32 = 0x8000;
+pub const SNAN: u32 = 0x4000;
+pub const OPERR: u32 = 0x2000;
+pub const OVFL: u32 = 0x1000;
+pub const UNFL: u32 = 0x0800;
+pub const DZ: u32 = 0x0400;
+pub const INEX2: u32 = 0x0200;
+pub const INEX1: u32 = 0x0100;
+
+const BIAS: i32 = 16383;
+const MAX_EXPONENT: u16 = 0x7fff;
+const INTEGER_BIT: u64 = 1 << 63;
+const QUIET_BIT: u64 = 1 << 62;
+// the exponent of the smallest normalized numbers
+const MIN_EXP: i32 = 1 - BIAS;
+const MAX_EXP: i32 = BIAS;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Precision {
+    Extended,
+    Double,
+    Single,
+}
+
+impl Precision {
+    fn bits(self) -> u32 {
+        match self {
+            Precision::Extended => 64,
+            Precision::Double => 53,
+            Precision::Single => 24,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    Nearest,
+    Zero,
+    Minus,
+    Plus,
+}
+
+// How results are rounded, and the exceptions raised while computing them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Context {
+    pub precision: Precision,
+    pub rounding: Rounding,
+    pub exceptions: u32,
+}
+
+impl Context {
+    pub fn new(precision: Precision, rounding: Rounding) -> Context {
+        Context { precision, rounding, exceptions: 0 }
+    }
+    fn rounds_to_infinity(&self, sign: bool) -> bool {
+        match self.rounding {
+            Rounding::Nearest => true,
+            Rounding::Zero => false,
+            Rounding::Minus => sign,
+            Rounding::Plus => !sign,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Extended {
+    pub sign: bool,
+    pub exponent: u16,
+    pub mantissa: u64,
+}
+
+// A finite value, sig * 2^(exp - 127), normalized so that bit 127 of sig
+// is set unless the value is zero
+#[derive(Clone, Copy, Debug)]
+struct Unpacked {
+    sign: bool,
+    exp: i32,
+    sig: u128,
+}
+
+fn shift_right_sticky(sig: u128, shift: u32) -> u128 {
+    if shift == 0 {
+        sig
+    } else if shift >= 128 {
+        (sig != 0) as u128
+    } else {
+        (sig >> shift) | ((sig << (128 - shift)) != 0) as u128
+    }
+}
+
+// Rounds sig * 2^(exp - 127), which must not be zero, to the given number
+// of significant bits, for a format whose normalized numbers have
+// exponents from min_exp up. Returns the rounded significand, which is
+// denormalized if bit (bits - 1) is clear, and its exponent, which may be
+// too large for the format.
+fn round(sign: bool, exp: i32, sig: u128, bits: u32, min_exp: i32, ctx: &mut Context) -> (u64, i32) {
+    let shift = sig.leading_zeros();
+    let mut sig = sig << shift;
+    let mut exp = exp - shift as i32;
+    let tiny = exp < min_exp;
+    if tiny {
+        sig = shift_right_sticky(sig, (min_exp - exp) as u32);
+        exp = min_exp;
+    }
+    let kept = sig >> (128 - bits);
+    let rest = sig << bits;
+    let half = 1u128 << 127;
+    let round_up = match ctx.rounding {
+        Rounding::Nearest => rest > half || (rest == half && kept & 1 == 1),
+        Rounding::Zero => false,
+        Rounding::Minus => sign && rest != 0,
+        Rounding::Plus => !sign && rest != 0,
+    };
+    if rest != 0 {
+        ctx.exceptions |= INEX2;
+        if tiny {
+            ctx.exceptions |= UNFL;
+        }
+    }
+    let mut kept = kept + round_up as u128;
+    if kept >> bits != 0 {
+        kept >>= 1;
+        exp += 1;
+    }
+    (kept as u64, exp)
+}
+
+// Rounds sig * 2^(exp - 127) to the precision of the context
+fn pack(sign: bool, exp: i32, sig: u128, ctx: &mut Context) -> Extended {
+    if sig == 0 {
+        return Extended::zero(sign);
+    }
+    let bits = ctx.precision.bits();
+    let (kept, exp) = round(sign, exp, sig, bits, MIN_EXP, ctx);
+    if exp > MAX_EXP {
+        ctx.exceptions |= OVFL | INEX2;
+        return if ctx.rounds_to_infinity(sign) {
+            Extended::infinity(sign)
+        } else {
+            Extended { sign, exponent: MAX_EXPONENT - 1, mantissa: !0u64 << (64 - bits) }
+        };
+    }
+    let mantissa = kept << (64 - bits);
+    let exponent = if mantissa & INTEGER_BIT != 0 { (exp + BIAS) as u16 } else { 0 };
+    Extended { sign, exponent, mantissa }
+}
+
+fn invalid(ctx: &mut Context) -> Extended {
+    ctx.exceptions |= OPERR;
+    Extended::default_nan()
+}
+
+// The result of an operation on a NaN: the destination NaN if there is
+// one, and otherwise the source NaN, quieted
+fn propagate_nan(dst: Extended, src: Extended, ctx: &mut Context) -> Extended {
+    if dst.is_signaling() || src.is_signaling() {
+        ctx.exceptions |= SNAN;
+    }
+    let nan = if dst.is_nan() { dst } else { src };
+    Extended { mantissa: nan.mantissa | QUIET_BIT, ..nan }
+}
+
+// Converts an IEEE single or double with the given number of fraction
+// and exponent bits
+fn from_ieee(bits: u64, fraction_bits: u32, exponent_bits: u32) -> Extended {
+    let sign = bits >> (fraction_bits + exponent_bits) != 0;
+    let max = (1u64 << exponent_bits) - 1;
+    let exponent = (bits >> fraction_bits) & max;
+    let fraction = bits & ((1u64 << fraction_bits) - 1);
+    let fraction = fraction << (63 - fraction_bits);
+    let bias = (max >> 1) as i32;
+    if exponent == max {
+        let mantissa = if fraction == 0 { 0 } else { INTEGER_BIT | fraction };
+        Extended { sign, exponent: MAX_EXPONENT, mantissa }
+    } else if exponent == 0 {
+        if fraction == 0 {
+            return Extended::zero(sign);
+        }
+        let shift = fraction.leading_zeros();
+        Extended { sign, exponent: (1 - bias + BIAS - shift as i32) as u16, mantissa: fraction << shift }
+    } else {
+        Extended { sign, exponent: (exponent as i32 - bias + BIAS) as u16, mantissa: INTEGER_BIT | fraction }
+    }
+}
+
+// Extended values of 10^(2^n), as in the constant ROM
+pub const POWERS_OF_TEN: [Extended; 13] = [
+    Extended { sign: false, exponent: 0x4002, mantissa: 0xa000_0000_0000_0000 },
+    Extended { sign: false, exponent: 0x4005, mantissa: 0xc800_0000_0000_0000 },
+    Extended { sign: false, exponent: 0x400c, mantissa: 0x9c40_0000_0000_0000 },
+    Extended { sign: false, exponent: 0x4019, mantissa: 0xbebc_2000_0000_0000 },
+    Extended { sign: false, exponent: 0x4034, mantissa: 0x8e1b_c9bf_0400_0000 },
+    Extended { sign: false, exponent: 0x4069, mantissa: 0x9dc5_ada8_2b70_b59e },
+    Extended { sign: false, exponent: 0x40d3, mantissa: 0xc278_1f49_ffcf_a6d5 },
+    Extended { sign: false, exponent: 0x41a8, mantissa: 0x93ba_47c9_80e9_8ce0 },
+    Extended { sign: false, exponent: 0x4351, mantissa: 0xaa7e_ebfb_9df9_de8e },
+    Extended { sign: false, exponent: 0x46a3, mantissa: 0xe319_a0ae_a60e_91c7 },
+    Extended { sign: false, exponent: 0x4d48, mantissa: 0xc976_7586_8175_0c17 },
+    Extended { sign: false, exponent: 0x5a92, mantissa: 0x9e8b_3b5d_c53d_5de5 },
+    Extended { sign: false, exponent: 0x7525, mantissa: 0xc460_5202_8a20_979b },
+];
+
+// 10^n, multiplied together from the powers above like the 68881 does
+fn power_of_ten(n: u32, ctx: &mut Context) -> Extended {
+    let mut result = Extended::one(false);
+    for (bit, power) in POWERS_OF_TEN.iter().enumerate() {
+        if n & (1 << bit) != 0 {
+            result = result.mul(*power, ctx);
+        }
+    }
+    if n >> POWERS_OF_TEN.len() != 0 {
+        result = Extended::infinity(false);
+    }
+    result
+}
+
+fn bcd(mut value: u64, digits: u32) -> u32 {
+    let mut result = 0;
+    for digit in 0..digits {
+        result |= ((value % 10) as u32) << (4 * digit);
+        value /= 10;
+    }
+    result
+}
+
+fn from_bcd(bcd: u32, digits: u32) -> u64 {
+    (0..digits).rev().fold(0, |value, digit| value * 10 + u64::from((bcd >> (4 * digit)) & 0xf))
+}
+
+impl Extended {
+    pub fn zero(sign: bool) -> Extended {
+        Extended { sign, exponent: 0, mantissa: 0 }
+    }
+    pub fn one(sign: bool) -> Extended {
+        Extended { sign, exponent: BIAS as u16, mantissa: INTEGER_BIT }
+    }
+    pub fn infinity(sign: bool) -> Extended {
+        Extended { sign, exponent: MAX_EXPONENT, mantissa: 0 }
+    }
+    // what invalid operations return
+    pub fn default_nan() -> Extended {
+        Extended { sign: false, exponent: MAX_EXPONENT, mantissa: !0 }
+    }
+
+    // the memory layout: the sign and exponent word, a zero word and the
+    // mantissa
+    pub fn from_words(words: [u32; 3]) -> Extended {
+        Extended {
+            sign: words[0] >> 31 != 0,
+            exponent: ((words[0] >> 16) & 0x7fff) as u16,
+            mantissa: (u64::from(words[1]) << 32) | u64::from(words[2]),
+        }
+    }
+    pub fn to_words(self) -> [u32; 3] {
+        [
+            (self.sign as u32) << 31 | u32::from(self.exponent) << 16,
+            (self.mantissa >> 32) as u32,
+            self.mantissa as u32,
+        ]
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.exponent == MAX_EXPONENT && self.mantissa << 1 != 0
+    }
+    pub fn is_signaling(self) -> bool {
+        self.is_nan() && self.mantissa & QUIET_BIT == 0
+    }
+    pub fn is_infinite(self) -> bool {
+        self.exponent == MAX_EXPONENT && self.mantissa << 1 == 0
+    }
+    pub fn is_zero(self) -> bool {
+        self.exponent != MAX_EXPONENT && self.mantissa == 0
+    }
+
+    fn unpack(self) -> Unpacked {
+        let exp = i32::from(self.exponent).max(1) - BIAS;
+        let sig = u128::from(self.mantissa) << 64;
+        let shift = sig.leading_zeros().min(127);
+        Unpacked { sign: self.sign, exp: exp - shift as i32, sig: sig << shift }
+    }
+
+    pub fn negate(self) -> Extended {
+        Extended { sign: !self.sign, ..self }
+    }
+    pub fn abs(self) -> Extended {
+        Extended { sign: false, ..self }
+    }
+
+    // Rounds to the precision of the context, as moving a value into a
+    // register does
+    pub fn round(self, ctx: &mut Context) -> Extended {
+        if self.is_nan() {
+            return propagate_nan(self, self, ctx);
+        }
+        if self.is_infinite() || self.is_zero() {
+            return self;
+        }
+        let a = self.unpack();
+        pack(a.sign, a.exp, a.sig, ctx)
+    }
+
+    pub fn add(self, other: Extended, ctx: &mut Context) -> Extended {
+        if self.is_nan() || other.is_nan() {
+            return propagate_nan(self, other, ctx);
+        }
+        match (self.is_infinite(), other.is_infinite()) {
+            (true, true) if self.sign != other.sign => invalid(ctx),
+            (true, _) => self,
+            (false, true) => other,
+            (false, false) => Extended::add_finite(self, other, ctx),
+        }
+    }
+    pub fn sub(self, other: Extended, ctx: &mut Context) -> Extended {
+        if self.is_nan() || other.is_nan() {
+            return propagate_nan(self, other, ctx);
+        }
+        self.add(other.negate(), ctx)
+    }
+    fn add_finite(a: Extended, b: Extended, ctx: &mut Context) -> Extended {
+        match (a.is_zero(), b.is_zero()) {
+            (true, true) => {
+                let sign = if a.sign == b.sign { a.sign } else { ctx.rounding == Rounding::Minus };
+                return Extended::zero(sign);
+            },
+            (true, false) => return b.round(ctx),
+            (false, true) => return a.round(ctx),
+            (false, false) => (),
+        }
+        let (a, b) = (a.unpack(), b.unpack());
+        let (big, small) = if (a.exp, a.sig) >= (b.exp, b.sig) { (a, b) } else { (b, a) };
+        // one bit of headroom for the carry
+        let big_sig = big.sig >> 1;
+        let small_sig = shift_right_sticky(small.sig, (big.exp - small.exp + 1) as u32);
+        let sig = if big.sign == small.sign { big_sig + small_sig } else { big_sig - small_sig };
+        if sig == 0 {
+            return Extended::zero(ctx.rounding == Rounding::Minus);
+        }
+        pack(big.sign, big.exp + 1, sig, ctx)
+    }
+
+    pub fn mul(self, other: Extended, ctx: &mut Context) -> Extended {
+        if self.is_nan() || other.is_nan() {
+            return propagate_nan(self, other, ctx);
+        }
+        let sign = self.sign != other.sign;
+        if self.is_infinite() || other.is_infinite() {
+            return if self.is_zero() || other.is_zero() { invalid(ctx) } else { Extended::infinity(sign) };
+        }
+        if self.is_zero() || other.is_zero() {
+            return Extended::zero(sign);
+        }
+        let (a, b) = (self.unpack(), other.unpack());
+        let product = (a.sig >> 64) * (b.sig >> 64);
+        pack(sign, a.exp + b.exp + 1, product, ctx)
+    }
+
+    pub fn div(self, other: Extended, ctx: &mut Context) -> Extended {
+        if self.is_nan() || other.is_nan() {
+            return propagate_nan(self, other, ctx);
+        }
+        let sign = self.sign != other.sign;
+        if self.is_infinite() {
+            return if other.is_infinite() { invalid(ctx) } else { Extended::infinity(sign) };
+        }
+        if other.is_infinite() {
+            return Extended::zero(sign);
+        }
+        if other.is_zero() {
+            if self.is_zero() {
+                return invalid(ctx);
+            }
+            ctx.exceptions |= DZ;
+            return Extended::infinity(sign);
+        }
+        if self.is_zero() {
+            return Extended::zero(sign);
+        }
+        let (a, b) = (self.unpack(), other.unpack());
+        let divisor = b.sig >> 64;
+        let mut remainder = a.sig >> 64;
+        let mut quotient = 0u128;
+        for _ in 0..128 {
+            quotient <<= 1;
+            if remainder >= divisor {
+                remainder -= divisor;
+                quotient |= 1;
+            }
+            remainder <<= 1;
+        }
+        pack(sign, a.exp - b.exp, quotient | (remainder != 0) as u128, ctx)
+    }
+
+    pub fn sqrt(self, ctx: &mut Context) -> Extended {
+        if self.is_nan() {
+            return propagate_nan(self, self, ctx);
+        }
+        if self.is_zero() {
+            return self;
+        }
+        if self.sign {
+            return invalid(ctx);
+        }
+        if self.is_infinite() {
+            return self;
+        }
+        let a = self.unpack();
+        // the integer square root of sig * 2^(3 + odd), bit by bit, which
+        // leaves two bits beyond the mantissa for rounding
+        let odd = (a.exp & 1) as u32;
+        let shift = 3 + odd;
+        let (high, low) = (a.sig >> (128 - shift), a.sig << shift);
+        let bit = |n: u32| if n >= 128 { (high >> (n - 128)) & 1 } else { (low >> n) & 1 };
+        let (mut root, mut remainder) = (0u128, 0u128);
+        for n in (0..66).rev() {
+            remainder = (remainder << 2) | (bit(2 * n + 1) << 1) | bit(2 * n);
+            let trial = (root << 2) | 1;
+            root <<= 1;
+            if remainder >= trial {
+                remainder -= trial;
+                root |= 1;
+            }
+        }
+        pack(false, a.exp >> 1, (root << 62) | (remainder != 0) as u128, ctx)
+    }
+
+    // The remainder of self / other, and the low seven bits of the
+    // quotient, which is truncated for FMOD or rounded to nearest for FREM
+    pub fn rem(self, other: Extended, nearest: bool, ctx: &mut Context) -> (Extended, u32) {
+        if self.is_nan() || other.is_nan() {
+            return (propagate_nan(self, other, ctx), 0);
+        }
+        if self.is_infinite() || other.is_zero() {
+            return (invalid(ctx), 0);
+        }
+        if other.is_infinite() || self.is_zero() {
+            return (self.round(ctx), 0);
+        }
+        let (a, b) = (self.unpack(), other.unpack());
+        let difference = a.exp - b.exp;
+        if difference < -1 || (difference == -1 && !nearest) {
+            return (self.round(ctx), 0);
+        }
+        // in units of the last place of the smaller exponent
+        let (mut remainder, divisor, unit) = if difference == -1 {
+            (a.sig >> 64, b.sig >> 63, a.exp - 63)
+        } else {
+            (a.sig >> 64, b.sig >> 64, b.exp - 63)
+        };
+        let mut quotient = 0u32;
+        if remainder >= divisor {
+            remainder -= divisor;
+            quotient = 1;
+        }
+        for _ in 0..difference.max(0) {
+            remainder <<= 1;
+            quotient <<= 1;
+            if remainder >= divisor {
+                remainder -= divisor;
+                quotient |= 1;
+            }
+            quotient &= 0x7f;
+        }
+        let mut sign = a.sign;
+        if nearest && (remainder << 1 > divisor || (remainder << 1 == divisor && quotient & 1 == 1)) {
+            remainder = divisor - remainder;
+            quotient += 1;
+            sign = !sign;
+        }
+        let result = if remainder == 0 { Extended::zero(a.sign) } else { pack(sign, unit + 127, remainder, ctx) };
+        (result, quotient & 0x7f)
+    }
+
+    // Rounds to an integral value
+    pub fn round_to_integer(self, rounding: Rounding, ctx: &mut Context) -> Extended {
+        if self.is_nan() {
+            return propagate_nan(self, self, ctx);
+        }
+        if self.is_infinite() || self.is_zero() {
+            return self;
+        }
+        let a = self.unpack();
+        if a.exp >= 63 {
+            return self;
+        }
+        if a.exp < 0 {
+            ctx.exceptions |= INEX2;
+            let up = match rounding {
+                Rounding::Nearest => a.exp == -1 && a.sig > 1 << 127,
+                Rounding::Zero => false,
+                Rounding::Minus => a.sign,
+                Rounding::Plus => !a.sign,
+            };
+            return if up { Extended::one(a.sign) } else { Extended::zero(a.sign) };
+        }
+        let bits = (a.exp + 1) as u32;
+        let mut integer_ctx = Context::new(Precision::Extended, rounding);
+        let (kept, exp) = round(a.sign, a.exp, a.sig, bits, MIN_EXP, &mut integer_ctx);
+        ctx.exceptions |= integer_ctx.exceptions;
+        pack(a.sign, exp, u128::from(kept) << (128 - bits), &mut Context::new(Precision::Extended, rounding))
+    }
+
+    // Rounds to an integer in min..=max, saturating with an operand error
+    // if it doesn't fit
+    pub fn to_integer(self, min: i64, max: i64, ctx: &mut Context) -> i64 {
+        let saturated = if self.sign { min } else { max };
+        if self.is_nan() || self.is_infinite() {
+            ctx.exceptions |= OPERR;
+            return saturated;
+        }
+        let rounding = ctx.rounding;
+        let integer = self.round_to_integer(rounding, ctx);
+        if integer.is_zero() {
+            return 0;
+        }
+        let a = integer.unpack();
+        if a.exp > 62 {
+            ctx.exceptions |= OPERR;
+            return saturated;
+        }
+        let magnitude = (a.sig >> (127 - a.exp)) as i64;
+        let value = if a.sign { -magnitude } else { magnitude };
+        if value < min || value > max {
+            ctx.exceptions |= OPERR;
+            return saturated;
+        }
+        value
+    }
+
+    // Adds the integer part of factor to the exponent
+    pub fn scale(self, factor: Extended, ctx: &mut Context) -> Extended {
+        if self.is_nan() || factor.is_nan() {
+            return propagate_nan(self, factor, ctx);
+        }
+        if factor.is_infinite() {
+            return invalid(ctx);
+        }
+        if self.is_infinite() || self.is_zero() {
+            return self;
+        }
+        // anything beyond this over- or underflows anyway
+        let n = factor.to_integer(-0x10000, 0x10000, &mut Context::new(Precision::Extended, Rounding::Zero));
+        let a = self.unpack();
+        pack(a.sign, a.exp + n as i32, a.sig, ctx)
+    }
+
+    pub fn get_exponent(self, ctx: &mut Context) -> Extended {
+        if self.is_nan() {
+            return propagate_nan(self, self, ctx);
+        }
+        if self.is_infinite() {
+            return invalid(ctx);
+        }
+        if self.is_zero() {
+            return self;
+        }
+        Extended::from_i64(i64::from(self.unpack().exp))
+    }
+    pub fn get_mantissa(self, ctx: &mut Context) -> Extended {
+        if self.is_nan() {
+            return propagate_nan(self, self, ctx);
+        }
+        if self.is_infinite() {
+            return invalid(ctx);
+        }
+        if self.is_zero() {
+            return self;
+        }
+        let a = self.unpack();
+        Extended { sign: a.sign, exponent: BIAS as u16, mantissa: (a.sig >> 64) as u64 }
+    }
+
+    pub fn compare(self, other: Extended) -> Option<Ordering> {
+        if self.is_nan() || other.is_nan() {
+            return None;
+        }
+        if self.is_zero() && other.is_zero() {
+            return Some(Ordering::Equal);
+        }
+        if self.sign != other.sign {
+            return Some(if self.sign { Ordering::Less } else { Ordering::Greater });
+        }
+        let magnitude = |x: Extended| if x.is_zero() {
+            (false, i32::MIN, 0)
+        } else if x.is_infinite() {
+            (true, 0, 0)
+        } else {
+            let a = x.unpack();
+            (false, a.exp, a.sig)
+        };
+        let ordering = magnitude(self).cmp(&magnitude(other));
+        Some(if self.sign { ordering.reverse() } else { ordering })
+    }
+
+    pub fn from_i64(value: i64) -> Extended {
+        if value == 0 {
+            return Extended::zero(false);
+        }
+        let magnitude = value.unsigned_abs();
+        let shift = magnitude.leading_zeros();
+        Extended { sign: value < 0, exponent: (63 - shift as i32 + BIAS) as u16, mantissa: magnitude << shift }
+    }
+
+    pub fn from_f32_bits(bits: u32) -> Extended {
+        from_ieee(u64::from(bits), 23, 8)
+    }
+    pub fn from_f64_bits(bits: u64) -> Extended {
+        from_ieee(bits, 52, 11)
+    }
+    pub fn from_f64(value: f64) -> Extended {
+        Extended::from_f64_bits(value.to_bits())
+    }
+
+    // Rounds to an IEEE single or double with the given number of fraction
+    // and exponent bits
+    fn to_ieee(self, fraction_bits: u32, exponent_bits: u32, ctx: &mut Context) -> u64 {
+        let sign = (self.sign as u64) << (fraction_bits + exponent_bits);
+        let max = (1u64 << exponent_bits) - 1;
+        let infinity = sign | max << fraction_bits;
+        if self.is_nan() {
+            if self.is_signaling() {
+                ctx.exceptions |= SNAN;
+            }
+            let fraction = (self.mantissa & !INTEGER_BIT) >> (63 - fraction_bits);
+            return infinity | fraction | 1 << (fraction_bits - 1);
+        }
+        if self.is_infinite() {
+            return infinity;
+        }
+        if self.is_zero() {
+            return sign;
+        }
+        let bias = (max >> 1) as i32;
+        let a = self.unpack();
+        let (kept, exp) = round(a.sign, a.exp, a.sig, fraction_bits + 1, 1 - bias, ctx);
+        if exp > bias {
+            ctx.exceptions |= OVFL | INEX2;
+            return if ctx.rounds_to_infinity(self.sign) { infinity } else { infinity - 1 };
+        }
+        if kept >> fraction_bits == 0 {
+            sign | kept
+        } else {
+            sign | ((exp + bias) as u64) << fraction_bits | (kept & ((1u64 << fraction_bits) - 1))
+        }
+    }
+    pub fn to_f32_bits(self, ctx: &mut Context) -> u32 {
+        self.to_ieee(23, 8, ctx) as u32
+    }
+    pub fn to_f64_bits(self, ctx: &mut Context) -> u64 {
+        self.to_ieee(52, 11, ctx)
+    }
+    pub fn to_f64(self) -> f64 {
+        f64::from_bits(self.to_f64_bits(&mut Context::new(Precision::Double, Rounding::Nearest)))
+    }
+
+    // Packed decimal real: a sign, a three digit exponent and a seventeen
+    // digit mantissa (one digit before the decimal point), all BCD. Values
+    // that aren't exact set INEX1.
+    pub fn from_packed(words: [u32; 3], ctx: &mut Context) -> Extended {
+        let sign = words[0] >> 31 != 0;
+        let mantissa = (u64::from(words[1]) << 32) | u64::from(words[2]);
+        if (words[0] >> 16) & 0x7fff == 0x7fff {
+            return if mantissa == 0 {
+                Extended::infinity(sign)
+            } else {
+                Extended { sign, exponent: MAX_EXPONENT, mantissa: mantissa | INTEGER_BIT }
+            };
+        }
+        let digits = u64::from(words[0] & 0xf) * 10_000_000_000_000_000
+            + from_bcd(words[1], 8) * 100_000_000 + from_bcd(words[2], 8);
+        if digits == 0 {
+            return Extended::zero(sign);
+        }
+        let exponent = from_bcd((words[0] >> 16) & 0xfff, 3) as i32;
+        let exponent = if words[0] & 0x4000_0000 != 0 { -exponent } else { exponent } - 16;
+        let mut conversion = Context::new(Precision::Extended, ctx.rounding);
+        let power = power_of_ten(exponent.unsigned_abs(), &mut conversion);
+        let value = Extended::from_i64(digits as i64);
+        let value = if exponent < 0 { value.div(power, &mut conversion) } else { value.mul(power, &mut conversion) };
+        if conversion.exceptions & INEX2 != 0 {
+            ctx.exceptions |= INEX1;
+        }
+        ctx.exceptions |= conversion.exceptions & (OVFL | UNFL);
+        Extended { sign, ..value }
+    }
+    // k is the number of significant digits if positive, and otherwise
+    // the number of digits to the right of the decimal point
+    pub fn to_packed(self, k: i32, ctx: &mut Context) -> [u32; 3] {
+        let sign = (self.sign as u32) << 31;
+        if self.is_nan() || self.is_infinite() {
+            let mantissa = if self.is_nan() { self.mantissa } else { 0 };
+            return [sign | 0x7fff_0000, (mantissa >> 32) as u32, mantissa as u32];
+        }
+        if self.is_zero() {
+            return [sign, 0, 0];
+        }
+        let k = if k > 17 {
+            ctx.exceptions |= OPERR;
+            17
+        } else {
+            k
+        };
+        let a = self.unpack();
+        let log2 = f64::from(a.exp) + ((a.sig >> 64) as f64 / 2f64.powi(63)).log2();
+        let mut exponent = (log2 * 2f64.log10()).floor() as i32;
+        let magnitude = self.abs();
+        let mut result = (0, exponent, 1);
+        // the estimate of the exponent may be off by one
+        for _ in 0..3 {
+            let digits = (if k > 0 { k } else { exponent + 1 - k }).clamp(1, 17) as u32;
+            let scale = exponent - (digits as i32 - 1);
+            let mut conversion = Context::new(Precision::Extended, ctx.rounding);
+            let power = power_of_ten(scale.unsigned_abs(), &mut conversion);
+            let scaled = if scale < 0 { magnitude.mul(power, &mut conversion) } else { magnitude.div(power, &mut conversion) };
+            let mut integer = Context::new(Precision::Extended, ctx.rounding);
+            let value = scaled.to_integer(0, i64::MAX, &mut integer) as u64;
+            conversion.exceptions |= integer.exceptions;
+            result = (value, exponent, digits);
+            if value >= 10u64.pow(digits) {
+                exponent += 1;
+            } else if value < 10u64.pow(digits - 1) && value != 0 {
+                exponent -= 1;
+            } else {
+                ctx.exceptions |= conversion.exceptions & INEX2;
+                break;
+            }
+        }
+        let (value, exponent, digits) = result;
+        let value = value * 10u64.pow(17 - digits.min(17));
+        if exponent.abs() > 999 {
+            ctx.exceptions |= OPERR;
+        }
+        let exponent_sign = if exponent < 0 { 0x4000_0000 } else { 0 };
+        let magnitude = u64::from(exponent.unsigned_abs());
+        [
+            sign | exponent_sign | bcd(magnitude % 1000, 3) << 16 | bcd(magnitude / 1000 % 10, 1) << 12
+                | (value / 10_000_000_000_000_000) as u32,
+            bcd(value / 100_000_000 % 100_000_000, 8),
+            bcd(value % 100_000_000, 8),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Context, Extended, Precision, Rounding, DZ, INEX1, INEX2, OPERR, OVFL, SNAN};
+    use std::cmp::Ordering;
+
+    fn ctx() -> Context {
+        Context::new(Precision::Extended, Rounding::Nearest)
+    }
+    fn x(value: f64) -> Extended {
+        Extended::from_f64(value)
+    }
+
+    #[test]
+    fn arithmetic_agrees_with_f64_on_exact_results() {
+        let mut ctx = ctx();
+        assert_eq!(x(5.75), x(2.5).add(x(3.25), &mut ctx));
+        assert_eq!(x(-0.75), x(2.5).sub(x(3.25), &mut ctx));
+        assert_eq!(x(8.125), x(2.5).mul(x(3.25), &mut ctx));
+        assert_eq!(x(0.3125), x(2.5).div(x(8.0), &mut ctx));
+        assert_eq!(x(12.0), x(144.0).sqrt(&mut ctx));
+        assert_eq!(0, ctx.exceptions);
+    }
+
+    #[test]
+    fn results_are_rounded_to_the_precision() {
+        let mut extended = ctx();
+        let third = x(1.0).div(x(3.0), &mut extended);
+        assert_eq!(0xaaaa_aaaa_aaaa_aaab, third.mantissa);
+        assert_eq!(INEX2, extended.exceptions);
+        let mut double = Context::new(Precision::Double, Rounding::Nearest);
+        assert_eq!(1.0 / 3.0, x(1.0).div(x(3.0), &mut double).to_f64());
+        let mut single = Context::new(Precision::Single, Rounding::Nearest);
+        assert_eq!(f64::from(1.0f32 / 3.0), x(1.0).div(x(3.0), &mut single).to_f64());
+        assert_eq!(2f64.sqrt(), x(2.0).sqrt(&mut double).to_f64());
+    }
+
+    #[test]
+    fn extended_has_more_range_than_double() {
+        let mut ctx = ctx();
+        let huge = x(2f64.powi(1000));
+        let big = huge.mul(huge, &mut ctx);
+        assert_eq!(0, ctx.exceptions);
+        assert_eq!(huge, big.div(huge, &mut ctx));
+        assert_eq!(f64::INFINITY, big.to_f64());
+        assert_eq!(0x7f80_0000, big.to_f32_bits(&mut ctx));
+        assert_eq!(OVFL | INEX2, ctx.exceptions & (OVFL | INEX2));
+    }
+
+    #[test]
+    fn invalid_operations_give_nans() {
+        let mut ctx = ctx();
+        assert!(Extended::infinity(false).sub(Extended::infinity(false), &mut ctx).is_nan());
+        assert!(x(0.0).div(x(0.0), &mut ctx).is_nan());
+        assert!(x(-1.0).sqrt(&mut ctx).is_nan());
+        assert_eq!(OPERR, ctx.exceptions);
+        let mut ctx = self::ctx();
+        assert_eq!(Extended::infinity(true), x(-1.0).div(x(0.0), &mut ctx));
+        assert_eq!(DZ, ctx.exceptions);
+        let mut ctx = self::ctx();
+        let signaling = Extended::from_f64_bits(0x7ff0_0000_0000_0001);
+        assert!(!x(1.0).add(signaling, &mut ctx).is_signaling());
+        assert_eq!(SNAN, ctx.exceptions);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let mut ctx = ctx();
+        for &value in &[0.1, -1.5e-300, 4.9e-324, 123456789.0, f64::MAX] {
+            assert_eq!(value.to_bits(), x(value).to_f64_bits(&mut ctx));
+            assert_eq!(f64::from(value as f32).to_bits(), x(f64::from(value as f32)).to_f64_bits(&mut ctx));
+        }
+        assert_eq!(1.5f32.to_bits(), Extended::from_f32_bits(1.5f32.to_bits()).to_f32_bits(&mut ctx));
+        assert_eq!(-7, x(-7.0).to_integer(i64::from(i32::MIN), i64::from(i32::MAX), &mut ctx));
+        assert_eq!(0, ctx.exceptions);
+    }
+
+    #[test]
+    fn integer_rounding_follows_the_mode() {
+        let mut ctx = ctx();
+        assert_eq!(x(2.0), x(2.5).round_to_integer(Rounding::Nearest, &mut ctx));
+        assert_eq!(x(-3.0), x(-2.5).round_to_integer(Rounding::Minus, &mut ctx));
+        assert_eq!(x(3.0), x(2.25).round_to_integer(Rounding::Plus, &mut ctx));
+        assert_eq!(x(-0.0), x(-0.75).round_to_integer(Rounding::Zero, &mut ctx));
+        assert_eq!(INEX2, ctx.exceptions);
+        assert_eq!(127, x(1e10).to_integer(-128, 127, &mut ctx));
+        assert_eq!(OPERR, ctx.exceptions & OPERR);
+    }
+
+    #[test]
+    fn remainders_keep_the_low_quotient_bits() {
+        let mut ctx = ctx();
+        assert_eq!((x(1.0), 3), x(10.0).rem(x(3.0), false, &mut ctx));
+        assert_eq!((x(-1.0), 4), x(11.0).rem(x(3.0), true, &mut ctx));
+        assert_eq!((x(-0.5), 0), x(-0.5).rem(x(3.0), false, &mut ctx));
+        assert_eq!(0, ctx.exceptions);
+    }
+
+    #[test]
+    fn comparisons_order_zeros_and_infinities() {
+        assert_eq!(Some(Ordering::Equal), x(0.0).compare(x(-0.0)));
+        assert_eq!(Some(Ordering::Less), x(-2.0).compare(x(-1.0)));
+        assert_eq!(Some(Ordering::Greater), Extended::infinity(false).compare(x(f64::MAX)));
+        assert_eq!(None, Extended::default_nan().compare(x(1.0)));
+    }
+
+    #[test]
+    fn packed_decimal_round_trips() {
+        let mut ctx = ctx();
+        // -1.25E+2
+        let packed = [0x8002_0001, 0x2500_0000, 0];
+        assert_eq!(x(-125.0), Extended::from_packed(packed, &mut ctx));
+        assert_eq!(packed, x(-125.0).to_packed(17, &mut ctx));
+        assert_eq!(0, ctx.exceptions);
+        // 3.33E-1
+        assert_eq!([0x4001_0003, 0x3300_0000, 0], x(1.0).div(x(3.0), &mut ctx).to_packed(3, &mut ctx));
+        let mut ctx = self::ctx();
+        // 1.000000000000000001E-1 has more digits than fit
+        let tenth = Extended::from_packed([0x4001_0001, 0, 1], &mut ctx);
+        assert_eq!(x(0.1), tenth.round(&mut Context::new(Precision::Double, Rounding::Nearest)));
+        assert_eq!(INEX1, ctx.exceptions);
+    }
+}
@@ -0,0 +1,1000 @@
+// The MC68881/MC68882 floating point coprocessor. Attached to a 68020
+// (see ConfiguredCore::attach_fpu) it executes the F-line instructions
+// with coprocessor id 1; without one, or on a 68000 or 68010, they still
+// cause the unimplemented instruction exception. The coprocessor
+// interface itself isn't emulated: the FPU instructions run as if they
+// were the CPU's own, enabled FPU exceptions are taken right after the
+// instruction that raised them, and the timings are the approximate
+// 68881 register to register ones.
+use std::cmp::Ordering;
+use cpu::{Core, Cycles, Exception, Result, EXCEPTION_TRAPV, EXCEPTION_UNIMPLEMENTED_1111};
+use cpu::effective_address;
+use savestate::{self, Snapshot, StateError, StateReader, StateWriter};
+use self::extended::{Context, Extended, Precision, Rounding, BSUN, DZ, INEX1, INEX2, OPERR, OVFL, SNAN, UNFL};
+pub mod extended;
+
+// the coprocessor id of the FPU in the F-line instructions
+pub const FPU_CPID: u16 = 1;
+
+pub const EXCEPTION_FP_BSUN: u8 = 48;
+pub const EXCEPTION_FP_INEX: u8 = 49;
+pub const EXCEPTION_FP_DZ: u8 = 50;
+pub const EXCEPTION_FP_UNFL: u8 = 51;
+pub const EXCEPTION_FP_OPERR: u8 = 52;
+pub const EXCEPTION_FP_OVFL: u8 = 53;
+pub const EXCEPTION_FP_SNAN: u8 = 54;
+
+// enabled exceptions trap in this order
+const TRAP_PRIORITY: [(u32, u8); 7] = [
+    (BSUN, EXCEPTION_FP_BSUN),
+    (SNAN, EXCEPTION_FP_SNAN),
+    (OPERR, EXCEPTION_FP_OPERR),
+    (OVFL, EXCEPTION_FP_OVFL),
+    (UNFL, EXCEPTION_FP_UNFL),
+    (DZ, EXCEPTION_FP_DZ),
+    (INEX1 | INEX2, EXCEPTION_FP_INEX),
+];
+
+const FPCR_MASK: u32 = 0xfff0;
+const FPSR_MASK: u32 = 0x0fff_fff8;
+// FPSR condition codes
+const CC_N: u32 = 0x0800_0000;
+const CC_Z: u32 = 0x0400_0000;
+const CC_I: u32 = 0x0200_0000;
+const CC_NAN: u32 = 0x0100_0000;
+const CC_MASK: u32 = 0x0f00_0000;
+const QUOTIENT_MASK: u32 = 0x00ff_0000;
+// the exception status byte of the FPSR, and the enable byte of the FPCR
+const EXCEPTION_MASK: u32 = 0xff00;
+// FPSR accrued exceptions
+const ACCRUED_IOP: u32 = 0x80;
+const ACCRUED_OVFL: u32 = 0x40;
+const ACCRUED_UNFL: u32 = 0x20;
+const ACCRUED_DZ: u32 = 0x10;
+const ACCRUED_INEX: u32 = 0x08;
+
+// FMOVE control register select bits, in the order they are moved
+const FPCR: u16 = 4;
+const FPSR: u16 = 2;
+const FPIAR: u16 = 1;
+
+// Operand formats, as in the source and destination specifier fields
+const LONG: u16 = 0;
+const SINGLE: u16 = 1;
+const EXTENDED: u16 = 2;
+const PACKED: u16 = 3;
+const WORD: u16 = 4;
+const DOUBLE: u16 = 5;
+const BYTE: u16 = 6;
+const PACKED_DYNAMIC: u16 = 7; // and FMOVECR, as a source
+const FORMAT_SIZES: [u32; 8] = [4, 4, 12, 12, 2, 8, 1, 12];
+
+// Effective address modes, numbering those of mode 7 from 7 up
+const DATA_REGISTER: u16 = 0;
+const ADDRESS_REGISTER: u16 = 1;
+const POSTINCREMENT: u16 = 3;
+const PREDECREMENT: u16 = 4;
+const PC_DISPLACEMENT: u16 = 9;
+const PC_INDEX: u16 = 10;
+const IMMEDIATE: u16 = 11;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FpuType {
+    MC68881,
+    MC68882,
+}
+
+impl FpuType {
+    // of the idle state frame, after the format word
+    fn idle_frame_size(self) -> u32 {
+        match self {
+            FpuType::MC68881 => 0x18,
+            FpuType::MC68882 => 0x38,
+        }
+    }
+    fn to_u8(self) -> u8 {
+        match self {
+            FpuType::MC68881 => 0,
+            FpuType::MC68882 => 1,
+        }
+    }
+    fn from_u8(value: u8) -> Option<FpuType> {
+        match value {
+            0 => Some(FpuType::MC68881),
+            1 => Some(FpuType::MC68882),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fpu {
+    fpu_type: FpuType,
+    pub fp: [Extended; 8],
+    pub fpcr: u32,
+    pub fpsr: u32,
+    pub fpiar: u32,
+    // whether an instruction has run since reset, so FSAVE writes an idle
+    // rather than a null frame
+    idle: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Location {
+    DataRegister(usize),
+    AddressRegister(usize),
+    Memory(u32),
+    Immediate,
+}
+
+impl Location {
+    // the next longword of a multiple register move
+    fn offset(self, offset: u32) -> Location {
+        match self {
+            Location::Memory(address) => Location::Memory(address.wrapping_add(offset)),
+            other => other,
+        }
+    }
+}
+
+fn f_line(ir: u16, pc: u32) -> Exception {
+    Exception::UnimplementedInstruction(ir, pc, EXCEPTION_UNIMPLEMENTED_1111)
+}
+
+fn mode(ir: u16) -> u16 {
+    let mode = (ir >> 3) & 7;
+    if mode == 7 { 7 + (ir & 7) } else { mode }
+}
+fn is_control(mode: u16) -> bool {
+    matches!(mode, 2 | 5..=PC_INDEX)
+}
+fn is_alterable_memory(mode: u16) -> bool {
+    matches!(mode, 2..=8)
+}
+
+// Decodes the effective address of the instruction, for an operand of
+// the given size. The mode must be a valid one.
+fn location<T: Core>(core: &mut T, size: u32) -> Result<Location> {
+    let ir = ir!(core);
+    let reg = (ir & 7) as usize;
+    // A7 is kept even
+    let step = if size == 1 && reg == 7 { 2 } else { size };
+    Ok(match mode(ir) {
+        DATA_REGISTER => Location::DataRegister(reg),
+        ADDRESS_REGISTER => Location::AddressRegister(reg),
+        2 => Location::Memory(effective_address::address_indirect_ay(core)?),
+        POSTINCREMENT => {
+            let address = dar!(core)[8 + reg];
+            dar!(core)[8 + reg] = address.wrapping_add(step);
+            Location::Memory(address)
+        },
+        PREDECREMENT => {
+            let address = dar!(core)[8 + reg].wrapping_sub(step);
+            dar!(core)[8 + reg] = address;
+            Location::Memory(address)
+        },
+        5 => Location::Memory(effective_address::displacement_ay(core)?),
+        6 => Location::Memory(effective_address::index_ay(core)?),
+        7 => Location::Memory(effective_address::absolute_word(core)?),
+        8 => Location::Memory(effective_address::absolute_long(core)?),
+        PC_DISPLACEMENT => Location::Memory(effective_address::displacement_pc(core)?),
+        PC_INDEX => Location::Memory(effective_address::index_pc(core)?),
+        _ => Location::Immediate,
+    })
+}
+
+// Bytes and words are returned in the first longword
+fn read<T: Core>(core: &mut T, location: Location, size: u32) -> Result<[u32; 3]> {
+    let mut longs = [0; 3];
+    match location {
+        Location::DataRegister(reg) => longs[0] = dar!(core)[reg],
+        Location::AddressRegister(reg) => longs[0] = dar!(core)[8 + reg],
+        Location::Memory(address) => match size {
+            1 => longs[0] = core.read_byte(address)?,
+            2 => longs[0] = core.read_word(address)?,
+            _ => for (i, long) in longs.iter_mut().take(size as usize / 4).enumerate() {
+                *long = core.read_long(address.wrapping_add(4 * i as u32))?;
+            },
+        },
+        Location::Immediate => match size {
+            1 => longs[0] = u32::from(core.read_imm_u16()? & 0xff),
+            2 => longs[0] = u32::from(core.read_imm_u16()?),
+            _ => for long in longs.iter_mut().take(size as usize / 4) {
+                *long = core.read_imm_u32()?;
+            },
+        },
+    }
+    Ok(longs)
+}
+
+fn write<T: Core>(core: &mut T, location: Location, size: u32, longs: [u32; 3]) -> Result<()> {
+    match location {
+        Location::DataRegister(reg) => {
+            let mask = match size { 1 => 0xff, 2 => 0xffff, _ => 0xffff_ffff };
+            dar!(core)[reg] = (dar!(core)[reg] & !mask) | (longs[0] & mask);
+        },
+        Location::AddressRegister(reg) => dar!(core)[8 + reg] = longs[0],
+        Location::Memory(address) => match size {
+            1 => core.write_byte(address, longs[0])?,
+            2 => core.write_word(address, longs[0])?,
+            _ => for (i, &long) in longs.iter().take(size as usize / 4).enumerate() {
+                core.write_long(address.wrapping_add(4 * i as u32), long)?;
+            },
+        },
+        Location::Immediate => unreachable!("immediate operands are not writable"),
+    }
+    Ok(())
+}
+
+fn to_extended(format: u16, longs: [u32; 3], ctx: &mut Context) -> Extended {
+    match format {
+        LONG => Extended::from_i64(i64::from(longs[0] as i32)),
+        SINGLE => Extended::from_f32_bits(longs[0]),
+        EXTENDED => Extended::from_words(longs),
+        PACKED => Extended::from_packed(longs, ctx),
+        WORD => Extended::from_i64(i64::from(longs[0] as i16)),
+        DOUBLE => Extended::from_f64_bits(u64::from(longs[0]) << 32 | u64::from(longs[1])),
+        _ => Extended::from_i64(i64::from(longs[0] as i8)),
+    }
+}
+
+fn from_extended(format: u16, value: Extended, k: i32, ctx: &mut Context) -> [u32; 3] {
+    let integer = |min: i64, max: i64, ctx: &mut Context| [value.to_integer(min, max, ctx) as u32, 0, 0];
+    match format {
+        LONG => integer(i64::from(i32::MIN), i64::from(i32::MAX), ctx),
+        SINGLE => [value.to_f32_bits(ctx), 0, 0],
+        EXTENDED => value.to_words(),
+        WORD => integer(i64::from(i16::MIN), i64::from(i16::MAX), ctx),
+        DOUBLE => {
+            let bits = value.to_f64_bits(ctx);
+            [(bits >> 32) as u32, bits as u32, 0]
+        },
+        BYTE => integer(i64::from(i8::MIN), i64::from(i8::MAX), ctx),
+        _ => value.to_packed(k, ctx),
+    }
+}
+
+fn condition_codes(value: Extended) -> u32 {
+    let mut cc = if value.sign { CC_N } else { 0 };
+    if value.is_nan() {
+        cc |= CC_NAN;
+    } else if value.is_infinite() {
+        cc |= CC_I;
+    } else if value.is_zero() {
+        cc |= CC_Z;
+    }
+    cc
+}
+
+// The constant ROM, as the 68881 returns it when rounding to nearest
+fn constant(offset: u16) -> Extended {
+    let constant = |exponent, mantissa| Extended { sign: false, exponent, mantissa };
+    match offset {
+        0x00 => constant(0x4000, 0xc90f_daa2_2168_c235), // pi
+        0x0b => constant(0x3ffd, 0x9a20_9a84_fbcf_f798), // log10(2)
+        0x0c => constant(0x4000, 0xadf8_5458_a2bb_4a9a), // e
+        0x0d => constant(0x3fff, 0xb8aa_3b29_5c17_f0bc), // log2(e)
+        0x0e => constant(0x3ffd, 0xde5b_d8a9_3728_7195), // log10(e)
+        0x30 => constant(0x3ffe, 0xb172_17f7_d1cf_79ac), // ln(2)
+        0x31 => constant(0x4000, 0x935d_8ddd_aaa8_ac17), // ln(10)
+        0x32 => Extended::one(false),
+        0x33..=0x3f => extended::POWERS_OF_TEN[usize::from(offset - 0x33)],
+        _ => Extended::zero(false),
+    }
+}
+
+// A transcendental function, evaluated in double precision
+fn approximate(x: Extended, f: fn(f64) -> f64, ctx: &mut Context) -> Extended {
+    if x.is_nan() {
+        return x.round(ctx);
+    }
+    let y = f(x.to_f64());
+    if y.is_nan() {
+        ctx.exceptions |= OPERR;
+        return Extended::default_nan();
+    }
+    if x.is_zero() || x.is_infinite() {
+        if y.is_infinite() {
+            ctx.exceptions |= DZ;
+        }
+        return Extended::from_f64(y);
+    }
+    if y.is_infinite() {
+        // a pole, like atanh(1), or an overflow
+        ctx.exceptions |= if x.to_f64().abs() <= 1.0 { DZ } else { OVFL | INEX2 };
+        return Extended::from_f64(y);
+    }
+    ctx.exceptions |= INEX2;
+    Extended::from_f64(y).round(ctx)
+}
+
+// the transcendental functions by opmode
+fn function(opmode: u16) -> Option<fn(f64) -> f64> {
+    Some(match opmode {
+        0x02 => f64::sinh,
+        0x06 => f64::ln_1p,
+        0x08 => f64::exp_m1,
+        0x09 => f64::tanh,
+        0x0a => f64::atan,
+        0x0c => f64::asin,
+        0x0d => f64::atanh,
+        0x0e => f64::sin,
+        0x0f => f64::tan,
+        0x10 => f64::exp,
+        0x11 => f64::exp2,
+        0x12 => |x| 10f64.powf(x),
+        0x14 => f64::ln,
+        0x15 => f64::log10,
+        0x16 => f64::log2,
+        0x19 => f64::cosh,
+        0x1c => f64::acos,
+        0x1d => f64::cos,
+        _ => return None,
+    })
+}
+
+// of the general instructions by opmode, or None for those the 68881
+// doesn't have
+fn general_cycles(opmode: u16) -> Option<i32> {
+    Some(match opmode {
+        0x00 => 21,                         // FMOVE
+        0x01 | 0x03 => 55,                  // FINT, FINTRZ
+        0x04 => 107,                        // FSQRT
+        0x18 | 0x1a => 35,                  // FABS, FNEG
+        0x1e | 0x1f => 31,                  // FGETEXP, FGETMAN
+        0x20 => 103,                        // FDIV
+        0x21 | 0x25 => 70,                  // FMOD, FREM
+        0x22 | 0x28 => 51,                  // FADD, FSUB
+        0x23 => 71,                         // FMUL
+        0x24 => 69,                         // FSGLDIV
+        0x26 => 41,                         // FSCALE
+        0x27 => 59,                         // FSGLMUL
+        0x30..=0x37 => 451,                 // FSINCOS
+        0x38 | 0x3a => 33,                  // FCMP, FTST
+        _ if function(opmode).is_some() => 400,
+        _ => return None,
+    })
+}
+
+impl Fpu {
+    pub fn new(fpu_type: FpuType) -> Fpu {
+        let mut fpu = Fpu { fpu_type, fp: [Extended::zero(false); 8], fpcr: 0, fpsr: 0, fpiar: 0, idle: false };
+        fpu.reset();
+        fpu
+    }
+    pub fn fpu_type(&self) -> FpuType {
+        self.fpu_type
+    }
+    // The state after a hardware reset, or FRESTORE of a null frame
+    pub fn reset(&mut self) {
+        self.fp = [Extended::default_nan(); 8];
+        self.fpcr = 0;
+        self.fpsr = 0;
+        self.fpiar = 0;
+        self.idle = false;
+    }
+
+    fn context(&self) -> Context {
+        let precision = match (self.fpcr >> 6) & 3 {
+            1 => Precision::Single,
+            2 => Precision::Double,
+            _ => Precision::Extended,
+        };
+        let rounding = match (self.fpcr >> 4) & 3 {
+            0 => Rounding::Nearest,
+            1 => Rounding::Zero,
+            2 => Rounding::Minus,
+            _ => Rounding::Plus,
+        };
+        Context::new(precision, rounding)
+    }
+    // Records the exceptions an instruction raised, and takes the highest
+    // priority one that is enabled
+    fn raise(&mut self, exceptions: u32) -> Result<()> {
+        let mut accrued = 0;
+        if exceptions & (BSUN | SNAN | OPERR) != 0 {
+            accrued |= ACCRUED_IOP;
+        }
+        if exceptions & OVFL != 0 {
+            accrued |= ACCRUED_OVFL;
+        }
+        if exceptions & UNFL != 0 && exceptions & INEX2 != 0 {
+            accrued |= ACCRUED_UNFL;
+        }
+        if exceptions & DZ != 0 {
+            accrued |= ACCRUED_DZ;
+        }
+        if exceptions & (OVFL | INEX1 | INEX2) != 0 {
+            accrued |= ACCRUED_INEX;
+        }
+        self.fpsr |= exceptions | accrued;
+        let enabled = exceptions & self.fpcr & EXCEPTION_MASK;
+        match TRAP_PRIORITY.iter().find(|&&(bits, _)| enabled & bits != 0) {
+            Some(&(_, vector)) => Err(Exception::Trap(vector, 34)),
+            None => Ok(()),
+        }
+    }
+    // Evaluates one of the 32 conditional predicates. Those from 0x10 up
+    // also signal a branch on unordered if the condition codes say NaN.
+    fn condition(&mut self, predicate: u16) -> Result<bool> {
+        let cc = self.fpsr;
+        let (n, z, nan) = (cc & CC_N != 0, cc & CC_Z != 0, cc & CC_NAN != 0);
+        if predicate & 0x10 != 0 && nan {
+            self.raise(BSUN)?;
+        }
+        Ok(match predicate & 0xf {
+            0x0 => false,
+            0x1 => z,
+            0x2 => !(nan || z || n),
+            0x3 => z || !(nan || n),
+            0x4 => n && !(nan || z),
+            0x5 => z || (n && !nan),
+            0x6 => !(nan || z),
+            0x7 => !nan,
+            0x8 => nan,
+            0x9 => nan || z,
+            0xa => nan || !(n || z),
+            0xb => nan || z || !n,
+            0xc => nan || (n && !z),
+            0xd => nan || z || n,
+            0xe => !z,
+            _ => true,
+        })
+    }
+
+    pub fn execute<T: Core>(&mut self, core: &mut T) -> Result<Cycles> {
+        let ir = ir!(core);
+        let pc = pc!(core).wrapping_sub(2);
+        match (ir >> 6) & 7 {
+            0 => self.general(core, ir, pc),
+            1 => self.conditional(core, ir, pc),
+            2 => self.branch(core, false),
+            3 => self.branch(core, true),
+            4 => self.fsave(core, ir, pc),
+            5 => self.frestore(core, ir, pc),
+            _ => Err(f_line(ir, pc)),
+        }
+    }
+
+    fn general<T: Core>(&mut self, core: &mut T, ir: u16, pc: u32) -> Result<Cycles> {
+        let command = core.read_imm_u16()?;
+        let ea_mode = mode(ir);
+        match command >> 13 {
+            0 => {
+                let cycles = general_cycles(command & 0x7f).ok_or_else(|| f_line(ir, pc))?;
+                self.idle = true;
+                self.fpiar = pc;
+                let src = self.fp[usize::from((command >> 10) & 7)];
+                self.arithmetic(command, src, Context::new(Precision::Extended, Rounding::Nearest))?;
+                Ok(Cycles(cycles))
+            },
+            2 if (command >> 10) & 7 == PACKED_DYNAMIC => {
+                // FMOVECR
+                if ir & 0x3f != 0 {
+                    return Err(f_line(ir, pc));
+                }
+                self.idle = true;
+                self.fpiar = pc;
+                let dst = usize::from((command >> 7) & 7);
+                self.fpsr &= !EXCEPTION_MASK;
+                let mut ctx = self.context();
+                self.fp[dst] = constant(command & 0x7f).round(&mut ctx);
+                self.fpsr = (self.fpsr & !CC_MASK) | condition_codes(self.fp[dst]);
+                self.raise(ctx.exceptions)?;
+                Ok(Cycles(29))
+            },
+            2 => {
+                let format = (command >> 10) & 7;
+                let size = FORMAT_SIZES[usize::from(format)];
+                let valid = match ea_mode {
+                    DATA_REGISTER => size <= 4,
+                    ADDRESS_REGISTER => false,
+                    mode => mode <= IMMEDIATE,
+                };
+                let cycles = general_cycles(command & 0x7f).filter(|_| valid).ok_or_else(|| f_line(ir, pc))?;
+                self.idle = true;
+                self.fpiar = pc;
+                let location = location(core, size)?;
+                let longs = read(core, location, size)?;
+                let mut conversion = self.context();
+                let src = to_extended(format, longs, &mut conversion);
+                self.arithmetic(command, src, conversion)?;
+                Ok(Cycles(cycles + 4))
+            },
+            3 => self.fmove_out(core, command, ir, pc),
+            4 | 5 => self.fmove_control(core, command, ir, pc),
+            6 | 7 => self.fmovem(core, command, ir, pc),
+            _ => Err(f_line(ir, pc)),
+        }
+    }
+
+    // The general instructions, on a source operand converted with the
+    // given exceptions
+    fn arithmetic(&mut self, command: u16, src: Extended, conversion: Context) -> Result<()> {
+        let opmode = command & 0x7f;
+        let dst_reg = usize::from((command >> 7) & 7);
+        let dst = self.fp[dst_reg];
+        self.fpsr &= !EXCEPTION_MASK;
+        let mut ctx = self.context();
+        ctx.exceptions = conversion.exceptions;
+        let single = |ctx: &mut Context, op: &dyn Fn(&mut Context) -> Extended| {
+            let mut single = Context { precision: Precision::Single, ..*ctx };
+            let result = op(&mut single);
+            ctx.exceptions = single.exceptions;
+            result
+        };
+        let result = match opmode {
+            0x00 => Some(src.round(&mut ctx)),
+            0x01 => Some(src.round_to_integer(ctx.rounding, &mut ctx).round(&mut ctx)),
+            0x03 => Some(src.round_to_integer(Rounding::Zero, &mut ctx).round(&mut ctx)),
+            0x04 => Some(src.sqrt(&mut ctx)),
+            0x18 => Some(src.abs().round(&mut ctx)),
+            0x1a => Some(src.negate().round(&mut ctx)),
+            0x1e => Some(src.get_exponent(&mut ctx)),
+            0x1f => Some(src.get_mantissa(&mut ctx)),
+            0x20 => Some(dst.div(src, &mut ctx)),
+            0x21 | 0x25 => {
+                let (remainder, quotient) = dst.rem(src, opmode == 0x25, &mut ctx);
+                let sign = if dst.sign != src.sign { 0x80 } else { 0 };
+                self.fpsr = (self.fpsr & !QUOTIENT_MASK) | (sign | quotient) << 16;
+                Some(remainder)
+            },
+            0x22 => Some(dst.add(src, &mut ctx)),
+            0x23 => Some(dst.mul(src, &mut ctx)),
+            0x24 => Some(single(&mut ctx, &|ctx| dst.div(src, ctx))),
+            0x26 => Some(dst.scale(src, &mut ctx)),
+            0x27 => Some(single(&mut ctx, &|ctx| dst.mul(src, ctx))),
+            0x28 => Some(dst.sub(src, &mut ctx)),
+            0x30..=0x37 => {
+                self.fp[usize::from(command & 7)] = approximate(src, f64::cos, &mut ctx);
+                Some(approximate(src, f64::sin, &mut ctx))
+            },
+            0x38 => {
+                let cc = match dst.compare(src) {
+                    None => {
+                        if dst.is_signaling() || src.is_signaling() {
+                            ctx.exceptions |= SNAN;
+                        }
+                        CC_NAN
+                    },
+                    Some(Ordering::Less) => CC_N,
+                    Some(Ordering::Greater) => 0,
+                    // as the sign of dst - src
+                    Some(Ordering::Equal) if dst.sign && (dst.is_infinite() || !src.sign) => CC_N | CC_Z,
+                    Some(Ordering::Equal) => CC_Z,
+                };
+                self.fpsr = (self.fpsr & !CC_MASK) | cc;
+                None
+            },
+            0x3a => {
+                if src.is_signaling() {
+                    ctx.exceptions |= SNAN;
+                }
+                self.fpsr = (self.fpsr & !CC_MASK) | condition_codes(src);
+                None
+            },
+            _ => Some(approximate(src, function(opmode).expect("checked by general_cycles"), &mut ctx)),
+        };
+        if let Some(result) = result {
+            self.fp[dst_reg] = result;
+            self.fpsr = (self.fpsr & !CC_MASK) | condition_codes(result);
+        }
+        self.raise(ctx.exceptions)
+    }
+
+    // FMOVE FPn, <ea>
+    fn fmove_out<T: Core>(&mut self, core: &mut T, command: u16, ir: u16, pc: u32) -> Result<Cycles> {
+        let format = (command >> 10) & 7;
+        let size = FORMAT_SIZES[usize::from(format)];
+        let valid = match mode(ir) {
+            DATA_REGISTER => size <= 4,
+            mode => is_alterable_memory(mode),
+        };
+        if !valid {
+            return Err(f_line(ir, pc));
+        }
+        self.idle = true;
+        self.fpiar = pc;
+        // a seven bit signed number
+        let k = if format == PACKED_DYNAMIC {
+            dar!(core)[usize::from((command >> 4) & 7)] as u8
+        } else {
+            command as u8
+        };
+        let k = ((k << 1) as i8) >> 1;
+        let value = self.fp[usize::from((command >> 7) & 7)];
+        self.fpsr &= !EXCEPTION_MASK;
+        let mut ctx = self.context();
+        let longs = from_extended(format, value, i32::from(k), &mut ctx);
+        let location = location(core, size)?;
+        write(core, location, size, longs)?;
+        self.raise(ctx.exceptions)?;
+        Ok(Cycles(25))
+    }
+
+    // FMOVE and FMOVEM of the control registers
+    fn fmove_control<T: Core>(&mut self, core: &mut T, command: u16, ir: u16, pc: u32) -> Result<Cycles> {
+        let to_control = command & 0x2000 == 0;
+        // no register at all means FPIAR
+        let list = match (command >> 10) & 7 { 0 => FPIAR, list => list };
+        let count = list.count_ones();
+        let valid = match mode(ir) {
+            DATA_REGISTER => count == 1,
+            ADDRESS_REGISTER => list == FPIAR,
+            PC_DISPLACEMENT | PC_INDEX | IMMEDIATE => to_control,
+            mode => mode <= 8,
+        };
+        if !valid || command & 0x3ff != 0 {
+            return Err(f_line(ir, pc));
+        }
+        self.idle = true;
+        let location = location(core, 4 * count)?;
+        let registers = [FPCR, FPSR, FPIAR].iter().filter(|&&register| list & register != 0);
+        for (i, &register) in registers.enumerate() {
+            let location = location.offset(4 * i as u32);
+            if to_control {
+                let value = read(core, location, 4)?[0];
+                match register {
+                    FPCR => self.fpcr = value & FPCR_MASK,
+                    FPSR => self.fpsr = value & FPSR_MASK,
+                    _ => self.fpiar = value,
+                }
+            } else {
+                let value = match register {
+                    FPCR => self.fpcr,
+                    FPSR => self.fpsr,
+                    _ => self.fpiar,
+                };
+                write(core, location, 4, [value, 0, 0])?;
+            }
+        }
+        Ok(Cycles(20 + 10 * count as i32))
+    }
+
+    // FMOVEM of the data registers, which are stored with the lowest
+    // numbered at the lowest address
+    fn fmovem<T: Core>(&mut self, core: &mut T, command: u16, ir: u16, pc: u32) -> Result<Cycles> {
+        let to_memory = command & 0x2000 != 0;
+        let predecrement = command & 0x1000 == 0;
+        let dynamic = command & 0x0800 != 0;
+        let valid = match (to_memory, predecrement) {
+            (false, false) => is_control(mode(ir)) || mode(ir) == POSTINCREMENT,
+            (true, false) => is_control(mode(ir)) && is_alterable_memory(mode(ir)),
+            (true, true) => mode(ir) == PREDECREMENT,
+            (false, true) => false,
+        };
+        if !valid {
+            return Err(f_line(ir, pc));
+        }
+        self.idle = true;
+        let list = if dynamic {
+            dar!(core)[usize::from((command >> 4) & 7)] & 0xff
+        } else {
+            u32::from(command & 0xff)
+        };
+        // the predecrement mode has FP7 in bit 7, the others FP0
+        let registers: Vec<usize> = (0..8)
+            .filter(|&reg| list & (1 << if predecrement { reg } else { 7 - reg }) != 0)
+            .collect();
+        let location = location(core, 12 * registers.len() as u32)?;
+        for (i, &reg) in registers.iter().enumerate() {
+            let location = location.offset(12 * i as u32);
+            if to_memory {
+                write(core, location, 12, self.fp[reg].to_words())?;
+            } else {
+                self.fp[reg] = Extended::from_words(read(core, location, 12)?);
+            }
+        }
+        Ok(Cycles(20 + 25 * registers.len() as i32))
+    }
+
+    // FScc, FDBcc and FTRAPcc
+    fn conditional<T: Core>(&mut self, core: &mut T, ir: u16, pc: u32) -> Result<Cycles> {
+        let ea_mode = mode(ir);
+        // An is FDBcc, and #<data> with the modes after it FTRAPcc
+        let valid = matches!(ea_mode, DATA_REGISTER | ADDRESS_REGISTER | 2..=8 | PC_DISPLACEMENT..=IMMEDIATE);
+        if !valid {
+            return Err(f_line(ir, pc));
+        }
+        let command = core.read_imm_u16()?;
+        if command & 0xffc0 != 0 {
+            return Err(f_line(ir, pc));
+        }
+        self.idle = true;
+        match ea_mode {
+            ADDRESS_REGISTER => {
+                let base = pc!(core);
+                let displacement = core.read_imm_i16()?;
+                if self.condition(command)? {
+                    return Ok(Cycles(20));
+                }
+                let reg = usize::from(ir & 7);
+                let count = (dar!(core)[reg] as u16).wrapping_sub(1);
+                dar!(core)[reg] = (dar!(core)[reg] & 0xffff_0000) | u32::from(count);
+                if count != 0xffff {
+                    core.jump(base.wrapping_add(displacement as u32));
+                }
+                Ok(Cycles(26))
+            },
+            PC_DISPLACEMENT..=IMMEDIATE => {
+                // the optional operand is just skipped, as for TRAPcc
+                let operand_size = [2, 4, 0][usize::from(ea_mode - PC_DISPLACEMENT)];
+                pc!(core) = pc!(core).wrapping_add(operand_size);
+                if self.condition(command)? {
+                    Err(Exception::Trap(EXCEPTION_TRAPV, 34))
+                } else {
+                    Ok(Cycles(20))
+                }
+            },
+            _ => {
+                let condition = self.condition(command)?;
+                let location = location(core, 1)?;
+                write(core, location, 1, [if condition { 0xff } else { 0 }, 0, 0])?;
+                Ok(Cycles(20))
+            },
+        }
+    }
+
+    // FBcc, with a word or long displacement from the end of the opcode.
+    // FNOP is FBF.W with a displacement of 0.
+    fn branch<T: Core>(&mut self, core: &mut T, long: bool) -> Result<Cycles> {
+        self.idle = true;
+        let base = pc!(core);
+        let displacement = if long {
+            core.read_imm_u32()?
+        } else {
+            core.read_imm_i16()? as u32
+        };
+        if self.condition(ir!(core) & 0x3f)? {
+            core.jump(base.wrapping_add(displacement));
+        }
+        Ok(Cycles(14))
+    }
+
+    // FSAVE writes a null frame (a zero longword) until the FPU has
+    // executed an instruction, and an idle frame after that. The idle
+    // frame is mostly internal registers, which are written as zeros.
+    fn fsave<T: Core>(&mut self, core: &mut T, ir: u16, pc: u32) -> Result<Cycles> {
+        if s_flag!(core) == 0 {
+            return Err(Exception::PrivilegeViolation(ir, pc));
+        }
+        let ea_mode = mode(ir);
+        if !(is_control(ea_mode) && is_alterable_memory(ea_mode)) && ea_mode != PREDECREMENT {
+            return Err(f_line(ir, pc));
+        }
+        let size = if self.idle { self.fpu_type.idle_frame_size() } else { 0 };
+        let location = location(core, 4 + size)?;
+        let header = if self.idle { 0x1f00_0000 | size << 16 } else { 0 };
+        write(core, location, 4, [header, 0, 0])?;
+        for offset in (4..size).step_by(4) {
+            write(core, location.offset(offset), 4, [0, 0, 0])?;
+        }
+        if self.idle {
+            // the BIU flags
+            write(core, location.offset(size), 4, [0x7000_0000, 0, 0])?;
+        }
+        Ok(Cycles(if self.idle { 36 } else { 14 }))
+    }
+
+    // FRESTORE of a null frame resets the FPU, and of an idle frame makes
+    // it idle. Any other frame is a format error.
+    fn frestore<T: Core>(&mut self, core: &mut T, ir: u16, pc: u32) -> Result<Cycles> {
+        if s_flag!(core) == 0 {
+            return Err(Exception::PrivilegeViolation(ir, pc));
+        }
+        let ea_mode = mode(ir);
+        if !is_control(ea_mode) && ea_mode != POSTINCREMENT {
+            return Err(f_line(ir, pc));
+        }
+        // (An)+ is advanced past the frame once its size is known
+        let location = if ea_mode == POSTINCREMENT {
+            Location::Memory(dar!(core)[usize::from(8 + (ir & 7))])
+        } else {
+            location(core, 0)?
+        };
+        let header = read(core, location, 4)?[0];
+        let size = (header >> 16) & 0xff;
+        if header >> 24 == 0 {
+            self.reset();
+        } else if size == self.fpu_type.idle_frame_size() {
+            self.idle = true;
+        } else {
+            return Err(Exception::FormatError(ir, pc));
+        }
+        if ea_mode == POSTINCREMENT {
+            let reg = usize::from(8 + (ir & 7));
+            dar!(core)[reg] = dar!(core)[reg].wrapping_add(4 + size);
+        }
+        Ok(Cycles(if self.idle { 36 } else { 14 }))
+    }
+}
+
+// Executes an F-line instruction with the coprocessor id of the FPU, on a
+// core that has one. The FPU is detached while it runs, so that it can use
+// the core. What the instruction changed in it before an exception stays
+// changed, as the exception bits of the FPSR must for the handler.
+pub fn execute<T: Core>(core: &mut T) -> Result<Cycles> {
+    let mut fpu = core.fpu().take().expect("an FPU instruction without an FPU");
+    let result = fpu.execute(core);
+    *core.fpu() = Some(fpu);
+    result
+}
+
+impl Snapshot for Fpu {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u8(self.fpu_type.to_u8());
+        for reg in self.fp.iter() {
+            for &long in reg.to_words().iter() {
+                writer.put_u32(long);
+            }
+        }
+        writer.put_u32(self.fpcr);
+        writer.put_u32(self.fpsr);
+        writer.put_u32(self.fpiar);
+        writer.put_bool(self.idle);
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.fpu_type = FpuType::from_u8(reader.get_u8()?).ok_or(StateError::Invalid("fpu type"))?;
+        for reg in self.fp.iter_mut() {
+            *reg = Extended::from_words([reader.get_u32()?, reader.get_u32()?, reader.get_u32()?]);
+        }
+        self.fpcr = reader.get_u32()?;
+        self.fpsr = reader.get_u32()?;
+        self.fpiar = reader.get_u32()?;
+        self.idle = reader.get_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FpuType, EXCEPTION_FP_BSUN};
+    use super::extended::Extended;
+    use cpu::{CpuType, TestCore, EXCEPTION_UNIMPLEMENTED_1111};
+    use ram::{AddressBus, SUPERVISOR_DATA};
+
+    fn core_020(words: &[u16]) -> TestCore {
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        let mut cpu = TestCore::new_mem(0x40, &bytes);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.attach_fpu(FpuType::MC68881);
+        cpu.dar[15] = 0x800;
+        cpu
+    }
+    fn fp(cpu: &TestCore, reg: usize) -> Extended {
+        cpu.fpu.as_ref().unwrap().fp[reg]
+    }
+    fn fpsr(cpu: &TestCore) -> u32 {
+        cpu.fpu.as_ref().unwrap().fpsr
+    }
+
+    #[test]
+    fn f_line_traps_without_a_68020_and_an_fpu() {
+        // f200,4000 is FMOVE.L D0, FP0
+        for &(cpu_type, has_fpu) in &[(CpuType::M68020, false), (CpuType::M68000, true)] {
+            let mut cpu = core_020(&[0xf200, 0x4000]);
+            cpu.set_cpu_type(cpu_type);
+            if !has_fpu {
+                cpu.fpu = None;
+            }
+            cpu.mem.write_long(SUPERVISOR_DATA, u32::from(EXCEPTION_UNIMPLEMENTED_1111) * 4, 0x1000);
+            cpu.execute1();
+            assert_eq!(0x1000, cpu.pc);
+        }
+    }
+
+    #[test]
+    fn arithmetic_is_extended_precision() {
+        // f23c,4000 is FMOVE.L #1, FP0, f23c,4020 FDIV.L #3, FP0 and
+        // f210,7400 FMOVE.D FP0, (A0)
+        let mut cpu = core_020(&[0xf23c, 0x4000, 0, 1, 0xf23c, 0x4020, 0, 3, 0xf210, 0x7400]);
+        cpu.dar[8] = 0x200;
+        for _ in 0..3 {
+            cpu.execute1();
+        }
+        assert_eq!(0xaaaa_aaaa_aaaa_aaab, fp(&cpu, 0).mantissa);
+        let double = (1.0f64 / 3.0).to_bits();
+        assert_eq!((double >> 32) as u32, cpu.mem.read_long(SUPERVISOR_DATA, 0x200));
+        assert_eq!(double as u32, cpu.mem.read_long(SUPERVISOR_DATA, 0x204));
+        // INEX2, and accrued INEX
+        assert_eq!(0x0208, fpsr(&cpu));
+        assert_eq!(0x50, cpu.fpu.as_ref().unwrap().fpiar);
+    }
+
+    #[test]
+    fn fmovecr_rounds_to_the_precision() {
+        // f200,5c80 is FMOVECR #0, FP1, after FMOVE.L #$80, FPCR (double)
+        let mut cpu = core_020(&[0xf23c, 0x9000, 0, 0x80, 0xf200, 0x5c80]);
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(Extended { sign: false, exponent: 0x4000, mantissa: 0xc90f_daa2_2168_c000 }, fp(&cpu, 1));
+        assert_eq!(::std::f64::consts::PI, fp(&cpu, 1).to_f64());
+    }
+
+    #[test]
+    fn conditionals_test_the_condition_codes() {
+        // f23c,4438 is FCMP.S #2.0, FP0, f241,0001 FSEQ D1, f282,0010
+        // FBOGT.W *+$12, and f24a,000e,fffc is FDBNE D2, * (a loop)
+        let mut cpu = core_020(&[0xf23c, 0x4438, 0x4000, 0, 0xf241, 0x0001, 0xf282, 0x0010]);
+        cpu.fpu.as_mut().unwrap().fp[0] = Extended::from_f64(3.0);
+        cpu.dar[1] = 0x1234_5678;
+        for _ in 0..3 {
+            cpu.execute1();
+        }
+        assert_eq!(0, fpsr(&cpu));
+        assert_eq!(0x1234_5600, cpu.dar[1]);
+        assert_eq!(0x4c + 0x12, cpu.pc);
+        let mut cpu = core_020(&[0xf24a, 0x000e, 0xfffc]);
+        cpu.fpu.as_mut().unwrap().fpsr = 0x0400_0000; // Z
+        cpu.dar[2] = 0x0001_0001;
+        cpu.execute1();
+        assert_eq!((0x0001_0000, 0x40), (cpu.dar[2], cpu.pc));
+        cpu.execute1();
+        assert_eq!((0x0001_ffff, 0x46), (cpu.dar[2], cpu.pc));
+    }
+
+    #[test]
+    fn unordered_comparisons_signal_bsun() {
+        // f292,0010 is FBGT.W *+$12
+        let mut cpu = core_020(&[0xf292, 0x0010]);
+        cpu.fpu.as_mut().unwrap().fpsr = 0x0100_0000; // NaN
+        cpu.fpu.as_mut().unwrap().fpcr = 0x8000; // BSUN enabled
+        cpu.mem.write_long(SUPERVISOR_DATA, u32::from(EXCEPTION_FP_BSUN) * 4, 0x1000);
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc);
+        assert_eq!(0x0100_8080, fpsr(&cpu));
+    }
+
+    #[test]
+    fn fmovem_moves_lists_of_registers() {
+        // f220,e005 is FMOVEM.X FP0/FP2, -(A0) and f218,d00c is
+        // FMOVEM.X (A0)+, FP4/FP5
+        let mut cpu = core_020(&[0xf220, 0xe005, 0xf218, 0xd00c]);
+        cpu.fpu.as_mut().unwrap().fp[0] = Extended::from_f64(1.5);
+        cpu.fpu.as_mut().unwrap().fp[2] = Extended::from_f64(-2.0);
+        cpu.dar[8] = 0x300;
+        cpu.execute1();
+        assert_eq!(0x2e8, cpu.dar[8]);
+        assert_eq!(0x3fff_0000, cpu.mem.read_long(SUPERVISOR_DATA, 0x2e8));
+        assert_eq!(0xc000_0000, cpu.mem.read_long(SUPERVISOR_DATA, 0x2f4));
+        cpu.execute1();
+        assert_eq!(0x300, cpu.dar[8]);
+        assert_eq!(Extended::from_f64(1.5), fp(&cpu, 4));
+        assert_eq!(Extended::from_f64(-2.0), fp(&cpu, 5));
+    }
+
+    #[test]
+    fn fsave_frames_follow_the_fpu_state() {
+        // f327 is FSAVE -(A7), f280,0000 FNOP and f35f FRESTORE (A7)+
+        let mut cpu = core_020(&[0xf327, 0xf280, 0x0000, 0xf327, 0xf35f, 0xf35f]);
+        cpu.execute1();
+        assert_eq!(0x7fc, cpu.dar[15]);
+        assert_eq!(0, cpu.mem.read_long(SUPERVISOR_DATA, 0x7fc));
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(0x7fc - 0x1c, cpu.dar[15]);
+        assert_eq!(0x1f18_0000, cpu.mem.read_long(SUPERVISOR_DATA, 0x7e0));
+        cpu.fpu.as_mut().unwrap().fpcr = 0x30;
+        cpu.execute1();
+        assert_eq!(0x7fc, cpu.dar[15]);
+        assert_eq!(0x30, cpu.fpu.as_ref().unwrap().fpcr);
+        // the null frame resets the FPU
+        cpu.execute1();
+        assert_eq!(0x800, cpu.dar[15]);
+        assert_eq!(0, cpu.fpu.as_ref().unwrap().fpcr);
+        assert!(fp(&cpu, 0).is_nan());
+    }
+
+    #[test]
+    fn save_state_keeps_the_fpu() {
+        let mut cpu = core_020(&[]);
+        cpu.attach_fpu(FpuType::MC68882);
+        cpu.fpu.as_mut().unwrap().fp[3] = Extended::from_f64(0.1);
+        cpu.fpu.as_mut().unwrap().fpsr = 0x0800_0000;
+        let state = cpu.save_state();
+        let mut other = TestCore::new(0x40);
+        assert_eq!(Ok(()), other.load_state(&state));
+        assert_eq!(cpu.fpu, other.fpu);
+        assert_eq!(FpuType::MC68882, other.fpu.unwrap().fpu_type());
+    }
+}
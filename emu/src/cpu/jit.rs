@@ -0,0 +1,849 @@
+// A dynamic recompiler for x86-64 hosts ("jit" feature). Straight line
+// code that has run often enough is compiled into native code, which the
+// run loop calls instead of interpreting it an instruction at a time.
+// Blocks are built from the decode cache (which enabling the JIT enables
+// too), so a block only holds instructions that have already run, all
+// from one of its pages.
+//
+// Register to register moves and arithmetic are compiled into x86-64 code
+// of their own, setting the flags the way ops/common.rs does. Any other
+// instruction is compiled into a call of its handler, after which control
+// returns to the interpreter should the instruction have raised an
+// exception, left the straight line, changed mode, let an interrupt in or
+// written to the page of the block. Blocks aren't entered while tracing,
+// debugging (watchpoints included), with instruction hooks, an enabled
+// MMU or cycle exact timing.
+use cpu::{ConfiguredCore, Cycles, Exception, T1FLAG_CLEAR};
+#[cfg(feature = "fc")]
+use cpu::Core;
+use cpu::decode_cache::{self, DecodeCache};
+use interrupts::InterruptController;
+use libc;
+use ram::AddressBus;
+use std::any::Any;
+use std::marker::PhantomData;
+use std::mem::{self, offset_of};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+const SLOTS: usize = 0x8000; // direct mapped by PC, like the decode cache
+const CODE_SIZE: usize = 4 << 20; // flushed when full
+const INSTRUCTIONS: usize = 32; // at most, in a block
+const THRESHOLD: u32 = 20;
+
+// a compiled block, called with the core and the cycles (or instructions,
+// without the "cycles" feature) it may use, returning those it used
+type Block<T, A> = unsafe extern "C" fn(*mut ConfiguredCore<T, A>, i32) -> i32;
+
+// runs an instruction for a block, see step
+type Step<T, A> = extern "C" fn(*mut ConfiguredCore<T, A>, u32, u32) -> u64;
+
+#[derive(Clone, Copy)]
+struct Slot {
+    tag: u32, // the PC, with the lowest bit set in supervisor mode
+    count: u32, // times the PC started an interpreted instruction
+    code: Option<(usize, u32)>, // the offset of the block, and the page generation it was built from
+}
+
+const EMPTY_SLOT: Slot = Slot { tag: 0, count: 0, code: None };
+
+pub struct Jit<T: InterruptController, A: AddressBus> {
+    slots: Box<[Slot]>,
+    code: CodeBuffer,
+    threshold: u32,
+    compiled: u64,
+    entered: u64,
+    pending: Option<Exception>, // raised by an instruction of the running block
+    panic: Option<Box<dyn Any + Send>>, // caught in a handler, to be resumed
+    core: PhantomData<fn(&mut ConfiguredCore<T, A>)>,
+}
+
+impl<T: InterruptController, A: AddressBus> Default for Jit<T, A> {
+    fn default() -> Jit<T, A> {
+        Jit::new()
+    }
+}
+
+// a clone compiles its own code
+impl<T: InterruptController, A: AddressBus> Clone for Jit<T, A> {
+    fn clone(&self) -> Jit<T, A> {
+        let mut jit = Jit::new();
+        jit.threshold = self.threshold;
+        jit
+    }
+}
+
+impl<T: InterruptController, A: AddressBus> Jit<T, A> {
+    pub fn new() -> Jit<T, A> {
+        Jit {
+            slots: vec![EMPTY_SLOT; SLOTS].into_boxed_slice(),
+            code: CodeBuffer::new(),
+            threshold: THRESHOLD,
+            compiled: 0,
+            entered: 0,
+            pending: None,
+            panic: None,
+            core: PhantomData,
+        }
+    }
+
+    // A block is compiled once its first instruction has been interpreted
+    // this many times
+    pub fn set_threshold(&mut self, threshold: u32) {
+        self.threshold = threshold;
+    }
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    // blocks compiled, and times blocks were run
+    pub fn compiled(&self) -> u64 {
+        self.compiled
+    }
+    pub fn entered(&self) -> u64 {
+        self.entered
+    }
+
+    pub fn invalidate_all(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.code = None;
+        }
+        self.code.clear();
+    }
+
+    // The block starting at the (masked) PC, if compiled, or compiled now
+    // that it is hot enough
+    fn block(&mut self, pc: u32, supervisor: bool, cache: &DecodeCache) -> Option<Block<T, A>> {
+        let index = (pc >> 1) as usize & (SLOTS - 1);
+        let tag = pc | supervisor as u32;
+        let generation = cache.generation(pc);
+        let slot = self.slots[index];
+        if slot.tag == tag {
+            match slot.code {
+                Some((offset, built_from)) if built_from == generation => return Some(self.code.block(offset)),
+                // rewritten since, so warm up again
+                Some(_) => self.slots[index] = Slot { tag, count: 0, code: None },
+                None => (),
+            }
+        } else {
+            self.slots[index] = Slot { tag, count: 0, code: None };
+        }
+        if self.slots[index].count < self.threshold {
+            self.slots[index].count += 1;
+            return None;
+        }
+        let code = compile::<T, A>(pc, supervisor, cache)?;
+        let offset = match self.code.add(&code) {
+            Some(offset) => offset,
+            None => {
+                self.invalidate_all();
+                self.code.add(&code)?
+            }
+        };
+        self.slots[index].code = Some((offset, generation));
+        self.compiled += 1;
+        Some(self.code.block(offset))
+    }
+}
+
+impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
+    // Runs the compiled block at PC, if there is one and it may run,
+    // returning what it used along with any exception to be processed
+    pub(super) fn run_compiled(&mut self, budget: i32, hooks: bool) -> Option<(i32, Option<Exception>)> {
+        if hooks || self.jit.is_none() || !self.may_run_compiled() {
+            return None;
+        }
+        let pc = self.pc & self.address_mask;
+        let supervisor = self.s_flag != 0;
+        let block = match (self.jit.as_mut(), self.decode_cache.as_ref()) {
+            (Some(jit), Some(cache)) => jit.block(pc, supervisor, cache)?,
+            _ => return None,
+        };
+        let core: *mut Self = self;
+        let used = unsafe { block(core, budget) };
+        let jit = self.jit.as_mut()?;
+        jit.entered += 1;
+        if let Some(payload) = jit.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        Some((used, jit.pending.take()))
+    }
+
+    fn may_run_compiled(&self) -> bool {
+        let mmu_enabled = self.mmu.as_ref().is_some_and(|mmu| mmu.is_enabled());
+        self.processing_state.running() && self.t1_flag == T1FLAG_CLEAR && self.pc & 1 == 0
+            && !cfg!(feature = "prefetch") && !mmu_enabled && self.bus_timing.is_none()
+            && !self.debugger.is_active() && self.debugger.watchpoints().is_empty()
+            && self.pending_interrupt().is_none()
+    }
+
+    // Runs the instruction at PC for a block, like the run loop would.
+    // Returns whether the block has to stop along with what it used.
+    fn step_compiled(&mut self, length: u32, generation: u32) -> (bool, u32) {
+        let pc = self.pc;
+        let supervisor = self.s_flag;
+        self.ppc = pc;
+        let result = self.read_instruction().and_then(|opcode| {
+            self.ir = opcode;
+            self.instruction_set[opcode as usize](self)
+        });
+        #[cfg(feature = "fc")]
+        self.set_fc(true);
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.end(result.is_ok());
+        }
+        match result {
+            Ok(cycles) => {
+                let page = pc & self.address_mask;
+                let straight = self.pc == pc.wrapping_add(length) && self.s_flag == supervisor;
+                let unwritten = self.decode_cache.as_ref().is_some_and(|cache| cache.generation(page) == generation);
+                let stop = !straight || !unwritten || self.t1_flag != T1FLAG_CLEAR
+                    || !self.processing_state.running() || self.pending_interrupt().is_some();
+                (stop, units(Some(cycles)))
+            },
+            Err(ex) => {
+                if let Some(ref mut jit) = self.jit {
+                    jit.pending = Some(ex);
+                }
+                (true, units(None))
+            },
+        }
+    }
+}
+
+// What an instruction uses of the budget; a faulting one uses nothing
+// but its exception with the "cycles" feature, as in the run loop
+#[cfg(feature = "cycles")]
+fn units(cycles: Option<Cycles>) -> u32 {
+    cycles.map_or(0, |cycles| cycles.0 as u32)
+}
+#[cfg(not(feature = "cycles"))]
+fn units(_: Option<Cycles>) -> u32 {
+    1
+}
+
+// Called from compiled code, returning the units used in the low half
+// and whether to stop in bit 32. Panics are caught, as they can't unwind
+// through compiled code, and resumed once the block has returned.
+extern "C" fn step<T: InterruptController, A: AddressBus>(core: *mut ConfiguredCore<T, A>, length: u32, generation: u32) -> u64 {
+    let core = unsafe { &mut *core };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| core.step_compiled(length, generation)));
+    let (stop, used) = match result {
+        Ok(step) => step,
+        Err(payload) => {
+            if let Some(ref mut jit) = core.jit {
+                jit.panic = Some(payload);
+            }
+            (true, 0)
+        },
+    };
+    u64::from(used) | (stop as u64) << 32
+}
+
+// Register to register instructions compiled into code of their own
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Inline {
+    Moveq(usize, u32), // MOVEQ #value,Dx
+    Move(usize, usize), // MOVE.L Dy,Dx
+    Add(usize, usize), // ADD.L Dy,Dx
+    Sub(usize, usize), // SUB.L Dy,Dx
+    Cmp(usize, usize), // CMP.L Dy,Dx
+    Addq(usize, u32), // ADDQ.L #quick,Dy
+    Subq(usize, u32), // SUBQ.L #quick,Dy
+    Tst(usize), // TST.L Dy
+    Clr(usize), // CLR.L Dy
+}
+
+impl Inline {
+    fn decode(opcode: u16) -> Option<Inline> {
+        let x = (opcode >> 9) as usize & 7;
+        let y = opcode as usize & 7;
+        let quick = ((u32::from(opcode) >> 9).wrapping_sub(1) & 7) + 1;
+        Some(match opcode {
+            _ if opcode & 0xf100 == 0x7000 => Inline::Moveq(x, opcode as u8 as i8 as u32),
+            _ if opcode & 0xf1f8 == 0x2000 => Inline::Move(x, y),
+            _ if opcode & 0xf1f8 == 0xd080 => Inline::Add(x, y),
+            _ if opcode & 0xf1f8 == 0x9080 => Inline::Sub(x, y),
+            _ if opcode & 0xf1f8 == 0xb080 => Inline::Cmp(x, y),
+            _ if opcode & 0xf1f8 == 0x5080 => Inline::Addq(y, quick),
+            _ if opcode & 0xf1f8 == 0x5180 => Inline::Subq(y, quick),
+            _ if opcode & 0xfff8 == 0x4a80 => Inline::Tst(y),
+            _ if opcode & 0xfff8 == 0x4280 => Inline::Clr(y),
+            _ => return None,
+        })
+    }
+
+    // as returned by their handlers
+    #[cfg(feature = "cycles")]
+    fn units(self) -> u32 {
+        match self {
+            Inline::Moveq(..) | Inline::Move(..) | Inline::Tst(_) => 4,
+            Inline::Add(..) | Inline::Sub(..) | Inline::Cmp(..) | Inline::Clr(_) => 6,
+            Inline::Addq(..) | Inline::Subq(..) => 8,
+        }
+    }
+    #[cfg(not(feature = "cycles"))]
+    fn units(self) -> u32 {
+        1
+    }
+}
+
+// Instructions after which a block isn't worth continuing, as they leave
+// the straight line (or the block would be left anyway)
+fn ends_block(opcode: u16) -> bool {
+    match opcode >> 12 {
+        0x6 | 0xa | 0xf => true, // Bcc, BRA, BSR and the emulator traps
+        _ => opcode & 0xf0f8 == 0x50c8 // DBcc
+            || opcode & 0xff80 == 0x4e80 // JSR, JMP
+            || opcode & 0xffc0 == 0x4e40 // TRAP, LINK, UNLK, MOVE USP, RESET, STOP, RTE, RTS ...
+            || opcode & 0xffc0 == 0x46c0 // MOVE to SR
+            || opcode == 0x007c || opcode == 0x027c || opcode == 0x0a7c // ORI, ANDI, EORI to SR
+            || opcode == 0x4afc, // ILLEGAL
+    }
+}
+
+// Offsets into the core, of what compiled code reads and writes
+struct Layout {
+    pc: i32,
+    ppc: i32,
+    ir: i32,
+    dar: i32,
+    n_flag: i32,
+    not_z_flag: i32,
+    v_flag: i32,
+    c_flag: i32,
+    x_flag: i32,
+}
+
+impl Layout {
+    fn of<T: InterruptController, A: AddressBus>() -> Layout {
+        Layout {
+            pc: offset_of!(ConfiguredCore<T, A>, pc) as i32,
+            ppc: offset_of!(ConfiguredCore<T, A>, ppc) as i32,
+            ir: offset_of!(ConfiguredCore<T, A>, ir) as i32,
+            dar: offset_of!(ConfiguredCore<T, A>, dar) as i32,
+            n_flag: offset_of!(ConfiguredCore<T, A>, n_flag) as i32,
+            not_z_flag: offset_of!(ConfiguredCore<T, A>, not_z_flag) as i32,
+            v_flag: offset_of!(ConfiguredCore<T, A>, v_flag) as i32,
+            c_flag: offset_of!(ConfiguredCore<T, A>, c_flag) as i32,
+            x_flag: offset_of!(ConfiguredCore<T, A>, x_flag) as i32,
+        }
+    }
+
+    fn d(&self, reg: usize) -> i32 {
+        self.dar + 4 * reg as i32
+    }
+}
+
+// Compiles the block at the (masked) PC, from the instructions the decode
+// cache has
+fn compile<T: InterruptController, A: AddressBus>(pc: u32, supervisor: bool, cache: &DecodeCache) -> Option<Vec<u8>> {
+    let layout = Layout::of::<T, A>();
+    let generation = cache.generation(pc);
+    let mut asm = Assembler::new();
+    asm.prologue();
+    let mut address = pc;
+    let mut instructions = 0;
+    while instructions < INSTRUCTIONS && decode_cache::same_page(pc, address) {
+        let words = match cache.instruction(address, supervisor) {
+            Some(words) => words,
+            None => break,
+        };
+        let opcode = words[0];
+        let length = 2 * words.len() as u32;
+        match Inline::decode(opcode) {
+            Some(inline) => asm.inline(&layout, opcode, inline),
+            None => asm.call(step::<T, A> as Step<T, A> as usize as u64, length, generation),
+        }
+        instructions += 1;
+        address = address.wrapping_add(length);
+        if ends_block(opcode) {
+            break;
+        }
+    }
+    if instructions == 0 {
+        return None;
+    }
+    asm.epilogue();
+    Some(asm.code)
+}
+
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RDX: u8 = 2;
+const RSI: u8 = 6;
+
+// Just enough of an x86-64 assembler. Compiled code keeps the core in
+// RBX, the budget in R12D and what has been used in R13D.
+struct Assembler {
+    code: Vec<u8>,
+    exits: Vec<usize>, // jumps to the epilogue, to be patched
+}
+
+impl Assembler {
+    fn new() -> Assembler {
+        Assembler { code: Vec::new(), exits: Vec::new() }
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn emit32(&mut self, value: u32) {
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn prologue(&mut self) {
+        // push rbx; push r12; push r13 (leaving the stack aligned for calls)
+        self.emit(&[0x53, 0x41, 0x54, 0x41, 0x55]);
+        // mov rbx, rdi; mov r12d, esi; xor r13d, r13d
+        self.emit(&[0x48, 0x89, 0xfb, 0x41, 0x89, 0xf4, 0x45, 0x31, 0xed]);
+    }
+
+    fn epilogue(&mut self) {
+        let end = self.code.len();
+        for &exit in &self.exits {
+            let rel = (end - (exit + 4)) as u32;
+            self.code[exit..exit + 4].copy_from_slice(&rel.to_le_bytes());
+        }
+        // mov eax, r13d; pop r13; pop r12; pop rbx; ret
+        self.emit(&[0x44, 0x89, 0xe8, 0x41, 0x5d, 0x41, 0x5c, 0x5b, 0xc3]);
+    }
+
+    // jcc rel32 to the epilogue
+    fn exit_if(&mut self, condition: u8) {
+        self.emit(&[0x0f, condition]);
+        self.exits.push(self.code.len());
+        self.emit32(0);
+    }
+
+    // stops once the budget is used up
+    fn check_budget(&mut self) {
+        // cmp r13d, r12d; jge
+        self.emit(&[0x45, 0x39, 0xe5]);
+        self.exit_if(0x8d);
+    }
+
+    // mov r32, [rbx+offset]
+    fn load(&mut self, reg: u8, offset: i32) {
+        self.emit(&[0x8b, 0x83 | reg << 3]);
+        self.emit32(offset as u32);
+    }
+
+    // mov [rbx+offset], r32
+    fn store(&mut self, offset: i32, reg: u8) {
+        self.emit(&[0x89, 0x83 | reg << 3]);
+        self.emit32(offset as u32);
+    }
+
+    // mov dword [rbx+offset], imm32
+    fn store_imm(&mut self, offset: i32, value: u32) {
+        self.emit(&[0xc7, 0x83]);
+        self.emit32(offset as u32);
+        self.emit32(value);
+    }
+
+    // mov r32, imm32
+    fn load_imm(&mut self, reg: u8, value: u32) {
+        self.emit(&[0xb8 + reg]);
+        self.emit32(value);
+    }
+
+    // op r64, r64 (with op one of the "op r/m64, r64" encodings)
+    fn op64(&mut self, op: u8, dst: u8, src: u8) {
+        self.emit(&[0x48, op, 0xc0 | src << 3 | dst]);
+    }
+
+    // shr r64, imm8
+    fn shr64(&mut self, reg: u8, shift: u8) {
+        self.emit(&[0x48, 0xc1, 0xe8 | reg, shift]);
+    }
+
+    // Calls the handler of an instruction, through step
+    fn call(&mut self, step: u64, length: u32, generation: u32) {
+        // mov rdi, rbx; mov esi, length; mov edx, generation
+        self.emit(&[0x48, 0x89, 0xdf, 0xbe]);
+        self.emit32(length);
+        self.emit(&[0xba]);
+        self.emit32(generation);
+        // mov rax, step; call rax
+        self.emit(&[0x48, 0xb8]);
+        self.code.extend_from_slice(&step.to_le_bytes());
+        self.emit(&[0xff, 0xd0]);
+        // add r13d, eax; bt rax, 32; jc
+        self.emit(&[0x41, 0x01, 0xc5, 0x48, 0x0f, 0xba, 0xe0, 0x20]);
+        self.exit_if(0x82);
+        self.check_budget();
+    }
+
+    fn inline(&mut self, l: &Layout, opcode: u16, inline: Inline) {
+        // ppc = pc; ir = opcode
+        self.load(RAX, l.pc);
+        self.store(l.ppc, RAX);
+        self.emit(&[0x66, 0xc7, 0x83]);
+        self.emit32(l.ir as u32);
+        self.emit(&opcode.to_le_bytes());
+        match inline {
+            Inline::Moveq(x, value) => {
+                self.store_imm(l.d(x), value);
+                self.move_flags_imm(l, value);
+            },
+            Inline::Move(x, y) => {
+                self.load(RAX, l.d(y));
+                self.store(l.d(x), RAX);
+                self.move_flags(l);
+            },
+            Inline::Tst(y) => {
+                self.load(RAX, l.d(y));
+                self.move_flags(l);
+            },
+            Inline::Clr(y) => {
+                self.store_imm(l.d(y), 0);
+                self.move_flags_imm(l, 0);
+            },
+            Inline::Add(x, y) => {
+                self.load(RAX, l.d(x));
+                self.load(RCX, l.d(y));
+                self.add_32(l, l.d(x));
+            },
+            Inline::Addq(y, quick) => {
+                self.load(RAX, l.d(y));
+                self.load_imm(RCX, quick);
+                self.add_32(l, l.d(y));
+            },
+            Inline::Sub(x, y) => {
+                self.load(RAX, l.d(x));
+                self.load(RCX, l.d(y));
+                self.sub_32(l, Some(l.d(x)));
+            },
+            Inline::Subq(y, quick) => {
+                self.load(RAX, l.d(y));
+                self.load_imm(RCX, quick);
+                self.sub_32(l, Some(l.d(y)));
+            },
+            Inline::Cmp(x, y) => {
+                self.load(RAX, l.d(x));
+                self.load(RCX, l.d(y));
+                self.sub_32(l, None);
+            },
+        }
+        // add dword [rbx+pc], 2; add r13d, units
+        self.emit(&[0x83, 0x83]);
+        self.emit32(l.pc as u32);
+        self.emit(&[2, 0x41, 0x81, 0xc5]);
+        self.emit32(inline.units());
+        self.check_budget();
+    }
+
+    // common::move_flags, of the long in EAX
+    fn move_flags(&mut self, l: &Layout) {
+        self.store(l.not_z_flag, RAX);
+        self.shr64(RAX, 24);
+        self.store(l.n_flag, RAX);
+        self.store_imm(l.v_flag, 0);
+        self.store_imm(l.c_flag, 0);
+    }
+
+    fn move_flags_imm(&mut self, l: &Layout, value: u32) {
+        self.store_imm(l.not_z_flag, value);
+        self.store_imm(l.n_flag, value >> 24);
+        self.store_imm(l.v_flag, 0);
+        self.store_imm(l.c_flag, 0);
+    }
+
+    // common::add_32, of dst in RAX and src in RCX, into the register
+    fn add_32(&mut self, l: &Layout, dst: i32) {
+        // res = dst + src, in 64 bits
+        self.op64(0x89, RDX, RAX);
+        self.op64(0x01, RDX, RCX);
+        self.store(dst, RDX);
+        self.store(l.not_z_flag, RDX);
+        // n = c = x = res >> 24
+        self.op64(0x89, RSI, RDX);
+        self.shr64(RSI, 24);
+        self.store(l.n_flag, RSI);
+        self.store(l.c_flag, RSI);
+        self.store(l.x_flag, RSI);
+        // v = ((src ^ res) & (dst ^ res)) >> 24
+        self.op64(0x31, RCX, RDX);
+        self.op64(0x31, RAX, RDX);
+        self.op64(0x21, RAX, RCX);
+        self.shr64(RAX, 24);
+        self.store(l.v_flag, RAX);
+    }
+
+    // common::sub_32 (or common::cmp_32, which keeps the result and X to
+    // itself), of dst in RAX and src in RCX
+    fn sub_32(&mut self, l: &Layout, dst: Option<i32>) {
+        // res = dst - src, in 64 bits
+        self.op64(0x89, RDX, RAX);
+        self.op64(0x29, RDX, RCX);
+        if let Some(dst) = dst {
+            self.store(dst, RDX);
+        }
+        self.store(l.not_z_flag, RDX);
+        // n = c (= x) = res >> 24
+        self.op64(0x89, RSI, RDX);
+        self.shr64(RSI, 24);
+        self.store(l.n_flag, RSI);
+        self.store(l.c_flag, RSI);
+        if dst.is_some() {
+            self.store(l.x_flag, RSI);
+        }
+        // v = ((src ^ dst) & (res ^ dst)) >> 24
+        self.op64(0x31, RCX, RAX);
+        self.op64(0x31, RAX, RDX);
+        self.op64(0x21, RAX, RCX);
+        self.shr64(RAX, 24);
+        self.store(l.v_flag, RAX);
+    }
+}
+
+// Executable memory for compiled code, mapped on first use. A page is
+// only ever writable or executable, never both at once, and adding code
+// only makes the pages it is copied to writable for as long as it takes.
+struct CodeBuffer {
+    memory: *mut u8,
+    used: usize,
+    page_size: usize,
+}
+
+// the buffer is only reached through the Jit owning it
+unsafe impl Send for CodeBuffer {}
+
+impl CodeBuffer {
+    fn new() -> CodeBuffer {
+        CodeBuffer { memory: ptr::null_mut(), used: 0, page_size: 0 }
+    }
+
+    // Copies the code in, and returns its offset, unless there's no room
+    fn add(&mut self, code: &[u8]) -> Option<usize> {
+        let offset = (self.used + 15) & !15;
+        if offset + code.len() > CODE_SIZE {
+            return None;
+        }
+        unsafe {
+            if self.memory.is_null() {
+                let memory = libc::mmap(ptr::null_mut(), CODE_SIZE, libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0);
+                if memory == libc::MAP_FAILED {
+                    return None;
+                }
+                self.memory = memory as *mut u8;
+                self.page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+            }
+            // the pages the code goes to, which CODE_SIZE is a multiple of
+            let start = offset & !(self.page_size - 1);
+            let end = (offset + code.len() + self.page_size - 1) & !(self.page_size - 1);
+            let pages = self.memory.add(start) as *mut libc::c_void;
+            if libc::mprotect(pages, end - start, libc::PROT_READ | libc::PROT_WRITE) != 0 {
+                return None;
+            }
+            ptr::copy_nonoverlapping(code.as_ptr(), self.memory.add(offset), code.len());
+            if libc::mprotect(pages, end - start, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return None;
+            }
+        }
+        self.used = offset + code.len();
+        Some(offset)
+    }
+
+    fn block<T: InterruptController, A: AddressBus>(&self, offset: usize) -> Block<T, A> {
+        unsafe { mem::transmute::<*mut u8, Block<T, A>>(self.memory.add(offset)) }
+    }
+
+    fn clear(&mut self) {
+        self.used = 0;
+    }
+}
+
+impl Drop for CodeBuffer {
+    fn drop(&mut self) {
+        if !self.memory.is_null() {
+            unsafe {
+                libc::munmap(self.memory as *mut libc::c_void, CODE_SIZE);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Inline;
+    use cpu::{TestCore, T1FLAG_SET};
+    use ram::{AddressBus, SUPERVISOR_DATA};
+
+    // A copy of the core, with the JIT compiling the code at PC as soon as
+    // it runs. The core first runs the given number of instructions on a
+    // copy of its own, so the decode cache has them.
+    fn jitted(core: &TestCore, instructions: usize) -> TestCore {
+        let mut warm = core.clone();
+        warm.enable_decode_cache();
+        for _ in 0..instructions {
+            warm.execute1();
+        }
+        let mut jitted = core.clone();
+        jitted.enable_jit();
+        jitted.jit.as_mut().unwrap().set_threshold(0);
+        jitted.decode_cache = warm.decode_cache.take();
+        jitted
+    }
+
+    fn flags(core: &TestCore) -> [u32; 5] {
+        [core.n_flag, core.not_z_flag, core.v_flag, core.c_flag, core.x_flag]
+    }
+
+    fn assert_same(interpreted: &TestCore, jitted: &TestCore) {
+        assert_eq!(interpreted.registers(), jitted.registers());
+        assert_eq!(flags(interpreted), flags(jitted));
+        assert_eq!((interpreted.ir, interpreted.ppc), (jitted.ir, jitted.ppc));
+    }
+
+    fn load(core: &mut TestCore, address: u32, words: &[u16]) {
+        for (i, &word) in words.iter().enumerate() {
+            core.mem.write_word(SUPERVISOR_DATA, address + 2 * i as u32, u32::from(word));
+        }
+    }
+
+    #[test]
+    fn inline_instructions_match_their_handlers() {
+        // MOVEQ, MOVE.L, ADD.L, SUB.L, CMP.L, ADDQ.L, SUBQ.L, TST.L, CLR.L
+        let opcodes = [0x7000, 0x72ff, 0x7480, 0x2001, 0x2000, 0xd081, 0xd080, 0x9081, 0x9e80,
+            0xb081, 0xb280, 0x5080, 0x5e81, 0x5180, 0x5381, 0x4a80, 0x4a81, 0x4281];
+        let values = [0, 1, 0x7f, 0x80, 0x7fff_ffff, 0x8000_0000, 0xffff_ffff, 0x1234_5678];
+        for &opcode in &opcodes {
+            assert!(Inline::decode(opcode).is_some(), "{:04x}", opcode);
+            for &d0 in &values {
+                for &d1 in &values {
+                    let mut core = TestCore::new_mem(0x40, &[(opcode >> 8) as u8, opcode as u8]);
+                    core.dar[0] = d0;
+                    core.dar[1] = d1;
+                    core.x_flag = 0x100;
+                    let mut jitted = jitted(&core, 1);
+                    assert_eq!(core.execute1(), jitted.execute1());
+                    assert_same(&core, &jitted);
+                    assert_eq!(1, jitted.jit.as_ref().unwrap().entered());
+                }
+            }
+        }
+    }
+
+    // sums 1..=D0 into D1, then stops
+    // loop: ADD.L D0,D1; SUBQ.L #1,D0; BNE.S loop; STOP #$2700
+    const SUM: &[u8] = &[0xd2, 0x80, 0x53, 0x80, 0x66, 0xfa, 0x4e, 0x72, 0x27, 0x00];
+
+    #[test]
+    fn hot_loops_run_compiled() {
+        let mut interpreted = TestCore::new_mem(0x1000, SUM);
+        interpreted.dar[0] = 1000;
+        let mut jitted = interpreted.clone();
+        jitted.enable_jit();
+        assert_eq!(interpreted.execute(100_000), jitted.execute(100_000));
+        assert_same(&interpreted, &jitted);
+        assert_eq!(500_500, jitted.dar[1]);
+        let jit = jitted.jit.as_ref().unwrap();
+        assert_eq!(1, jit.compiled());
+        assert_eq!(1000 - jit.threshold() as u64, jit.entered());
+    }
+
+    #[test]
+    fn blocks_stop_when_the_budget_runs_out() {
+        // MOVEQ #1,D0; ADD.L D0,D1 (four times); STOP #$2700
+        let code = &[0x70, 0x01, 0xd2, 0x80, 0xd2, 0x80, 0xd2, 0x80, 0xd2, 0x80, 0x4e, 0x72, 0x27, 0x00];
+        let core = TestCore::new_mem(0x40, code);
+        for budget in 1..40 {
+            let mut interpreted = core.clone();
+            let mut jitted = jitted(&core, 6);
+            assert_eq!(interpreted.execute(budget), jitted.execute(budget), "{}", budget);
+            assert_same(&interpreted, &jitted);
+        }
+    }
+
+    #[test]
+    fn exceptions_return_to_the_interpreter() {
+        // loop: ADDQ.L #1,D2; DIVU D1,D0; BRA.S loop
+        // and for zero divides: ADDQ.L #1,D3; RTE
+        let mut core = TestCore::new_mem(0x1000, &[0x52, 0x82, 0x80, 0xc1, 0x60, 0xfa]);
+        load(&mut core, 0x2000, &[0x5283, 0x4e73]);
+        core.mem.write_long(SUPERVISOR_DATA, 5 * 4, 0x2000);
+        core.dar[15] = 0x8000;
+        let mut jitted = core.clone();
+        jitted.enable_jit();
+        for _ in 0..10 {
+            assert_eq!(core.execute(1000), jitted.execute(1000));
+            assert_same(&core, &jitted);
+        }
+        assert!(core.dar[3] > 50);
+        assert!(jitted.jit.as_ref().unwrap().entered() > 50);
+    }
+
+    #[test]
+    fn blocks_writing_to_their_own_page_are_noticed() {
+        // loop: MOVE.W D1,(A0); ADDQ.L #1,D0; ADDQ.L #1,D0; MOVEQ #1,D2;
+        // BRA.S loop, where A0 ends up pointing at the MOVEQ
+        let mut core = TestCore::new_mem(0x40, &[0x30, 0x81, 0x52, 0x80, 0x52, 0x80, 0x74, 0x01, 0x60, 0xf6]);
+        core.dar[1] = 0x7402; // MOVEQ #2,D2
+        core.dar[8] = 0x2000;
+        let mut jitted = core.clone();
+        jitted.enable_jit();
+        for round in 0..20 {
+            if round == 10 {
+                assert!(jitted.jit.as_ref().unwrap().entered() > 0);
+                assert_eq!(1, jitted.dar[2]);
+                core.dar[8] = 0x46;
+                jitted.dar[8] = 0x46;
+            }
+            assert_eq!(core.execute(100), jitted.execute(100));
+            assert_same(&core, &jitted);
+        }
+        assert_eq!(2, jitted.dar[2]);
+    }
+
+    #[test]
+    fn tracing_and_debugging_stay_in_the_interpreter() {
+        let mut core = TestCore::new_mem(0x1000, SUM);
+        core.dar[0] = 100;
+        core.enable_jit();
+        core.debugger.add_breakpoint(0x2000);
+        core.execute(100_000);
+        assert_eq!(5050, core.dar[1]);
+        assert_eq!(0, core.jit.as_ref().unwrap().entered());
+        core.debugger.clear();
+        core.t1_flag = T1FLAG_SET;
+        assert!(!core.may_run_compiled());
+    }
+
+    #[test]
+    fn pending_interrupts_are_taken_by_the_interpreter() {
+        // level 7 interrupts: ADDQ.L #1,D3; RTE
+        let mut core = TestCore::new_mem(0x1000, SUM);
+        load(&mut core, 0x2000, &[0x5283, 0x4e73]);
+        core.mem.write_long(SUPERVISOR_DATA, 31 * 4, 0x2000);
+        core.dar[0] = 1000;
+        core.dar[15] = 0x8000;
+        let mut jitted = core.clone();
+        jitted.enable_jit();
+        for core in [&mut core, &mut jitted] {
+            core.execute(500);
+            core.int_ctrl.request_interrupt(7);
+            core.execute(500);
+        }
+        assert_same(&core, &jitted);
+        assert_eq!(1, jitted.dar[3]);
+        assert!(jitted.jit.as_ref().unwrap().entered() > 0);
+    }
+
+    #[test]
+    fn invalidating_the_decode_cache_drops_compiled_code() {
+        let mut core = TestCore::new_mem(0x1000, SUM);
+        core.dar[0] = 1000;
+        core.enable_jit();
+        core.execute(2000);
+        assert_eq!(1, core.jit.as_ref().unwrap().compiled());
+        core.invalidate_decode_cache();
+        // MOVEQ #5,D1 in place of ADD.L D0,D1
+        core.mem.write_word(SUPERVISOR_DATA, 0x1000, 0x7205);
+        core.execute(100_000);
+        assert_eq!(5, core.dar[1]);
+    }
+}
@@ -0,0 +1,755 @@
+// The paged memory management unit of the 68030. Once TC enables it, every
+// logical address the core accesses is mapped to a physical one, by the
+// transparent translation registers TT0/TT1, the address translation
+// cache (ATC), or a search of the translation tables CRP and SRP point
+// to. Accessing an invalid, write protected or supervisor only page ends
+// in a bus error at the logical address. The PMMU instructions (PMOVE,
+// PFLUSH, PLOAD and PTEST) are the F-line instructions with coprocessor
+// id 0. Cache inhibition isn't modelled, and table searches take no time.
+use cpu::{Core, Cycles, Exception, Result, EXCEPTION_UNIMPLEMENTED_1111};
+use cpu::effective_address;
+use ram::{AddressBus, BusError, BusResult, SUPERVISOR_DATA};
+use savestate::{self, Snapshot, StateError, StateReader, StateWriter};
+
+// the coprocessor id of the PMMU in the F-line instructions
+pub const MMU_CPID: u16 = 0;
+
+pub const EXCEPTION_MMU_CONFIGURATION: u8 = 56;
+
+pub const TC_E: u32 = 0x8000_0000;
+pub const TC_SRE: u32 = 0x0200_0000; // use SRP for supervisor accesses
+pub const TC_FCL: u32 = 0x0100_0000; // look the function code up first
+const TC_MASK: u32 = 0x83ff_ffff;
+
+pub const TT_E: u32 = 0x8000;
+const TT_RW: u32 = 0x0200; // matches reads rather than writes, unless RWM
+const TT_RWM: u32 = 0x0100;
+const TT_MASK: u32 = 0xffff_8777;
+
+// the limit and descriptor type in the upper longword, the table address
+// in the lower one
+const ROOT_POINTER_MASK: u64 = 0xffff_0003_ffff_fff0;
+
+pub const MMUSR_B: u16 = 0x8000; // bus error
+pub const MMUSR_L: u16 = 0x4000; // limit violation
+pub const MMUSR_S: u16 = 0x2000; // supervisor only
+pub const MMUSR_W: u16 = 0x0800; // write protected
+pub const MMUSR_I: u16 = 0x0400; // invalid
+pub const MMUSR_M: u16 = 0x0200; // modified
+pub const MMUSR_T: u16 = 0x0040; // transparent
+pub const MMUSR_N: u16 = 0x0007; // levels searched
+
+// descriptor types
+const DT_INVALID: u32 = 0;
+const DT_PAGE: u32 = 1;
+const DT_SHORT: u32 = 2; // a table of, or a pointer to, 4 byte descriptors
+const DT_LONG: u32 = 3; // the same, of 8 byte ones
+
+const DESCRIPTOR_WP: u32 = 0x004;
+const DESCRIPTOR_U: u32 = 0x008;
+const DESCRIPTOR_M: u32 = 0x010;
+const DESCRIPTOR_S: u32 = 0x100; // long descriptors only
+const LOWER_LIMIT: u32 = 0x8000_0000;
+
+const ATC_ENTRIES: usize = 22;
+
+// Where table searches read and update descriptors: physical memory, in
+// the supervisor data space
+pub trait TableBus {
+    fn read_descriptor(&mut self, address: u32) -> BusResult<u32>;
+    fn write_descriptor(&mut self, address: u32, value: u32) -> BusResult<()>;
+}
+
+impl<A: AddressBus> TableBus for A {
+    fn read_descriptor(&mut self, address: u32) -> BusResult<u32> {
+        self.try_read_long(SUPERVISOR_DATA, address)
+    }
+    fn write_descriptor(&mut self, address: u32, value: u32) -> BusResult<()> {
+        self.try_write_long(SUPERVISOR_DATA, address, value)
+    }
+}
+
+// Reads descriptors for a search that must not have side effects
+struct Peek<'a, A: 'a>(&'a mut A);
+
+impl<'a, A: AddressBus> TableBus for Peek<'a, A> {
+    fn read_descriptor(&mut self, address: u32) -> BusResult<u32> {
+        (0..4).try_fold(0, |value, i| Ok(value << 8 | self.0.peek_byte(SUPERVISOR_DATA, address.wrapping_add(i))?))
+    }
+    fn write_descriptor(&mut self, _address: u32, _value: u32) -> BusResult<()> {
+        Err(BusError)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct AtcEntry {
+    fc: u32,
+    logical: u32, // page addresses
+    physical: u32,
+    // the MMUSR bits S, W and M of the search, and B if it failed
+    status: u16,
+}
+
+struct Search {
+    physical: u32,
+    status: u16, // MMUSR bits, with the levels searched
+    descriptor: u32, // the address of the last descriptor fetched
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mmu {
+    pub tc: u32,
+    pub crp: u64,
+    pub srp: u64,
+    pub tt0: u32,
+    pub tt1: u32,
+    pub mmusr: u16,
+    atc: Vec<AtcEntry>, // the oldest entry first
+}
+
+fn low_bits(bits: u32) -> u32 {
+    if bits >= 32 { 0xffff_ffff } else { (1 << bits) - 1 }
+}
+
+fn fetch<B: TableBus + ?Sized>(bus: &mut B, address: u32, long: bool) -> BusResult<(u32, u32)> {
+    let first = bus.read_descriptor(address)?;
+    let second = if long { bus.read_descriptor(address.wrapping_add(4))? } else { 0 };
+    Ok((first, second))
+}
+
+// The page size and the widths of the initial shift and the table
+// indices must add up to the 32 address bits
+fn valid_translation_control(tc: u32) -> bool {
+    let page_size = (tc >> 20) & 0xf;
+    let mut bits = page_size + ((tc >> 16) & 0xf);
+    for shift in [12, 8, 4, 0].iter() {
+        let width = (tc >> shift) & 0xf;
+        if width == 0 {
+            break;
+        }
+        bits += width;
+    }
+    page_size >= 8 && (tc >> 12) & 0xf != 0 && bits == 32
+}
+
+impl Mmu {
+    pub fn new() -> Mmu {
+        Mmu::default()
+    }
+    // Reset disables translation, and leaves the root pointers alone
+    pub fn reset(&mut self) {
+        self.tc = 0;
+        self.tt0 &= !TT_E;
+        self.tt1 &= !TT_E;
+        self.mmusr = 0;
+        self.flush();
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.tc & TC_E != 0
+    }
+    // PFLUSHA, and loading a register without FD
+    pub fn flush(&mut self) {
+        self.atc.clear();
+    }
+    // PFLUSH drops the entries whose function code matches under the mask,
+    // and, if given, that map the page of the address
+    fn flush_matching(&mut self, fc: u32, mask: u32, address: Option<u32>) {
+        let page = address.map(|address| address & !self.page_mask());
+        self.atc.retain(|entry| (entry.fc ^ fc) & mask != 0 || (page.is_some() && page != Some(entry.logical)));
+    }
+
+    fn page_mask(&self) -> u32 {
+        low_bits((self.tc >> 20) & 0xf)
+    }
+    fn transparent(&self, address: u32, fc: u32, write: bool) -> bool {
+        [self.tt0, self.tt1].iter().any(|&tt| {
+            let address_mask = (tt >> 16) & 0xff;
+            tt & TT_E != 0
+                && ((address >> 24) ^ (tt >> 24)) & !address_mask & 0xff == 0
+                && (fc ^ (tt >> 4)) & !tt & 7 == 0
+                && (tt & TT_RWM != 0 || (tt & TT_RW != 0) != write)
+        })
+    }
+    fn lookup(&self, fc: u32, address: u32) -> Option<usize> {
+        let logical = address & !self.page_mask();
+        self.atc.iter().position(|entry| entry.fc == fc && entry.logical == logical)
+    }
+
+    // Maps a logical address to a physical one, or faults. The first write
+    // to a page the ATC doesn't know is modified searches the tables
+    // again, to set its M bit.
+    pub fn translate<B: TableBus + ?Sized>(&mut self, bus: &mut B, address: u32, fc: u32, write: bool) -> BusResult<u32> {
+        if self.transparent(address, fc, write) {
+            return Ok(address);
+        }
+        let entry = match self.lookup(fc, address) {
+            Some(i) if !write || self.atc[i].status & (MMUSR_B | MMUSR_W | MMUSR_M) != 0 => self.atc[i],
+            _ => self.load(bus, address, fc, write),
+        };
+        let supervisor_only = entry.status & MMUSR_S != 0 && fc & 4 == 0;
+        let write_protected = entry.status & MMUSR_W != 0 && write;
+        if entry.status & MMUSR_B != 0 || supervisor_only || write_protected {
+            Err(BusError)
+        } else {
+            Ok(entry.physical | (address & self.page_mask()))
+        }
+    }
+
+    // Maps a logical address for a debugger or tracer, as a read would,
+    // but leaving the ATC and the descriptors alone
+    pub fn peek_translate<A: AddressBus>(&self, bus: &mut A, address: u32, fc: u32) -> BusResult<u32> {
+        if self.transparent(address, fc, false) {
+            return Ok(address);
+        }
+        let page_mask = self.page_mask();
+        let (physical, status) = match self.lookup(fc, address) {
+            Some(i) => (self.atc[i].physical, self.atc[i].status),
+            None => {
+                let search = self.search(&mut Peek(bus), address, fc, false, 7, false);
+                let invalid = search.status & (MMUSR_B | MMUSR_L | MMUSR_I) != 0;
+                (search.physical & !page_mask, if invalid { MMUSR_B } else { search.status & MMUSR_S })
+            },
+        };
+        if status & MMUSR_B != 0 || (status & MMUSR_S != 0 && fc & 4 == 0) {
+            Err(BusError)
+        } else {
+            Ok(physical | (address & page_mask))
+        }
+    }
+
+    // PLOAD searches the tables, and replaces the ATC entry of the page
+    fn load<B: TableBus + ?Sized>(&mut self, bus: &mut B, address: u32, fc: u32, write: bool) -> AtcEntry {
+        let search = self.search(bus, address, fc, write, 7, true);
+        let page_mask = self.page_mask();
+        let mut status = search.status & (MMUSR_S | MMUSR_W | MMUSR_M);
+        if search.status & (MMUSR_B | MMUSR_L | MMUSR_I) != 0 {
+            status |= MMUSR_B;
+        }
+        let entry = AtcEntry { fc, logical: address & !page_mask, physical: search.physical & !page_mask, status };
+        if let Some(i) = self.lookup(fc, address) {
+            self.atc.remove(i);
+        } else if self.atc.len() == ATC_ENTRIES {
+            self.atc.remove(0);
+        }
+        self.atc.push(entry);
+        entry
+    }
+
+    // PTEST reports in MMUSR what the ATC (at level 0) or a search of the
+    // tables down to the level knows of the address, and returns the
+    // address of the last descriptor the search fetched
+    fn test<B: TableBus + ?Sized>(&mut self, bus: &mut B, address: u32, fc: u32, write: bool, level: u16) -> u32 {
+        if level == 0 {
+            self.mmusr = if self.transparent(address, fc, write) {
+                MMUSR_T
+            } else {
+                match self.lookup(fc, address) {
+                    Some(i) if self.atc[i].status & MMUSR_B != 0 => MMUSR_B | MMUSR_I,
+                    Some(i) => self.atc[i].status & (MMUSR_W | MMUSR_M),
+                    None => MMUSR_I,
+                }
+            };
+            return 0;
+        }
+        let search = self.search(bus, address, fc, write, level, false);
+        // supervisor only pages are only reported to user accesses
+        let reported = if fc & 4 != 0 { !MMUSR_S } else { 0xffff };
+        self.mmusr = search.status & reported;
+        search.descriptor
+    }
+
+    // Walks the translation tables for the address, at most the given
+    // number of levels down. Updating sets the U bit of the descriptors
+    // on the way, and the M bit of the page descriptor for a write.
+    fn search<B: TableBus + ?Sized>(&self, bus: &mut B, address: u32, fc: u32, write: bool, max_levels: u16, update: bool) -> Search {
+        let mut search = Search { physical: 0, status: 0, descriptor: 0 };
+        // the table indices, and how many address bits each takes, the
+        // function code lookup taking none
+        let mut indices = [(0, 0); 5];
+        let mut count = 0;
+        if self.tc & TC_FCL != 0 {
+            indices[0] = (fc & 7, 0);
+            count = 1;
+        }
+        let mut remaining = 32 - ((self.tc >> 16) & 0xf);
+        for shift in [12, 8, 4, 0].iter() {
+            let width = (self.tc >> shift) & 0xf;
+            if width == 0 || width > remaining {
+                break;
+            }
+            remaining -= width;
+            indices[count] = ((address >> remaining) & low_bits(width), width);
+            count += 1;
+        }
+        let root = if self.tc & TC_SRE != 0 && fc & 4 != 0 { self.srp } else { self.crp };
+        // of the descriptor in hand: the type, the address field, and the
+        // limit of a root pointer or long table descriptor
+        let mut dt = (root >> 32) as u32 & 3;
+        let mut address_field = root as u32;
+        let mut limit = Some((root >> 32) as u32);
+        // the low address bits an early page descriptor passes through
+        let mut offset_bits = 32 - ((self.tc >> 16) & 0xf);
+        let mut level = 0;
+        loop {
+            match dt {
+                DT_INVALID => {
+                    search.status |= MMUSR_I;
+                    break;
+                },
+                DT_PAGE => {
+                    search.physical = (address_field & !0xff).wrapping_add(address & low_bits(offset_bits));
+                    break;
+                },
+                _ => {},
+            }
+            if search.status & MMUSR_N >= max_levels {
+                break;
+            }
+            if level == count {
+                search.status |= MMUSR_I;
+                break;
+            }
+            let (index, width) = indices[level];
+            if let Some(limit) = limit {
+                let bound = (limit >> 16) & 0x7fff;
+                let lower = limit & LOWER_LIMIT != 0;
+                if (lower && index < bound) || (!lower && index > bound) {
+                    search.status |= MMUSR_L | MMUSR_I;
+                    break;
+                }
+            }
+            let mut long = dt == DT_LONG;
+            let mut descriptor_address = (address_field & !0xf).wrapping_add(index * if long { 8 } else { 4 });
+            level += 1;
+            offset_bits -= width;
+            let mut fetched = fetch(bus, descriptor_address, long);
+            // a table descriptor where the page descriptor is due points to
+            // it instead (an indirect descriptor)
+            if let Ok((first, second)) = fetched {
+                if level == count && first & 3 >= DT_SHORT {
+                    search.status += 1;
+                    descriptor_address = if long { second } else { first } & !3;
+                    long = first & 3 == DT_LONG;
+                    fetched = fetch(bus, descriptor_address, long);
+                }
+            }
+            let (first, second) = match fetched {
+                Ok(descriptor) => descriptor,
+                Err(_) => {
+                    search.status |= MMUSR_B | MMUSR_I;
+                    break;
+                },
+            };
+            search.status += 1;
+            search.descriptor = descriptor_address;
+            dt = first & 3;
+            if first & DESCRIPTOR_WP != 0 {
+                search.status |= MMUSR_W;
+            }
+            if long && first & DESCRIPTOR_S != 0 {
+                search.status |= MMUSR_S;
+            }
+            if dt == DT_PAGE && first & DESCRIPTOR_M != 0 {
+                search.status |= MMUSR_M;
+            }
+            if update && dt != DT_INVALID {
+                let mut updated = first | DESCRIPTOR_U;
+                if dt == DT_PAGE && write && search.status & MMUSR_W == 0 {
+                    updated |= DESCRIPTOR_M;
+                    search.status |= MMUSR_M;
+                }
+                if updated != first && bus.write_descriptor(descriptor_address, updated).is_err() {
+                    search.status |= MMUSR_B | MMUSR_I;
+                    break;
+                }
+            }
+            address_field = if long { second } else { first };
+            limit = if long && dt != DT_PAGE { Some(first) } else { None };
+        }
+        search
+    }
+}
+
+fn f_line(ir: u16, pc: u32) -> Exception {
+    Exception::UnimplementedInstruction(ir, pc, EXCEPTION_UNIMPLEMENTED_1111)
+}
+
+// The address of a control addressing mode operand, or None for the other
+// modes. The PC relative ones aren't alterable.
+fn control_address<T: Core>(core: &mut T, ir: u16, alterable: bool) -> Result<Option<u32>> {
+    let address = match (ir >> 3) & 7 {
+        2 => effective_address::address_indirect_ay(core)?,
+        5 => effective_address::displacement_ay(core)?,
+        6 => effective_address::index_ay(core)?,
+        7 => match ir & 7 {
+            0 => effective_address::absolute_word(core)?,
+            1 => effective_address::absolute_long(core)?,
+            2 if !alterable => effective_address::displacement_pc(core)?,
+            3 if !alterable => effective_address::index_pc(core)?,
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(address))
+}
+
+// The function code field of PFLUSH, PLOAD and PTEST
+fn function_code<T: Core>(core: &mut T, field: u16) -> Option<u32> {
+    match field {
+        0b00000 => Some(*core.sfc()),
+        0b00001 => Some(*core.dfc()),
+        0b01000..=0b01111 => Some(dar!(core)[usize::from(field & 7)] & 7),
+        0b10000..=0b10111 => Some(u32::from(field & 7)),
+        _ => None,
+    }
+}
+
+fn pmmu<T: Core>(core: &mut T) -> &mut Mmu {
+    core.mmu().expect("a PMMU instruction without a PMMU").0
+}
+
+// Executes an F-line instruction with the coprocessor id of the PMMU, on
+// a 68030. Only the general instructions exist, all of them privileged.
+pub fn execute<T: Core>(core: &mut T) -> Result<Cycles> {
+    let ir = ir!(core);
+    let pc = pc!(core).wrapping_sub(2);
+    if (ir >> 6) & 7 != 0 {
+        return Err(f_line(ir, pc));
+    }
+    if s_flag!(core) == 0 {
+        return Err(Exception::PrivilegeViolation(ir, pc));
+    }
+    let command = core.read_imm_u16()?;
+    match command >> 13 {
+        0 | 2 | 3 => pmove(core, command, ir, pc),
+        1 => pflush_or_pload(core, command, ir, pc),
+        4 => ptest(core, command, ir, pc),
+        _ => Err(f_line(ir, pc)),
+    }
+}
+
+// PMOVE between memory and TC, SRP, CRP, TT0, TT1 or MMUSR. Loading any
+// but MMUSR flushes the ATC, unless FD is set, and loading an invalid
+// TC or root pointer is a configuration error.
+fn pmove<T: Core>(core: &mut T, command: u16, ir: u16, pc: u32) -> Result<Cycles> {
+    let register = (command >> 13, (command >> 10) & 7);
+    let size = match register {
+        (2, 0) | (0, 2) | (0, 3) => 4, // TC, TT0 and TT1
+        (2, 2) | (2, 3) => 8, // SRP and CRP
+        (3, 0) => 2, // MMUSR
+        _ => return Err(f_line(ir, pc)),
+    };
+    let flush = command & 0x100 == 0;
+    if command & 0xff != 0 || (size == 2 && !flush) {
+        return Err(f_line(ir, pc));
+    }
+    let to_memory = command & 0x200 != 0;
+    let address = control_address(core, ir, to_memory)?.ok_or_else(|| f_line(ir, pc))?;
+    if to_memory {
+        let value = {
+            let mmu = pmmu(core);
+            match register {
+                (2, 0) => u64::from(mmu.tc),
+                (2, 2) => mmu.srp,
+                (2, 3) => mmu.crp,
+                (0, 2) => u64::from(mmu.tt0),
+                (0, 3) => u64::from(mmu.tt1),
+                _ => u64::from(mmu.mmusr),
+            }
+        };
+        match size {
+            2 => core.write_word(address, value as u32)?,
+            4 => core.write_long(address, value as u32)?,
+            _ => {
+                core.write_long(address, (value >> 32) as u32)?;
+                core.write_long(address.wrapping_add(4), value as u32)?;
+            },
+        }
+        return Ok(Cycles(if size == 8 { 20 } else { 16 }));
+    }
+    let value = match size {
+        2 => u64::from(core.read_word(address)?),
+        4 => u64::from(core.read_long(address)?),
+        _ => (u64::from(core.read_long(address)?) << 32) | u64::from(core.read_long(address.wrapping_add(4))?),
+    };
+    let mmu = pmmu(core);
+    match register {
+        (2, 0) => {
+            let tc = value as u32 & TC_MASK;
+            if tc & TC_E != 0 && !valid_translation_control(tc) {
+                return Err(Exception::Trap(EXCEPTION_MMU_CONFIGURATION, 0));
+            }
+            mmu.tc = tc;
+        },
+        (2, 2) | (2, 3) => {
+            if (value >> 32) as u32 & 3 == DT_INVALID {
+                return Err(Exception::Trap(EXCEPTION_MMU_CONFIGURATION, 0));
+            }
+            if register.1 == 2 {
+                mmu.srp = value & ROOT_POINTER_MASK;
+            } else {
+                mmu.crp = value & ROOT_POINTER_MASK;
+            }
+        },
+        (0, 2) => mmu.tt0 = value as u32 & TT_MASK,
+        (0, 3) => mmu.tt1 = value as u32 & TT_MASK,
+        _ => mmu.mmusr = value as u16,
+    }
+    if size != 2 && flush {
+        mmu.flush();
+    }
+    Ok(Cycles(if size == 8 { 24 } else { 20 }))
+}
+
+// PLOAD, PFLUSHA, PFLUSH fc,#mask and PFLUSH fc,#mask,<ea>
+fn pflush_or_pload<T: Core>(core: &mut T, command: u16, ir: u16, pc: u32) -> Result<Cycles> {
+    let mode = (command >> 10) & 7;
+    if mode == 1 && command & 0x3ff == 0 {
+        pmmu(core).flush();
+        return Ok(Cycles(12));
+    }
+    let fc = function_code(core, command & 0x1f).ok_or_else(|| f_line(ir, pc))?;
+    match mode {
+        0 if command & 0x1e0 == 0 => {
+            let address = control_address(core, ir, true)?.ok_or_else(|| f_line(ir, pc))?;
+            let (mmu, bus) = core.mmu().expect("a PMMU instruction without a PMMU");
+            mmu.load(bus, address, fc, command & 0x200 == 0);
+            Ok(Cycles(24))
+        },
+        4 | 6 if command & 0x300 == 0 => {
+            let mask = u32::from((command >> 5) & 7);
+            let address = if mode == 6 {
+                Some(control_address(core, ir, true)?.ok_or_else(|| f_line(ir, pc))?)
+            } else {
+                None
+            };
+            pmmu(core).flush_matching(fc, mask, address);
+            Ok(Cycles(12))
+        },
+        _ => Err(f_line(ir, pc)),
+    }
+}
+
+// PTEST fc,<ea>,#level, optionally loading An with the address of the
+// last descriptor fetched
+fn ptest<T: Core>(core: &mut T, command: u16, ir: u16, pc: u32) -> Result<Cycles> {
+    let level = (command >> 10) & 7;
+    let with_register = command & 0x100 != 0;
+    if level == 0 && with_register {
+        return Err(f_line(ir, pc));
+    }
+    let fc = function_code(core, command & 0x1f).ok_or_else(|| f_line(ir, pc))?;
+    let address = control_address(core, ir, true)?.ok_or_else(|| f_line(ir, pc))?;
+    let descriptor = {
+        let (mmu, bus) = core.mmu().expect("a PMMU instruction without a PMMU");
+        mmu.test(bus, address, fc, command & 0x200 == 0, level)
+    };
+    if with_register {
+        dar!(core)[8 + usize::from((command >> 5) & 7)] = descriptor;
+    }
+    Ok(Cycles(if level == 0 { 12 } else { 24 + 8 * i32::from(level) }))
+}
+
+impl Snapshot for Mmu {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u32(self.tc);
+        for &root in [self.crp, self.srp].iter() {
+            writer.put_u32((root >> 32) as u32);
+            writer.put_u32(root as u32);
+        }
+        writer.put_u32(self.tt0);
+        writer.put_u32(self.tt1);
+        writer.put_u16(self.mmusr);
+        writer.put_u8(self.atc.len() as u8);
+        for entry in self.atc.iter() {
+            writer.put_u8(entry.fc as u8);
+            writer.put_u32(entry.logical);
+            writer.put_u32(entry.physical);
+            writer.put_u16(entry.status);
+        }
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.tc = reader.get_u32()?;
+        self.crp = (u64::from(reader.get_u32()?) << 32) | u64::from(reader.get_u32()?);
+        self.srp = (u64::from(reader.get_u32()?) << 32) | u64::from(reader.get_u32()?);
+        self.tt0 = reader.get_u32()?;
+        self.tt1 = reader.get_u32()?;
+        self.mmusr = reader.get_u16()?;
+        let entries = usize::from(reader.get_u8()?);
+        if entries > ATC_ENTRIES {
+            return Err(StateError::Invalid("atc entries"));
+        }
+        self.atc.clear();
+        for _ in 0..entries {
+            let fc = u32::from(reader.get_u8()?);
+            let logical = reader.get_u32()?;
+            let physical = reader.get_u32()?;
+            let status = reader.get_u16()?;
+            self.atc.push(AtcEntry { fc, logical, physical, status });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EXCEPTION_MMU_CONFIGURATION, MMUSR_I, TC_E};
+    use cpu::{CpuType, TestCore, EXCEPTION_BUS_ERROR, EXCEPTION_PRIVILEGE_VIOLATION, EXCEPTION_UNIMPLEMENTED_1111};
+    use ram::{AddressBus, BusError, SUPERVISOR_DATA};
+
+    // 4K pages and two levels of 10 bit indices, from a short table at
+    // 0x10000 (whose upper limit lets every index through)
+    const TC: u32 = TC_E | 0x00c0_aa00;
+    const CRP: u64 = 0x7fff_0002_0001_0000;
+
+    // The first level A entry has the level B table at 0x11000, which
+    // maps page 0 to itself and page 1 to 0x5000
+    fn core_030(words: &[u16]) -> TestCore {
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        let mut cpu = TestCore::new_mem_init(0x40, &bytes, 0);
+        cpu.set_cpu_type(CpuType::M68030);
+        cpu.dar[15] = 0x800;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x10000, 0x0001_1002);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x11000, 0x0000_0001);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x11004, 0x0000_5001);
+        cpu
+    }
+    fn enable(cpu: &mut TestCore) {
+        let mmu = cpu.mmu.as_mut().unwrap();
+        mmu.tc = TC;
+        mmu.crp = CRP;
+    }
+
+    #[test]
+    fn accesses_are_translated_and_mark_the_descriptors() {
+        // 2038,1234 is MOVE.L ($1234).W, D0, 21c0,1238 MOVE.L D0, ($1238).W
+        // and 2238,3000 MOVE.L ($3000).W, D1
+        let mut cpu = core_030(&[0x2038, 0x1234, 0x21c0, 0x1238, 0x2238, 0x3000]);
+        enable(&mut cpu);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x5234, 0xdead_beef);
+        cpu.mem.write_long(SUPERVISOR_DATA, u32::from(EXCEPTION_BUS_ERROR) * 4, 0x400);
+        cpu.execute1();
+        assert_eq!(0xdead_beef, cpu.dar[0]);
+        // used
+        assert_eq!(0x0001_100a, cpu.mem.read_long(SUPERVISOR_DATA, 0x10000));
+        assert_eq!(0x0000_5009, cpu.mem.read_long(SUPERVISOR_DATA, 0x11004));
+        cpu.execute1();
+        assert_eq!(0xdead_beef, cpu.mem.read_long(SUPERVISOR_DATA, 0x5238));
+        // and modified
+        assert_eq!(0x0000_5019, cpu.mem.read_long(SUPERVISOR_DATA, 0x11004));
+        // page 3 is invalid
+        cpu.execute1();
+        assert_eq!(0x400, cpu.pc);
+    }
+
+    #[test]
+    fn peeking_translates_without_side_effects() {
+        let mut cpu = core_030(&[]);
+        enable(&mut cpu);
+        cpu.mem.write_byte(SUPERVISOR_DATA, 0x5234, 0x91);
+        assert_eq!(Ok(0x91), cpu.peek_byte(SUPERVISOR_DATA, 0x1234));
+        assert_eq!(Err(BusError), cpu.peek_byte(SUPERVISOR_DATA, 0x3000));
+        // and so does poking
+        assert_eq!(Ok(()), cpu.poke_byte(SUPERVISOR_DATA, 0x1235, 0x92));
+        assert_eq!(0x92, cpu.mem.read_byte(SUPERVISOR_DATA, 0x5235));
+        assert_eq!(Err(BusError), cpu.poke_byte(SUPERVISOR_DATA, 0x3000, 0x92));
+        // the descriptors aren't marked used
+        assert_eq!(0x0001_1002, cpu.mem.read_long(SUPERVISOR_DATA, 0x10000));
+        assert_eq!(0x0000_5001, cpu.mem.read_long(SUPERVISOR_DATA, 0x11004));
+        assert!(cpu.mmu.as_ref().unwrap().atc.is_empty());
+    }
+
+    #[test]
+    fn protection_and_transparent_translation() {
+        let mut cpu = core_030(&[]);
+        enable(&mut cpu);
+        // page 2 is write protected
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x11008, 0x0000_6005);
+        let mmu = cpu.mmu.as_mut().unwrap();
+        assert_eq!(Ok(0x6010), mmu.translate(&mut cpu.mem, 0x2010, 1, false));
+        assert_eq!(Err(BusError), mmu.translate(&mut cpu.mem, 0x2010, 1, true));
+        assert_eq!(Err(BusError), mmu.translate(&mut cpu.mem, 0x4012_3456, 1, false));
+        // TT0 passes 0x40xxxxxx through, for any function code and access
+        mmu.tt0 = 0x4000_8107;
+        assert_eq!(Ok(0x4012_3456), mmu.translate(&mut cpu.mem, 0x4012_3456, 1, true));
+    }
+
+    #[test]
+    fn pmove_checks_the_configuration() {
+        // f011,4c00 is PMOVE (A1), CRP, f010,4000 PMOVE (A0), TC, f012,4200
+        // PMOVE TC, (A2) and f013,4000 PMOVE (A3), TC
+        let mut cpu = core_030(&[0xf011, 0x4c00, 0xf010, 0x4000, 0xf012, 0x4200, 0xf013, 0x4000]);
+        cpu.dar[8..12].copy_from_slice(&[0x200, 0x208, 0x220, 0x210]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x200, TC);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x208, (CRP >> 32) as u32);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x20c, CRP as u32);
+        // the indices only cover 22 bits
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x210, TC_E | 0x00c0_a000);
+        cpu.mem.write_long(SUPERVISOR_DATA, u32::from(EXCEPTION_MMU_CONFIGURATION) * 4, 0x400);
+        for _ in 0..4 {
+            cpu.execute1();
+        }
+        assert_eq!(CRP, cpu.mmu.as_ref().unwrap().crp);
+        assert_eq!(TC, cpu.mmu.as_ref().unwrap().tc);
+        assert_eq!(TC, cpu.mem.read_long(SUPERVISOR_DATA, 0x220));
+        assert_eq!(0x400, cpu.pc);
+    }
+
+    #[test]
+    fn ptest_pload_and_pflush() {
+        // f010,9f55 is PTESTR #5, (A0), #7, A2, f010,8215 PTESTR #5, (A0), #0,
+        // f010,2215 PLOADR #5, (A0), f000,2400 PFLUSHA and f011,9e15
+        // PTESTR #5, (A1), #7
+        let mut cpu = core_030(&[0xf010, 0x9f55, 0xf010, 0x8215, 0xf010, 0x2215, 0xf010, 0x8215,
+            0xf000, 0x2400, 0xf011, 0x9e15]);
+        enable(&mut cpu);
+        cpu.dar[8] = 0x1234;
+        cpu.dar[9] = 0x3000;
+        let mmusr = |cpu: &TestCore| cpu.mmu.as_ref().unwrap().mmusr;
+        cpu.execute1();
+        assert_eq!((2, 0x11004), (mmusr(&cpu), cpu.dar[10]));
+        // without setting the U bit
+        assert_eq!(0x0000_5001, cpu.mem.read_long(SUPERVISOR_DATA, 0x11004));
+        // PTEST doesn't load the ATC, while PLOAD does
+        cpu.execute1();
+        assert_eq!(MMUSR_I, mmusr(&cpu));
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(0, mmusr(&cpu));
+        cpu.execute1();
+        assert!(cpu.mmu.as_ref().unwrap().atc.iter().all(|entry| entry.fc != 5));
+        cpu.execute1();
+        assert_eq!(MMUSR_I | 2, mmusr(&cpu));
+    }
+
+    #[test]
+    fn pmmu_instructions_need_a_68030_in_supervisor_mode() {
+        // f000,2400 is PFLUSHA
+        let mut cpu = core_030(&[0xf000, 0x2400]);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.mem.write_long(SUPERVISOR_DATA, u32::from(EXCEPTION_UNIMPLEMENTED_1111) * 4, 0x400);
+        cpu.execute1();
+        assert_eq!(0x400, cpu.pc);
+        let mut cpu = core_030(&[0xf000, 0x2400]);
+        cpu.s_flag = 0;
+        cpu.inactive_ssp = 0x800;
+        cpu.mem.write_long(SUPERVISOR_DATA, u32::from(EXCEPTION_PRIVILEGE_VIOLATION) * 4, 0x500);
+        cpu.execute1();
+        assert_eq!(0x500, cpu.pc);
+    }
+
+    #[test]
+    fn save_state_keeps_the_mmu() {
+        let mut cpu = core_030(&[]);
+        enable(&mut cpu);
+        // TT1 passes all writes through
+        cpu.mmu.as_mut().unwrap().tt1 = 0x00ff_8007;
+        cpu.read_data_long(0x1000).unwrap();
+        let state = cpu.save_state();
+        let mut other = TestCore::new(0x40);
+        assert_eq!(Ok(()), other.load_state(&state));
+        assert_eq!(CpuType::M68030, other.cpu_type());
+        assert_eq!(cpu.mmu, other.mmu);
+        assert_eq!(1, other.mmu.unwrap().atc.len());
+    }
+}
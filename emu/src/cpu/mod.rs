@@ -1,13 +1,32 @@
 // type alias for exception handling
 use std::result;
 pub type Result<T> = result::Result<T, Exception>;
-use interrupts::{InterruptController, AutoInterruptController, SPURIOUS_INTERRUPT};
+use interrupts::{InterruptController, AutoInterruptController, AUTOVECTOR_BASE, SPURIOUS_INTERRUPT, UNINITIALIZED_INTERRUPT};
 use ram::loggingmem::{LoggingMem, OpsLogger};
+use savestate::{self, Snapshot, StateError, StateReader, StateWriter};
+use self::debugger::{Debugger, Register, StopReason};
+use self::trace::Registers;
+use self::fpu::{Fpu, FpuType};
+use self::mmu::{Mmu, TableBus};
+use self::timing::BusTiming;
+use self::decode_cache::DecodeCache;
+#[cfg(feature = "jit")]
+use self::jit::Jit;
 pub type TestCore = ConfiguredCore<AutoInterruptController, LoggingMem<OpsLogger>>;
 pub type Handler<T> = fn(&mut T) -> Result<Cycles>;
 pub type InstructionSet<T> = Vec<Handler<T>>;
-use ram::{AddressBus, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA};
+use ram::{AddressBus, BusResult, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA};
 pub mod ops;
+pub mod fpu;
+pub mod mmu;
+pub mod timing;
+pub mod decode_cache;
+#[cfg(feature = "jit")]
+pub mod jit;
+#[cfg(all(feature = "jit", not(all(target_arch = "x86_64", unix))))]
+compile_error!("the jit feature needs an x86-64 unix host");
+pub mod debugger;
+pub mod trace;
 mod effective_address;
 mod operator;
 
@@ -44,6 +63,12 @@ pub trait Core {
     fn write_program_byte(&mut self, address: u32, value: u32) -> Result<()>;
     fn write_program_word(&mut self, address: u32, value: u32) -> Result<()>;
     fn write_program_long(&mut self, address: u32, value: u32) -> Result<()>;
+    fn read_byte_fc(&mut self, fc: u32, address: u32) -> Result<u32>;
+    fn read_word_fc(&mut self, fc: u32, address: u32) -> Result<u32>;
+    fn read_long_fc(&mut self, fc: u32, address: u32) -> Result<u32>;
+    fn write_byte_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()>;
+    fn write_word_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()>;
+    fn write_long_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()>;
     #[cfg(feature = "fc")]
     fn is_fc_data(&self) -> bool;
     #[cfg(feature = "fc")]
@@ -74,28 +99,109 @@ pub trait Core {
     fn read_imm_u16(&mut self) -> Result<u16>;
     fn read_imm_u32(&mut self) -> Result<u32>;
     fn jump(&mut self, pc: u32);
-    fn push_32(&mut self, value: u32) -> u32;
-    fn pop_32(&mut self) -> u32;
-    fn push_16(&mut self, value: u16) -> u32;
-    fn pop_16(&mut self) -> u16;
-    fn push_sp(&mut self) -> u32;
+    fn push_32(&mut self, value: u32) -> Result<u32>;
+    fn pop_32(&mut self) -> Result<u32>;
+    fn push_16(&mut self, value: u16) -> Result<u32>;
+    fn pop_16(&mut self) -> Result<u16>;
+    fn push_sp(&mut self) -> Result<u32>;
     fn inactive_ssp(&self) -> u32;
     fn inactive_usp(&mut self) -> &mut u32;
+    fn vbr(&mut self) -> &mut u32;
+    fn sfc(&mut self) -> &mut u32;
+    fn dfc(&mut self) -> &mut u32;
+    fn cpu_type(&self) -> CpuType;
+    fn control_register(&self, cr: u16) -> Option<u32>;
+    fn set_control_register(&mut self, cr: u16, value: u32) -> bool;
     fn reset_external_devices(&mut self);
     fn resume_normal_processing(&mut self);
     fn stop_instruction_processing(&mut self);
     fn allow_tas_writeback(&mut self) -> bool;
+    // the FPU (68020), if one is attached
+    fn fpu(&mut self) -> &mut Option<Fpu>;
+    // the PMMU (68030), with the memory its table searches read
+    fn mmu(&mut self) -> Option<(&mut Mmu, &mut dyn TableBus)>;
+    // the address of the instruction being executed
+    fn ppc(&self) -> u32;
+    // BGND, which stops the run loop if background debug mode is enabled
+    fn enter_background_mode(&mut self) -> bool;
+    // whether the core keeps cycle exact timing
+    fn exact_timing(&self) -> bool;
+    // a bus cycle reading data that is thrown away, like the extra word
+    // MOVEM reads from memory; only its timing matters
+    fn discarded_read_cycle(&mut self, address: u32);
+    // the DBcc loop a 68010 runs from its prefetch queue, if it does
+    fn loop_mode(&mut self) -> &mut Option<LoopBuffer>;
+    // the word before the current instruction, as the 68010 has it in its
+    // prefetch queue, read without a bus cycle
+    fn previous_word(&mut self) -> Option<u16>;
+}
+
+// The processor being emulated, which selects the instruction set and
+// the exception stack frames (like Musashi's m68k_set_cpu_type)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CpuType {
+    M68000,
+    M68010, // adds VBR, SFC, DFC, MOVEC, MOVES, RTD and format words
+    M68EC020, // adds the 68020 instructions and addressing modes
+    M68020, // the same, with a 32-bit address bus
+    M68030, // the 68020 instruction set, with an on-chip PMMU
+    Cpu32, // the 68010 with part of the 68020 set, TBL, LPSTOP and BGND (683xx)
+}
+
+impl CpuType {
+    fn to_u8(self) -> u8 {
+        match self {
+            CpuType::M68000 => 0,
+            CpuType::M68010 => 1,
+            CpuType::M68EC020 => 2,
+            CpuType::M68020 => 3,
+            CpuType::M68030 => 4,
+            CpuType::Cpu32 => 5,
+        }
+    }
+    fn from_u8(value: u8) -> Option<CpuType> {
+        match value {
+            0 => Some(CpuType::M68000),
+            1 => Some(CpuType::M68010),
+            2 => Some(CpuType::M68EC020),
+            3 => Some(CpuType::M68020),
+            4 => Some(CpuType::M68030),
+            5 => Some(CpuType::Cpu32),
+            _ => None,
+        }
+    }
+    pub fn is_020(self) -> bool {
+        matches!(self, CpuType::M68EC020 | CpuType::M68020 | CpuType::M68030)
+    }
+    // the address lines the processor drives
+    pub fn address_mask(self) -> u32 {
+        match self {
+            CpuType::M68020 | CpuType::M68030 => 0xffff_ffff,
+            _ => ADDRBUS_MASK,
+        }
+    }
 }
 
 pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     pub pc: u32,
     pub inactive_ssp: u32, // when in user mode
     pub inactive_usp: u32, // when in supervisor mode
+    pub inactive_msp: u32, // when not on the master stack (68020)
     pub ir: u16,
     pub dar: [u32; 16],
     instruction_set: std::sync::Arc<InstructionSet<ConfiguredCore<T, A>>>,
+    cpu_type: CpuType,
+    pub vbr: u32, // the MC68010 control registers
+    pub sfc: u32,
+    pub dfc: u32,
+    pub cacr: u32, // the MC68020 control registers
+    pub caar: u32,
+    address_mask: u32,
+    ppc: u32, // the address of the current instruction
     pub s_flag: u32,
-    pub irq_level: u8,
+    pub m_flag: u32,
+    pub t1_flag: u32,
+    pub irq_level: u8, // the interrupt level last seen
     pub int_mask: u32,
     pub int_ctrl: T,
     pub x_flag: u32,
@@ -104,9 +210,17 @@ pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     pub n_flag: u32,
     pub prefetch_addr: u32,
     pub prefetch_data: u32,
+    loop_mode: Option<LoopBuffer>, // a DBcc loop run from the prefetch queue (68010)
     pub not_z_flag: u32,
     pub processing_state: ProcessingState,
     pub mem: A,
+    pub debugger: Debugger,
+    pub fpu: Option<Fpu>, // the floating point coprocessor (68020)
+    pub mmu: Option<Mmu>, // the paged memory management unit (68030)
+    pub bus_timing: Option<BusTiming>, // cycle exact timing ("cycles" feature)
+    pub decode_cache: Option<DecodeCache>, // predecoded instructions, when enabled
+    #[cfg(feature = "jit")]
+    pub jit: Option<Jit<T, A>>, // native code for hot blocks, when enabled
     #[cfg(feature = "fc")]
     fc_is_data: bool,
 }
@@ -229,6 +343,24 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn write_program_long(&mut self, address: u32, value: u32) -> Result<()> {
         self.write_program_long(address, value)
     }
+    fn read_byte_fc(&mut self, fc: u32, address: u32) -> Result<u32> {
+        self.read_byte_fc(fc, address)
+    }
+    fn read_word_fc(&mut self, fc: u32, address: u32) -> Result<u32> {
+        self.read_word_fc(fc, address)
+    }
+    fn read_long_fc(&mut self, fc: u32, address: u32) -> Result<u32> {
+        self.read_long_fc(fc, address)
+    }
+    fn write_byte_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()> {
+        self.write_byte_fc(fc, address, value)
+    }
+    fn write_word_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()> {
+        self.write_word_fc(fc, address, value)
+    }
+    fn write_long_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()> {
+        self.write_long_fc(fc, address, value)
+    }
     #[cfg(feature = "fc")]
     fn is_fc_data(&self) -> bool {
         self.fc_is_data
@@ -329,19 +461,19 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn jump(&mut self, pc: u32) {
         self.jump(pc)
     }
-    fn push_32(&mut self, value: u32) -> u32 {
+    fn push_32(&mut self, value: u32) -> Result<u32> {
         self.push_32(value)
     }
-    fn pop_32(&mut self) -> u32 {
+    fn pop_32(&mut self) -> Result<u32> {
         self.pop_32()
     }
-    fn push_16(&mut self, value: u16) -> u32 {
+    fn push_16(&mut self, value: u16) -> Result<u32> {
         self.push_16(value)
     }
-    fn pop_16(&mut self) -> u16 {
+    fn pop_16(&mut self) -> Result<u16> {
         self.pop_16()
     }
-    fn push_sp(&mut self) -> u32 {
+    fn push_sp(&mut self) -> Result<u32> {
         self.push_sp()
     }
     fn inactive_ssp(&self) -> u32 {
@@ -350,6 +482,24 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn inactive_usp(&mut self) -> &mut u32 {
         &mut self.inactive_usp
     }
+    fn vbr(&mut self) -> &mut u32 {
+        &mut self.vbr
+    }
+    fn sfc(&mut self) -> &mut u32 {
+        &mut self.sfc
+    }
+    fn dfc(&mut self) -> &mut u32 {
+        &mut self.dfc
+    }
+    fn cpu_type(&self) -> CpuType {
+        self.cpu_type
+    }
+    fn control_register(&self, cr: u16) -> Option<u32> {
+        self.control_register(cr)
+    }
+    fn set_control_register(&mut self, cr: u16, value: u32) -> bool {
+        self.set_control_register(cr, value)
+    }
     fn reset_external_devices(&mut self) {
         self.int_ctrl.reset_external_devices()
     }
@@ -362,9 +512,66 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn allow_tas_writeback(&mut self) -> bool {
         true
     }
+    fn fpu(&mut self) -> &mut Option<Fpu> {
+        &mut self.fpu
+    }
+    fn mmu(&mut self) -> Option<(&mut Mmu, &mut dyn TableBus)> {
+        match self.mmu {
+            Some(ref mut mmu) => Some((mmu, &mut self.mem)),
+            None => None,
+        }
+    }
+    fn ppc(&self) -> u32 {
+        self.ppc
+    }
+    fn enter_background_mode(&mut self) -> bool {
+        self.debugger.enter_background_mode(self.pc)
+    }
+    fn exact_timing(&self) -> bool {
+        self.bus_timing.is_some()
+    }
+    fn discarded_read_cycle(&mut self, address: u32) {
+        if let Some(ref mut timing) = self.bus_timing {
+            let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+            timing.bus_cycles(&mut self.mem, address_space, address & self.address_mask, 2);
+        }
+    }
+    fn loop_mode(&mut self) -> &mut Option<LoopBuffer> {
+        &mut self.loop_mode
+    }
+    fn previous_word(&mut self) -> Option<u16> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        let address = self.ppc.wrapping_sub(2);
+        let high = self.peek_byte(address_space, address).ok()?;
+        let low = self.peek_byte(address_space, address.wrapping_add(1)).ok()?;
+        Some((high << 8 | low) as u16)
+    }
 }
 pub const STACK_POINTER_REG: usize = 15;
 
+// The words of a DBcc loop that a 68010 holds in its prefetch queue in
+// loop mode: the loop instruction at the address, then the DBcc and its
+// displacement. The loop runs from these, without fetching them again.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopBuffer {
+    pub address: u32,
+    pub words: [u16; 3],
+}
+impl LoopBuffer {
+    fn word(&self, address: u32) -> Option<u16> {
+        let offset = address.wrapping_sub(self.address);
+        if offset & 1 == 0 {
+            self.words.get((offset / 2) as usize).copied()
+        } else {
+            None
+        }
+    }
+    // the word after the DBcc, which the 68010 doesn't prefetch in loop mode
+    fn ends_before(&self, address: u32) -> bool {
+        address == self.address.wrapping_add(6)
+    }
+}
+
 #[cfg(feature = "cycles")]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cycles(pub i32);
@@ -401,7 +608,16 @@ pub fn Cycles(_c: i32) {}
 pub type Cycles = ();
 
 pub trait Callbacks {
+    // The instruction hooks are only called when this is true, so they
+    // cost nothing otherwise
+    const INSTRUCTION_HOOKS: bool = false;
+
     fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles>;
+    // after the instruction at pc has been fetched, before it executes
+    fn before_instruction<T: InterruptController, A: AddressBus>(&mut self, _core: &mut ConfiguredCore<T, A>, _pc: u32, _ir: u16) {}
+    // after the instruction, including any exception it caused, has been
+    // processed in the given cycles
+    fn after_instruction<T: InterruptController, A: AddressBus>(&mut self, _core: &mut ConfiguredCore<T, A>, _before: &Registers, _after: &Registers, _cycles: Cycles) {}
 }
 
 struct EmulateAllExceptions;
@@ -422,6 +638,27 @@ pub enum ProcessingState {
 }
 
 impl ProcessingState {
+    fn to_u8(self) -> u8 {
+        match self {
+            ProcessingState::Normal => 0,
+            ProcessingState::Group2Exception => 1,
+            ProcessingState::Group1Exception => 2,
+            ProcessingState::Group0Exception => 3,
+            ProcessingState::Stopped => 4,
+            ProcessingState::Halted => 5,
+        }
+    }
+    fn from_u8(value: u8) -> Option<ProcessingState> {
+        match value {
+            0 => Some(ProcessingState::Normal),
+            1 => Some(ProcessingState::Group2Exception),
+            2 => Some(ProcessingState::Group1Exception),
+            3 => Some(ProcessingState::Group0Exception),
+            4 => Some(ProcessingState::Stopped),
+            5 => Some(ProcessingState::Halted),
+            _ => None,
+        }
+    }
     // The processor is processing an instruction if it is in the normal
     // state or processing a group 2 exception; the processor is not
     // processing an instruction if it is processing a group 0 or a group 1
@@ -442,18 +679,38 @@ impl ProcessingState {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum AccessType {Read, Write}
 use ram::AddressSpace;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Exception {
     AddressError { address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace},
+    BusError { address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace},
     IllegalInstruction(u16, u32), // ir, pc
     Trap(u8, i32),                // trap no, exception cycles
     PrivilegeViolation(u16, u32), // ir, pc
     UnimplementedInstruction(u16, u32, u8), // ir, pc, vector no
     Interrupt(u8, u8), // irq, vector no
+    Trace(u32), // pc
+    FormatError(u16, u32), // ir, pc (010+)
+}
+impl Exception {
+    // the vector the exception is taken through, which for an interrupt
+    // is up to the interrupt controller
+    pub fn vector(self) -> Option<u8> {
+        match self {
+            Exception::AddressError { .. } => Some(EXCEPTION_ADDRESS_ERROR),
+            Exception::BusError { .. } => Some(EXCEPTION_BUS_ERROR),
+            Exception::IllegalInstruction(_, _) => Some(EXCEPTION_ILLEGAL_INSTRUCTION),
+            Exception::Trap(num, _) => Some(num),
+            Exception::PrivilegeViolation(_, _) => Some(EXCEPTION_PRIVILEGE_VIOLATION),
+            Exception::UnimplementedInstruction(_, _, vector) => Some(vector),
+            Exception::Interrupt(_, _) => None,
+            Exception::Trace(_) => Some(EXCEPTION_TRACE),
+            Exception::FormatError(_, _) => Some(EXCEPTION_FORMAT_ERROR),
+        }
+    }
 }
 use std::fmt;
 impl fmt::Display for Exception {
@@ -462,11 +719,16 @@ impl fmt::Display for Exception {
             Exception::AddressError {
                 address, access_type, processing_state, address_space
                 } => write!(f, "Address Error: {:?} {:?} at {:08x} during {:?} processing", access_type, address_space, address, processing_state),
+            Exception::BusError {
+                address, access_type, processing_state, address_space
+                } => write!(f, "Bus Error: {:?} {:?} at {:08x} during {:?} processing", access_type, address_space, address, processing_state),
             Exception::IllegalInstruction(ir, pc) => write!(f, "Illegal Instruction {:04x} at {:08x}", ir, pc),
             Exception::Trap(num, ea_cyc) => write!(f, "Trap: {:02x} (ea cyc {})", num, ea_cyc),
             Exception::PrivilegeViolation(ir, pc) => write!(f, "Privilege Violation {:04x} at {:08x}", ir, pc),
             Exception::UnimplementedInstruction(ir, pc, _) => write!(f, "Unimplemented Instruction {:04x} at {:08x}", ir, pc),
             Exception::Interrupt(irq, vec) => write!(f, "Interrupt {:1x} (vector {:02x})", irq, vec),
+            Exception::Trace(pc) => write!(f, "Trace at {:08x}", pc),
+            Exception::FormatError(ir, pc) => write!(f, "Format Error {:04x} at {:08x}", ir, pc),
         }
     }
 }
@@ -475,11 +737,14 @@ impl error::Error for Exception {
     fn description(&self) -> &str {
          match *self {
             Exception::AddressError{..} => "Address Error",
+            Exception::BusError{..} => "Bus Error",
             Exception::IllegalInstruction(_, _) => "Illegal Instruction",
             Exception::Trap(_, _) => "Trap",
             Exception::PrivilegeViolation(_, _) => "PrivilegeViolation",
             Exception::UnimplementedInstruction(_, _, _) => "UnimplementedInstruction",
             Exception::Interrupt(_, _) => "Interrupt",
+            Exception::Trace(_) => "Trace",
+            Exception::FormatError(_, _) => "FormatError",
          }
     }
     fn cause(&self) -> Option<&dyn error::Error> {
@@ -493,12 +758,15 @@ use crate::ram::ADDRBUS_MASK;
 // these values are borrowed from Musashi
 // and not yet fully understood
 const SFLAG_SET: u32 =  0x04;
+const MFLAG_SET: u32 =  0x02;
+const T1FLAG_SET: u32 = 0x8000;
 const XFLAG_SET: u32 = 0x100;
 const ZFLAG_SET: u32 = 0x00;
 const NFLAG_SET: u32 =  0x80;
 const VFLAG_SET: u32 =  0x80;
 const CFLAG_SET: u32 = 0x100;
 const CPU_SR_MASK: u16 = 0xa71f; /* T1 -- S  -- -- I2 I1 I0 -- -- -- X  N  Z  V  C  */
+const CPU_SR_MASK_020: u16 = 0xb71f; /* T1 -- S  M  -- I2 I1 I0 -- -- -- X  N  Z  V  C  */
 const CPU_SR_INT_MASK: u32 = 0x0700;
 
 const VFLAG_CLEAR: u32 =  0x00;
@@ -506,32 +774,68 @@ const XFLAG_CLEAR: u32 =  0x00;
 const NFLAG_CLEAR: u32 =  0x00;
 const CFLAG_CLEAR: u32 =  0x00;
 const SFLAG_CLEAR: u32 =  0x00;
+const MFLAG_CLEAR: u32 =  0x00;
+const T1FLAG_CLEAR: u32 =  0x00;
 const ZFLAG_CLEAR: u32 =  0xffff_ffff; // used as "non-z-flag"
 
 // Exception Vectors
-//pub const EXCEPTION_BUS_ERROR: u8               =  2;
+pub const EXCEPTION_BUS_ERROR: u8               =  2;
 pub const EXCEPTION_ADDRESS_ERROR: u8           =  3;
 pub const EXCEPTION_ILLEGAL_INSTRUCTION: u8     =  4;
 pub const EXCEPTION_ZERO_DIVIDE: u8             =  5;
 pub const EXCEPTION_CHK: u8                     =  6;
 pub const EXCEPTION_TRAPV: u8                   =  7;
 pub const EXCEPTION_PRIVILEGE_VIOLATION: u8     =  8;
-// pub const EXCEPTION_TRACE: u8                   =  9;
+pub const EXCEPTION_TRACE: u8                   =  9;
 pub const EXCEPTION_UNIMPLEMENTED_1010: u8      = 10;
 pub const EXCEPTION_UNIMPLEMENTED_1111: u8      = 11;
-// pub const EXCEPTION_FORMAT_ERROR: u8            = 14;
+pub const EXCEPTION_FORMAT_ERROR: u8            = 14;
 // pub const EXCEPTION_UNINITIALIZED_INTERRUPT: u8 = 15;
 // pub const EXCEPTION_SPURIOUS_INTERRUPT: u8      = 24;
 // pub const EXCEPTION_INTERRUPT_AUTOVECTOR: u8    = 24;
 pub const EXCEPTION_TRAP_BASE: u8               = 32;
 
+// The cycles of an exception on the 68000 and the 68010, by vector, as in
+// Musashi's m68ki_exception_cycle_table. Bus and address errors are
+// timed on their own.
+fn exception_cycles_000_010(vector: u8) -> (i32, i32) {
+    match vector {
+        EXCEPTION_ILLEGAL_INSTRUCTION => (34, 38),
+        EXCEPTION_ZERO_DIVIDE => (38, 44),
+        EXCEPTION_CHK => (40, 44),
+        EXCEPTION_TRAPV => (34, 34),
+        EXCEPTION_PRIVILEGE_VIOLATION => (34, 38),
+        EXCEPTION_TRACE => (34, 38),
+        EXCEPTION_UNIMPLEMENTED_1010 | EXCEPTION_UNIMPLEMENTED_1111 => (34, 4),
+        UNINITIALIZED_INTERRUPT => (44, 44),
+        // the spurious interrupt, then the autovectors
+        _ if (SPURIOUS_INTERRUPT..=AUTOVECTOR_BASE + 7).contains(&vector) => (44, 46),
+        _ if (EXCEPTION_TRAP_BASE..EXCEPTION_TRAP_BASE + 16).contains(&vector) => (34, 38),
+        _ => (4, 4),
+    }
+}
+
+// MOVEC control register numbers; those at 0x002 and 0x803 up are 020+
+pub const CR_SFC: u16 = 0x000;
+pub const CR_DFC: u16 = 0x001;
+pub const CR_CACR: u16 = 0x002;
+pub const CR_USP: u16 = 0x800;
+pub const CR_VBR: u16 = 0x801;
+pub const CR_CAAR: u16 = 0x802;
+pub const CR_MSP: u16 = 0x803;
+pub const CR_ISP: u16 = 0x804;
+
 impl TestCore {
     pub fn new(base: u32) -> TestCore {
         TestCore {
-            pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
+            pc: base, prefetch_addr: 0, prefetch_data: 0, loop_mode: None, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
             dar: [0u32; 16], mem: LoggingMem::new(0xaaaa_aaaa, OpsLogger::new()), instruction_set: std::sync::Arc::new(ops::instruction_set()),
+            cpu_type: CpuType::M68000, vbr: 0, sfc: 0, dfc: 0, cacr: 0, caar: 0, inactive_msp: 0, address_mask: ADDRBUS_MASK, ppc: 0,
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            s_flag: SFLAG_SET, m_flag: MFLAG_CLEAR, t1_flag: T1FLAG_CLEAR, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            debugger: Debugger::new(), fpu: None, mmu: None, bus_timing: None, decode_cache: None,
+            #[cfg(feature = "jit")]
+            jit: None,
             #[cfg(feature = "fc")]
             fc_is_data: false,
         }
@@ -548,10 +852,14 @@ impl TestCore {
             lm.write_u8(base + offset as u32, u32::from(*byte));
         }
         TestCore {
-            pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
+            pc: base, prefetch_addr: 0, prefetch_data: 0, loop_mode: None, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
             dar: [0u32; 16], mem: lm, instruction_set: std::sync::Arc::new(ops::instruction_set()),
+            cpu_type: CpuType::M68000, vbr: 0, sfc: 0, dfc: 0, cacr: 0, caar: 0, inactive_msp: 0, address_mask: ADDRBUS_MASK, ppc: 0,
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            s_flag: SFLAG_SET, m_flag: MFLAG_CLEAR, t1_flag: T1FLAG_CLEAR, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            debugger: Debugger::new(), fpu: None, mmu: None, bus_timing: None, decode_cache: None,
+            #[cfg(feature = "jit")]
+            jit: None,
             #[cfg(feature = "fc")]
             fc_is_data: false,
         }
@@ -563,10 +871,14 @@ impl TestCore {
             lm.write_u8(base + offset as u32, u32::from(*byte));
         }
         TestCore {
-            pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
+            pc: base, prefetch_addr: 0, prefetch_data: 0, loop_mode: None, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
             dar: [0u32; 16], mem: lm, instruction_set: (*ops::INSTRUCTION_SET_TEST).clone(),
+            cpu_type: CpuType::M68000, vbr: 0, sfc: 0, dfc: 0, cacr: 0, caar: 0, inactive_msp: 0, address_mask: ADDRBUS_MASK, ppc: 0,
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            s_flag: SFLAG_SET, m_flag: MFLAG_CLEAR, t1_flag: T1FLAG_CLEAR, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            debugger: Debugger::new(), fpu: None, mmu: None, bus_timing: None, decode_cache: None,
+            #[cfg(feature = "jit")]
+            jit: None,
             #[cfg(feature = "fc")]
             fc_is_data: false,
         }
@@ -579,20 +891,35 @@ impl TestCore {
         self.pc = base;
         self.prefetch_addr = 0;
         self.prefetch_data = 0;
+        self.loop_mode = None;
         self.inactive_ssp = 0;
         self.inactive_usp = 0;
+        self.vbr = 0;
+        self.sfc = 0;
+        self.dfc = 0;
+        self.cacr = 0;
+        self.caar = 0;
+        self.inactive_msp = 0;
         self.ir = 0;
         self.processing_state = ProcessingState::Normal;
         self.dar.fill(0);
         self.irq_level = 0;
         self.int_ctrl.reset_external_devices();
         self.s_flag = SFLAG_SET;
+        self.m_flag = MFLAG_CLEAR;
+        self.t1_flag = T1FLAG_CLEAR;
         self.int_mask = CPU_SR_INT_MASK;
         self.x_flag = 0;
         self.v_flag = 0;
         self.c_flag = 0;
         self.n_flag = 0;
         self.not_z_flag = 0xffff_ffff;
+        if let Some(fpu) = self.fpu.as_mut() {
+            fpu.reset();
+        }
+        if let Some(mmu) = self.mmu.as_mut() {
+            mmu.reset();
+        }
         #[cfg(feature = "fc")]
         {
             self.fc_is_data = false;
@@ -600,29 +927,261 @@ impl TestCore {
     }
 }
 
+// The CPU registers are followed by the state of the interrupt
+// controller and the memory. The instruction set is not part of the state.
+impl<T: InterruptController + Snapshot + 'static, A: AddressBus + Snapshot + 'static> Snapshot for ConfiguredCore<T, A> {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u32(self.pc);
+        writer.put_u32(self.inactive_ssp);
+        writer.put_u32(self.inactive_usp);
+        writer.put_u16(self.ir);
+        for &reg in self.dar.iter() {
+            writer.put_u32(reg);
+        }
+        writer.put_u32(self.s_flag);
+        writer.put_u32(self.t1_flag);
+        writer.put_u8(self.irq_level);
+        writer.put_u32(self.int_mask);
+        writer.put_u32(self.x_flag);
+        writer.put_u32(self.c_flag);
+        writer.put_u32(self.v_flag);
+        writer.put_u32(self.n_flag);
+        writer.put_u32(self.not_z_flag);
+        writer.put_u32(self.prefetch_addr);
+        writer.put_u32(self.prefetch_data);
+        writer.put_u8(self.processing_state.to_u8());
+        // always present, so states don't depend on the "fc" feature
+        #[cfg(feature = "fc")]
+        writer.put_bool(self.fc_is_data);
+        #[cfg(not(feature = "fc"))]
+        writer.put_bool(false);
+        writer.put_u8(self.cpu_type.to_u8());
+        writer.put_u32(self.vbr);
+        writer.put_u32(self.sfc);
+        writer.put_u32(self.dfc);
+        writer.put_u32(self.m_flag);
+        writer.put_u32(self.inactive_msp);
+        writer.put_u32(self.cacr);
+        writer.put_u32(self.caar);
+        writer.put_bool(self.loop_mode.is_some());
+        if let Some(buffer) = self.loop_mode {
+            writer.put_u32(buffer.address);
+            for &word in buffer.words.iter() {
+                writer.put_u16(word);
+            }
+        }
+        writer.put_bool(self.fpu.is_some());
+        if let Some(fpu) = self.fpu.as_ref() {
+            fpu.save(writer);
+        }
+        // a 68030 always has one
+        if let Some(mmu) = self.mmu.as_ref() {
+            mmu.save(writer);
+        }
+        writer.put_bool(self.bus_timing.is_some());
+        if let Some(timing) = self.bus_timing.as_ref() {
+            timing.save(writer);
+        }
+        self.int_ctrl.save(writer);
+        self.mem.save(writer);
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.pc = reader.get_u32()?;
+        self.inactive_ssp = reader.get_u32()?;
+        self.inactive_usp = reader.get_u32()?;
+        self.ir = reader.get_u16()?;
+        for reg in self.dar.iter_mut() {
+            *reg = reader.get_u32()?;
+        }
+        self.s_flag = reader.get_u32()?;
+        self.t1_flag = reader.get_u32()?;
+        self.irq_level = reader.get_u8()?;
+        self.int_mask = reader.get_u32()?;
+        self.x_flag = reader.get_u32()?;
+        self.c_flag = reader.get_u32()?;
+        self.v_flag = reader.get_u32()?;
+        self.n_flag = reader.get_u32()?;
+        self.not_z_flag = reader.get_u32()?;
+        self.prefetch_addr = reader.get_u32()?;
+        self.prefetch_data = reader.get_u32()?;
+        self.processing_state = ProcessingState::from_u8(reader.get_u8()?)
+            .ok_or(StateError::Invalid("processing state"))?;
+        let _fc_is_data = reader.get_bool()?;
+        #[cfg(feature = "fc")]
+        {
+            self.fc_is_data = _fc_is_data;
+        }
+        let cpu_type = CpuType::from_u8(reader.get_u8()?)
+            .ok_or(StateError::Invalid("cpu type"))?;
+        self.set_cpu_type(cpu_type);
+        self.vbr = reader.get_u32()?;
+        self.sfc = reader.get_u32()?;
+        self.dfc = reader.get_u32()?;
+        self.m_flag = reader.get_u32()?;
+        self.inactive_msp = reader.get_u32()?;
+        self.cacr = reader.get_u32()?;
+        self.caar = reader.get_u32()?;
+        self.loop_mode = if reader.get_bool()? {
+            let address = reader.get_u32()?;
+            let mut words = [0; 3];
+            for word in words.iter_mut() {
+                *word = reader.get_u16()?;
+            }
+            Some(LoopBuffer { address, words })
+        } else {
+            None
+        };
+        self.fpu = if reader.get_bool()? {
+            let mut fpu = Fpu::new(FpuType::MC68881);
+            fpu.load(reader)?;
+            Some(fpu)
+        } else {
+            None
+        };
+        if let Some(mmu) = self.mmu.as_mut() {
+            mmu.load(reader)?;
+        }
+        self.bus_timing = if reader.get_bool()? {
+            let mut timing = BusTiming::new();
+            timing.load(reader)?;
+            Some(timing)
+        } else {
+            None
+        };
+        self.int_ctrl.load(reader)?;
+        self.mem.load(reader)?;
+        self.invalidate_decode_cache();
+        Ok(())
+    }
+}
+
+impl<T: InterruptController + Snapshot + 'static, A: AddressBus + Snapshot + 'static> ConfiguredCore<T, A> {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        self.save(&mut writer);
+        writer.into_bytes()
+    }
+    // If loading fails, the core is left in an unspecified state, and
+    // should be reset or loaded from another state before use
+    pub fn load_state(&mut self, state: &[u8]) -> savestate::Result<()> {
+        let mut reader = StateReader::new(state)?;
+        self.load(&mut reader)?;
+        if reader.is_empty() { Ok(()) } else { Err(StateError::Invalid("trailing data")) }
+    }
+}
+
 impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
     pub fn new_with(base: u32, int_ctrl: T, memory: A) -> ConfiguredCore<T, A> {
         ConfiguredCore {
-            pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
+            pc: base, prefetch_addr: 0, prefetch_data: 0, loop_mode: None, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
             dar: [0u32; 16], mem: memory, instruction_set: std::sync::Arc::new(ops::instruction_set()),
+            cpu_type: CpuType::M68000, vbr: 0, sfc: 0, dfc: 0, cacr: 0, caar: 0, inactive_msp: 0, address_mask: ADDRBUS_MASK, ppc: 0,
             irq_level: 0, int_ctrl,
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            s_flag: SFLAG_SET, m_flag: MFLAG_CLEAR, t1_flag: T1FLAG_CLEAR, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
+            debugger: Debugger::new(), fpu: None, mmu: None, bus_timing: None, decode_cache: None,
+            #[cfg(feature = "jit")]
+            jit: None,
             #[cfg(feature = "fc")]
             fc_is_data: false,
         }
     }
+    pub fn cpu_type(&self) -> CpuType {
+        self.cpu_type
+    }
+    // Switches to the instruction set of the given processor. Like Musashi,
+    // this leaves the registers alone, so is best followed by a reset.
+    // Limitation: instructions take the cycles of the 68000 on the 68020
+    // and 68030 (and those of the 68010 on the CPU32), not their own,
+    // which depend on the instruction cache and pipeline.
+    pub fn set_cpu_type(&mut self, cpu_type: CpuType) where Self: 'static {
+        if cpu_type != self.cpu_type {
+            self.cpu_type = cpu_type;
+            self.instruction_set = ops::shared_instruction_set_for(cpu_type);
+            self.address_mask = cpu_type.address_mask();
+            self.mem.set_address_mask(self.address_mask);
+            self.mmu = if cpu_type == CpuType::M68030 { Some(Mmu::new()) } else { None };
+        }
+    }
+    // Switches to cycle exact timing, with the clock starting from zero
+    #[cfg(feature = "cycles")]
+    pub fn enable_bus_timing(&mut self) {
+        self.bus_timing = Some(BusTiming::new());
+    }
+    // Keeps instructions with their extension words once they have run,
+    // so they need not be fetched again (see decode_cache.rs)
+    pub fn enable_decode_cache(&mut self) {
+        self.decode_cache = Some(DecodeCache::new());
+    }
+    // to be called after changing code through self.mem, rather than
+    // through the core (which also drops any compiled code)
+    pub fn invalidate_decode_cache(&mut self) {
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.invalidate_all();
+        }
+        #[cfg(feature = "jit")]
+        {
+            if let Some(ref mut jit) = self.jit {
+                jit.invalidate_all();
+            }
+        }
+    }
+    // Compiles code that runs often into native code (see jit.rs). This
+    // needs the decode cache, so enables that too.
+    #[cfg(feature = "jit")]
+    pub fn enable_jit(&mut self) {
+        if self.decode_cache.is_none() {
+            self.enable_decode_cache();
+        }
+        self.jit = Some(Jit::new());
+    }
+    // Attaches a floating point coprocessor, which only a 68020 can use
+    pub fn attach_fpu(&mut self, fpu_type: FpuType) {
+        self.fpu = Some(Fpu::new(fpu_type));
+    }
     pub fn reset(&mut self) {
         self.processing_state = ProcessingState::Group0Exception;
-        self.s_flag = SFLAG_SET;
+        self.change_stack_mode(SFLAG_SET, MFLAG_CLEAR);
+        self.t1_flag = T1FLAG_CLEAR;
         self.int_mask = CPU_SR_INT_MASK;
-        self.prefetch_addr = 1; // non-zero, or the prefetch won't kick in
+        self.vbr = 0;
+        self.cacr = 0;
+        if let Some(fpu) = self.fpu.as_mut() {
+            fpu.reset();
+        }
+        if let Some(mmu) = self.mmu.as_mut() {
+            mmu.reset();
+        }
+        self.invalidate_prefetch();
         self.jump(0);
-        // these reads cannot possibly cause AddressError, as we forced PC to 0
-        self.dar[15] = self.read_imm_u32().unwrap();
-        let new_pc = self.read_imm_u32().unwrap();
-        self.jump(new_pc);
+        // these reads cannot possibly cause AddressError, as we forced PC to 0,
+        // but a bus error while fetching the reset vector halts the processor
+        let vectors = self.read_imm_u32().and_then(|sp| self.read_imm_u32().map(|pc| (sp, pc)));
+        // 40 clock periods, according to MC68000UM, Table 8-14
+        if let Some(ref mut timing) = self.bus_timing {
+            timing.finish(40);
+        }
+        if let Ok((new_sp, new_pc)) = vectors {
+            self.dar[15] = new_sp;
+            self.jump(new_pc);
+            self.processing_state = ProcessingState::Normal;
+        } else {
+            self.processing_state = ProcessingState::Halted;
+        }
+    }
+    // Starts the core at an entry point in supervisor mode, with the given
+    // stack and interrupt mask, as a loader starts a program that doesn't
+    // go through the reset vectors
+    pub fn start_at(&mut self, entry: u32, stack: u32, int_mask: u8) {
+        self.sr_to_flags(0x2000 | u16::from(int_mask & 7) << 8);
+        self.dar[STACK_POINTER_REG] = stack;
+        self.invalidate_prefetch();
+        self.jump(entry);
         self.processing_state = ProcessingState::Normal;
     }
+    // an odd address, where no instruction is, so the next fetch refills it
+    fn invalidate_prefetch(&mut self) {
+        self.prefetch_addr = 1;
+    }
     pub fn x_flag_as_1(&self) -> u32 {
         (self.x_flag>>8)&1
     }
@@ -630,7 +1189,9 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
     // which I don't fully understand (they are not matching their
     // positions in the SR/CCR)
     pub fn status_register(&self) -> u16 {
-        ((self.s_flag << 11)                |
+        (self.t1_flag                        |
+        (self.s_flag << 11)                 |
+        (self.m_flag << 11)                 |
         self.int_mask                        |
         ((self.x_flag & XFLAG_SET) >> 4)    |
         ((self.n_flag & NFLAG_SET) >> 4)    |
@@ -648,35 +1209,93 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
             self.dar[15]
         }
     }
+    // the interrupt stack pointer on the 68020
     pub fn ssp(&self) -> u32 {
-        if self.s_flag > 0 {
+        if self.s_flag > 0 && self.m_flag == MFLAG_CLEAR {
             self.dar[15]
         } else {
             self.inactive_ssp
         }
     }
+    pub fn msp(&self) -> u32 {
+        if self.s_flag > 0 && self.m_flag > 0 {
+            self.dar[15]
+        } else {
+            self.inactive_msp
+        }
+    }
+    // where the stack pointer of the given mode is kept while inactive
+    fn inactive_sp(&mut self, s_flag: u32, m_flag: u32) -> &mut u32 {
+        if s_flag == SFLAG_CLEAR {
+            &mut self.inactive_usp
+        } else if m_flag == MFLAG_CLEAR {
+            &mut self.inactive_ssp
+        } else {
+            &mut self.inactive_msp
+        }
+    }
+    // sets S and M, swapping A7 for the stack pointer of the new mode
+    fn change_stack_mode(&mut self, s_flag: u32, m_flag: u32) {
+        // M only selects the stack in supervisor mode
+        let stack = |s_flag, m_flag| if s_flag == SFLAG_CLEAR { (s_flag, MFLAG_CLEAR) } else { (s_flag, m_flag) };
+        let (old_s_flag, old_m_flag) = stack(self.s_flag, self.m_flag);
+        self.s_flag = s_flag;
+        self.m_flag = m_flag;
+        if (old_s_flag, old_m_flag) != stack(s_flag, m_flag) {
+            *self.inactive_sp(old_s_flag, old_m_flag) = self.dar[15];
+            self.dar[15] = *self.inactive_sp(s_flag, m_flag);
+        }
+    }
+    pub fn control_register(&self, cr: u16) -> Option<u32> {
+        let is_020 = self.cpu_type.is_020();
+        match cr {
+            CR_SFC => Some(self.sfc),
+            CR_DFC => Some(self.dfc),
+            CR_USP => Some(self.usp()),
+            CR_VBR => Some(self.vbr),
+            CR_CACR if is_020 => Some(self.cacr),
+            CR_CAAR if is_020 => Some(self.caar),
+            CR_MSP if is_020 => Some(self.msp()),
+            CR_ISP if is_020 => Some(self.ssp()),
+            _ => None,
+        }
+    }
+    // false if there is no such register
+    pub fn set_control_register(&mut self, cr: u16, value: u32) -> bool {
+        let is_020 = self.cpu_type.is_020();
+        match cr {
+            CR_SFC => self.sfc = value & 7,
+            CR_DFC => self.dfc = value & 7,
+            CR_USP if self.s_flag == SFLAG_CLEAR => self.dar[15] = value,
+            CR_USP => self.inactive_usp = value,
+            CR_VBR => self.vbr = value,
+            // only the enable, freeze, burst and write allocate bits are
+            // kept, as there are no caches
+            CR_CACR if self.cpu_type == CpuType::M68030 => self.cacr = value & 0x3313,
+            CR_CACR if is_020 => self.cacr = value & 3,
+            CR_CAAR if is_020 => self.caar = value,
+            CR_MSP if is_020 && self.s_flag != SFLAG_CLEAR && self.m_flag != MFLAG_CLEAR => self.dar[15] = value,
+            CR_MSP if is_020 => self.inactive_msp = value,
+            CR_ISP if is_020 && self.s_flag != SFLAG_CLEAR && self.m_flag == MFLAG_CLEAR => self.dar[15] = value,
+            CR_ISP if is_020 => self.inactive_ssp = value,
+            _ => return false,
+        }
+        true
+    }
     // admittely I've chosen to reuse Musashi's representation of flags
     // which I don't fully understand (they are not matching their
     // positions in the SR/CCR)
     pub fn sr_to_flags(&mut self, sr: u16) {
-        let sr = u32::from(sr & CPU_SR_MASK);
-        let old_sflag = self.s_flag;
+        let sr_mask = if self.cpu_type.is_020() { CPU_SR_MASK_020 } else { CPU_SR_MASK };
+        let sr = u32::from(sr & sr_mask);
         self.int_mask = sr & CPU_SR_INT_MASK;
-        self.s_flag =           (sr >> 11) & SFLAG_SET;
+        self.t1_flag =           sr & T1FLAG_SET;
+        self.change_stack_mode((sr >> 11) & SFLAG_SET, (sr >> 11) & MFLAG_SET);
         self.x_flag =            (sr <<  4) & XFLAG_SET;
         self.n_flag =            (sr <<  4) & NFLAG_SET;
         self.not_z_flag = not1!(sr & 0b00100);
         self.v_flag =            (sr <<  6) & VFLAG_SET;
         self.c_flag =            (sr <<  8) & CFLAG_SET;
-        if old_sflag != self.s_flag {
-            if self.s_flag == SFLAG_SET {
-                self.inactive_usp = self.dar[15];
-                self.dar[15] = self.inactive_ssp;
-            } else {
-                self.inactive_ssp = self.dar[15];
-                self.dar[15] = self.inactive_usp;
-            }
-        }
         // println!("{} {:016b} {} {}", self.flags(), sr, self.not_z_flag, sr & 0b00100);
     }
     pub fn ccr_to_flags(&mut self, ccr: u16) {
@@ -711,23 +1330,20 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
         let mut temp_val = 0u32;
         if cfg!(feature = "prefetch") {
             if self.pc != self.prefetch_addr {
-                self.prefetch_addr = self.pc;
-                self.prefetch_data = self.read_program_word(self.prefetch_addr & ADDRBUS_MASK).unwrap();
+                self.prefetch()?;
             }
             temp_val = self.prefetch_data & 0xffff;
-            self.pc += 2;
-            self.prefetch_addr = self.pc;
-            self.prefetch_data = self.read_program_word(self.prefetch_addr & ADDRBUS_MASK).unwrap();
+            self.pc = self.pc.wrapping_add(2);
+            self.prefetch()?;
 
             temp_val = (temp_val << 16) | (self.prefetch_data & 0xffff);
-            self.pc += 2;
-            self.prefetch_addr = self.pc;
-            self.prefetch_data = self.read_program_word(self.prefetch_addr & ADDRBUS_MASK).unwrap();
+            self.pc = self.pc.wrapping_add(2);
+            self.prefetch()?;
         } else {
-            temp_val = self.read_program_word(self.pc & ADDRBUS_MASK).unwrap();
-            self.pc += 2;
-            temp_val = (temp_val << 16) | (self.read_program_word(self.pc & ADDRBUS_MASK).unwrap() & 0xffff);
-            self.pc += 2;
+            temp_val = self.fetch_word(self.pc)?;
+            self.pc = self.pc.wrapping_add(2);
+            temp_val = (temp_val << 16) | (self.fetch_word(self.pc)? & 0xffff);
+            self.pc = self.pc.wrapping_add(2);
         }
 
         Ok(temp_val)
@@ -751,178 +1367,385 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
         let mut result = 0u32;
         if cfg!(feature = "prefetch") {
             if self.pc != self.prefetch_addr {
-                self.prefetch_addr = self.pc;
-                self.prefetch_data = self.read_program_word(self.prefetch_addr & ADDRBUS_MASK).unwrap();
+                self.prefetch()?;
             }
             result = self.prefetch_data & 0xffff;
-            self.pc += 2;
-            self.prefetch_addr = self.pc;
-            self.prefetch_data = self.read_program_word(self.prefetch_addr & ADDRBUS_MASK).unwrap();
+            self.pc = self.pc.wrapping_add(2);
+            self.prefetch()?;
         } else {
-            result = self.read_program_word(self.pc & ADDRBUS_MASK).unwrap();
-            self.pc += 2;
+            result = self.fetch_word(self.pc)?;
+            self.pc = self.pc.wrapping_add(2);
         }
         Ok(result as u16)
     }
-    pub fn push_sp(&mut self) -> u32 {
+    // Fetches a word of the running instruction, which the decode cache
+    // may already have
+    fn fetch_word(&mut self, pc: u32) -> Result<u32> {
+        let address = pc & self.address_mask;
+        if let Some(word) = self.decode_cache.as_ref().and_then(|cache| cache.word(address)) {
+            return Ok(u32::from(word));
+        }
+        let word = self.program_word(pc)?;
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.fetched(address, word as u16);
+        }
+        Ok(word)
+    }
+    // Fills the prefetch queue with the word at PC
+    fn prefetch(&mut self) -> Result<()> {
+        match self.loop_mode {
+            Some(buffer) if buffer.ends_before(self.pc) => self.invalidate_prefetch(),
+            _ => {
+                self.prefetch_addr = self.pc;
+                self.prefetch_data = self.program_word(self.pc)?;
+            },
+        }
+        Ok(())
+    }
+    // Reads a word of the instruction stream, unless it is one of the loop
+    // that loop mode runs from the prefetch queue
+    fn program_word(&mut self, pc: u32) -> Result<u32> {
+        match self.loop_mode.and_then(|buffer| buffer.word(pc)) {
+            Some(word) => Ok(u32::from(word)),
+            None => self.read_program_word(pc),
+        }
+    }
+    // Returns the opcode at PC, should the decode cache have it. The cache
+    // stays out of the way of the prefetch emulation, the MMU, cycle exact
+    // timing and the debugger.
+    fn begin_decode(&mut self) -> Option<u16> {
+        match self.decode_cache {
+            Some(ref mut cache) => {
+                let mmu_enabled = self.mmu.as_ref().is_some_and(|mmu| mmu.is_enabled());
+                if cfg!(feature = "prefetch") || mmu_enabled || self.bus_timing.is_some() || self.debugger.is_active() {
+                    cache.bypass();
+                    None
+                } else {
+                    cache.begin(self.pc & self.address_mask, self.s_flag != 0)
+                }
+            },
+            None => None,
+        }
+    }
+    pub fn push_sp(&mut self) -> Result<u32> {
          let new_sp = (Wrapping(self.dar[15]) - Wrapping(4)).0;
          self.dar[15] = new_sp;
-         self.write_long(new_sp, new_sp).unwrap();
-         new_sp
+         self.write_long(new_sp, new_sp)?;
+         Ok(new_sp)
     }
-    pub fn push_32(&mut self, value: u32) -> u32 {
+    pub fn push_32(&mut self, value: u32) -> Result<u32> {
          let new_sp = (Wrapping(self.dar[15]) - Wrapping(4)).0;
          self.dar[15] = new_sp;
-         self.write_long(new_sp, value).unwrap();
-         new_sp
+         self.write_long(new_sp, value)?;
+         Ok(new_sp)
     }
-    pub fn pop_32(&mut self) -> u32 {
+    pub fn pop_32(&mut self) -> Result<u32> {
         let sp = self.dar[15];
-        let data = self.read_long(sp).unwrap();
+        let data = self.read_long(sp)?;
         self.dar[15] = sp.wrapping_add(4);
-        data
+        Ok(data)
     }
-    pub fn push_16(&mut self, value: u16) -> u32 {
+    pub fn push_16(&mut self, value: u16) -> Result<u32> {
          let new_sp = (Wrapping(self.dar[15]) - Wrapping(2)).0;
          self.dar[15] = new_sp;
-         self.write_word(new_sp, u32::from(value)).unwrap();
-         new_sp
+         self.write_word(new_sp, u32::from(value))?;
+         Ok(new_sp)
     }
-    pub fn pop_16(&mut self) -> u16 {
+    pub fn pop_16(&mut self) -> Result<u16> {
         let sp = self.dar[15];
-        let data = self.read_word(sp).unwrap() as u16;
+        let data = self.read_word(sp)? as u16;
         self.dar[15] = sp.wrapping_add(2);
-        data
+        Ok(data)
     }
     pub fn read_data_byte(&mut self, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(true);
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(self.mem.read_byte(address_space, address))
+        self.debugger.check_access(address, 1, AccessType::Read, address_space);
+        let physical = self.bus_address(address, 1, AccessType::Read, address_space)?;
+        self.mem.try_read_byte(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
     }
     pub fn read_program_byte(&mut self, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(false);
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        Ok(self.mem.read_byte(address_space, address))
+        self.debugger.check_access(address, 1, AccessType::Read, address_space);
+        let physical = self.bus_address(address, 1, AccessType::Read, address_space)?;
+        self.mem.try_read_byte(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
     }
     pub fn write_data_byte(&mut self, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(true);
-        self.mem.write_byte(if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA}, address, value);
-        Ok(())
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        self.debugger.check_access(address, 1, AccessType::Write, address_space);
+        let physical = self.bus_address(address, 1, AccessType::Write, address_space)?;
+        self.mem.try_write_byte(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
     }
     pub fn write_program_byte(&mut self, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(false);
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        self.mem.write_byte(address_space, address, value);
-        Ok(())
+        self.debugger.check_access(address, 1, AccessType::Write, address_space);
+        let physical = self.bus_address(address, 1, AccessType::Write, address_space)?;
+        self.mem.try_write_byte(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
     }
     pub fn read_data_word(&mut self, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(true);
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 2, AccessType::Read, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_word(address_space, address))
+            let physical = self.bus_address(address, 2, AccessType::Read, address_space)?;
+            self.mem.try_read_word(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
         }
     }
     pub fn read_program_word(&mut self, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(false);
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 2, AccessType::Read, address_space);
+        if self.misaligned(address) {
             // println!("{}", std::backtrace::Backtrace::force_capture());
             Err(Exception::AddressError {address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_word(address_space, address))
+            let physical = self.bus_address(address, 2, AccessType::Read, address_space)?;
+            self.mem.try_read_word(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
         }
     }
     pub fn write_data_word(&mut self, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(true);
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 2, AccessType::Write, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
         } else {
-            self.mem.write_word(address_space, address, value);
-            Ok(())
+            let physical = self.bus_address(address, 2, AccessType::Write, address_space)?;
+            self.mem.try_write_word(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
         }
     }
     pub fn write_program_word(&mut self, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(false);
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 2, AccessType::Write, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
         } else {
-            self.mem.write_word(address_space, address, value);
-            Ok(())
+            let physical = self.bus_address(address, 2, AccessType::Write, address_space)?;
+            self.mem.try_write_word(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
         }
     }
     pub fn read_data_long(&mut self, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(true);
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 4, AccessType::Read, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_long(address_space, address))
+            let physical = self.bus_address(address, 4, AccessType::Read, address_space)?;
+            self.mem.try_read_long(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
         }
     }
     pub fn read_program_long(&mut self, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(false);
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 4, AccessType::Read, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_long(address_space, address))
+            let physical = self.bus_address(address, 4, AccessType::Read, address_space)?;
+            self.mem.try_read_long(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
         }
     }
     pub fn write_data_long(&mut self, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(true);
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 4, AccessType::Write, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
         } else {
-            self.mem.write_long(address_space, address, value);
-            Ok(())
+            let physical = self.bus_address(address, 4, AccessType::Write, address_space)?;
+            self.mem.try_write_long(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
         }
     }
     pub fn write_program_long(&mut self, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
         #[cfg(feature = "fc")]
         self.set_fc(false);
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
+        self.debugger.check_access(address, 4, AccessType::Write, address_space);
+        if self.misaligned(address) {
+            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
+        } else {
+            let physical = self.bus_address(address, 4, AccessType::Write, address_space)?;
+            self.mem.try_write_long(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
+        }
+    }
+    // MOVES reaches the address space named by SFC or DFC. No device
+    // answers the function codes that don't name one of the four spaces,
+    // so those accesses end in a bus error, reported in the space that
+    // has the same supervisor bit.
+    fn fc_address_space(&self, fc: u32, address: u32, access_type: AccessType) -> Result<AddressSpace> {
+        AddressSpace::from_fc(fc).ok_or_else(|| {
+            let address_space = if fc & 0b100 != 0 {SUPERVISOR_DATA} else {USER_DATA};
+            self.bus_error(address, access_type, address_space)
+        })
+    }
+    pub fn read_byte_fc(&mut self, fc: u32, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
+        let address_space = self.fc_address_space(fc, address, AccessType::Read)?;
+        self.debugger.check_access(address, 1, AccessType::Read, address_space);
+        let physical = self.bus_address(address, 1, AccessType::Read, address_space)?;
+        self.mem.try_read_byte(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
+    }
+    pub fn read_word_fc(&mut self, fc: u32, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
+        let address_space = self.fc_address_space(fc, address, AccessType::Read)?;
+        self.debugger.check_access(address, 2, AccessType::Read, address_space);
+        if self.misaligned(address) {
+            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        } else {
+            let physical = self.bus_address(address, 2, AccessType::Read, address_space)?;
+            self.mem.try_read_word(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
+        }
+    }
+    pub fn read_long_fc(&mut self, fc: u32, address: u32) -> Result<u32> {
+        let address = address & self.address_mask;
+        let address_space = self.fc_address_space(fc, address, AccessType::Read)?;
+        self.debugger.check_access(address, 4, AccessType::Read, address_space);
+        if self.misaligned(address) {
+            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        } else {
+            let physical = self.bus_address(address, 4, AccessType::Read, address_space)?;
+            self.mem.try_read_long(address_space, physical).map_err(|_| self.bus_error(address, AccessType::Read, address_space))
+        }
+    }
+    pub fn write_byte_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
+        let address_space = self.fc_address_space(fc, address, AccessType::Write)?;
+        self.debugger.check_access(address, 1, AccessType::Write, address_space);
+        let physical = self.bus_address(address, 1, AccessType::Write, address_space)?;
+        self.mem.try_write_byte(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
+    }
+    pub fn write_word_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
+        let address_space = self.fc_address_space(fc, address, AccessType::Write)?;
+        self.debugger.check_access(address, 2, AccessType::Write, address_space);
+        if self.misaligned(address) {
             Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
         } else {
-            self.mem.write_long(address_space, address, value);
-            Ok(())
+            let physical = self.bus_address(address, 2, AccessType::Write, address_space)?;
+            self.mem.try_write_word(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
+        }
+    }
+    pub fn write_long_fc(&mut self, fc: u32, address: u32, value: u32) -> Result<()> {
+        let address = address & self.address_mask;
+        let address_space = self.fc_address_space(fc, address, AccessType::Write)?;
+        self.debugger.check_access(address, 4, AccessType::Write, address_space);
+        if self.misaligned(address) {
+            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
+        } else {
+            let physical = self.bus_address(address, 4, AccessType::Write, address_space)?;
+            self.mem.try_write_long(address_space, physical, value).map_err(|_| self.bus_error(address, AccessType::Write, address_space))
+        }
+    }
+    // The PMMU of a 68030 maps the logical address to a physical one. A
+    // fault is a bus error at the logical address.
+    fn translate(&mut self, address: u32, access_type: AccessType, address_space: AddressSpace) -> Result<u32> {
+        match self.mmu {
+            Some(ref mut mmu) if mmu.is_enabled() =>
+                mmu.translate(&mut self.mem, address, address_space.fc(), access_type == AccessType::Write)
+                    .map_err(|_| self.bus_error(address, access_type, address_space)),
+            _ => Ok(address),
+        }
+    }
+    // Reads a byte at a logical address through the PMMU, for a debugger
+    // or tracer: no time passes, watchpoints don't fire, the ATC and
+    // translation tables are left alone, and devices aren't read
+    pub fn peek_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        let address = address & self.address_mask;
+        let physical = match self.mmu {
+            Some(ref mmu) if mmu.is_enabled() => mmu.peek_translate(&mut self.mem, address, address_space.fc())?,
+            _ => address,
+        };
+        self.mem.peek_byte(address_space, physical)
+    }
+    // Writes a byte at a logical address like peek_byte reads one, for a
+    // debugger patching memory; decoded instructions there are dropped
+    pub fn poke_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        let address = address & self.address_mask;
+        let physical = match self.mmu {
+            Some(ref mmu) if mmu.is_enabled() => mmu.peek_translate(&mut self.mem, address, address_space.fc())?,
+            _ => address,
+        };
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.written(physical, 1);
+        }
+        self.mem.try_write_byte(address_space, physical, value)
+    }
+    // Translates the address of an access, charges its bus cycles to the
+    // cycle exact timing, and has writes invalidate decoded instructions
+    fn bus_address(&mut self, address: u32, size: u32, access_type: AccessType, address_space: AddressSpace) -> Result<u32> {
+        let physical = self.translate(address, access_type, address_space)?;
+        if let Some(ref mut timing) = self.bus_timing {
+            timing.bus_cycles(&mut self.mem, address_space, physical, size);
         }
+        if let (AccessType::Write, Some(cache)) = (access_type, self.decode_cache.as_mut()) {
+            cache.written(physical, size);
+        }
+        Ok(physical)
+    }
+    // the 68020 splits unaligned data accesses into several bus cycles
+    fn misaligned(&self, address: u32) -> bool {
+        address & 1 > 0 && !self.cpu_type.is_020()
+    }
+    fn bus_error(&self, address: u32, access_type: AccessType, address_space: AddressSpace) -> Exception {
+        Exception::BusError { address, access_type, address_space, processing_state: self.processing_state }
     }
     pub fn jump(&mut self, pc: u32) {
         self.pc = pc;
+        self.loop_mode = None;
     }
-    pub fn jump_vector(&mut self, vector: u8) {
-        let vector_address = u32::from(vector) << 2;
-        self.pc = self.read_long(vector_address).unwrap();
+    pub fn jump_vector(&mut self, vector: u8) -> Result<()> {
+        let vector_address = self.vbr.wrapping_add(u32::from(vector) << 2);
+        self.pc = self.read_long(vector_address)?;
+        Ok(())
     }
     pub fn ensure_supervisor_mode(&mut self) -> u16 {
         let backup_sr = self.status_register();
-        // if in user mode, swap stack pointers!
-        if self.s_flag == SFLAG_CLEAR {
-            self.inactive_usp = self.dar[15];
-            self.dar[15] = self.inactive_ssp;
-        }
-        // enter supervisor mode
-        self.s_flag = SFLAG_SET;
+        // enter supervisor mode (swapping stack pointers if in user
+        // mode), and stop tracing
+        let m_flag = self.m_flag;
+        self.change_stack_mode(SFLAG_SET, m_flag);
+        self.t1_flag = T1FLAG_CLEAR;
         backup_sr
     }
     pub fn handle_address_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        self.handle_group0_exception(EXCEPTION_ADDRESS_ERROR, bad_address, access_type, processing_state, address_space)
+    }
+    pub fn handle_bus_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        self.handle_group0_exception(EXCEPTION_BUS_ERROR, bad_address, access_type, processing_state, address_space)
+    }
+    fn handle_group0_exception(&mut self, vector: u8, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
     {
         if processing_state == ProcessingState::Group0Exception {
             self.processing_state = ProcessingState::Halted;
@@ -933,10 +1756,6 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
 
         // Bus error stack frame (68000 only).
         let (pc, ir) = (self.pc, self.ir);
-        self.push_32(pc);
-        self.push_16(backup_sr);
-        self.push_16(ir);
-        self.push_32(bad_address);    /* access address */
         /* 0 0 0 0 0 0 0 0 0 0 0 R/W I/N FC
          * R/W  0 = write, 1 = read
          * I/N  0 = instruction, 1 = not
@@ -945,9 +1764,159 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
         let access_info = match access_type {AccessType::Read => 0b10000, _ => 0 } |
             if processing_state.instruction_processing() { 0 } else { 0b01000 } |
             (address_space.fc() as u16);
-        self.push_16(access_info);
-        self.jump_vector(EXCEPTION_ADDRESS_ERROR);
-        Cycles(50)
+        let stacked = match self.cpu_type {
+            CpuType::M68000 => self.push_32(pc)
+                .and_then(|_| self.push_16(backup_sr))
+                .and_then(|_| self.push_16(ir))
+                .and_then(|_| self.push_32(bad_address))    /* access address */
+                .and_then(|_| self.push_16(access_info))
+                .map(|_| ()),
+            CpuType::M68010 =>
+                self.push_bus_error_frame_010(pc, backup_sr, vector, bad_address, access_type, address_space),
+            CpuType::M68EC020 | CpuType::M68020 | CpuType::M68030 =>
+                self.push_bus_error_frame_020(pc, backup_sr, vector, bad_address, access_type, address_space),
+            CpuType::Cpu32 =>
+                self.push_bus_error_frame_cpu32(pc, backup_sr, vector, bad_address, access_type, address_space),
+        }.and_then(|_| self.jump_vector(vector));
+        if stacked.is_err() {
+            // a double fault halts the processor
+            self.processing_state = ProcessingState::Halted;
+            return Cycles(0);
+        }
+        match self.cpu_type {
+            CpuType::M68000 => Cycles(50),
+            CpuType::M68010 => Cycles(126),
+            CpuType::M68EC020 | CpuType::M68020 | CpuType::M68030 | CpuType::Cpu32 => Cycles(50),
+        }
+    }
+    // Bus and address error stack frame (68010, format $8, 29 words).
+    // Like Musashi, the 16 words of internal state are skipped rather
+    // than written, so RTE cannot rerun the faulted bus cycle from them.
+    fn push_bus_error_frame_010(&mut self, pc: u32, sr: u16, vector: u8, bad_address: u32, access_type: AccessType, address_space: AddressSpace) -> Result<()> {
+        /* RW  bit 8, 1 = read
+         * IF  bit 13, DF bit 12, an instruction or data fetch
+         * FC  bits 2-0
+         */
+        let special_status = match (access_type, address_space) {
+            (AccessType::Read, SUPERVISOR_PROGRAM) | (AccessType::Read, USER_PROGRAM) => 0x2100,
+            (AccessType::Read, _) => 0x1100,
+            _ => 0,
+        } | (address_space.fc() as u16);
+        self.dar[15] = self.dar[15].wrapping_sub(32);
+        // the instruction input, data input and data output buffers,
+        // each followed by an unused word
+        for _ in 0..3 {
+            self.push_16(0)?;
+            self.dar[15] = self.dar[15].wrapping_sub(2);
+        }
+        self.push_32(bad_address)?;
+        self.push_16(special_status)?;
+        self.push_16(0x8000 | u16::from(vector) << 2)?;
+        self.push_32(pc)?;
+        self.push_16(sr)?;
+        Ok(())
+    }
+    // Bus and address error stack frame (68020, format $A, 16 words),
+    // the short bus cycle fault frame. As on the 68010, the internal state
+    // is not written.
+    fn push_bus_error_frame_020(&mut self, pc: u32, sr: u16, vector: u8, bad_address: u32, access_type: AccessType, address_space: AddressSpace) -> Result<()> {
+        /* FB  bit 14, RB bit 12, a fault in stage B of the pipe
+         * DF  bit 8, a data cycle fault
+         * RW  bit 6, 1 = read
+         * FC  bits 2-0
+         */
+        let special_status = match (access_type, address_space) {
+            (AccessType::Read, SUPERVISOR_PROGRAM) | (AccessType::Read, USER_PROGRAM) => 0x5000,
+            (AccessType::Read, _) => 0x0140,
+            _ => 0x0100,
+        } | (address_space.fc() as u16);
+        // the data output buffer, surrounded by internal registers
+        self.dar[15] = self.dar[15].wrapping_sub(12);
+        self.push_32(bad_address)?;
+        self.push_16(0)?; // instruction pipe stage B
+        self.push_16(0)?; // and C
+        self.push_16(special_status)?;
+        self.push_16(0)?;
+        self.push_16(0xa000 | u16::from(vector) << 2)?;
+        self.push_32(pc)?;
+        self.push_16(sr)?;
+        Ok(())
+    }
+    // Bus and address error stack frame (CPU32, format $C, 12 words).
+    // The transfer count is not kept, so RTE restarts at the stacked pc
+    // rather than completing a faulted MOVEM.
+    fn push_bus_error_frame_cpu32(&mut self, pc: u32, sr: u16, vector: u8, bad_address: u32, access_type: AccessType, address_space: AddressSpace) -> Result<()> {
+        /* IN  bit 7, an instruction prefetch
+         * RW  bit 6, 1 = read
+         * FC  bits 2-0
+         */
+        let special_status = match (access_type, address_space) {
+            (AccessType::Read, SUPERVISOR_PROGRAM) | (AccessType::Read, USER_PROGRAM) => 0x00c0,
+            (AccessType::Read, _) => 0x0040,
+            _ => 0,
+        } | (address_space.fc() as u16);
+        let ppc = self.ppc;
+        self.push_16(special_status)?;
+        self.push_16(0)?; // the internal transfer count
+        self.push_32(ppc)?;
+        self.push_32(0)?; // the data output buffer
+        self.push_32(bad_address)?;
+        self.push_16(0xc000 | u16::from(vector) << 2)?;
+        self.push_32(pc)?;
+        self.push_16(sr)?;
+        Ok(())
+    }
+    // Group 1 and 2 stack frame, which on the 68010 starts with a format
+    // word (format $0) holding the vector offset. On the 68020 some
+    // exceptions use format $2, which also holds the address of the
+    // instruction that caused it, as on the CPU32.
+    fn push_exception_frame(&mut self, pc: u32, sr: u16, vector: u8) -> Result<()> {
+        let format_2 = (self.cpu_type.is_020() || self.cpu_type == CpuType::Cpu32) &&
+            matches!(vector, EXCEPTION_ZERO_DIVIDE | EXCEPTION_CHK | EXCEPTION_TRAPV | EXCEPTION_TRACE);
+        if format_2 {
+            let ppc = self.ppc;
+            self.push_32(ppc)?;
+            self.push_16(0x2000 | u16::from(vector) << 2)?;
+        } else if self.cpu_type != CpuType::M68000 {
+            self.push_16(u16::from(vector) << 2)?;
+        }
+        self.push_32(pc)?;
+        self.push_16(sr)?;
+        Ok(())
+    }
+    // The cycles passed are the 68000's, with the effective address time
+    // of CHK and DIVx on top, which the 68010 takes too. The CPU32 is
+    // taken to spend another write on the format word. The 68020 has its
+    // own timings, which like Musashi's don't depend on how the exception
+    // came about.
+    fn exception_cycles(&self, vector: u8, cycles: i32) -> i32 {
+        match self.cpu_type {
+            CpuType::M68000 => cycles,
+            CpuType::M68010 => {
+                let (m68000, m68010) = exception_cycles_000_010(vector);
+                cycles - m68000 + m68010
+            },
+            CpuType::Cpu32 => cycles + 4,
+            CpuType::M68EC020 | CpuType::M68020 | CpuType::M68030 => match vector {
+                EXCEPTION_ZERO_DIVIDE => 38,
+                EXCEPTION_CHK => 40,
+                EXCEPTION_PRIVILEGE_VIOLATION => 34,
+                EXCEPTION_TRACE => 25,
+                EXCEPTION_FORMAT_ERROR => 4,
+                _ => 20,
+            },
+        }
+    }
+    // A fault while stacking or fetching the vector of a group 1 or 2
+    // exception is processed as a group 0 exception in its own right
+    fn handle_exception_processing_fault(&mut self, ex: Exception) -> Cycles {
+        match ex {
+            Exception::AddressError { address, access_type, processing_state, address_space } =>
+                self.handle_address_error(address, access_type, processing_state, address_space),
+            Exception::BusError { address, access_type, processing_state, address_space } =>
+                self.handle_bus_error(address, access_type, processing_state, address_space),
+            _ => unreachable!("stacking can only fault on the bus: {}", ex),
+        }
     }
     pub fn handle_unimplemented_instruction(&mut self, pc: u32, vector: u8) -> Cycles {
         // somewhat unclear if the unimplemented instruction exceptions
@@ -971,17 +1940,28 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
         let pc = self.pc;
         self.handle_exception(ProcessingState::Group2Exception, pc, trap, cycles)
     }
+    pub fn handle_format_error(&mut self, pc: u32) -> Cycles {
+        // Musashi charges just the exception cycles, not those of the RTE
+        // that found the bad frame
+        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_FORMAT_ERROR, 4)
+    }
+    pub fn handle_trace(&mut self, pc: u32) -> Cycles {
+        // Trace is a group 1 exception, taken after the traced instruction
+        // completes. It also ends the stopped state, should the traced
+        // instruction have been a STOP.
+        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_TRACE, 34)
+    }
 
     pub fn handle_exception(&mut self, new_state: ProcessingState, pc: u32, vector: u8, cycles: i32) -> Cycles {
         self.processing_state = new_state;
         let backup_sr = self.ensure_supervisor_mode();
 
-        // Group 1 and 2 stack frame (68000 only).
-        self.push_32(pc);
-        self.push_16(backup_sr);
-
-        self.jump_vector(vector);
-        Cycles(cycles)
+        let stacked = self.push_exception_frame(pc, backup_sr, vector)
+            .and_then(|_| self.jump_vector(vector));
+        match stacked {
+            Ok(_) => Cycles(self.exception_cycles(vector, cycles)),
+            Err(ex) => self.handle_exception_processing_fault(ex),
+        }
     }
 
     pub fn handle_interrupt(&mut self, irq_level: u8, vector: u8) -> Cycles {
@@ -991,28 +1971,48 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
         // new mask set here, in order to exclude from backup_sr
         self.int_mask = u32::from(irq_level) << 8;
         self.irq_level = irq_level;
+        if let Some(ref mut timing) = self.bus_timing {
+            timing.interrupt_acknowledge(vector == AUTOVECTOR_BASE + irq_level);
+        }
 
         // Musashi jumps first, and stacks later for interrupts,
         // but the other way around for exceptions
-        self.jump_vector(vector);
-
-        // Group 1 and 2 stack frame (68000 only).
-        self.push_32(pc);
-        self.push_16(backup_sr);
-
-        // 44 cycles for an interrupt according to MC68000UM, Table 8-14
-        // The interrupt acknowledge cycle is assumed to take four clock periods
-        Cycles(44)
+        let stacked = self.jump_vector(vector)
+            .and_then(|_| self.push_exception_frame(pc, backup_sr, vector))
+            .and_then(|_| self.push_throwaway_frame(pc, backup_sr, vector));
+
+        match stacked {
+            Ok(_) if self.cpu_type.is_020() => Cycles(30),
+            // 44 cycles for an interrupt according to MC68000UM, Table 8-14
+            // The interrupt acknowledge cycle is assumed to take four clock periods
+            Ok(_) => Cycles(self.exception_cycles(vector, 44)),
+            Err(ex) => self.handle_exception_processing_fault(ex),
+        }
+    }
+    // An interrupt taken on the master stack of the 68020 switches to the
+    // interrupt stack, leaving a format $1 frame there that RTE skips
+    fn push_throwaway_frame(&mut self, pc: u32, sr: u16, vector: u8) -> Result<()> {
+        if self.m_flag == MFLAG_CLEAR {
+            return Ok(());
+        }
+        self.change_stack_mode(SFLAG_SET, MFLAG_CLEAR);
+        self.push_16(0x1000 | u16::from(vector) << 2)?;
+        self.push_32(pc)?;
+        self.push_16(sr | 0x2000)?;
+        Ok(())
     }
     fn stopped_with_pending_interrups(&mut self) -> bool {
-        self.processing_state == ProcessingState::Stopped && self.pending_interrupt().is_some()
+        self.processing_state == ProcessingState::Stopped && self.sample_interrupts().is_some()
     }
     fn can_execute(&mut self) -> bool {
         self.processing_state.running() || self.stopped_with_pending_interrups()
     }
+    #[cfg(any(test, feature = "jit"))]
     fn pending_interrupt(&self) -> Option<u8> {
+        self.interrupt_at(self.int_ctrl.highest_priority())
+    }
+    fn interrupt_at(&self, new_level: u8) -> Option<u8> {
         let old_level = self.irq_level;
-        let new_level = self.int_ctrl.highest_priority();
         let edge_triggered_nmi = old_level != 7 && new_level == 7;
         if u32::from(new_level) << 8 > self.int_mask || edge_triggered_nmi {
             Some(new_level)
@@ -1020,14 +2020,32 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
             None
         }
     }
+    // Like pending_interrupt, but remembers the level seen when there is
+    // none, so that a level 7 that drops and comes back is a new edge
+    fn sample_interrupts(&mut self) -> Option<u8> {
+        let level = self.int_ctrl.highest_priority();
+        let pending = self.interrupt_at(level);
+        if pending.is_none() {
+            self.irq_level = level;
+        }
+        pending
+    }
     pub fn read_instruction(&mut self) -> Result<u16> {
         // first check for interrupts
-        if let Some(irq) = self.pending_interrupt() {
+        if let Some(irq) = self.sample_interrupts() {
             let vector = self.int_ctrl.acknowledge_interrupt(irq).unwrap_or(SPURIOUS_INTERRUPT);
             Err(Exception::Interrupt(irq, vector))
         } else {
             // not interrupted, read instruction from PC
-            self.read_imm_u16()
+            match self.begin_decode() {
+                Some(opcode) => {
+                    #[cfg(feature = "fc")]
+                    self.set_fc(false);
+                    self.pc = self.pc.wrapping_add(2);
+                    Ok(opcode)
+                },
+                None => self.read_imm_u16(),
+            }
         }
     }
     pub fn execute1(&mut self) -> Cycles {
@@ -1036,84 +2054,252 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
     pub fn execute(&mut self, cycles: i32) -> Cycles {
         self.execute_with_state(cycles, &mut EmulateAllExceptions)
     }
+    fn process_exception<S: Callbacks>(&mut self, ex: Exception, state: &mut S) -> Cycles {
+        // which ends loop mode, as does a jump
+        self.loop_mode = None;
+        match state.exception_callback(self, ex) {
+            Ok(cycles_used) => cycles_used,
+            Err(Exception::AddressError { address, access_type, processing_state, address_space }) =>
+                self.handle_address_error(address, access_type, processing_state, address_space),
+            Err(Exception::BusError { address, access_type, processing_state, address_space }) =>
+                self.handle_bus_error(address, access_type, processing_state, address_space),
+            Err(Exception::IllegalInstruction(_, pc)) =>
+                self.handle_illegal_instruction(pc),
+            Err(Exception::UnimplementedInstruction(_, pc, vector)) =>
+                self.handle_unimplemented_instruction(pc, vector),
+            Err(Exception::Trap(num, ea_calculation_cycles)) =>
+                self.handle_trap(num, ea_calculation_cycles),
+            Err(Exception::PrivilegeViolation(_, pc)) =>
+                self.handle_privilege_violation(pc),
+            Err(Exception::Interrupt(irq, vec)) =>
+                self.handle_interrupt(irq, vec),
+            Err(Exception::Trace(pc)) =>
+                self.handle_trace(pc),
+            Err(Exception::FormatError(_, pc)) =>
+                self.handle_format_error(pc),
+        }
+    }
     pub fn execute_with_state<S: Callbacks>(&mut self, cycles: i32, state: &mut S) -> Cycles {
+        self.run_with_state(cycles, state).0
+    }
+    pub fn run(&mut self, cycles: i32) -> (Cycles, StopReason) {
+        self.run_with_state(cycles, &mut EmulateAllExceptions)
+    }
+    // Executes one instruction, and if it called a subroutine, runs until
+    // that returns. Should the cycles run out first, run() continues the
+    // step over.
+    pub fn step_over(&mut self, cycles: i32) -> (Cycles, StopReason) {
+        self.debugger.start_step_over();
+        self.run(cycles)
+    }
+    pub fn registers(&self) -> Registers {
+        Registers { dar: self.dar, pc: self.pc, sr: self.status_register() }
+    }
+    pub fn register_value(&self, register: Register) -> u32 {
+        match register {
+            Register::D(reg) => self.dar[reg & 7],
+            Register::A(reg) => self.dar[8 + (reg & 7)],
+            Register::Pc => self.pc,
+            Register::Sr => u32::from(self.status_register()),
+        }
+    }
+    // Checks breakpoints, and whether a step over has returned. Returns
+    // the stack pointer to step over from, if asked to.
+    fn debugger_before(&mut self) -> result::Result<Option<u32>, StopReason> {
+        let pc = self.pc;
+        if let Some((return_address, sp)) = self.debugger.return_to() {
+            // deeper recursion reaches the return address on a lower stack
+            if pc == return_address && self.dar[15] >= sp {
+                self.debugger.set_return_to(None);
+                return Err(StopReason::Returned(pc));
+            }
+        }
+        if let Some(condition) = self.debugger.breakpoint_condition(pc) {
+            if condition.is_none_or(|c| c.holds(self.register_value(c.register))) {
+                self.debugger.stopped_at(pc);
+                return Err(StopReason::Breakpoint(pc));
+            }
+        }
+        self.debugger.resumed();
+        Ok(if self.debugger.take_stepping() { Some(self.dar[15]) } else { None })
+    }
+    fn debugger_after(&mut self, stepping_from: Option<u32>) -> Option<StopReason> {
+        if let Some(pc) = self.debugger.take_background() {
+            return Some(StopReason::Background(pc));
+        }
+        if let Some(sp) = stepping_from {
+            // JSR and BSR push exactly the return address
+            let call = self.ir & 0xffc0 == 0x4e80 || self.ir & 0xff00 == 0x6100;
+            if call && self.dar[15] == sp.wrapping_sub(4) {
+                let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+                let return_address = self.mem.read_long(address_space, self.dar[15]);
+                self.debugger.set_return_to(Some((return_address, sp)));
+            } else if let Some(hit) = self.debugger.take_hit() {
+                return Some(StopReason::Watchpoint(hit));
+            } else {
+                return Some(StopReason::Stepped(self.pc));
+            }
+        }
+        self.debugger.take_hit().map(StopReason::Watchpoint)
+    }
+    fn stop_reason(&self) -> StopReason {
+        match self.processing_state {
+            ProcessingState::Halted => StopReason::Halted,
+            ProcessingState::Stopped => StopReason::Stopped,
+            _ => StopReason::CyclesExhausted,
+        }
+    }
+    // Runs until the cycles are exhausted, the processor stops, or the
+    // debugger asks to stop, and returns the cycles used and why it
+    // returned
+    pub fn run_with_state<S: Callbacks>(&mut self, cycles: i32, state: &mut S) -> (Cycles, StopReason) {
         #[cfg(feature = "cycles")]
         let cycles = Cycles(cycles);
         let mut remaining_cycles = cycles;
+        let mut stop = None;
         #[cfg(feature = "cycles")]
         {
             while remaining_cycles.any() && self.can_execute() {
+                #[cfg(feature = "jit")]
+                {
+                    if let Some((used, exception)) = self.run_compiled(remaining_cycles.0, S::INSTRUCTION_HOOKS) {
+                        let used = match exception {
+                            Some(ex) => Cycles(used) + self.process_exception(ex, state),
+                            None => Cycles(used),
+                        };
+                        remaining_cycles = remaining_cycles - used;
+                        if let Some(reason) = self.debugger_after(None) {
+                            stop = Some(reason);
+                            break;
+                        }
+                        continue;
+                    }
+                }
+                let stepping_from = if self.debugger.is_active() {
+                    match self.debugger_before() {
+                        Ok(stepping_from) => stepping_from,
+                        Err(reason) => { stop = Some(reason); break; }
+                    }
+                } else { None };
+                // Tracing is decided by T1 when the instruction starts
+                let tracing = self.t1_flag != T1FLAG_CLEAR;
+                self.ppc = self.pc;
+                let before = if S::INSTRUCTION_HOOKS { Some(self.registers()) } else { None };
+                let mut fetched = false;
                 // Read an instruction from PC (increments PC by 2)
                 let result = self.read_instruction().and_then(|opcode| {
                         self.ir = opcode;
+                        fetched = true;
+                        if S::INSTRUCTION_HOOKS {
+                            let pc = before.map_or(0, |regs| regs.pc);
+                            state.before_instruction(self, pc, opcode);
+                        }
                         // Call instruction handler to mutate Core accordingly
                         self.instruction_set[opcode as usize](self)
                     });
                 #[cfg(feature = "fc")]
                 self.set_fc(true);
-                remaining_cycles = remaining_cycles - match result {
+                if let Some(ref mut cache) = self.decode_cache {
+                    cache.end(result.is_ok());
+                }
+                let cycles_used = match result {
+                    Ok(cycles_used) if tracing => {
+                        let pc = self.pc;
+                        cycles_used + self.process_exception(Exception::Trace(pc), state)
+                    },
                     Ok(cycles_used) => cycles_used,
-                    Err(ex) => {
-                        match state.exception_callback(self, ex) {
-                            Ok(cycles_used) => cycles_used,
-                            Err(Exception::AddressError { address, access_type, processing_state, address_space }) =>
-                                self.handle_address_error(address, access_type, processing_state, address_space),
-                            Err(Exception::IllegalInstruction(_, pc)) =>
-                                self.handle_illegal_instruction(pc),
-                            Err(Exception::UnimplementedInstruction(_, pc, vector)) =>
-                                self.handle_unimplemented_instruction(pc, vector),
-                            Err(Exception::Trap(num, ea_calculation_cycles)) =>
-                                self.handle_trap(num, ea_calculation_cycles),
-                            Err(Exception::PrivilegeViolation(_, pc)) =>
-                                self.handle_privilege_violation(pc),
-                            Err(Exception::Interrupt(irq, vec)) =>
-                                self.handle_interrupt(irq, vec),
-                        }
-                    }
+                    Err(ex) => self.process_exception(ex, state),
+                };
+                let cycles_used = match self.bus_timing {
+                    Some(ref mut timing) => Cycles(timing.finish(cycles_used.0)),
+                    None => cycles_used,
                 };
+                remaining_cycles = remaining_cycles - cycles_used;
+                if let (true, Some(before)) = (fetched, before) {
+                    let after = self.registers();
+                    state.after_instruction(self, &before, &after, cycles_used);
+                }
+                if let Some(reason) = self.debugger_after(stepping_from) {
+                    stop = Some(reason);
+                    break;
+                }
             }
+            let reason = stop.unwrap_or_else(|| self.stop_reason());
             if self.processing_state.running() {
-                cycles - remaining_cycles
+                (cycles - remaining_cycles, reason)
             } else {
                 // if not running, consume all available cycles
                 // including overconsumed cycles
                 let adjust = if remaining_cycles.0 < 0 { remaining_cycles } else { Cycles(0) };
-                cycles - adjust
+                if let Some(ref mut timing) = self.bus_timing {
+                    timing.idle((remaining_cycles - adjust).0);
+                }
+                (cycles - adjust, reason)
             }
         }
         #[cfg(not(feature = "cycles"))]
         {
             while remaining_cycles > 0 && self.can_execute() {
+                #[cfg(feature = "jit")]
+                {
+                    if let Some((used, exception)) = self.run_compiled(remaining_cycles, S::INSTRUCTION_HOOKS) {
+                        if let Some(ex) = exception {
+                            self.process_exception(ex, state);
+                        }
+                        remaining_cycles -= used;
+                        if let Some(reason) = self.debugger_after(None) {
+                            stop = Some(reason);
+                            break;
+                        }
+                        continue;
+                    }
+                }
+                let stepping_from = if self.debugger.is_active() {
+                    match self.debugger_before() {
+                        Ok(stepping_from) => stepping_from,
+                        Err(reason) => { stop = Some(reason); break; }
+                    }
+                } else { None };
+                // Tracing is decided by T1 when the instruction starts
+                let tracing = self.t1_flag != T1FLAG_CLEAR;
+                self.ppc = self.pc;
+                let before = if S::INSTRUCTION_HOOKS { Some(self.registers()) } else { None };
+                let mut fetched = false;
                 // Read an instruction from PC (increments PC by 2)
                 let result = self.read_instruction().and_then(|opcode| {
                         self.ir = opcode;
+                        fetched = true;
+                        if S::INSTRUCTION_HOOKS {
+                            let pc = before.map_or(0, |regs| regs.pc);
+                            state.before_instruction(self, pc, opcode);
+                        }
                         // Call instruction handler to mutate Core accordingly
                         self.instruction_set[opcode as usize](self)
                     });
                 #[cfg(feature = "fc")]
                 self.set_fc(true);
+                if let Some(ref mut cache) = self.decode_cache {
+                    cache.end(result.is_ok());
+                }
                 match result {
+                    Ok(_) if tracing => {
+                        let pc = self.pc;
+                        self.process_exception(Exception::Trace(pc), state)
+                    },
                     Ok(_) => {},
-                    Err(ex) => {
-                        match state.exception_callback(self, ex) {
-                            Ok(_) => {},
-                            Err(Exception::AddressError { address, access_type, processing_state, address_space }) =>
-                                self.handle_address_error(address, access_type, processing_state, address_space),
-                            Err(Exception::IllegalInstruction(_, pc)) =>
-                                self.handle_illegal_instruction(pc),
-                            Err(Exception::UnimplementedInstruction(_, pc, vector)) =>
-                                self.handle_unimplemented_instruction(pc, vector),
-                            Err(Exception::Trap(num, ea_calculation_cycles)) =>
-                                self.handle_trap(num, ea_calculation_cycles),
-                            Err(Exception::PrivilegeViolation(_, pc)) =>
-                                self.handle_privilege_violation(pc),
-                            Err(Exception::Interrupt(irq, vec)) =>
-                                self.handle_interrupt(irq, vec),
-                        }
-                    }
+                    Err(ex) => self.process_exception(ex, state),
                 };
                 remaining_cycles -= 1;
+                if let (true, Some(before)) = (fetched, before) {
+                    let after = self.registers();
+                    state.after_instruction(self, &before, &after, ());
+                }
+                if let Some(reason) = self.debugger_after(stepping_from) {
+                    stop = Some(reason);
+                    break;
+                }
             }
+            ((), stop.unwrap_or_else(|| self.stop_reason()))
         }
     }
 }
@@ -1124,10 +2310,14 @@ impl Clone for TestCore {
         lm.copy_from(&self.mem);
         assert_eq!(0, lm.logger.len());
         TestCore {
-            pc: self.pc, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
+            pc: self.pc, prefetch_addr: 0, prefetch_data: 0, loop_mode: None, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
             dar: self.dar, mem: lm, instruction_set: self.instruction_set.clone(),
+            cpu_type: self.cpu_type, vbr: self.vbr, sfc: self.sfc, dfc: self.dfc, cacr: self.cacr, caar: self.caar, inactive_msp: self.inactive_msp, address_mask: self.address_mask, ppc: self.ppc,
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag,
+            s_flag: self.s_flag, m_flag: self.m_flag, t1_flag: self.t1_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag,
+            debugger: self.debugger.clone(), fpu: self.fpu.clone(), mmu: self.mmu.clone(), bus_timing: self.bus_timing.clone(), decode_cache: self.decode_cache.clone(),
+            #[cfg(feature = "jit")]
+            jit: self.jit.clone(),
             #[cfg(feature = "fc")]
             fc_is_data: false,
         }
@@ -1138,7 +2328,7 @@ impl Clone for TestCore {
 mod tests {
     use super::{TestCore, Cycles};
     use super::ops; //::instruction_set;
-    use ram::{AddressBus, SUPERVISOR_PROGRAM, USER_PROGRAM, USER_DATA};
+    use ram::{AddressBus, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA};
     use ram::loggingmem::Operation;
     use cpu::ops::opcodes;
     
@@ -1228,6 +2418,18 @@ mod tests {
         assert_eq!(Operation::ReadWord(SUPERVISOR_PROGRAM, 2, 0x100), cpu.mem.logger.ops()[1]);
     }
 
+    #[test]
+    fn start_at_runs_from_the_entry_point_in_supervisor_mode() {
+        // 7005 is MOVEQ #5, D0
+        let mut cpu = TestCore::new_mem(0x400, &[0x70, 0x05]);
+        cpu.sr_to_flags(0x0000);
+        cpu.start_at(0x400, 0x8000, 7);
+        assert_eq!((0x400, 0x8000), (cpu.pc, cpu.dar[15]));
+        assert_eq!("-S7-----", cpu.flags());
+        cpu.execute1();
+        assert_eq!(5, cpu.dar[0]);
+    }
+
     #[test]
     fn execute_reads_from_pc_and_does_not_panic_on_illegal_instruction() {
         let mut cpu = TestCore::new_mem(0xba, &[0xba,0xd1,1u8,0u8, 0u8,0u8,0u8,128u8]);
@@ -1606,7 +2808,8 @@ mod tests {
         let mut core = TestCore::new(0x40);
         //Status register bits are:
         //      TTSM_0iii_000X_NZVC;
-        let f=0b0000_1000_1110_0000; // these bits should always be zero
+        let f=0b0100_1000_1110_0000; // these bits should always be zero
+        let t=0b1000_0000_0000_0000;
         let s=0b0010_0000_0000_0000;
         let i=0b0000_0111_0000_0000;
         let x=0b0000_0000_0001_0000;
@@ -1614,7 +2817,7 @@ mod tests {
         let z=0b0000_0000_0000_0100;
         let v=0b0000_0000_0000_0010;
         let c=0b0000_0000_0000_0001;
-        let flags = vec![x,n,z,v,c,f,s,i,0];
+        let flags = vec![x,n,z,v,c,f,t,s,i,0];
         for sf in flags {
             core.sr_to_flags(sf);
             let sr = core.status_register();
@@ -1672,7 +2875,7 @@ mod tests {
         cpu.dar[15] = 0x2000;
         assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
         assert_eq!(0x2000, cpu.usp());
-        cpu.sr_to_flags(0xffff); // Supa mode
+        cpu.sr_to_flags(0x7fff); // Supa mode, but not tracing
         assert_eq!(0x1000, cpu.ssp());
         assert_eq!(0x1000, cpu.dar[15]);
         assert_eq!(super::SFLAG_SET, cpu.s_flag);
@@ -1683,7 +2886,7 @@ mod tests {
     fn core_can_stop() {
         let initial_pc = 0x40;
         let mut cpu = TestCore::new_mem_init(initial_pc, &[0x4e, 0x72, 0x00, 0x00], opcodes::OP_NOP);
-        cpu.sr_to_flags(0xffff); // Supa mode
+        cpu.sr_to_flags(0x7fff); // Supa mode, but not tracing
         cpu.execute1();
         assert_eq!(0x0000, cpu.status_register());
         let next_instruction = initial_pc + 2 + 2; // 40 + instruction word + immediate word
@@ -1824,6 +3027,65 @@ mod tests {
         assert_eq!(None, cpu.pending_interrupt());
     }
 
+    #[test]
+    fn trace_exception_is_taken_after_instruction_when_t1_is_set() {
+        // opcodes d200 is ADD.B D0, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]);
+        let trace_handler = 0x1010;
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_TRACE as u32 * 4, trace_handler);
+        let trace_bit = 1 << 15;
+        let supervisor_bit = 1 << 13;
+        cpu.sr_to_flags(trace_bit | supervisor_bit | 0x0700);
+        cpu.dar[15] = 0x200;
+
+        assert_eq!(Cycles(4 + 34), cpu.execute1()); // ADD.B followed by trace exception
+        assert_eq!(trace_handler, cpu.pc);
+        assert_eq!(super::ProcessingState::Group1Exception, cpu.processing_state);
+        assert_eq!(super::T1FLAG_CLEAR, cpu.t1_flag);
+        // stacked PC points past the traced instruction, and stacked SR still has T1 set
+        // (as well as Z, set by ADD.B)
+        assert_eq!(0x200-6, cpu.dar[15]);
+        assert_eq!(0x42, cpu.mem.read_long(SUPERVISOR_PROGRAM, 0x200-4));
+        assert_eq!((trace_bit | supervisor_bit | 0x0700 | 0x0004) as u32, cpu.mem.read_word(SUPERVISOR_PROGRAM, 0x200-6));
+    }
+
+    #[test]
+    fn trace_exception_is_not_taken_when_t1_is_clear() {
+        // opcodes d200 is ADD.B D0, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]);
+        cpu.execute1();
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+    }
+
+    #[test]
+    fn trace_exception_is_not_taken_for_instruction_that_raises_exception() {
+        // real illegal instruction = 0x4afc
+        let mut cpu = TestCore::new_mem(0x40, &[0x4a, 0xfc]);
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x1010);
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_TRACE as u32 * 4, 0x2020);
+        cpu.sr_to_flags((1 << 15) | (1 << 13));
+        cpu.dar[15] = 0x200;
+
+        cpu.execute1();
+        assert_eq!(0x1010, cpu.pc);
+        assert_eq!(super::T1FLAG_CLEAR, cpu.t1_flag);
+        assert_eq!(0x200-6, cpu.dar[15]);
+    }
+
+    #[test]
+    fn trace_exception_ends_stopped_state() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72, 0xa7, 0x00]); // 0x4e72 STOP #$a700
+        let trace_handler = 0x1010;
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_TRACE as u32 * 4, trace_handler);
+        cpu.sr_to_flags((1 << 15) | (1 << 13));
+
+        cpu.execute1(); // will execute STOP, followed by the trace exception
+        assert_eq!(super::ProcessingState::Group1Exception, cpu.processing_state);
+        assert_eq!(trace_handler, cpu.pc);
+        assert_eq!(0x44, cpu.mem.read_long(SUPERVISOR_PROGRAM, cpu.dar[15] + 2));
+    }
+
     #[test]
     fn can_enter_halted_state() {
         // halted state is entered when a second group 0 exception
@@ -1841,6 +3103,75 @@ mod tests {
         // An external reset is needed.
     }
 
+    #[test]
+    fn bus_error_on_unmapped_read_stacks_group0_frame() {
+        // opcodes d278,0108 is ADD.W    $0108, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x78, 0x01, 0x08]);
+        let bus_error_handler = 0x1010;
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_BUS_ERROR as u32 * 4, bus_error_handler);
+        cpu.mem.unmap(0x100..0x200);
+        cpu.dar[15] = 0x1000;
+
+        assert_eq!(Cycles(50), cpu.execute1());
+        assert_eq!(bus_error_handler, cpu.pc);
+        assert_eq!(super::ProcessingState::Group0Exception, cpu.processing_state);
+        assert_eq!(0x1000-14, cpu.dar[15]);
+        // read access, during instruction processing
+        assert_eq!(0b10000, cpu.mem.read_word(SUPERVISOR_DATA, 0x1000-14) & 0b11000);
+        assert_eq!(0x108, cpu.mem.read_long(SUPERVISOR_DATA, 0x1000-12));
+        assert_eq!(0xd278, cpu.mem.read_word(SUPERVISOR_DATA, 0x1000-8));
+    }
+
+    #[test]
+    fn bus_error_on_unmapped_write_is_reported_to_exception_callback() {
+        // opcodes 31c1,0108 is MOVE.W   D1, $0108
+        let mut cpu = TestCore::new_mem(0x40, &[0x31, 0xc1, 0x01, 0x08]);
+        cpu.mem.unmap(0x100..0x200);
+
+        let mut handler = CustomExceptionHandler { suppress: true, count: 0, ex: None };
+        cpu.execute_with_state(1, &mut handler);
+        assert_eq!(1, handler.count);
+        if let Some(Exception::BusError { address, access_type: super::AccessType::Write, .. }) = handler.ex {
+            assert_eq!(0x108, address);
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn bus_error_while_stacking_exception_frame_halts() {
+        // real illegal instruction = 0x4afc
+        let mut cpu = TestCore::new_mem(0x40, &[0x4a, 0xfc]);
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x1010);
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_BUS_ERROR as u32 * 4, 0x2020);
+        // supervisor stack is unmapped, so both the illegal instruction
+        // frame and the following bus error frame will fault
+        cpu.mem.unmap(0x800..0x1000);
+        cpu.dar[15] = 0x1000;
+
+        cpu.execute1();
+        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
+    }
+
+    #[test]
+    fn bus_error_while_fetching_exception_vector_halts() {
+        // real illegal instruction = 0x4afc
+        let mut cpu = TestCore::new_mem(0x1040, &[0x4a, 0xfc]);
+        cpu.mem.unmap(0x0..0x400); // the entire vector table
+        cpu.dar[15] = 0x1000;
+
+        cpu.execute1();
+        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
+    }
+
+    #[test]
+    fn bus_error_while_fetching_reset_vector_halts() {
+        let mut cpu = TestCore::new(0x40);
+        cpu.mem.unmap(0x0..0x8);
+        cpu.reset();
+        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
+    }
+
     #[test]
     fn nmi_has_no_effect_in_halted_state() {
         let mut cpu = TestCore::new_mem(0x41, &[0x4e, 0x72]); // 0x4e72 STOP
@@ -1914,4 +3245,582 @@ mod tests {
             assert!(false);
         }
     }
+
+    #[test]
+    fn trace_exception_is_passed_to_exception_callback() {
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]); // d200 is ADD.B D0, D1
+        cpu.sr_to_flags((1 << 15) | (1 << 13));
+
+        let mut handler = CustomExceptionHandler { suppress: true, count: 0, ex: None };
+        let cycles = cpu.execute_with_state(1, &mut handler);
+        assert_eq!(1, handler.count);
+        assert_eq!(Cycles(4 + 1000), cycles); // ADD.B plus the cycles faked in our exception_callback
+        assert_eq!(0x42, cpu.pc);
+        if let Some(Exception::Trace(pc)) = handler.ex {
+            assert_eq!(0x42, pc);
+        } else {
+            assert!(false);
+        }
+    }
+    #[test]
+    fn save_state_roundtrip_restores_registers_interrupts_and_memory() {
+        // opcodes d278,0108 is ADD.W    $0108, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x78, 0x01, 0x08, 0xd2, 0x78, 0x01, 0x08]);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x108, 0x1234);
+        cpu.dar[1] = 0x8000;
+        cpu.dar[15] = 0x200;
+        cpu.inactive_usp = 0x100;
+        cpu.int_ctrl.request_interrupt(2);
+        cpu.execute1();
+        let state = cpu.save_state();
+
+        cpu.execute1();
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x108, 0x5678);
+        cpu.int_ctrl.reset_external_devices();
+        assert_eq!(Ok(()), cpu.load_state(&state));
+
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(0x9234, cpu.dar[1]);
+        assert_eq!(0x200, cpu.dar[15]);
+        assert_eq!(0x100, cpu.inactive_usp);
+        assert_eq!(0x1234, cpu.mem.read_word(SUPERVISOR_DATA, 0x108));
+        assert_eq!(2, cpu.int_ctrl.highest_priority());
+        assert_eq!(0b0010_0111_0000_1000, cpu.status_register()); // S, mask 7, N
+        // and executes the same way again
+        cpu.execute1();
+        assert_eq!(0xa468, cpu.dar[1]);
+    }
+
+    #[test]
+    fn save_state_can_be_loaded_into_another_core() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72, 0x00, 0x00]); // 0x4e72 STOP #0
+        cpu.execute1();
+        let state = cpu.save_state();
+        let mut other = TestCore::new(0x1000);
+        assert_eq!(Ok(()), other.load_state(&state));
+        assert_eq!(super::ProcessingState::Stopped, other.processing_state);
+        assert_eq!(0x44, other.pc);
+        assert_eq!(0x4e72, other.mem.read_word(SUPERVISOR_PROGRAM, 0x40));
+    }
+
+    #[test]
+    fn load_state_rejects_bad_states() {
+        use savestate::StateError;
+        let mut cpu = TestCore::new(0x40);
+        let state = cpu.save_state();
+        assert_eq!(Err(StateError::Truncated), cpu.load_state(&state[..state.len() - 1]));
+        let mut with_trailing_data = state.clone();
+        with_trailing_data.push(0);
+        assert_eq!(Err(StateError::Invalid("trailing data")), cpu.load_state(&with_trailing_data));
+        assert_eq!(Err(StateError::BadMagic), cpu.load_state(&state[1..]));
+    }
+
+    #[test]
+    fn run_stops_at_conditional_breakpoints() {
+        use cpu::debugger::{Comparison, Condition, Register, StopReason};
+        // 5280 is ADDQ.L #1, D0 and 60fc is BRA.S back to it
+        let mut cpu = TestCore::new_mem(0x40, &[0x52, 0x80, 0x60, 0xfc]);
+        cpu.debugger.add_conditional_breakpoint(0x42, Condition::new(Register::D(0), Comparison::Equal, 3));
+        assert_eq!(StopReason::Breakpoint(0x42), cpu.run(1000).1);
+        assert_eq!(3, cpu.dar[0]);
+        // resuming passes the breakpoint, and the condition doesn't hold again
+        assert_eq!(StopReason::CyclesExhausted, cpu.run(1000).1);
+        assert!(cpu.dar[0] > 3);
+    }
+
+    #[test]
+    fn run_stops_after_watched_access() {
+        use cpu::debugger::{StopReason, Watch, WatchHit, Watchpoint};
+        use cpu::AccessType;
+        // 21c0,0100 is MOVE.L D0, ($0100).W and 60fe is BRA.S *
+        let mut cpu = TestCore::new_mem(0x40, &[0x21, 0xc0, 0x01, 0x00, 0x60, 0xfe]);
+        cpu.debugger.add_watchpoint(Watchpoint::new(0x102..=0x103, Watch::Write).in_address_space(USER_DATA));
+        assert_eq!(StopReason::CyclesExhausted, cpu.run(100).1);
+        cpu.jump(0x40);
+        cpu.debugger.add_watchpoint(Watchpoint::new(0x102..=0x103, Watch::Write).in_address_space(SUPERVISOR_DATA));
+        let hit = WatchHit { address: 0x100, access_type: AccessType::Write, address_space: SUPERVISOR_DATA };
+        assert_eq!(StopReason::Watchpoint(hit), cpu.run(100).1);
+        assert_eq!(0x44, cpu.pc);
+    }
+
+    #[test]
+    fn step_over_runs_until_subroutine_returns() {
+        use cpu::debugger::StopReason;
+        // 6104 is BSR.S to 0x46, 4e71 is NOP and 4e75 is RTS
+        let mut cpu = TestCore::new_mem(0x40, &[0x61, 0x04, 0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x75]);
+        cpu.dar[15] = 0x1000;
+        assert_eq!(StopReason::Returned(0x42), cpu.step_over(1000).1);
+        assert_eq!(0x1000, cpu.dar[15]);
+        assert_eq!(StopReason::Stepped(0x44), cpu.step_over(1000).1);
+
+        // a breakpoint in the subroutine interrupts the step over, until resumed
+        cpu.jump(0x40);
+        cpu.debugger.add_breakpoint(0x48);
+        assert_eq!(StopReason::Breakpoint(0x48), cpu.step_over(1000).1);
+        assert_eq!(StopReason::Returned(0x42), cpu.run(1000).1);
+    }
+
+    #[test]
+    fn run_reports_stop_and_halt() {
+        use cpu::debugger::StopReason;
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72, 0x27, 0x00]); // 0x4e72 STOP #$2700
+        assert_eq!(StopReason::Stopped, cpu.run(1000).1);
+        cpu.processing_state = super::ProcessingState::Halted;
+        assert_eq!(StopReason::Halted, cpu.run(1000).1);
+    }
+
+    #[test]
+    fn movec_is_illegal_on_the_68000() {
+        use cpu::CpuType;
+        // 4e7b,0801 is MOVEC D0, VBR
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x7b, 0x08, 0x01]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x10, 0x1000);
+        cpu.dar[0] = 0x2000;
+        cpu.dar[15] = 0x400;
+        let mut mc68010 = cpu.clone();
+        mc68010.set_cpu_type(CpuType::M68010);
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc);
+        assert_eq!(0, cpu.vbr);
+        mc68010.execute1();
+        assert_eq!(0x44, mc68010.pc);
+        assert_eq!(0x2000, mc68010.vbr);
+    }
+
+    #[test]
+    fn mc68010_exceptions_use_the_vbr_and_format_words() {
+        use cpu::CpuType;
+        // 4e7b,0801 is MOVEC D0, VBR and 4e40 is TRAP #0
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x7b, 0x08, 0x01, 0x4e, 0x40]);
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x1080, 0x2000);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x2000, 0x4e73); // RTE
+        cpu.dar[0] = 0x1000;
+        cpu.dar[15] = 0x400;
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(0x2000, cpu.pc);
+        assert_eq!(0x3f8, cpu.dar[15]);
+        assert_eq!(0x46, cpu.mem.read_long(SUPERVISOR_DATA, 0x3fa));
+        assert_eq!(0x0080, cpu.mem.read_word(SUPERVISOR_DATA, 0x3fe)); // format 0, vector 32
+        cpu.execute1();
+        assert_eq!(0x46, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
+    }
+
+    #[test]
+    fn mc68010_rte_rejects_unknown_frame_formats() {
+        use cpu::CpuType;
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x73]); // RTE
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x38, 0x3000);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x3f8, 0x2700);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x3fa, 0x100);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x3fe, 0x9000);
+        cpu.dar[15] = 0x3f8;
+        cpu.execute1();
+        assert_eq!(0x3000, cpu.pc);
+        assert_eq!(0x3f0, cpu.dar[15]);
+        assert_eq!(0x42, cpu.mem.read_long(SUPERVISOR_DATA, 0x3f2));
+        assert_eq!(0x0038, cpu.mem.read_word(SUPERVISOR_DATA, 0x3f6)); // format 0, vector 14
+    }
+
+    #[test]
+    fn mc68010_move_from_sr_is_privileged() {
+        use cpu::CpuType;
+        // 40c0 is MOVE SR, D0 and 42c1 is MOVE CCR, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0x40, 0xc0, 0x42, 0xc1]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x20, 0x1000);
+        cpu.sr_to_flags(0x0004);
+        cpu.inactive_ssp = 0x400;
+        let mut mc68010 = cpu.clone();
+        mc68010.set_cpu_type(CpuType::M68010);
+        cpu.execute1();
+        assert_eq!(0x0004, cpu.dar[0]);
+        mc68010.execute1();
+        assert_eq!(0x1000, mc68010.pc);
+        assert_eq!(0, mc68010.dar[0]);
+        mc68010.pc = 0x42;
+        mc68010.sr_to_flags(0x0004);
+        mc68010.execute1();
+        assert_eq!(0x0004, mc68010.dar[1]);
+    }
+
+    #[test]
+    fn mc68010_moves_uses_the_function_code_registers() {
+        use cpu::CpuType;
+        // 0e90,0800 is MOVES.L D0, (A0) and 0e50,9000 is MOVES.W (A0), A1
+        let mut cpu = TestCore::new_mem(0x40, &[0x0e, 0x90, 0x08, 0x00, 0x0e, 0x50, 0x90, 0x00]);
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.dar[0] = 0x8765_4321;
+        cpu.dar[8] = 0x200;
+        cpu.sfc = 1;
+        cpu.dfc = 1;
+        cpu.execute1();
+        assert!(cpu.mem.logger.ops().contains(&Operation::WriteLong(USER_DATA, 0x200, 0x8765_4321)));
+        cpu.execute1();
+        assert!(cpu.mem.logger.ops().contains(&Operation::ReadWord(USER_DATA, 0x200, 0x8765)));
+        assert_eq!(0xffff_8765, cpu.dar[9]);
+    }
+
+    #[test]
+    fn mc68010_rtd_deallocates_parameters() {
+        use cpu::CpuType;
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x74, 0x00, 0x08]); // RTD #8
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x400, 0x100);
+        cpu.dar[15] = 0x400;
+        cpu.execute1();
+        assert_eq!(0x100, cpu.pc);
+        assert_eq!(0x40c, cpu.dar[15]);
+    }
+
+    #[test]
+    fn mc68010_runs_dbcc_loops_in_loop_mode() {
+        use cpu::CpuType;
+        // 32d8 is MOVE.W (A0)+, (A1)+ and 51c8,fffc is DBF D0 back to it
+        let mut cpu = TestCore::new_mem(0x40, &[0x32, 0xd8, 0x51, 0xc8, 0xff, 0xfc]);
+        cpu.dar[0] = 2;
+        cpu.dar[8] = 0x100;
+        cpu.dar[9] = 0x200;
+        let mut mc68010 = cpu.clone();
+        mc68010.set_cpu_type(CpuType::M68010);
+        let cycles: Vec<Cycles> = (0..6).map(|_| cpu.execute1()).collect();
+        assert_eq!(vec![Cycles(12), Cycles(10), Cycles(12), Cycles(10), Cycles(12), Cycles(14)], cycles);
+        // the first iteration enters loop mode, and the later ones fetch
+        // no opcodes until the count expires
+        let cycles: Vec<Cycles> = (0..6).map(|_| mc68010.execute1()).collect();
+        assert_eq!(vec![Cycles(12), Cycles(10), Cycles(12), Cycles(2), Cycles(12), Cycles(10)], cycles);
+        assert_eq!((0x46, 0xffff, 0x106), (mc68010.pc, mc68010.dar[0], mc68010.dar[8]));
+        assert_eq!(None, mc68010.loop_mode);
+    }
+
+    #[test]
+    fn mc68010_loop_mode_reads_only_data() {
+        use cpu::{CpuType, LoopBuffer};
+        use ram::loggingmem::Operation;
+        let mut cpu = TestCore::new_mem(0x40, &[0x32, 0xd8, 0x51, 0xc8, 0xff, 0xfc, 0x4e, 0x71]);
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.dar[0] = 2;
+        cpu.dar[8] = 0x100;
+        cpu.dar[9] = 0x200;
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(Some(LoopBuffer { address: 0x40, words: [0x32d8, 0x51c8, 0xfffc] }), cpu.loop_mode);
+        let fetched = cpu.mem.logger.len();
+        for _ in 0..3 {
+            cpu.execute1();
+        }
+        // MOVE, DBF and MOVE again, reading nothing but the operands (which
+        // only the fc feature tells from program reads)
+        let operands = if cfg!(feature = "fc") {SUPERVISOR_DATA} else {SUPERVISOR_PROGRAM};
+        assert_eq!(vec![Operation::ReadWord(operands, 0x102, 0xaaaa), Operation::WriteWord(SUPERVISOR_DATA, 0x202, 0xaaaa),
+            Operation::ReadWord(operands, 0x104, 0xaaaa), Operation::WriteWord(SUPERVISOR_DATA, 0x204, 0xaaaa)],
+            cpu.mem.logger.ops()[fetched..].to_vec());
+        // the loop ends, and the instruction after it is fetched as usual
+        cpu.execute1();
+        cpu.execute1();
+        assert!(cpu.mem.logger.ops()[fetched..].contains(&Operation::ReadWord(SUPERVISOR_PROGRAM, 0x46, 0x4e71)));
+    }
+
+    #[test]
+    fn mc68010_loop_mode_needs_a_loopable_instruction() {
+        use cpu::CpuType;
+        // 3200 is MOVE.W D0, D1, which has no memory operand
+        let mut cpu = TestCore::new_mem(0x40, &[0x32, 0x00, 0x51, 0xc8, 0xff, 0xfc]);
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.dar[0] = 2;
+        let cycles: Vec<Cycles> = (0..4).map(|_| cpu.execute1()).collect();
+        assert_eq!(vec![Cycles(4), Cycles(10), Cycles(4), Cycles(10)], cycles);
+        assert_eq!(None, cpu.loop_mode);
+        for &(opcode, loopable) in [(0x20d9, true), (0x4298, true), (0xd090, true), (0xd1d0, true), (0xc308, true),
+            (0xb549, true), (0xe2d0, true), (0x4a40, false), (0x80d0, false), (0xc348, false), (0xd1c8, false), (0x3210, false)].iter() {
+            assert_eq!(loopable, ops::is_loopable(opcode), "{:04x}", opcode);
+        }
+    }
+
+    #[test]
+    fn mc68010_exception_cycles_are_taken_by_vector() {
+        use cpu::CpuType;
+        // 4afc is ILLEGAL, a000 an unimplemented 1010 instruction and
+        // 4e40 TRAP #0
+        for &(opcode, m68000, m68010) in [(0x4afc, 34, 38), (0xa000, 34, 4), (0x4e40, 34, 38)].iter() {
+            let mut cpu = TestCore::new_mem(0x40, &[(opcode >> 8) as u8, opcode as u8]);
+            cpu.dar[15] = 0x400;
+            let mut mc68010 = cpu.clone();
+            mc68010.set_cpu_type(CpuType::M68010);
+            assert_eq!((Cycles(m68000), Cycles(m68010)), (cpu.execute1(), mc68010.execute1()), "{:04x}", opcode);
+        }
+    }
+
+    #[test]
+    fn save_state_keeps_the_cpu_type() {
+        use cpu::CpuType;
+        let mut cpu = TestCore::new(0x40);
+        cpu.set_cpu_type(CpuType::M68010);
+        cpu.vbr = 0x1000;
+        cpu.dfc = 5;
+        let state = cpu.save_state();
+        let mut other = TestCore::new(0x40);
+        assert_eq!(Ok(()), other.load_state(&state));
+        assert_eq!(CpuType::M68010, other.cpu_type());
+        assert_eq!(0x1000, other.vbr);
+        assert_eq!(5, other.dfc);
+    }
+
+    #[test]
+    fn only_the_mc68020_has_a_32_bit_address_bus() {
+        use cpu::CpuType;
+        let mut cpu = TestCore::new_mem(0x40, &[0x20, 0x80]); // MOVE.L D0, (A0)
+        cpu.dar[0] = 0x1234_5678;
+        cpu.dar[8] = 0x0100_0200;
+        let mut mc68ec020 = cpu.clone();
+        mc68ec020.set_cpu_type(CpuType::M68EC020);
+        let mut mc68020 = cpu.clone();
+        mc68020.set_cpu_type(CpuType::M68020);
+        cpu.execute1();
+        assert!(cpu.mem.logger.ops().contains(&Operation::WriteLong(SUPERVISOR_DATA, 0x200, 0x1234_5678)));
+        mc68ec020.execute1();
+        assert!(mc68ec020.mem.logger.ops().contains(&Operation::WriteLong(SUPERVISOR_DATA, 0x200, 0x1234_5678)));
+        mc68020.execute1();
+        assert!(mc68020.mem.logger.ops().contains(&Operation::WriteLong(SUPERVISOR_DATA, 0x0100_0200, 0x1234_5678)));
+    }
+
+    #[test]
+    fn cores_of_the_same_processor_share_an_instruction_set() {
+        use cpu::CpuType;
+        let mut one = TestCore::new_mem(0x40, &[0x4e, 0x71]);
+        let mut other = one.clone();
+        one.set_cpu_type(CpuType::M68020);
+        other.set_cpu_type(CpuType::M68020);
+        assert!(std::sync::Arc::ptr_eq(&one.instruction_set, &other.instruction_set));
+        other.set_cpu_type(CpuType::M68010);
+        assert!(!std::sync::Arc::ptr_eq(&one.instruction_set, &other.instruction_set));
+    }
+
+    #[test]
+    fn mc68020_reads_data_from_odd_addresses() {
+        use cpu::CpuType;
+        let mut cpu = TestCore::new_mem(0x40, &[0x30, 0x10]); // MOVE.W (A0), D0
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x200, 0x1234_5678);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0xc, 0x1000);
+        cpu.dar[8] = 0x201;
+        cpu.dar[15] = 0x400;
+        let mut mc68020 = cpu.clone();
+        mc68020.set_cpu_type(CpuType::M68020);
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc); // address error
+        mc68020.execute1();
+        assert_eq!(0x42, mc68020.pc);
+        assert_eq!(0x3456, mc68020.dar[0]);
+    }
+
+    #[test]
+    fn mc68020_divide_by_zero_stacks_the_instruction_address() {
+        use cpu::CpuType;
+        // 4e71 is NOP and 80c1 is DIVU.W D1, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x80, 0xc1]);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x14, 0x1000);
+        cpu.dar[15] = 0x400;
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc);
+        assert_eq!(0x3f4, cpu.dar[15]);
+        assert_eq!(0x44, cpu.mem.read_long(SUPERVISOR_DATA, 0x3f6));
+        assert_eq!(0x2014, cpu.mem.read_word(SUPERVISOR_DATA, 0x3fa)); // format 2, vector 5
+        assert_eq!(0x42, cpu.mem.read_long(SUPERVISOR_DATA, 0x3fc));
+    }
+
+    #[test]
+    fn mc68020_interrupts_leave_the_master_stack() {
+        use cpu::CpuType;
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71]); // NOP
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x74, 0x1000);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1000, 0x4e73); // RTE
+        cpu.dar[15] = 0x400;
+        cpu.inactive_msp = 0x800;
+        cpu.sr_to_flags(0x3000); // supervisor mode, on the master stack
+        assert_eq!(0x800, cpu.dar[15]);
+        cpu.int_ctrl.request_interrupt(5);
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc);
+        assert_eq!(0x7f8, cpu.msp());
+        assert_eq!(0x0074, cpu.mem.read_word(SUPERVISOR_DATA, 0x7fe)); // format 0, vector 29
+        assert_eq!(0x3f8, cpu.dar[15]);
+        assert_eq!(0x1074, cpu.mem.read_word(SUPERVISOR_DATA, 0x3fe)); // the throwaway frame
+        cpu.execute1();
+        assert_eq!(0x40, cpu.pc);
+        assert_eq!(0x800, cpu.dar[15]);
+        assert_eq!(0x400, cpu.ssp());
+        assert_eq!(0x3000, cpu.status_register() & 0xff00);
+    }
+
+    #[test]
+    fn mc68020_cas2_updates_both_operands_or_neither() {
+        use cpu::CpuType;
+        // 0efc,8080,90c1 is CAS2.L D0:D1, D2:D3, (A0):(A1)
+        let mut cpu = TestCore::new_mem(0x40, &[0x0e, 0xfc, 0x80, 0x80, 0x90, 0xc1]);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x200, 0x11);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x300, 0x22);
+        cpu.dar = [0x11, 0x23, 0xaa, 0xbb, 0, 0, 0, 0, 0x200, 0x300, 0, 0, 0, 0, 0, 0x400];
+        let mut matching = cpu.clone();
+        matching.dar[1] = 0x22;
+        cpu.execute1();
+        assert_eq!(0x11, cpu.mem.read_long(SUPERVISOR_DATA, 0x200));
+        assert_eq!(0x22, cpu.mem.read_long(SUPERVISOR_DATA, 0x300));
+        assert_eq!(0x22, cpu.dar[1]);
+        assert_eq!(super::ZFLAG_CLEAR, cpu.not_z_flag);
+        matching.execute1();
+        assert_eq!(0xaa, matching.mem.read_long(SUPERVISOR_DATA, 0x200));
+        assert_eq!(0xbb, matching.mem.read_long(SUPERVISOR_DATA, 0x300));
+        assert_eq!(super::ZFLAG_SET, matching.not_z_flag);
+    }
+
+    #[test]
+    fn mc68020_chk2_traps_outside_the_bounds() {
+        use cpu::CpuType;
+        // 04d0,0800 is CHK2.L (A0), D0
+        let mut cpu = TestCore::new_mem(0x40, &[0x04, 0xd0, 0x08, 0x00]);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x18, 0x1000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x200, 10);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x204, 20);
+        cpu.dar[8] = 0x200;
+        cpu.dar[15] = 0x400;
+        cpu.dar[0] = 20;
+        let mut above = cpu.clone();
+        above.dar[0] = 21;
+        cpu.execute1();
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(super::ZFLAG_SET, cpu.not_z_flag);
+        above.execute1();
+        assert_eq!(0x1000, above.pc);
+        assert_eq!(super::CFLAG_SET, above.c_flag);
+    }
+
+    #[test]
+    fn mc68020_pack_and_unpk_convert_bcd() {
+        use cpu::CpuType;
+        // 8340,0000 is PACK D0, D1, #0 and 8581,3030 is UNPK D1, D2, #$3030
+        let mut cpu = TestCore::new_mem(0x40, &[0x83, 0x40, 0x00, 0x00, 0x85, 0x81, 0x30, 0x30]);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.dar[0] = 0x3334; // "34"
+        cpu.execute1();
+        assert_eq!(0x34, cpu.dar[1]);
+        cpu.execute1();
+        assert_eq!(0x3334, cpu.dar[2]);
+    }
+
+    #[test]
+    fn mc68020_bitfields_count_from_the_most_significant_bit() {
+        use cpu::CpuType;
+        // e9c0,1108 is BFEXTU D0{4:8}, D1 and efd0,1308 is BFINS D1, (A0){12:8}
+        let mut cpu = TestCore::new_mem(0x40, &[0xe9, 0xc0, 0x11, 0x08, 0xef, 0xd0, 0x13, 0x08]);
+        cpu.set_cpu_type(CpuType::M68020);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x200, 0);
+        cpu.dar[0] = 0x1234_5678;
+        cpu.dar[8] = 0x200;
+        cpu.execute1();
+        assert_eq!(0x23, cpu.dar[1]);
+        cpu.execute1();
+        assert_eq!(0x0002_3000, cpu.mem.read_long(SUPERVISOR_DATA, 0x200));
+    }
+
+    #[test]
+    fn cpu32_tbl_interpolates_between_entries() {
+        use cpu::CpuType;
+        // f810,0140 is TBLU.W (A0), D0 and f801,3c02 is TBLSN.B D1:D2, D3
+        let mut cpu = TestCore::new_mem(0x40, &[0xf8, 0x10, 0x01, 0x40, 0xf8, 0x01, 0x3c, 0x02]);
+        cpu.set_cpu_type(CpuType::Cpu32);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x200, 100);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x202, 200);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x204, 301);
+        cpu.dar[0] = 0x1234_0180; // halfway between the second and third entry
+        cpu.dar[8] = 0x200;
+        cpu.dar[1] = 0x10;
+        cpu.dar[2] = 0xf0;
+        cpu.dar[3] = 0xc0;
+        cpu.execute1();
+        assert_eq!(0x1234_00fb, cpu.dar[0]);
+        cpu.execute1();
+        assert_eq!(0xffff_f800, cpu.dar[3]); // -8.0, with eight fraction bits
+        assert!(cpu.cond_mi());
+    }
+
+    #[test]
+    fn cpu32_lpstop_is_a_privileged_stop() {
+        use cpu::CpuType;
+        // f800,01c0,2700 is LPSTOP #$2700
+        let mut cpu = TestCore::new_mem(0x40, &[0xf8, 0x00, 0x01, 0xc0, 0x27, 0x00]);
+        cpu.set_cpu_type(CpuType::Cpu32);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x20, 0x1000);
+        cpu.dar[15] = 0x400;
+        let mut user_mode = cpu.clone();
+        cpu.execute1();
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+        assert_eq!(0x2700, cpu.status_register());
+        user_mode.sr_to_flags(0x0000);
+        user_mode.execute1();
+        assert_eq!(0x1000, user_mode.pc);
+        assert_eq!(0x40, user_mode.mem.read_long(SUPERVISOR_DATA, 0x3fa));
+    }
+
+    #[test]
+    fn cpu32_bgnd_stops_the_run_loop_in_background_mode() {
+        use cpu::CpuType;
+        use cpu::debugger::StopReason;
+        let mut cpu = TestCore::new_mem(0x40, &[0x4a, 0xfa]); // BGND
+        cpu.set_cpu_type(CpuType::Cpu32);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x10, 0x1000);
+        cpu.dar[15] = 0x400;
+        let mut background = cpu.clone();
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc); // an illegal instruction
+        background.debugger.enable_background_mode(true);
+        assert_eq!(StopReason::Background(0x42), background.run(1000).1);
+        assert_eq!(0x42, background.pc);
+    }
+
+    #[test]
+    fn cpu32_scales_the_index_but_has_no_full_format() {
+        use cpu::CpuType;
+        // 3030,1204 is MOVE.W (4,A0,D1.W*2), D0 and 3030,1110 the same
+        // with a full format extension word
+        let mut cpu = TestCore::new_mem(0x40, &[0x30, 0x30, 0x12, 0x04, 0x30, 0x30, 0x11, 0x10]);
+        cpu.set_cpu_type(CpuType::Cpu32);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x10, 0x1000);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x208, 0x1234);
+        cpu.dar[1] = 2;
+        cpu.dar[8] = 0x200;
+        cpu.dar[15] = 0x400;
+        cpu.execute1();
+        assert_eq!(0x1234, cpu.dar[0] & 0xffff);
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc);
+        assert_eq!(0x44, cpu.mem.read_long(SUPERVISOR_DATA, 0x3fa));
+    }
+
+    #[test]
+    fn cpu32_bus_error_stacks_a_format_c_frame() {
+        use cpu::CpuType;
+        // opcodes d278,0108 is ADD.W    $0108, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x78, 0x01, 0x08]);
+        cpu.set_cpu_type(CpuType::Cpu32);
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_BUS_ERROR as u32 * 4, 0x1000);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1000, 0x4e73); // RTE
+        cpu.mem.unmap(0x100..0x200);
+        cpu.dar[15] = 0x1000;
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.pc);
+        assert_eq!(0x1000-24, cpu.dar[15]);
+        assert_eq!(0xc008, cpu.mem.read_word(SUPERVISOR_DATA, 0x1000-18)); // format $C, vector 2
+        assert_eq!(0x108, cpu.mem.read_long(SUPERVISOR_DATA, 0x1000-16));
+        assert_eq!(0x40, cpu.mem.read_long(SUPERVISOR_DATA, 0x1000-8));
+        assert_eq!(0x40, cpu.mem.read_word(SUPERVISOR_DATA, 0x1000-2) & 0x40); // a read
+        cpu.execute1();
+        assert_eq!(0x1000, cpu.dar[15]);
+    }
 }
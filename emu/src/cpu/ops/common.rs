@@ -1,6 +1,6 @@
 #![macro_use]
 use super::super::Core;
-use cpu::{CFLAG_SET, ZFLAG_SET, XFLAG_SET, NFLAG_SET, ZFLAG_CLEAR, VFLAG_CLEAR, CFLAG_CLEAR, XFLAG_CLEAR, NFLAG_CLEAR};
+use cpu::{CFLAG_SET, ZFLAG_SET, XFLAG_SET, NFLAG_SET, VFLAG_SET, ZFLAG_CLEAR, VFLAG_CLEAR, CFLAG_CLEAR, XFLAG_CLEAR, NFLAG_CLEAR};
 use std::num::Wrapping;
 
 macro_rules! ir_dx {
@@ -509,6 +509,77 @@ static SHIFT_32_TABLE: [u32; 65] = [
  0xffff_ffff, 0xffff_ffff, 0xffff_ffff, 0xffff_ffff, 0xffff_ffff
 ];
 
+// Put common implementation of BFCHG, BFCLR, BFEXTS, BFEXTU, BFFFO, BFINS, BFSET and BFTST here
+// These get the field right aligned, and return its new value if it is to
+// be written back. The offset is only used by BFFFO.
+fn bitfield_flags<T: Core>(core: &mut T, field: u32, width: u32) {
+    n_flag!(core) = (field << (32 - width)) >> 24;
+    not_z_flag!(core) = field;
+    v_flag!(core) = VFLAG_CLEAR;
+    c_flag!(core) = CFLAG_CLEAR;
+}
+fn width_mask(width: u32) -> u32 {
+    0xffff_ffff >> (32 - width)
+}
+pub fn bfchg<T: Core>(core: &mut T, _word2: u16, _offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    Some(!field & width_mask(width))
+}
+pub fn bfclr<T: Core>(core: &mut T, _word2: u16, _offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    Some(0)
+}
+pub fn bfexts<T: Core>(core: &mut T, word2: u16, _offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    dar!(core)[((word2 >> 12) & 7) as usize] = (((field << (32 - width)) as i32) >> (32 - width)) as u32;
+    None
+}
+pub fn bfextu<T: Core>(core: &mut T, word2: u16, _offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    dar!(core)[((word2 >> 12) & 7) as usize] = field;
+    None
+}
+// the offset of the first set bit, or of the bit after the field
+pub fn bfffo<T: Core>(core: &mut T, word2: u16, offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    let zeroes = (field << (32 - width)).leading_zeros().min(width);
+    dar!(core)[((word2 >> 12) & 7) as usize] = offset.wrapping_add(zeroes);
+    None
+}
+pub fn bfins<T: Core>(core: &mut T, word2: u16, _offset: u32, width: u32, _field: u32) -> Option<u32> {
+    let insert = dar!(core)[((word2 >> 12) & 7) as usize] & width_mask(width);
+    bitfield_flags(core, insert, width);
+    Some(insert)
+}
+pub fn bfset<T: Core>(core: &mut T, _word2: u16, _offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    Some(width_mask(width))
+}
+pub fn bftst<T: Core>(core: &mut T, _word2: u16, _offset: u32, width: u32, field: u32) -> Option<u32> {
+    bitfield_flags(core, field, width);
+    None
+}
+
+// Put common implementation of CHK2, CMP2 here
+// Rn (bits 15-12) is within the bounds if it is no further above the
+// lower bound than the upper bound is, which works for both signed and
+// unsigned bounds. Address registers are compared in full, against
+// sign extended bounds. Returns true if Rn is out of bounds.
+pub fn chk2cmp2<T: Core>(core: &mut T, word2: u16, lower: u32, upper: u32, bits: u32) -> bool {
+    let rn = dar!(core)[(word2 >> 12) as usize];
+    let (lower, upper, mask) = if word2 & 0x8000 != 0 {
+        let sign_extend = |bound: u32| (((bound << (32 - bits)) as i32) >> (32 - bits)) as u32;
+        (sign_extend(lower), sign_extend(upper), 0xffff_ffff)
+    } else {
+        (lower, upper, 0xffff_ffff >> (32 - bits))
+    };
+    let (rn, lower, upper) = (rn & mask, lower & mask, upper & mask);
+    let out_of_bounds = rn.wrapping_sub(lower) & mask > upper.wrapping_sub(lower) & mask;
+    not_z_flag!(core) = if rn == lower || rn == upper { ZFLAG_SET } else { ZFLAG_CLEAR };
+    c_flag!(core) = if out_of_bounds { CFLAG_SET } else { CFLAG_CLEAR };
+    out_of_bounds
+}
+
 pub fn cmp_8<T: Core>(core: &mut T, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
@@ -589,6 +660,52 @@ pub fn divu_16<T: Core>(core: &mut T, dst: u32, src: u16) {
     }
 }
 
+// Put common implementation of DIVS.L, DIVU.L here
+// The extension word selects a signed (bit 11) division of Dq (bits 14-12)
+// or of Dr:Dq (bit 10), with the remainder going to Dr (bits 2-0). The
+// divisor must not be zero.
+pub fn divl_32<T: Core>(core: &mut T, word2: u16, divisor: u32) {
+    let dq = ((word2 >> 12) & 7) as usize;
+    let dr = (word2 & 7) as usize;
+    let signed = word2 & 0x0800 != 0;
+    let (quotient, remainder) = if word2 & 0x0400 != 0 {
+        let dividend = (u64::from(dar!(core)[dr]) << 32) | u64::from(dar!(core)[dq]);
+        if signed {
+            let dividend = dividend as i64;
+            let divisor = i64::from(divisor as i32);
+            match dividend.checked_div(divisor) {
+                Some(quotient) if quotient == i64::from(quotient as i32) => (quotient as u32, (dividend % divisor) as u32),
+                _ => {
+                    v_flag!(core) = VFLAG_SET;
+                    return;
+                }
+            }
+        } else {
+            let quotient = dividend / u64::from(divisor);
+            if quotient > 0xffff_ffff {
+                v_flag!(core) = VFLAG_SET;
+                return;
+            }
+            (quotient as u32, (dividend % u64::from(divisor)) as u32)
+        }
+    } else if signed {
+        // like Musashi, $80000000 / -1 doesn't overflow
+        let dividend = i64::from(dar!(core)[dq] as i32);
+        let divisor = i64::from(divisor as i32);
+        ((dividend / divisor) as u32, (dividend % divisor) as u32)
+    } else {
+        let dividend = dar!(core)[dq];
+        (dividend / divisor, dividend % divisor)
+    };
+    dar!(core)[dr] = remainder;
+    dar!(core)[dq] = quotient;
+
+    not_z_flag!(core) = quotient;
+    n_flag!(core) = quotient >> 24;
+    v_flag!(core) = VFLAG_CLEAR;
+    c_flag!(core) = CFLAG_CLEAR;
+}
+
 // Put common implementation of EOR here
 pub fn eor_8<T: Core>(core: &mut T, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
@@ -832,6 +949,34 @@ pub fn muls_16<T: Core>(core: &mut T, dst: i16, src: i16) -> u32 {
     c_flag!(core) = 0;
     res
 }
+// Put common implementation of MULS.L, MULU.L here
+// The extension word selects a signed (bit 11) multiplication with Dl
+// (bits 14-12), and whether the high long of the result goes to Dh
+// (bit 10 set, Dh in bits 2-0) or sets V if it is significant
+pub fn mull_32<T: Core>(core: &mut T, word2: u16, src: u32) {
+    let dl = ((word2 >> 12) & 7) as usize;
+    let dh = (word2 & 7) as usize;
+    let dst = dar!(core)[dl];
+    let signed = word2 & 0x0800 != 0;
+    let res = if signed {
+        (i64::from(src as i32) * i64::from(dst as i32)) as u64
+    } else {
+        u64::from(src) * u64::from(dst)
+    };
+    c_flag!(core) = CFLAG_CLEAR;
+    if word2 & 0x0400 != 0 {
+        not_z_flag!(core) = (res as u32) | (res >> 32) as u32;
+        n_flag!(core) = (res >> 56) as u32;
+        v_flag!(core) = VFLAG_CLEAR;
+        dar!(core)[dh] = (res >> 32) as u32;
+    } else {
+        let overflow = if signed { res as i64 != i64::from(res as i32) } else { res > 0xffff_ffff };
+        not_z_flag!(core) = res as u32;
+        n_flag!(core) = (res as u32) >> 24;
+        v_flag!(core) = if overflow { VFLAG_SET } else { VFLAG_CLEAR };
+    }
+    dar!(core)[dl] = res as u32;
+}
 // Put common implementation of MULU here
 pub fn mulu_16<T: Core>(core: &mut T, dst: u16, src: u16) -> u32 {
     let res = u32::from(dst).wrapping_mul(u32::from(src)) as u32;
@@ -1357,6 +1502,43 @@ pub fn subx_32<T: Core>(core: &mut T, dst: u32, src: u32) -> u32 {
 
 // Put common implementation of SWAP here
 // Put common implementation of TAS here
+// Put common implementation of TBLS, TBLU, TBLSN and TBLUN here (CPU32)
+// Interpolates between the entries y0 and y1 by the fraction in Dx bits
+// 7-0. TBLS and TBLU round the result to the size of the entries, while
+// TBLSN and TBLUN (bit 10 set) keep the fraction in the low byte of Dx,
+// which can overflow a long result.
+pub fn tbl<T: Core>(core: &mut T, extension: u16, y0: u32, y1: u32, bits: u32) {
+    let dx = ((extension >> 12) & 7) as usize;
+    let signed = extension & 0x0800 != 0;
+    let mask = 0xffff_ffff >> (32 - bits);
+    let extend = |y: u32| if signed {
+        i64::from(((y << (32 - bits)) as i32) >> (32 - bits))
+    } else {
+        i64::from(y & mask)
+    };
+    let (y0, y1) = (extend(y0), extend(y1));
+    let fraction = i64::from(dar!(core)[dx] & 0xff);
+    let scaled = y0 * 256 + (y1 - y0) * fraction;
+    if extension & 0x0400 == 0 {
+        let res = ((scaled + 128) >> 8) as u32 & mask;
+        dar!(core)[dx] = dar!(core)[dx] & !mask | res;
+        n_flag!(core) = res >> (bits - 8);
+        not_z_flag!(core) = res;
+        v_flag!(core) = VFLAG_CLEAR;
+    } else {
+        let overflow = if signed {
+            scaled != i64::from(scaled as i32)
+        } else {
+            scaled != i64::from(scaled as u32)
+        };
+        let res = scaled as u32;
+        dar!(core)[dx] = res;
+        n_flag!(core) = res >> 24;
+        not_z_flag!(core) = res;
+        v_flag!(core) = if overflow { VFLAG_SET } else { VFLAG_CLEAR };
+    }
+    c_flag!(core) = CFLAG_CLEAR;
+}
 // Put common implementation of TRAP here
 // Put common implementation of TRAPV here
 // Put common implementation of TST here
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use r68k_common::constants::*;
 use super::super::Handler;
 use super::opcodes::*;
-use super::super::InstructionSet;
+use super::super::{CpuType, InstructionSet};
 use super::*;
 
 #[allow(dead_code)]
@@ -23,9 +23,17 @@ pub struct InstructionSetGenerator<T: Core> {
 
 impl<T: Core> InstructionSetGenerator<T> {
     pub fn new() -> InstructionSetGenerator<T> {
-        InstructionSetGenerator {
-            optable: generate_optable()
-        }
+        InstructionSetGenerator::for_cpu_type(CpuType::M68000)
+    }
+
+    pub fn for_cpu_type(cpu_type: CpuType) -> InstructionSetGenerator<T> {
+        let optable = match cpu_type {
+            CpuType::M68000 => generate_optable(),
+            CpuType::M68010 => generate_optable_010(),
+            CpuType::M68EC020 | CpuType::M68020 | CpuType::M68030 => generate_optable_020(),
+            CpuType::Cpu32 => generate_optable_cpu32(),
+        };
+        InstructionSetGenerator { optable }
     }
 
     pub fn generate(&self) -> InstructionSet<T> {
@@ -1895,14 +1903,351 @@ fn generate_optable<T: Core>() -> Vec<OpcodeHandler<T>> {
     ]
 }
 
+// The 68010 instruction set is the 68000 one, with these entries added
+// or replacing the 68000 ones (as later entries win)
+fn generate_optable_010<T: Core>() -> Vec<OpcodeHandler<T>> {
+    let mut optable = generate_optable();
+    optable.extend(vec![
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_DN, move_16_frs_dn_010),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_AI, move_16_frs_ai_010),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_PI, move_16_frs_pi_010),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_PD, move_16_frs_pd_010),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_DI, move_16_frs_di_010),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRS_IX, move_16_frs_ix_010),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRS_AW, move_16_frs_aw_010),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRS_AL, move_16_frs_al_010),
+
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_DN, move_16_frc_dn),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_AI, move_16_frc_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_PI, move_16_frc_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_PD, move_16_frc_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_DI, move_16_frc_di),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_IX, move_16_frc_ix),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRC_AW, move_16_frc_aw),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRC_AL, move_16_frc_al),
+
+        op_entry!(MASK_EXACT, OP_MOVEC_32_CR, movec_32_cr),
+        op_entry!(MASK_EXACT, OP_MOVEC_32_RC, movec_32_rc),
+
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_AI, moves_8_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_PI, moves_8_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_PD, moves_8_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_DI, moves_8_di),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_IX, moves_8_ix),
+        op_entry!(MASK_EXACT, OP_MOVES_8_AW, moves_8_aw),
+        op_entry!(MASK_EXACT, OP_MOVES_8_AL, moves_8_al),
+
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_AI, moves_16_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_PI, moves_16_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_PD, moves_16_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_DI, moves_16_di),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_IX, moves_16_ix),
+        op_entry!(MASK_EXACT, OP_MOVES_16_AW, moves_16_aw),
+        op_entry!(MASK_EXACT, OP_MOVES_16_AL, moves_16_al),
+
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_AI, moves_32_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_PI, moves_32_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_PD, moves_32_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_DI, moves_32_di),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_IX, moves_32_ix),
+        op_entry!(MASK_EXACT, OP_MOVES_32_AW, moves_32_aw),
+        op_entry!(MASK_EXACT, OP_MOVES_32_AL, moves_32_al),
+
+        op_entry!(MASK_EXACT, OP_RTD_32, rtd_32),
+        op_entry!(MASK_EXACT, OP_RTE_32, rte_32_010),
+    ]);
+    optable
+}
+
+// The 68020 instructions that the CPU32 has as well
+fn generate_optable_020_and_cpu32<T: Core>() -> Vec<OpcodeHandler<T>> {
+    vec![
+        op_entry!(MASK_EXACT, OP_BHI_32, bhi_32),
+        op_entry!(MASK_EXACT, OP_BLS_32, bls_32),
+        op_entry!(MASK_EXACT, OP_BCC_32, bcc_32),
+        op_entry!(MASK_EXACT, OP_BCS_32, bcs_32),
+        op_entry!(MASK_EXACT, OP_BNE_32, bne_32),
+        op_entry!(MASK_EXACT, OP_BEQ_32, beq_32),
+        op_entry!(MASK_EXACT, OP_BVC_32, bvc_32),
+        op_entry!(MASK_EXACT, OP_BVS_32, bvs_32),
+        op_entry!(MASK_EXACT, OP_BPL_32, bpl_32),
+        op_entry!(MASK_EXACT, OP_BMI_32, bmi_32),
+        op_entry!(MASK_EXACT, OP_BGE_32, bge_32),
+        op_entry!(MASK_EXACT, OP_BLT_32, blt_32),
+        op_entry!(MASK_EXACT, OP_BGT_32, bgt_32),
+        op_entry!(MASK_EXACT, OP_BLE_32, ble_32),
+        op_entry!(MASK_EXACT, OP_BRA_32, bra_32),
+        op_entry!(MASK_EXACT, OP_BSR_32, bsr_32),
+
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_DN, chk_32_dn),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_AI, chk_32_ai),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_PI, chk_32_pi),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_PD, chk_32_pd),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_DI, chk_32_di),
+        op_entry!(MASK_OUT_X_Y, OP_CHK_32_IX, chk_32_ix),
+        op_entry!(MASK_OUT_X, OP_CHK_32_AW, chk_32_aw),
+        op_entry!(MASK_OUT_X, OP_CHK_32_AL, chk_32_al),
+        op_entry!(MASK_OUT_X, OP_CHK_32_PCDI, chk_32_pcdi),
+        op_entry!(MASK_OUT_X, OP_CHK_32_PCIX, chk_32_pcix),
+        op_entry!(MASK_OUT_X, OP_CHK_32_IMM, chk_32_imm),
+
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_8_AI, chk2cmp2_8_ai),
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_8_DI, chk2cmp2_8_di),
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_8_IX, chk2cmp2_8_ix),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_8_AW, chk2cmp2_8_aw),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_8_AL, chk2cmp2_8_al),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_8_PCDI, chk2cmp2_8_pcdi),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_8_PCIX, chk2cmp2_8_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_16_AI, chk2cmp2_16_ai),
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_16_DI, chk2cmp2_16_di),
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_16_IX, chk2cmp2_16_ix),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_16_AW, chk2cmp2_16_aw),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_16_AL, chk2cmp2_16_al),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_16_PCDI, chk2cmp2_16_pcdi),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_16_PCIX, chk2cmp2_16_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_32_AI, chk2cmp2_32_ai),
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_32_DI, chk2cmp2_32_di),
+        op_entry!(MASK_OUT_Y, OP_CHK2CMP2_32_IX, chk2cmp2_32_ix),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_32_AW, chk2cmp2_32_aw),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_32_AL, chk2cmp2_32_al),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_32_PCDI, chk2cmp2_32_pcdi),
+        op_entry!(MASK_EXACT, OP_CHK2CMP2_32_PCIX, chk2cmp2_32_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_DIVL_32_DN, divl_32_dn),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32_AI, divl_32_ai),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32_PI, divl_32_pi),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32_PD, divl_32_pd),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32_DI, divl_32_di),
+        op_entry!(MASK_OUT_Y, OP_DIVL_32_IX, divl_32_ix),
+        op_entry!(MASK_EXACT, OP_DIVL_32_AW, divl_32_aw),
+        op_entry!(MASK_EXACT, OP_DIVL_32_AL, divl_32_al),
+        op_entry!(MASK_EXACT, OP_DIVL_32_PCDI, divl_32_pcdi),
+        op_entry!(MASK_EXACT, OP_DIVL_32_PCIX, divl_32_pcix),
+        op_entry!(MASK_EXACT, OP_DIVL_32_IMM, divl_32_imm),
+
+        op_entry!(MASK_OUT_Y, OP_EXT_BL, ext_bl),
+        op_entry!(MASK_OUT_Y, OP_LINK_32, link_32),
+
+        op_entry!(MASK_OUT_Y, OP_MULL_32_DN, mull_32_dn),
+        op_entry!(MASK_OUT_Y, OP_MULL_32_AI, mull_32_ai),
+        op_entry!(MASK_OUT_Y, OP_MULL_32_PI, mull_32_pi),
+        op_entry!(MASK_OUT_Y, OP_MULL_32_PD, mull_32_pd),
+        op_entry!(MASK_OUT_Y, OP_MULL_32_DI, mull_32_di),
+        op_entry!(MASK_OUT_Y, OP_MULL_32_IX, mull_32_ix),
+        op_entry!(MASK_EXACT, OP_MULL_32_AW, mull_32_aw),
+        op_entry!(MASK_EXACT, OP_MULL_32_AL, mull_32_al),
+        op_entry!(MASK_EXACT, OP_MULL_32_PCDI, mull_32_pcdi),
+        op_entry!(MASK_EXACT, OP_MULL_32_PCIX, mull_32_pcix),
+        op_entry!(MASK_EXACT, OP_MULL_32_IMM, mull_32_imm),
+
+        op_entry!(MASK_EXACT, OP_TRAPT_16, trapt_16),
+        op_entry!(MASK_EXACT, OP_TRAPF_16, trapf_16),
+        op_entry!(MASK_EXACT, OP_TRAPHI_16, traphi_16),
+        op_entry!(MASK_EXACT, OP_TRAPLS_16, trapls_16),
+        op_entry!(MASK_EXACT, OP_TRAPCC_16, trapcc_16),
+        op_entry!(MASK_EXACT, OP_TRAPCS_16, trapcs_16),
+        op_entry!(MASK_EXACT, OP_TRAPNE_16, trapne_16),
+        op_entry!(MASK_EXACT, OP_TRAPEQ_16, trapeq_16),
+        op_entry!(MASK_EXACT, OP_TRAPVC_16, trapvc_16),
+        op_entry!(MASK_EXACT, OP_TRAPVS_16, trapvs_16),
+        op_entry!(MASK_EXACT, OP_TRAPPL_16, trappl_16),
+        op_entry!(MASK_EXACT, OP_TRAPMI_16, trapmi_16),
+        op_entry!(MASK_EXACT, OP_TRAPGE_16, trapge_16),
+        op_entry!(MASK_EXACT, OP_TRAPLT_16, traplt_16),
+        op_entry!(MASK_EXACT, OP_TRAPGT_16, trapgt_16),
+        op_entry!(MASK_EXACT, OP_TRAPLE_16, traple_16),
+
+        op_entry!(MASK_EXACT, OP_TRAPT_32, trapt_32),
+        op_entry!(MASK_EXACT, OP_TRAPF_32, trapf_32),
+        op_entry!(MASK_EXACT, OP_TRAPHI_32, traphi_32),
+        op_entry!(MASK_EXACT, OP_TRAPLS_32, trapls_32),
+        op_entry!(MASK_EXACT, OP_TRAPCC_32, trapcc_32),
+        op_entry!(MASK_EXACT, OP_TRAPCS_32, trapcs_32),
+        op_entry!(MASK_EXACT, OP_TRAPNE_32, trapne_32),
+        op_entry!(MASK_EXACT, OP_TRAPEQ_32, trapeq_32),
+        op_entry!(MASK_EXACT, OP_TRAPVC_32, trapvc_32),
+        op_entry!(MASK_EXACT, OP_TRAPVS_32, trapvs_32),
+        op_entry!(MASK_EXACT, OP_TRAPPL_32, trappl_32),
+        op_entry!(MASK_EXACT, OP_TRAPMI_32, trapmi_32),
+        op_entry!(MASK_EXACT, OP_TRAPGE_32, trapge_32),
+        op_entry!(MASK_EXACT, OP_TRAPLT_32, traplt_32),
+        op_entry!(MASK_EXACT, OP_TRAPGT_32, trapgt_32),
+        op_entry!(MASK_EXACT, OP_TRAPLE_32, traple_32),
+
+        op_entry!(MASK_EXACT, OP_TRAPT, trapt),
+        op_entry!(MASK_EXACT, OP_TRAPF, trapf),
+        op_entry!(MASK_EXACT, OP_TRAPHI, traphi),
+        op_entry!(MASK_EXACT, OP_TRAPLS, trapls),
+        op_entry!(MASK_EXACT, OP_TRAPCC, trapcc),
+        op_entry!(MASK_EXACT, OP_TRAPCS, trapcs),
+        op_entry!(MASK_EXACT, OP_TRAPNE, trapne),
+        op_entry!(MASK_EXACT, OP_TRAPEQ, trapeq),
+        op_entry!(MASK_EXACT, OP_TRAPVC, trapvc),
+        op_entry!(MASK_EXACT, OP_TRAPVS, trapvs),
+        op_entry!(MASK_EXACT, OP_TRAPPL, trappl),
+        op_entry!(MASK_EXACT, OP_TRAPMI, trapmi),
+        op_entry!(MASK_EXACT, OP_TRAPGE, trapge),
+        op_entry!(MASK_EXACT, OP_TRAPLT, traplt),
+        op_entry!(MASK_EXACT, OP_TRAPGT, trapgt),
+        op_entry!(MASK_EXACT, OP_TRAPLE, traple),
+
+        op_entry!(MASK_EXACT, OP_TST_8_PCDI, tst_8_pcdi),
+        op_entry!(MASK_EXACT, OP_TST_8_PCIX, tst_8_pcix),
+        op_entry!(MASK_EXACT, OP_TST_8_IMM, tst_8_imm),
+        op_entry!(MASK_OUT_Y, OP_TST_16_AN, tst_16_an),
+        op_entry!(MASK_EXACT, OP_TST_16_PCDI, tst_16_pcdi),
+        op_entry!(MASK_EXACT, OP_TST_16_PCIX, tst_16_pcix),
+        op_entry!(MASK_EXACT, OP_TST_16_IMM, tst_16_imm),
+        op_entry!(MASK_OUT_Y, OP_TST_32_AN, tst_32_an),
+        op_entry!(MASK_EXACT, OP_TST_32_PCDI, tst_32_pcdi),
+        op_entry!(MASK_EXACT, OP_TST_32_PCIX, tst_32_pcix),
+        op_entry!(MASK_EXACT, OP_TST_32_IMM, tst_32_imm),
+    ]
+}
+
+// The 68020 instruction set is the 68010 one, with these entries added
+// or replacing the 68010 ones
+fn generate_optable_020<T: Core>() -> Vec<OpcodeHandler<T>> {
+    let mut optable = generate_optable_010();
+    optable.extend(generate_optable_020_and_cpu32());
+    optable.extend(vec![
+        op_entry!(MASK_OUT_Y, OP_BFCHG_32_DN, bfchg_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFCHG_32_AI, bfchg_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFCHG_32_DI, bfchg_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFCHG_32_IX, bfchg_32_ix),
+        op_entry!(MASK_EXACT, OP_BFCHG_32_AW, bfchg_32_aw),
+        op_entry!(MASK_EXACT, OP_BFCHG_32_AL, bfchg_32_al),
+
+        op_entry!(MASK_OUT_Y, OP_BFCLR_32_DN, bfclr_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFCLR_32_AI, bfclr_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFCLR_32_DI, bfclr_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFCLR_32_IX, bfclr_32_ix),
+        op_entry!(MASK_EXACT, OP_BFCLR_32_AW, bfclr_32_aw),
+        op_entry!(MASK_EXACT, OP_BFCLR_32_AL, bfclr_32_al),
+
+        op_entry!(MASK_OUT_Y, OP_BFEXTS_32_DN, bfexts_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFEXTS_32_AI, bfexts_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFEXTS_32_DI, bfexts_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFEXTS_32_IX, bfexts_32_ix),
+        op_entry!(MASK_EXACT, OP_BFEXTS_32_AW, bfexts_32_aw),
+        op_entry!(MASK_EXACT, OP_BFEXTS_32_AL, bfexts_32_al),
+        op_entry!(MASK_EXACT, OP_BFEXTS_32_PCDI, bfexts_32_pcdi),
+        op_entry!(MASK_EXACT, OP_BFEXTS_32_PCIX, bfexts_32_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_BFEXTU_32_DN, bfextu_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFEXTU_32_AI, bfextu_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFEXTU_32_DI, bfextu_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFEXTU_32_IX, bfextu_32_ix),
+        op_entry!(MASK_EXACT, OP_BFEXTU_32_AW, bfextu_32_aw),
+        op_entry!(MASK_EXACT, OP_BFEXTU_32_AL, bfextu_32_al),
+        op_entry!(MASK_EXACT, OP_BFEXTU_32_PCDI, bfextu_32_pcdi),
+        op_entry!(MASK_EXACT, OP_BFEXTU_32_PCIX, bfextu_32_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_BFFFO_32_DN, bfffo_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFFFO_32_AI, bfffo_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFFFO_32_DI, bfffo_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFFFO_32_IX, bfffo_32_ix),
+        op_entry!(MASK_EXACT, OP_BFFFO_32_AW, bfffo_32_aw),
+        op_entry!(MASK_EXACT, OP_BFFFO_32_AL, bfffo_32_al),
+        op_entry!(MASK_EXACT, OP_BFFFO_32_PCDI, bfffo_32_pcdi),
+        op_entry!(MASK_EXACT, OP_BFFFO_32_PCIX, bfffo_32_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_BFINS_32_DN, bfins_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFINS_32_AI, bfins_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFINS_32_DI, bfins_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFINS_32_IX, bfins_32_ix),
+        op_entry!(MASK_EXACT, OP_BFINS_32_AW, bfins_32_aw),
+        op_entry!(MASK_EXACT, OP_BFINS_32_AL, bfins_32_al),
+
+        op_entry!(MASK_OUT_Y, OP_BFSET_32_DN, bfset_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFSET_32_AI, bfset_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFSET_32_DI, bfset_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFSET_32_IX, bfset_32_ix),
+        op_entry!(MASK_EXACT, OP_BFSET_32_AW, bfset_32_aw),
+        op_entry!(MASK_EXACT, OP_BFSET_32_AL, bfset_32_al),
+
+        op_entry!(MASK_OUT_Y, OP_BFTST_32_DN, bftst_32_dn),
+        op_entry!(MASK_OUT_Y, OP_BFTST_32_AI, bftst_32_ai),
+        op_entry!(MASK_OUT_Y, OP_BFTST_32_DI, bftst_32_di),
+        op_entry!(MASK_OUT_Y, OP_BFTST_32_IX, bftst_32_ix),
+        op_entry!(MASK_EXACT, OP_BFTST_32_AW, bftst_32_aw),
+        op_entry!(MASK_EXACT, OP_BFTST_32_AL, bftst_32_al),
+        op_entry!(MASK_EXACT, OP_BFTST_32_PCDI, bftst_32_pcdi),
+        op_entry!(MASK_EXACT, OP_BFTST_32_PCIX, bftst_32_pcix),
+
+        op_entry!(MASK_OUT_Y, OP_CAS_8_AI, cas_8_ai),
+        op_entry!(MASK_OUT_Y, OP_CAS_8_PI, cas_8_pi),
+        op_entry!(MASK_OUT_Y, OP_CAS_8_PD, cas_8_pd),
+        op_entry!(MASK_OUT_Y, OP_CAS_8_DI, cas_8_di),
+        op_entry!(MASK_OUT_Y, OP_CAS_8_IX, cas_8_ix),
+        op_entry!(MASK_EXACT, OP_CAS_8_AW, cas_8_aw),
+        op_entry!(MASK_EXACT, OP_CAS_8_AL, cas_8_al),
+
+        op_entry!(MASK_OUT_Y, OP_CAS_16_AI, cas_16_ai),
+        op_entry!(MASK_OUT_Y, OP_CAS_16_PI, cas_16_pi),
+        op_entry!(MASK_OUT_Y, OP_CAS_16_PD, cas_16_pd),
+        op_entry!(MASK_OUT_Y, OP_CAS_16_DI, cas_16_di),
+        op_entry!(MASK_OUT_Y, OP_CAS_16_IX, cas_16_ix),
+        op_entry!(MASK_EXACT, OP_CAS_16_AW, cas_16_aw),
+        op_entry!(MASK_EXACT, OP_CAS_16_AL, cas_16_al),
+
+        op_entry!(MASK_OUT_Y, OP_CAS_32_AI, cas_32_ai),
+        op_entry!(MASK_OUT_Y, OP_CAS_32_PI, cas_32_pi),
+        op_entry!(MASK_OUT_Y, OP_CAS_32_PD, cas_32_pd),
+        op_entry!(MASK_OUT_Y, OP_CAS_32_DI, cas_32_di),
+        op_entry!(MASK_OUT_Y, OP_CAS_32_IX, cas_32_ix),
+        op_entry!(MASK_EXACT, OP_CAS_32_AW, cas_32_aw),
+        op_entry!(MASK_EXACT, OP_CAS_32_AL, cas_32_al),
+
+        op_entry!(MASK_EXACT, OP_CAS2_16, cas2_16),
+        op_entry!(MASK_EXACT, OP_CAS2_32, cas2_32),
+
+        op_entry!(MASK_OUT_X_Y, OP_PACK_16_RR, pack_16_rr),
+        op_entry!(MASK_OUT_X_Y, OP_PACK_16_MM, pack_16_mm),
+
+        op_entry!(MASK_OUT_X_Y, OP_UNPK_16_RR, unpk_16_rr),
+        op_entry!(MASK_OUT_X_Y, OP_UNPK_16_MM, unpk_16_mm),
+
+        op_entry!(MASK_EXACT, OP_RTE_32, rte_32_020),
+    ]);
+    optable
+}
+
+// The CPU32 instruction set is the 68010 one, with part of the 68020 set
+// and instructions of its own added. It has no bit field, CAS, PACK or
+// UNPK instructions, nor a coprocessor interface.
+fn generate_optable_cpu32<T: Core>() -> Vec<OpcodeHandler<T>> {
+    let mut optable = generate_optable_010();
+    optable.extend(generate_optable_020_and_cpu32());
+    optable.extend(vec![
+        op_entry!(MASK_EXACT, OP_BGND, bgnd),
+
+        op_entry!(MASK_OUT_Y, OP_TBL_DN, tbl_dn),
+        op_entry!(MASK_OUT_Y, OP_TBL_AI, tbl_ai),
+        op_entry!(MASK_OUT_Y, OP_TBL_DI, tbl_di),
+        op_entry!(MASK_OUT_Y, OP_TBL_IX, tbl_ix),
+        op_entry!(MASK_EXACT, OP_TBL_AW, tbl_aw),
+        op_entry!(MASK_EXACT, OP_TBL_AL, tbl_al),
+        op_entry!(MASK_EXACT, OP_TBL_PCDI, tbl_pcdi),
+        op_entry!(MASK_EXACT, OP_TBL_PCIX, tbl_pcix),
+        op_entry!(MASK_EXACT, OP_LPSTOP, lpstop),
+
+        op_entry!(MASK_EXACT, OP_RTE_32, rte_32_cpu32),
+    ]);
+    optable
+}
+
 #[cfg(test)]
 mod tests {
     
-    use cpu::TestCore;
+    use cpu::{CpuType, TestCore};
+    use super::InstructionSetGenerator;
     
     #[test]
     fn optable_mask_and_matching_makes_sense() {
-        let optable = super::generate_optable::<TestCore>();
+        let optable = super::generate_optable_020::<TestCore>();
 
         for op in optable {
             if op.mask & op.matching != op.matching {
@@ -1910,4 +2255,38 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn the_020_only_adds_instructions_where_the_010_has_none() {
+        let names_010 = InstructionSetGenerator::<TestCore>::for_cpu_type(CpuType::M68010).generate_with("illegal", |op| op.name);
+        let names_020 = InstructionSetGenerator::<TestCore>::for_cpu_type(CpuType::M68020).generate_with("illegal", |op| op.name);
+
+        for opcode in 0..0x10000 {
+            let (name_010, name_020) = (names_010[opcode], names_020[opcode]);
+            if name_010 != name_020 && name_010 != "illegal" && name_010 != "rte_32_010" {
+                panic!("{:04x} is {} on the 68010, but {} on the 68020", opcode, name_010, name_020);
+            }
+        }
+    }
+
+    #[test]
+    fn the_cpu32_has_part_of_the_020_instructions() {
+        let names_020 = InstructionSetGenerator::<TestCore>::for_cpu_type(CpuType::M68020).generate_with("illegal", |op| op.name);
+        let names_cpu32 = InstructionSetGenerator::<TestCore>::for_cpu_type(CpuType::Cpu32).generate_with("illegal", |op| op.name);
+
+        assert_eq!("ext_bl", names_cpu32[0x49c0]);
+        assert_eq!("tbl_dn", names_cpu32[0xf801]);
+        assert_eq!("lpstop", names_cpu32[0xf800]);
+        assert_eq!("bgnd", names_cpu32[0x4afa]);
+        // BFTST, CAS and PACK
+        for &opcode in &[0xe8c0, 0x0ad0, 0x8140] {
+            assert_eq!("illegal", names_cpu32[opcode]);
+        }
+        for opcode in 0..0x10000 {
+            let (name_020, name_cpu32) = (names_020[opcode], names_cpu32[opcode]);
+            if name_020 != name_cpu32 && name_020 != "illegal" && name_020 != "unimplemented_1111" && name_020 != "rte_32_020" {
+                assert_eq!("illegal", name_cpu32, "{:04x} is {} on the 68020", opcode, name_020);
+            }
+        }
+    }
 }
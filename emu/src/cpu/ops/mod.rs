@@ -1,6 +1,9 @@
 #![macro_use]
-use super::{Core, Cycles, Result, EXCEPTION_CHK, EXCEPTION_UNIMPLEMENTED_1010, EXCEPTION_UNIMPLEMENTED_1111, EXCEPTION_ZERO_DIVIDE, EXCEPTION_TRAP_BASE, EXCEPTION_TRAPV};
+use super::{Core, CpuType, Cycles, LoopBuffer, Result, EXCEPTION_CHK, EXCEPTION_UNIMPLEMENTED_1010, EXCEPTION_UNIMPLEMENTED_1111, EXCEPTION_ZERO_DIVIDE, EXCEPTION_TRAP_BASE, EXCEPTION_TRAPV};
 use super::Exception::*;
+use super::fpu;
+use super::mmu;
+use super::timing;
 
 mod common;
 pub mod handlers;
@@ -197,13 +200,30 @@ pub fn illegal<T: Core>(core: &mut T) -> Result<Cycles> {
 }
 use super::InstructionSet;
 pub fn instruction_set<T: Core>() -> InstructionSet<T> {
-    handlers::InstructionSetGenerator::new().generate()
+    instruction_set_for(CpuType::M68000)
+}
+pub fn instruction_set_for<T: Core>(cpu_type: CpuType) -> InstructionSet<T> {
+    handlers::InstructionSetGenerator::for_cpu_type(cpu_type).generate()
 }
 
-#[cfg(test)]
 extern crate once_cell;
-#[cfg(test)]
 use self::once_cell::sync::Lazy;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// The instruction sets cores have switched to, generated once for each
+// kind of core and processor rather than on every switch
+type InstructionSets = HashMap<(TypeId, CpuType), Arc<dyn Any + Send + Sync>>;
+static INSTRUCTION_SETS: Lazy<Mutex<InstructionSets>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn shared_instruction_set_for<T: Core + 'static>(cpu_type: CpuType) -> Arc<InstructionSet<T>> {
+    let mut sets = INSTRUCTION_SETS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let set = sets.entry((TypeId::of::<T>(), cpu_type))
+        .or_insert_with(|| Arc::new(instruction_set_for::<T>(cpu_type)))
+        .clone();
+    set.downcast().expect("an instruction set generated for another core")
+}
 #[cfg(test)]
 use cpu::TestCore;
 #[cfg(test)]
@@ -225,6 +245,13 @@ pub fn unimplemented_1010<T: Core>(core: &mut T) -> Result<Cycles> {
 }
 
 pub fn unimplemented_1111<T: Core>(core: &mut T) -> Result<Cycles> {
+    // the coprocessor interface came with the 68020
+    if core.cpu_type().is_020() && (ir!(core) >> 9) & 7 == fpu::FPU_CPID && core.fpu().is_some() {
+        return fpu::execute(core);
+    }
+    if (ir!(core) >> 9) & 7 == mmu::MMU_CPID && core.mmu().is_some() {
+        return mmu::execute(core);
+    }
     Err(UnimplementedInstruction(ir!(core), pc!(core).wrapping_sub(2), EXCEPTION_UNIMPLEMENTED_1111))
 }
 
@@ -736,6 +763,19 @@ macro_rules! branch {
             })
         }
     };
+    (32, $name:ident, $cond:ident) => {
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            Ok(if core.$cond()
+            {
+                let offset = core.read_imm_u32()?;
+                pc!(core) = pc!(core).wrapping_sub(4).wrapping_add(offset);
+                Cycles(10)
+            } else {
+                pc!(core) = pc!(core).wrapping_add(4);
+                Cycles(12)
+            })
+        }
+    };
     (16, $name:ident, $cond:ident, dy) => {
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
             Ok(if !core.$cond()
@@ -747,14 +787,14 @@ macro_rules! branch {
                     let offset = core.read_imm_i16()?;
                     pc!(core) = pc!(core).wrapping_sub(2);
                     core.branch_16(offset);
-                    Cycles(10)
+                    loop_continued(core, offset)
                 } else {
                     pc!(core) = pc!(core).wrapping_add(2);
-                    Cycles(14)
+                    loop_ended(core, 14)
                 }
             } else {
                 pc!(core) = pc!(core).wrapping_add(2);
-                Cycles(12)
+                loop_ended(core, 12)
             })
         }
     };
@@ -790,6 +830,22 @@ branch!(16, blt_16, cond_lt);
 branch!(16, bgt_16, cond_gt);
 branch!(16, ble_16, cond_le);
 
+// 020+
+branch!(32, bhi_32, cond_hi);
+branch!(32, bls_32, cond_ls);
+branch!(32, bcc_32, cond_cc);
+branch!(32, bcs_32, cond_cs);
+branch!(32, bne_32, cond_ne);
+branch!(32, beq_32, cond_eq);
+branch!(32, bvc_32, cond_vc);
+branch!(32, bvs_32, cond_vs);
+branch!(32, bpl_32, cond_pl);
+branch!(32, bmi_32, cond_mi);
+branch!(32, bge_32, cond_ge);
+branch!(32, blt_32, cond_lt);
+branch!(32, bgt_32, cond_gt);
+branch!(32, ble_32, cond_le);
+
 macro_rules! bchg_8 {
     ($name:ident, $src:ident, $dst:ident, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
@@ -983,6 +1039,130 @@ btst_8!(btst_8_s_pcdi, imm_8, pcdi_8,  8+8);
 btst_8!(btst_8_s_pcix, imm_8, pcix_8,  8+10);
 // btst_8!(btst_8_s_imm,  imm_8, imm_8,   8+4); // not present
 
+// Put implementation of BFCHG, BFCLR, BFEXTS, BFEXTU, BFFFO, BFINS, BFSET and BFTST ops here (020+)
+// The extension word holds the offset in bits 10-6, or the Dn holding it
+// if bit 11 is set, and the width in bits 4-0 (0 meaning 32), or the Dn
+// holding it if bit 5 is set. Fields in data registers wrap around, while
+// fields in memory start offset bits from the msb of the byte at the
+// effective address, and may reach into a fifth byte.
+fn bitfield_offset_width<T: Core>(core: &mut T, word2: u16) -> (u32, u32) {
+    let offset = if word2 & 0x0800 != 0 {
+        dar!(core)[((word2 >> 6) & 7) as usize]
+    } else {
+        u32::from((word2 >> 6) & 31)
+    };
+    let width = if word2 & 0x0020 != 0 {
+        dar!(core)[(word2 & 7) as usize]
+    } else {
+        u32::from(word2)
+    };
+    (offset, (width.wrapping_sub(1) & 31) + 1)
+}
+macro_rules! bitfield {
+    ($name:ident, $common:ident, dy, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u16()?;
+            let (offset, width) = bitfield_offset_width(core, word2);
+            let offset = offset & 31;
+            let dst = dy!(core);
+            let field = dst.rotate_left(offset) >> (32 - width);
+            if let Some(res) = common::$common(core, word2, offset, width, field) {
+                let mask = (0xffff_ffffu32 << (32 - width)).rotate_right(offset);
+                dy!(core) = (dst & !mask) | (res << (32 - width)).rotate_right(offset);
+            }
+            Ok(Cycles($cycles))
+        });
+    ($name:ident, $common:ident, $ea:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u16()?;
+            let ea = effective_address::$ea(core)?;
+            let (offset, width) = bitfield_offset_width(core, word2);
+            // the offset is signed here
+            let ea = ea.wrapping_add(((offset as i32) >> 3) as u32);
+            let first_bit = offset & 7;
+            let five_bytes = first_bit + width > 32;
+            let mut data = u64::from(core.read_long(ea)?) << 8;
+            if five_bytes {
+                data |= u64::from(core.read_byte(ea.wrapping_add(4))?);
+            }
+            let shift = 40 - first_bit - width;
+            let mask = u64::from(0xffff_ffffu32 >> (32 - width)) << shift;
+            let field = ((data & mask) >> shift) as u32;
+            if let Some(res) = common::$common(core, word2, offset, width, field) {
+                let data = (data & !mask) | (u64::from(res) << shift);
+                core.write_long(ea, (data >> 8) as u32)?;
+                if five_bytes {
+                    core.write_byte(ea.wrapping_add(4), mask_out_above_8!(data as u32))?;
+                }
+            }
+            Ok(Cycles($cycles))
+        });
+}
+
+bitfield!(bfchg_32_dn, bfchg, dy, 12);
+bitfield!(bfchg_32_ai, bfchg, address_indirect_ay, 20);
+bitfield!(bfchg_32_di, bfchg, displacement_ay, 22);
+bitfield!(bfchg_32_ix, bfchg, index_ay, 24);
+bitfield!(bfchg_32_aw, bfchg, absolute_word, 22);
+bitfield!(bfchg_32_al, bfchg, absolute_long, 24);
+
+bitfield!(bfclr_32_dn, bfclr, dy, 12);
+bitfield!(bfclr_32_ai, bfclr, address_indirect_ay, 20);
+bitfield!(bfclr_32_di, bfclr, displacement_ay, 22);
+bitfield!(bfclr_32_ix, bfclr, index_ay, 24);
+bitfield!(bfclr_32_aw, bfclr, absolute_word, 22);
+bitfield!(bfclr_32_al, bfclr, absolute_long, 24);
+
+bitfield!(bfexts_32_dn, bfexts, dy, 5);
+bitfield!(bfexts_32_ai, bfexts, address_indirect_ay, 15);
+bitfield!(bfexts_32_di, bfexts, displacement_ay, 17);
+bitfield!(bfexts_32_ix, bfexts, index_ay, 19);
+bitfield!(bfexts_32_aw, bfexts, absolute_word, 17);
+bitfield!(bfexts_32_al, bfexts, absolute_long, 19);
+bitfield!(bfexts_32_pcdi, bfexts, displacement_pc, 17);
+bitfield!(bfexts_32_pcix, bfexts, index_pc, 19);
+
+bitfield!(bfextu_32_dn, bfextu, dy, 5);
+bitfield!(bfextu_32_ai, bfextu, address_indirect_ay, 15);
+bitfield!(bfextu_32_di, bfextu, displacement_ay, 17);
+bitfield!(bfextu_32_ix, bfextu, index_ay, 19);
+bitfield!(bfextu_32_aw, bfextu, absolute_word, 17);
+bitfield!(bfextu_32_al, bfextu, absolute_long, 19);
+bitfield!(bfextu_32_pcdi, bfextu, displacement_pc, 17);
+bitfield!(bfextu_32_pcix, bfextu, index_pc, 19);
+
+bitfield!(bfffo_32_dn, bfffo, dy, 18);
+bitfield!(bfffo_32_ai, bfffo, address_indirect_ay, 28);
+bitfield!(bfffo_32_di, bfffo, displacement_ay, 30);
+bitfield!(bfffo_32_ix, bfffo, index_ay, 32);
+bitfield!(bfffo_32_aw, bfffo, absolute_word, 30);
+bitfield!(bfffo_32_al, bfffo, absolute_long, 32);
+bitfield!(bfffo_32_pcdi, bfffo, displacement_pc, 30);
+bitfield!(bfffo_32_pcix, bfffo, index_pc, 32);
+
+bitfield!(bfins_32_dn, bfins, dy, 10);
+bitfield!(bfins_32_ai, bfins, address_indirect_ay, 17);
+bitfield!(bfins_32_di, bfins, displacement_ay, 19);
+bitfield!(bfins_32_ix, bfins, index_ay, 21);
+bitfield!(bfins_32_aw, bfins, absolute_word, 19);
+bitfield!(bfins_32_al, bfins, absolute_long, 21);
+
+bitfield!(bfset_32_dn, bfset, dy, 12);
+bitfield!(bfset_32_ai, bfset, address_indirect_ay, 20);
+bitfield!(bfset_32_di, bfset, displacement_ay, 22);
+bitfield!(bfset_32_ix, bfset, index_ay, 24);
+bitfield!(bfset_32_aw, bfset, absolute_word, 22);
+bitfield!(bfset_32_al, bfset, absolute_long, 24);
+
+bitfield!(bftst_32_dn, bftst, dy, 6);
+bitfield!(bftst_32_ai, bftst, address_indirect_ay, 13);
+bitfield!(bftst_32_di, bftst, displacement_ay, 15);
+bitfield!(bftst_32_ix, bftst, index_ay, 17);
+bitfield!(bftst_32_aw, bftst, absolute_word, 15);
+bitfield!(bftst_32_al, bftst, absolute_long, 17);
+bitfield!(bftst_32_pcdi, bftst, displacement_pc, 15);
+bitfield!(bftst_32_pcix, bftst, index_pc, 17);
+
 pub fn bra_8<T: Core>(core: &mut T) -> Result<Cycles> {
     let offset = mask_out_above_8!(ir!(core)) as i8;
     core.branch_8(offset);
@@ -996,10 +1176,16 @@ pub fn bra_16<T: Core>(core: &mut T) -> Result<Cycles> {
     Ok(Cycles(10))
 }
 
+pub fn bra_32<T: Core>(core: &mut T) -> Result<Cycles> {
+    let offset = core.read_imm_u32()?;
+    pc!(core) = pc!(core).wrapping_sub(4).wrapping_add(offset);
+    Ok(Cycles(10))
+}
+
 pub fn bsr_8<T: Core>(core: &mut T) -> Result<Cycles> {
     let offset = mask_out_above_8!(ir!(core)) as i8;
     let pc = pc!(core);
-    core.push_32(pc);
+    core.push_32(pc)?;
     core.branch_8(offset);
     Ok(Cycles(18))
 }
@@ -1007,12 +1193,110 @@ pub fn bsr_8<T: Core>(core: &mut T) -> Result<Cycles> {
 pub fn bsr_16<T: Core>(core: &mut T) -> Result<Cycles> {
     let offset = core.read_imm_i16()?;
     let pc = pc!(core);
-    core.push_32(pc);
+    core.push_32(pc)?;
     pc!(core) = pc!(core).wrapping_sub(2);
     core.branch_16(offset);
     Ok(Cycles(18))
 }
 
+pub fn bsr_32<T: Core>(core: &mut T) -> Result<Cycles> {
+    let offset = core.read_imm_u32()?;
+    let pc = pc!(core);
+    core.push_32(pc)?;
+    pc!(core) = pc!(core).wrapping_sub(4).wrapping_add(offset);
+    Ok(Cycles(18))
+}
+
+// Put implementation of BGND ops here (CPU32)
+// Without background debug mode enabled, BGND is an illegal instruction
+pub fn bgnd<T: Core>(core: &mut T) -> Result<Cycles> {
+    if core.enter_background_mode() {
+        Ok(Cycles(4))
+    } else {
+        Err(IllegalInstruction(ir!(core), pc!(core).wrapping_sub(2)))
+    }
+}
+
+// Put implementation of CAS, CAS2 ops here (020+)
+// The extension word holds Du in bits 8-6 and Dc in bits 2-0. Dc is
+// compared with the operand, which is replaced by Du if they are equal,
+// and otherwise loaded into Dc.
+macro_rules! cas {
+    ($name:ident, $cmp:ident, $dst:ident, $write:ident, $keep:expr, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u16()?;
+            let (dst, ea) = operator::$dst(core)?;
+            let dc = (word2 & 7) as usize;
+            let compare = dar!(core)[dc];
+            common::$cmp(core, dst, compare);
+            if core.cond_eq() {
+                let update = dar!(core)[((word2 >> 6) & 7) as usize];
+                core.$write(ea, update & !$keep)?;
+            } else {
+                dar!(core)[dc] = (compare & $keep) | dst;
+            }
+            Ok(Cycles($cycles))
+        })
+}
+cas!(cas_8_ai,  cmp_8,  ea_ay_ai_8,  write_byte, 0xffff_ff00, 12+4);
+cas!(cas_8_pi,  cmp_8,  ea_ay_pi_8,  write_byte, 0xffff_ff00, 12+4);
+cas!(cas_8_pd,  cmp_8,  ea_ay_pd_8,  write_byte, 0xffff_ff00, 12+6);
+cas!(cas_8_di,  cmp_8,  ea_ay_di_8,  write_byte, 0xffff_ff00, 12+8);
+cas!(cas_8_ix,  cmp_8,  ea_ay_ix_8,  write_byte, 0xffff_ff00, 12+10);
+cas!(cas_8_aw,  cmp_8,  ea_aw_8,     write_byte, 0xffff_ff00, 12+8);
+cas!(cas_8_al,  cmp_8,  ea_al_8,     write_byte, 0xffff_ff00, 12+12);
+
+cas!(cas_16_ai, cmp_16, ea_ay_ai_16, write_word, 0xffff_0000, 12+4);
+cas!(cas_16_pi, cmp_16, ea_ay_pi_16, write_word, 0xffff_0000, 12+4);
+cas!(cas_16_pd, cmp_16, ea_ay_pd_16, write_word, 0xffff_0000, 12+6);
+cas!(cas_16_di, cmp_16, ea_ay_di_16, write_word, 0xffff_0000, 12+8);
+cas!(cas_16_ix, cmp_16, ea_ay_ix_16, write_word, 0xffff_0000, 12+10);
+cas!(cas_16_aw, cmp_16, ea_aw_16,    write_word, 0xffff_0000, 12+8);
+cas!(cas_16_al, cmp_16, ea_al_16,    write_word, 0xffff_0000, 12+12);
+
+cas!(cas_32_ai, cmp_32, ea_ay_ai_32, write_long, 0x0000_0000, 12+8);
+cas!(cas_32_pi, cmp_32, ea_ay_pi_32, write_long, 0x0000_0000, 12+8);
+cas!(cas_32_pd, cmp_32, ea_ay_pd_32, write_long, 0x0000_0000, 12+10);
+cas!(cas_32_di, cmp_32, ea_ay_di_32, write_long, 0x0000_0000, 12+12);
+cas!(cas_32_ix, cmp_32, ea_ay_ix_32, write_long, 0x0000_0000, 12+14);
+cas!(cas_32_aw, cmp_32, ea_aw_32,    write_long, 0x0000_0000, 12+12);
+cas!(cas_32_al, cmp_32, ea_al_32,    write_long, 0x0000_0000, 12+16);
+
+// CAS2 has two extension words (read as a long), each holding the address
+// register (or Dn) in bits 15-12 and Du and Dc like CAS. Both operands
+// are updated only if both comparisons succeed, otherwise both are loaded,
+// Dc1 last.
+macro_rules! cas2 {
+    ($name:ident, $cmp:ident, $read:ident, $write:ident, $keep:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u32()?;
+            let (rn1, du1, dc1) = ((word2 >> 28) as usize, ((word2 >> 22) & 7) as usize, ((word2 >> 16) & 7) as usize);
+            let (rn2, du2, dc2) = (((word2 >> 12) & 15) as usize, ((word2 >> 6) & 7) as usize, (word2 & 7) as usize);
+            let ea1 = dar!(core)[rn1];
+            let ea2 = dar!(core)[rn2];
+            let dst1 = core.$read(ea1)?;
+            let dst2 = core.$read(ea2)?;
+            let compare1 = dar!(core)[dc1];
+            let compare2 = dar!(core)[dc2];
+            common::$cmp(core, dst1, compare1);
+            if core.cond_eq() {
+                common::$cmp(core, dst2, compare2);
+                if core.cond_eq() {
+                    let update1 = dar!(core)[du1];
+                    let update2 = dar!(core)[du2];
+                    core.$write(ea1, update1 & !$keep)?;
+                    core.$write(ea2, update2 & !$keep)?;
+                    return Ok(Cycles(24));
+                }
+            }
+            dar!(core)[dc2] = (compare2 & $keep) | dst2;
+            dar!(core)[dc1] = (compare1 & $keep) | dst1;
+            Ok(Cycles(24))
+        })
+}
+cas2!(cas2_16, cmp_16, read_word, write_word, 0xffff_0000);
+cas2!(cas2_32, cmp_32, read_long, write_long, 0x0000_0000);
+
 macro_rules! chk_16 {
     ($name:ident, $dst:ident, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
@@ -1031,7 +1315,8 @@ macro_rules! chk_16 {
                 // 40 cycles for the CHK trap + EA calculation time
                 // deduct the 10 base cycles for the instruction, to extract EA cycles.
                 // TODO: update musashi to account for this
-                Err(Trap(EXCEPTION_CHK, 40 /*+ $cycles*/))
+                // (cycle exact timing already does)
+                Err(Trap(EXCEPTION_CHK, if core.exact_timing() { 40 + $cycles } else { 40 }))
             }
         });
 }
@@ -1047,6 +1332,80 @@ chk_16!(chk_16_pcix, pcix_16,   10);
 chk_16!(chk_16_pd,   ay_pd_16,   6);
 chk_16!(chk_16_pi,   ay_pi_16,   4);
 
+macro_rules! chk_32 {
+    ($name:ident, $dst:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let src = dx!(core) as i32;
+            let bound = operator::$dst(core)? as i32;
+
+            not_z_flag!(core) = src as u32;
+            v_flag!(core) = 0;
+            c_flag!(core) = 0;
+
+            if src >= 0 && src <= bound
+            {
+                Ok(Cycles(10 + $cycles))
+            } else {
+                n_flag!(core) = if src < 0 {1 << 7} else {0};
+                Err(Trap(EXCEPTION_CHK, 40))
+            }
+        });
+}
+// 020+
+chk_32!(chk_32_ai,   ay_ai_32,   8);
+chk_32!(chk_32_al,   al_32,     16);
+chk_32!(chk_32_aw,   aw_32,     12);
+chk_32!(chk_32_dn,   dy,         0);
+chk_32!(chk_32_di,   ay_di_32,  12);
+chk_32!(chk_32_imm,  imm_32,     8);
+chk_32!(chk_32_ix,   ay_ix_32,  14);
+chk_32!(chk_32_pcdi, pcdi_32,   12);
+chk_32!(chk_32_pcix, pcix_32,   14);
+chk_32!(chk_32_pd,   ay_pd_32,  10);
+chk_32!(chk_32_pi,   ay_pi_32,   8);
+
+// Put implementation of CHK2, CMP2 ops here (020+)
+// The bounds are a pair of operands, lower bound first. The extension word
+// holds Rn in bits 15-12, and bit 11 is set for CHK2, which traps when Rn
+// is out of bounds.
+macro_rules! chk2cmp2 {
+    ($name:ident, $ea:ident, $read:ident, $bits:expr, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u16()?;
+            let ea = effective_address::$ea(core)?;
+            let lower = core.$read(ea)?;
+            let upper = core.$read(ea.wrapping_add($bits / 8))?;
+            if common::chk2cmp2(core, word2, lower, upper, $bits) && word2 & 0x0800 != 0 {
+                Err(Trap(EXCEPTION_CHK, 40))
+            } else {
+                Ok(Cycles($cycles))
+            }
+        });
+}
+chk2cmp2!(chk2cmp2_8_ai,    address_indirect_ay, read_byte, 8, 18+4);
+chk2cmp2!(chk2cmp2_8_di,    displacement_ay,     read_byte, 8, 18+8);
+chk2cmp2!(chk2cmp2_8_ix,    index_ay,            read_byte, 8, 18+10);
+chk2cmp2!(chk2cmp2_8_aw,    absolute_word,       read_byte, 8, 18+8);
+chk2cmp2!(chk2cmp2_8_al,    absolute_long,       read_byte, 8, 18+12);
+chk2cmp2!(chk2cmp2_8_pcdi,  displacement_pc,     read_byte, 8, 18+8);
+chk2cmp2!(chk2cmp2_8_pcix,  index_pc,            read_byte, 8, 18+10);
+
+chk2cmp2!(chk2cmp2_16_ai,   address_indirect_ay, read_word, 16, 18+4);
+chk2cmp2!(chk2cmp2_16_di,   displacement_ay,     read_word, 16, 18+8);
+chk2cmp2!(chk2cmp2_16_ix,   index_ay,            read_word, 16, 18+10);
+chk2cmp2!(chk2cmp2_16_aw,   absolute_word,       read_word, 16, 18+8);
+chk2cmp2!(chk2cmp2_16_al,   absolute_long,       read_word, 16, 18+12);
+chk2cmp2!(chk2cmp2_16_pcdi, displacement_pc,     read_word, 16, 18+8);
+chk2cmp2!(chk2cmp2_16_pcix, index_pc,            read_word, 16, 18+10);
+
+chk2cmp2!(chk2cmp2_32_ai,   address_indirect_ay, read_long, 32, 18+8);
+chk2cmp2!(chk2cmp2_32_di,   displacement_ay,     read_long, 32, 18+12);
+chk2cmp2!(chk2cmp2_32_ix,   index_ay,            read_long, 32, 18+14);
+chk2cmp2!(chk2cmp2_32_aw,   absolute_word,       read_long, 32, 18+12);
+chk2cmp2!(chk2cmp2_32_al,   absolute_long,       read_long, 32, 18+16);
+chk2cmp2!(chk2cmp2_32_pcdi, displacement_pc,     read_long, 32, 18+12);
+chk2cmp2!(chk2cmp2_32_pcix, index_pc,            read_long, 32, 18+14);
+
 use cpu::effective_address;
 
 macro_rules! clr {
@@ -1253,6 +1612,65 @@ impl_op!(-, cmp_16, cmpm_16, ay_pi_16, ax_pi_16, 12);
 impl_op!(-, cmp_32, cmpm_32, ay_pi_32, ax_pi_32, 20);
 
 // Put implementation of DBcc ops here
+
+// The 68010 runs a DBcc loop over a one-word instruction from its
+// prefetch queue ("loop mode"), which the first iteration enters. The
+// later ones fetch neither the loop instruction nor the DBcc, which the
+// core then serves from the LoopBuffer; that saves the 4 cycles of the
+// loop instruction's prefetch, and the DBcc takes 6 cycles rather than
+// 10. Leaving the loop refills the queue as usual. Musashi doesn't have
+// loop mode, so these follow the loop mode timing of the MC68010UM,
+// charged to the DBcc.
+fn loop_continued<T: Core>(core: &mut T, offset: i16) -> Cycles {
+    if core.cpu_type() != CpuType::M68010 {
+        return Cycles(10);
+    }
+    if core.loop_mode().is_some() {
+        return Cycles(6 - 4);
+    }
+    if offset == -4 {
+        if let Some(word) = core.previous_word().filter(|&word| is_loopable(word)) {
+            let words = [word, core.ir(), offset as u16];
+            *core.loop_mode() = Some(LoopBuffer { address: core.ppc().wrapping_sub(2), words });
+        }
+    }
+    Cycles(10)
+}
+fn loop_ended<T: Core>(core: &mut T, cycles: i32) -> Cycles {
+    if core.loop_mode().take().is_some() {
+        Cycles(cycles - 4)
+    } else {
+        Cycles(cycles)
+    }
+}
+// Whether the 68010 can run a DBcc loop over the opcode in loop mode: a
+// one-word instruction with its memory operands at (An), (An)+ or -(An)
+pub(crate) fn is_loopable(opcode: u16) -> bool {
+    let memory = |mode: u16| (2..=4).contains(&mode);
+    let (ea, opmode) = (opcode >> 3 & 7, opcode >> 6 & 7);
+    match opcode >> 12 {
+        // MOVE to memory, from a register or memory
+        0x1..=0x3 => memory(opmode) && ea <= 4,
+        // NEGX, CLR, NEG, NOT and TST, and NBCD
+        0x4 => match opcode >> 8 & 0xf {
+            0x0 | 0x2 | 0x4 | 0x6 | 0xa => opcode >> 6 & 3 != 3 && memory(ea),
+            0x8 => opcode >> 6 & 3 == 0 && memory(ea),
+            _ => false,
+        },
+        // OR, SUB, CMP, EOR, AND and ADD with memory
+        0x8 | 0x9 | 0xb | 0xc | 0xd => match opmode {
+            0..=2 => memory(ea),
+            // SUBA, CMPA and ADDA, but not DIVx or MULx
+            3 | 7 => opcode >> 12 & 1 == 1 && memory(ea),
+            // SBCD and ABCD, SUBX, CMPM and ADDX, but not EXG or PACK
+            4..=6 if ea == 1 => opmode == 4 || opcode >> 12 & 1 == 1,
+            _ => memory(ea),
+        },
+        // ASd, LSd, ROXd and ROd of memory
+        0xe => opcode >> 6 & 3 == 3 && opcode >> 11 & 1 == 0 && memory(ea),
+        _ => false,
+    }
+}
 branch!(16, dbt_16,  cond_t,  dy);
 branch!(16, dbf_16,  cond_f,  dy);
 branch!(16, dbhi_16, cond_hi, dy);
@@ -1272,28 +1690,32 @@ branch!(16, dble_16, cond_le, dy);
 
 // Put implementation of DIVS ops here
 macro_rules! div_op {
-    ($common:ident, $srctype:ty, $name:ident, $src:ident, $base_cycles:expr, $cycles:expr) => (
+    ($common:ident, $timing:ident, $srctype:ty, $name:ident, $src:ident, $base_cycles:expr, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
             // as opposed to ADDA, we execute src op first
             // even though the PI/PD addressing modes will change AX (if AX=AY)
             let src = operator::$src(core)? as $srctype;
             let dst = dx!(core);
             if src != 0 {
+                // Musashi charges the worst case, while cycle exact
+                // timing charges what these operands take
+                let base_cycles = if core.exact_timing() { timing::$timing(dst as _, src) } else { $base_cycles };
                 common::$common(core, dst, src);
-                Ok(Cycles($base_cycles + $cycles))
+                Ok(Cycles(base_cycles + $cycles))
             } else {
                 // 38 cycles for the ZERO_DIVIDE trap + EA calculation time
                 // deduct the base cycles for the instruction, to extract EA cycles.
                 // TODO: update musashi to account for this
-                Err(Trap(EXCEPTION_ZERO_DIVIDE, 38 /*+ $cycles*/))
+                // (cycle exact timing already does)
+                Err(Trap(EXCEPTION_ZERO_DIVIDE, if core.exact_timing() { 38 + $cycles } else { 38 }))
             }
         })
 }
 macro_rules! divs {
-    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divs_16, i16, $name, $src, 158, $cycles);)
+    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divs_16, divs_cycles, i16, $name, $src, 158, $cycles);)
 }
 macro_rules! divu {
-    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divu_16, u16, $name, $src, 140, $cycles);)
+    ($name:ident, $src:ident, $cycles:expr) => (div_op!(divu_16, divu_cycles, u16, $name, $src, 140, $cycles);)
 }
 
 divs!(divs_16_dn, dy,        0);
@@ -1323,6 +1745,32 @@ divu!(divu_16_pcdi, pcdi_16, 8);
 divu!(divu_16_pcix, pcix_16, 10);
 divu!(divu_16_imm, imm_16,   4);
 
+// Put implementation of DIVS.L, DIVU.L ops here (020+)
+macro_rules! divl {
+    ($name:ident, $src:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u16()?;
+            let src = operator::$src(core)?;
+            if src != 0 {
+                common::divl_32(core, word2, src);
+                Ok(Cycles(84 + $cycles))
+            } else {
+                Err(Trap(EXCEPTION_ZERO_DIVIDE, 38))
+            }
+        })
+}
+divl!(divl_32_dn, dy,        0);
+divl!(divl_32_ai, ay_ai_32,  8);
+divl!(divl_32_pi, ay_pi_32,  8);
+divl!(divl_32_pd, ay_pd_32,  10);
+divl!(divl_32_di, ay_di_32,  12);
+divl!(divl_32_ix, ay_ix_32,  14);
+divl!(divl_32_aw, aw_32,     12);
+divl!(divl_32_al, al_32,     16);
+divl!(divl_32_pcdi, pcdi_32, 12);
+divl!(divl_32_pcix, pcix_32, 14);
+divl!(divl_32_imm, imm_32,   8);
+
 // Put implementation of EOR, EORI, EORI to CCR and EORI to SR ops here
 macro_rules! eor_8 {
     ($name:ident, $dst:ident, $cycles:expr) => (impl_op!(8, eor_8, $name, dx, $dst, $cycles);)
@@ -1482,6 +1930,18 @@ pub fn ext_wl<T: Core>(core: &mut T) -> Result<Cycles> {
     not_z_flag!(core) = res;
     Ok(Cycles(4))
 }
+// EXTB.L (020+)
+pub fn ext_bl<T: Core>(core: &mut T) -> Result<Cycles> {
+    let dst = dy!(core);
+    let res = mask_out_above_8!(dst) | if (dst & 0x80) > 0 {0xffff_ff00} else {0};
+    dy!(core) = res;
+
+    n_flag!(core) = res >> 24;
+    v_flag!(core) = 0;
+    c_flag!(core) = 0;
+    not_z_flag!(core) = res;
+    Ok(Cycles(4))
+}
 
 // Put implementation of ILLEGAL op here
 
@@ -1501,7 +1961,7 @@ macro_rules! jump {
             // using a constant expression will optimize this check away
             if $push {
                 let pc = pc!(core);
-                core.push_32(pc);
+                core.push_32(pc)?;
             }
             core.jump(ea);
             Ok(Cycles($cycles))
@@ -1546,15 +2006,28 @@ lea!(lea_32_pcix, index_pc, 12);
 // Put implementation of LINK ops here
 pub fn link_16<T: Core>(core: &mut T) -> Result<Cycles> {
     let sp = if ir_ay!(core) == super::STACK_POINTER_REG {
-        core.push_sp()
+        core.push_sp()?
     } else {
         let ay = ay!(core);
-        core.push_32(ay)
+        core.push_32(ay)?
     };
     ay!(core) = sp;
     sp!(core) = effective_address::displacement(core, sp)?;
     Ok(Cycles(16))
 }
+// 020+
+pub fn link_32<T: Core>(core: &mut T) -> Result<Cycles> {
+    let sp = if ir_ay!(core) == super::STACK_POINTER_REG {
+        core.push_sp()?
+    } else {
+        let ay = ay!(core);
+        core.push_32(ay)?
+    };
+    ay!(core) = sp;
+    let displacement = core.read_imm_u32()?;
+    sp!(core) = sp.wrapping_add(displacement);
+    Ok(Cycles(20))
+}
 
 // Put implementation of LSL, LSR ops here
 macro_rules! lsr_8 {
@@ -2074,6 +2547,62 @@ move_frs!(move_16_frs_ix, index_ay,            8+10);
 move_frs!(move_16_frs_aw, absolute_word,       8+8);
 move_frs!(move_16_frs_al, absolute_long,       8+12);
 
+// MOVE from SR is privileged on the 68010 and later
+macro_rules! move_frs_010 {
+    ($name:ident, dy, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            if s_flag!(core) != 0 {
+                dy!(core) = mask_out_below_16!(dy!(core)) | u32::from(core.status_register());
+                Ok(Cycles($cycles))
+            } else {
+                Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+            }
+        });
+    ($name:ident, $src:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            if s_flag!(core) != 0 {
+                let sr = core.status_register();
+                let ea = effective_address::$src(core)?;
+                core.write_word(ea, u32::from(sr))?;
+                Ok(Cycles($cycles))
+            } else {
+                Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+            }
+        })
+}
+move_frs_010!(move_16_frs_dn_010, dy, 4);
+move_frs_010!(move_16_frs_ai_010, address_indirect_ay, 8+4);
+move_frs_010!(move_16_frs_pi_010, postincrement_ay_16, 8+4);
+move_frs_010!(move_16_frs_pd_010, predecrement_ay_16,  8+6);
+move_frs_010!(move_16_frs_di_010, displacement_ay,     8+8);
+move_frs_010!(move_16_frs_ix_010, index_ay,            8+10);
+move_frs_010!(move_16_frs_aw_010, absolute_word,       8+8);
+move_frs_010!(move_16_frs_al_010, absolute_long,       8+12);
+
+// Put implementation of MOVE from CCR ops here (010+)
+macro_rules! move_frc {
+    ($name:ident, dy, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            dy!(core) = mask_out_below_16!(dy!(core)) | u32::from(core.condition_code_register());
+            Ok(Cycles($cycles))
+        });
+    ($name:ident, $src:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let ccr = core.condition_code_register();
+            let ea = effective_address::$src(core)?;
+            core.write_word(ea, u32::from(ccr))?;
+            Ok(Cycles($cycles))
+        })
+}
+move_frc!(move_16_frc_dn, dy, 4);
+move_frc!(move_16_frc_ai, address_indirect_ay, 8+4);
+move_frc!(move_16_frc_pi, postincrement_ay_16, 8+4);
+move_frc!(move_16_frc_pd, predecrement_ay_16,  8+6);
+move_frc!(move_16_frc_di, displacement_ay,     8+8);
+move_frc!(move_16_frc_ix, index_ay,            8+10);
+move_frc!(move_16_frc_aw, absolute_word,       8+8);
+move_frc!(move_16_frc_al, absolute_long,       8+12);
+
 // Put implementation of MOVE to SR ops here
 macro_rules! move_tos {
     ($name:ident, $src:ident, $cycles:expr) => (
@@ -2116,6 +2645,36 @@ pub fn move_32_fru<T: Core>(core: &mut T) -> Result<Cycles> {
         Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
     }
 }
+
+// Put implementation of MOVEC ops here (010+)
+// The extension word holds the general register in bits 15-12 and the
+// control register in bits 11-0. USP is the inactive stack pointer, as
+// MOVEC is privileged.
+pub fn movec_32_cr<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) != 0 {
+        let word2 = core.read_imm_u16()?;
+        let value = match core.control_register(word2 & 0xfff) {
+            Some(value) => value,
+            None => return Err(IllegalInstruction(ir!(core), pc!(core).wrapping_sub(4))),
+        };
+        dar!(core)[(word2 >> 12) as usize] = value;
+        Ok(Cycles(12))
+    } else {
+        Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+    }
+}
+pub fn movec_32_rc<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) != 0 {
+        let word2 = core.read_imm_u16()?;
+        let value = dar!(core)[(word2 >> 12) as usize];
+        if !core.set_control_register(word2 & 0xfff, value) {
+            return Err(IllegalInstruction(ir!(core), pc!(core).wrapping_sub(4)));
+        }
+        Ok(Cycles(10))
+    } else {
+        Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+    }
+}
 // Put implementation of MOVEM ops here
 macro_rules! movem_16_re {
     ($name:ident, predecrement_ay_16, $cycles:expr) => (
@@ -2164,6 +2723,8 @@ macro_rules! movem_16_er {
                     moves += 1;
                 }
             }
+            // the 68000 reads one more word than it loads
+            core.discarded_read_cycle(ea);
             ay!(core) = ea;
             Ok(Cycles($cycles + 4 * moves))
         });
@@ -2180,6 +2741,7 @@ macro_rules! movem_16_er {
                     moves += 1;
                 }
             }
+            core.discarded_read_cycle(ea);
             Ok(Cycles($cycles + 4 * moves))
         })
 }
@@ -2231,6 +2793,8 @@ macro_rules! movem_32_er {
                     moves += 1;
                 }
             }
+            // the 68000 reads one more word than it loads
+            core.discarded_read_cycle(ea);
             ay!(core) = ea;
             Ok(Cycles($cycles + 8 * moves))
         });
@@ -2247,6 +2811,7 @@ macro_rules! movem_32_er {
                     moves += 1;
                 }
             }
+            core.discarded_read_cycle(ea);
             Ok(Cycles($cycles + 8 * moves))
         })
 }
@@ -2326,6 +2891,62 @@ pub fn moveq_32<T: Core>(core: &mut T) -> Result<Cycles> {
     Ok(Cycles(4))
 }
 
+// Put implementation of MOVES ops here (010+)
+// The extension word holds the general register in bits 15-12, and bit
+// 11 is set when moving from the register to memory, in the DFC space.
+// Memory is read from the SFC space, sign extended into address registers.
+macro_rules! moves {
+    ($name:ident, $ea:ident, $read:ident, $write:ident, $bits:expr, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            if s_flag!(core) != 0 {
+                let word2 = core.read_imm_u16()?;
+                let ea = effective_address::$ea(core)?;
+                let reg = (word2 >> 12) as usize;
+                let mask = (1u64 << $bits).wrapping_sub(1) as u32;
+                if word2 & 0x0800 != 0 {
+                    let fc = *core.dfc();
+                    let value = dar!(core)[reg] & mask;
+                    core.$write(fc, ea, value)?;
+                } else {
+                    let fc = *core.sfc();
+                    let value = core.$read(fc, ea)?;
+                    dar!(core)[reg] = if reg >= 8 {
+                        let shift = 32 - $bits;
+                        ((value << shift) as i32 >> shift) as u32
+                    } else {
+                        dar!(core)[reg] & !mask | value
+                    };
+                }
+                Ok(Cycles($cycles))
+            } else {
+                Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+            }
+        })
+}
+moves!(moves_8_ai, address_indirect_ay, read_byte_fc, write_byte_fc, 8, 14+4);
+moves!(moves_8_pi, postincrement_ay_8,  read_byte_fc, write_byte_fc, 8, 14+4);
+moves!(moves_8_pd, predecrement_ay_8,   read_byte_fc, write_byte_fc, 8, 14+6);
+moves!(moves_8_di, displacement_ay,     read_byte_fc, write_byte_fc, 8, 14+8);
+moves!(moves_8_ix, index_ay,            read_byte_fc, write_byte_fc, 8, 14+10);
+moves!(moves_8_aw, absolute_word,       read_byte_fc, write_byte_fc, 8, 14+8);
+moves!(moves_8_al, absolute_long,       read_byte_fc, write_byte_fc, 8, 14+12);
+
+moves!(moves_16_ai, address_indirect_ay, read_word_fc, write_word_fc, 16, 14+4);
+moves!(moves_16_pi, postincrement_ay_16, read_word_fc, write_word_fc, 16, 14+4);
+moves!(moves_16_pd, predecrement_ay_16,  read_word_fc, write_word_fc, 16, 14+6);
+moves!(moves_16_di, displacement_ay,     read_word_fc, write_word_fc, 16, 14+8);
+moves!(moves_16_ix, index_ay,            read_word_fc, write_word_fc, 16, 14+10);
+moves!(moves_16_aw, absolute_word,       read_word_fc, write_word_fc, 16, 14+8);
+moves!(moves_16_al, absolute_long,       read_word_fc, write_word_fc, 16, 14+12);
+
+moves!(moves_32_ai, address_indirect_ay, read_long_fc, write_long_fc, 32, 16+8);
+moves!(moves_32_pi, postincrement_ay_32, read_long_fc, write_long_fc, 32, 16+8);
+moves!(moves_32_pd, predecrement_ay_32,  read_long_fc, write_long_fc, 32, 16+10);
+moves!(moves_32_di, displacement_ay,     read_long_fc, write_long_fc, 32, 16+12);
+moves!(moves_32_ix, index_ay,            read_long_fc, write_long_fc, 32, 16+14);
+moves!(moves_32_aw, absolute_word,       read_long_fc, write_long_fc, 32, 16+12);
+moves!(moves_32_al, absolute_long,       read_long_fc, write_long_fc, 32, 16+16);
+
 // Put implementation of MULS ops here
 macro_rules! mul_op {
     ($common:ident, $srctype:ty, $name:ident, $src:ident, $cycles:expr) => (
@@ -2367,6 +2988,28 @@ mulu!(mulu_16_pcdi, pcdi_16, 54+8);
 mulu!(mulu_16_pcix, pcix_16, 54+10);
 mulu!(mulu_16_imm, imm_16, 54+4);
 
+// Put implementation of MULS.L, MULU.L ops here (020+)
+macro_rules! mull {
+    ($name:ident, $src:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let word2 = core.read_imm_u16()?;
+            let src = operator::$src(core)?;
+            common::mull_32(core, word2, src);
+            Ok(Cycles(43 + $cycles))
+        })
+}
+mull!(mull_32_dn, dy,       0);
+mull!(mull_32_ai, ay_ai_32, 8);
+mull!(mull_32_pi, ay_pi_32, 8);
+mull!(mull_32_pd, ay_pd_32, 10);
+mull!(mull_32_di, ay_di_32, 12);
+mull!(mull_32_ix, ay_ix_32, 14);
+mull!(mull_32_aw, aw_32,    12);
+mull!(mull_32_al, al_32,    16);
+mull!(mull_32_pcdi, pcdi_32, 12);
+mull!(mull_32_pcix, pcix_32, 14);
+mull!(mull_32_imm, imm_32,   8);
+
 // Put implementation of NBCD ops here
 macro_rules! nbcd {
     ($name:ident, dy, $cycles:expr) => (
@@ -2774,12 +3417,32 @@ pub fn ori_16_tos<T: Core>(core: &mut T) -> Result<Cycles> {
     }
 }
 
+// Put implementation of PACK ops here (020+)
+// The adjustment is added to the unpacked digits, a word in Dy or two
+// bytes in memory, and the low nibbles of both bytes packed into one
+pub fn pack_16_rr<T: Core>(core: &mut T) -> Result<Cycles> {
+    let adjustment = u32::from(core.read_imm_u16()?);
+    let src = dy!(core).wrapping_add(adjustment);
+    dx!(core) = mask_out_below_8!(dx!(core)) | ((src >> 4) & 0xf0) | low_nibble!(src);
+    Ok(Cycles(6))
+}
+pub fn pack_16_mm<T: Core>(core: &mut T) -> Result<Cycles> {
+    let adjustment = u32::from(core.read_imm_u16()?);
+    // the low byte is at the higher address, so it comes first
+    let lo = operator::ay_pd_8(core)?;
+    let hi = operator::ay_pd_8(core)?;
+    let src = ((hi << 8) | lo).wrapping_add(adjustment);
+    let ea = effective_address::predecrement_ax_8(core)?;
+    core.write_byte(ea, ((src >> 4) & 0xf0) | low_nibble!(src))?;
+    Ok(Cycles(13))
+}
+
 // Put implementation of PEA ops here
 macro_rules! pea {
     ($name:ident, $src:ident, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
             let ea = effective_address::$src(core)?;
-            core.push_32(ea);
+            core.push_32(ea)?;
             Ok(Cycles($cycles))
         });
 }
@@ -2907,25 +3570,115 @@ roxr_16!(roxr_16_ix, ea_ay_ix_16, 18);
 roxr_16!(roxr_16_aw, ea_aw_16,    16);
 roxr_16!(roxr_16_al, ea_al_16,    20);
 
+// Put implementation of RTD ops here (010+)
+pub fn rtd_32<T: Core>(core: &mut T) -> Result<Cycles> {
+    let new_pc = core.pop_32()?;
+    let displacement = core.read_imm_i16()?;
+    sp!(core) = sp!(core).wrapping_add(displacement as u32);
+    core.jump(new_pc);
+    Ok(Cycles(16))
+}
+
 // Put implementation of RTE ops here
 pub fn rte_32<T: Core>(core: &mut T) -> Result<Cycles> {
     if s_flag!(core) != 0 {
-        let new_sr = core.pop_16();
-        let new_pc = core.pop_32();
+        let new_sr = core.pop_16()?;
+        let new_pc = core.pop_32()?;
+        core.jump(new_pc);
+        core.sr_to_flags(new_sr);
+        core.resume_normal_processing();
+
+        Ok(Cycles(20))
+    } else {
+        Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+    }
+}
+
+// The 68010 checks the format of the frame; only the short format $0
+// frame is accepted, as the long format $8 bus error frame would need
+// the faulted bus cycle to be rerun (which Musashi doesn't do either)
+pub fn rte_32_010<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) != 0 {
+        let format_address = sp!(core).wrapping_add(6);
+        let format = core.read_word(format_address)? >> 12;
+        if format != 0 {
+            return Err(FormatError(ir!(core), pc!(core)));
+        }
+        let new_sr = core.pop_16()?;
+        let new_pc = core.pop_32()?;
+        sp!(core) = sp!(core).wrapping_add(2); // the format word
         core.jump(new_pc);
         core.sr_to_flags(new_sr);
         core.resume_normal_processing();
 
+        Ok(Cycles(24))
+    } else {
+        Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+    }
+}
+
+// The 68020 also restores from throwaway frames (format $1), by
+// continuing with the frame on the stack it switches to, and skips the
+// instruction address of format $2 frames
+pub fn rte_32_020<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) != 0 {
+        loop {
+            let format_address = sp!(core).wrapping_add(6);
+            let format = core.read_word(format_address)? >> 12;
+            match format {
+                0 | 2 => {
+                    let new_sr = core.pop_16()?;
+                    let new_pc = core.pop_32()?;
+                    let frame_size = if format == 2 { 6 } else { 2 };
+                    sp!(core) = sp!(core).wrapping_add(frame_size);
+                    core.jump(new_pc);
+                    core.sr_to_flags(new_sr);
+                    break;
+                },
+                1 => {
+                    let new_sr = core.pop_16()?;
+                    sp!(core) = sp!(core).wrapping_add(6); // the pc and format word
+                    core.sr_to_flags(new_sr);
+                },
+                _ => return Err(FormatError(ir!(core), pc!(core))),
+            }
+        }
+        core.resume_normal_processing();
+
         Ok(Cycles(20))
     } else {
         Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
     }
 }
 
+// The CPU32 has no throwaway frames, but restores from its format $C bus
+// error frame, at the stacked pc, without rerunning the faulted bus cycle
+pub fn rte_32_cpu32<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) != 0 {
+        let format_address = sp!(core).wrapping_add(6);
+        let frame_size = match core.read_word(format_address)? >> 12 {
+            0 => 2,
+            2 => 6,
+            0xc => 18,
+            _ => return Err(FormatError(ir!(core), pc!(core))),
+        };
+        let new_sr = core.pop_16()?;
+        let new_pc = core.pop_32()?;
+        sp!(core) = sp!(core).wrapping_add(frame_size);
+        core.jump(new_pc);
+        core.sr_to_flags(new_sr);
+        core.resume_normal_processing();
+
+        Ok(Cycles(24))
+    } else {
+        Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
+    }
+}
+
 // Put implementation of RTR ops here
 pub fn rtr_32<T: Core>(core: &mut T) -> Result<Cycles> {
-    let new_ccr = core.pop_16();
-    let new_pc = core.pop_32();
+    let new_ccr = core.pop_16()?;
+    let new_pc = core.pop_32()?;
     core.jump(new_pc);
     core.ccr_to_flags(new_ccr);
     Ok(Cycles(20))
@@ -2933,7 +3686,7 @@ pub fn rtr_32<T: Core>(core: &mut T) -> Result<Cycles> {
 
 // Put implementation of RTS ops here
 pub fn rts_32<T: Core>(core: &mut T) -> Result<Cycles> {
-    let new_pc = core.pop_32();
+    let new_pc = core.pop_32()?;
     core.jump(new_pc);
     Ok(Cycles(16))
 }
@@ -3138,6 +3891,26 @@ pub fn stop<T: Core>(core: &mut T) -> Result<Cycles> {
     }
 }
 
+// Put implementation of LPSTOP ops here (CPU32)
+// LPSTOP shares its first word with TBL D0:Dn,Dx, and only the extension
+// word tells them apart. The interrupt mask it also writes to the system
+// integration module, to wake up from the low power mode, is not modelled.
+pub fn lpstop<T: Core>(core: &mut T) -> Result<Cycles> {
+    let extension = core.read_imm_u16()?;
+    if extension != 0x01c0 {
+        return tbl_registers(core, extension);
+    }
+    if s_flag!(core) != 0 {
+        let sr = core.read_imm_u16()?;
+        core.sr_to_flags(sr);
+        core.stop_instruction_processing();
+
+        Ok(Cycles(16))
+    } else {
+        Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(4)))
+    }
+}
+
 // Put implementation of SUB ops here
 
 macro_rules! sub_8_er {
@@ -3459,6 +4232,65 @@ tas_8!(tas_8_ix, ea_ay_ix_8, 14+10);
 tas_8!(tas_8_aw, ea_aw_8, 14+8);
 tas_8!(tas_8_al, ea_al_8, 14+12);
 
+// Put implementation of TBLS, TBLU, TBLSN and TBLUN ops here (CPU32)
+// The extension word holds Dx in bits 14-12, the size in bits 7-6, and
+// bit 8 is set when interpolating between table entries rather than
+// registers. Dx bits 15-8 index the table, and bits 7-0 are the fraction
+// to interpolate by.
+fn tbl_size(extension: u16) -> Option<u32> {
+    match (extension >> 6) & 3 {
+        0 => Some(8),
+        1 => Some(16),
+        2 => Some(32),
+        _ => None,
+    }
+}
+fn tbl_f_line<T: Core>(core: &mut T) -> Result<Cycles> {
+    Err(UnimplementedInstruction(ir!(core), pc!(core).wrapping_sub(4), EXCEPTION_UNIMPLEMENTED_1111))
+}
+fn tbl_registers<T: Core>(core: &mut T, extension: u16) -> Result<Cycles> {
+    match tbl_size(extension) {
+        Some(bits) if extension & 0x8338 == 0 => {
+            let (y0, y1) = (dy!(core), dar!(core)[(extension & 7) as usize]);
+            common::tbl(core, extension, y0, y1, bits);
+            Ok(Cycles(26))
+        },
+        _ => tbl_f_line(core),
+    }
+}
+pub fn tbl_dn<T: Core>(core: &mut T) -> Result<Cycles> {
+    let extension = core.read_imm_u16()?;
+    tbl_registers(core, extension)
+}
+macro_rules! tbl {
+    ($name:ident, $ea:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let extension = core.read_imm_u16()?;
+            let bits = match tbl_size(extension) {
+                Some(bits) if extension & 0x833f == 0x0100 => bits,
+                _ => return tbl_f_line(core),
+            };
+            let ea = effective_address::$ea(core)?;
+            let index = (dar!(core)[((extension >> 12) & 7) as usize] >> 8) & 0xff;
+            let entry = ea.wrapping_add(index * bits / 8);
+            let next = entry.wrapping_add(bits / 8);
+            let (y0, y1) = match bits {
+                8 => (core.read_byte(entry)?, core.read_byte(next)?),
+                16 => (core.read_word(entry)?, core.read_word(next)?),
+                _ => (core.read_long(entry)?, core.read_long(next)?),
+            };
+            common::tbl(core, extension, y0, y1, bits);
+            Ok(Cycles($cycles))
+        });
+}
+tbl!(tbl_ai,   address_indirect_ay, 30+4);
+tbl!(tbl_di,   displacement_ay,     30+8);
+tbl!(tbl_ix,   index_ay,            30+10);
+tbl!(tbl_aw,   absolute_word,       30+8);
+tbl!(tbl_al,   absolute_long,       30+12);
+tbl!(tbl_pcdi, displacement_pc,     30+8);
+tbl!(tbl_pcix, index_pc,            30+10);
+
 // Put implementation of TRAP ops here
 pub fn trap<T: Core>(core: &mut T) -> Result<Cycles> {
     Err(Trap(EXCEPTION_TRAP_BASE + low_nibble!(ir!(core)) as u8, 34))
@@ -3473,6 +4305,71 @@ pub fn trapv<T: Core>(core: &mut T) -> Result<Cycles> {
     }
 }
 
+// Put implementation of TRAPcc ops here (020+)
+// The optional word or long operand is just skipped, for the trap
+// handler to look at
+macro_rules! trapcc {
+    ($name:ident, $cond:ident, $operand_size:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            pc!(core) = pc!(core).wrapping_add($operand_size);
+            if core.$cond() {
+                Err(Trap(EXCEPTION_TRAPV, 34))
+            } else {
+                Ok(Cycles(4))
+            }
+        })
+}
+trapcc!(trapt_16, cond_t, 2);
+trapcc!(trapf_16, cond_f, 2);
+trapcc!(traphi_16, cond_hi, 2);
+trapcc!(trapls_16, cond_ls, 2);
+trapcc!(trapcc_16, cond_cc, 2);
+trapcc!(trapcs_16, cond_cs, 2);
+trapcc!(trapne_16, cond_ne, 2);
+trapcc!(trapeq_16, cond_eq, 2);
+trapcc!(trapvc_16, cond_vc, 2);
+trapcc!(trapvs_16, cond_vs, 2);
+trapcc!(trappl_16, cond_pl, 2);
+trapcc!(trapmi_16, cond_mi, 2);
+trapcc!(trapge_16, cond_ge, 2);
+trapcc!(traplt_16, cond_lt, 2);
+trapcc!(trapgt_16, cond_gt, 2);
+trapcc!(traple_16, cond_le, 2);
+
+trapcc!(trapt_32, cond_t, 4);
+trapcc!(trapf_32, cond_f, 4);
+trapcc!(traphi_32, cond_hi, 4);
+trapcc!(trapls_32, cond_ls, 4);
+trapcc!(trapcc_32, cond_cc, 4);
+trapcc!(trapcs_32, cond_cs, 4);
+trapcc!(trapne_32, cond_ne, 4);
+trapcc!(trapeq_32, cond_eq, 4);
+trapcc!(trapvc_32, cond_vc, 4);
+trapcc!(trapvs_32, cond_vs, 4);
+trapcc!(trappl_32, cond_pl, 4);
+trapcc!(trapmi_32, cond_mi, 4);
+trapcc!(trapge_32, cond_ge, 4);
+trapcc!(traplt_32, cond_lt, 4);
+trapcc!(trapgt_32, cond_gt, 4);
+trapcc!(traple_32, cond_le, 4);
+
+trapcc!(trapt, cond_t, 0);
+trapcc!(trapf, cond_f, 0);
+trapcc!(traphi, cond_hi, 0);
+trapcc!(trapls, cond_ls, 0);
+trapcc!(trapcc, cond_cc, 0);
+trapcc!(trapcs, cond_cs, 0);
+trapcc!(trapne, cond_ne, 0);
+trapcc!(trapeq, cond_eq, 0);
+trapcc!(trapvc, cond_vc, 0);
+trapcc!(trapvs, cond_vs, 0);
+trapcc!(trappl, cond_pl, 0);
+trapcc!(trapmi, cond_mi, 0);
+trapcc!(trapge, cond_ge, 0);
+trapcc!(traplt, cond_lt, 0);
+trapcc!(trapgt, cond_gt, 0);
+trapcc!(traple, cond_le, 0);
+
 // Put implementation of TST ops here
 macro_rules! tst_8 {
     ($name:ident, dy, $cycles:expr) => (
@@ -3499,6 +4396,17 @@ macro_rules! tst_8 {
         });
 }
 macro_rules! tst_16 {
+    ($name:ident, ay, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let src = mask_out_above_16!(ay!(core));
+
+            not_z_flag!(core) = src;
+            n_flag!(core) = src >> 8;
+            v_flag!(core) = 0;
+            c_flag!(core) = 0;
+
+            Ok(Cycles($cycles))
+        });
     ($name:ident, dy, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
             let src = mask_out_above_16!(dy!(core));
@@ -3543,12 +4451,12 @@ tst_8!(tst_8_di,   ay_di_8, 4+8);
 tst_8!(tst_8_ix,   ay_ix_8, 4+10);
 tst_8!(tst_8_aw,   aw_8,    4+8);
 tst_8!(tst_8_al,   al_8,    4+12);
-// tst_8!(tst_8_pcdi, pcdi_8,  4+8);
-// tst_8!(tst_8_pcix, pcix_8,  4+10);
-// tst_8!(tst_8_imm,  imm_8,   4+4);
+tst_8!(tst_8_pcdi, pcdi_8,  4+8); // 020+
+tst_8!(tst_8_pcix, pcix_8,  4+10); // 020+
+tst_8!(tst_8_imm,  imm_8,   4+4); // 020+
 
 tst_16!(tst_16_dn,   dy,       4);
-// tst_16!(tst_16_an,   ay,       4);
+tst_16!(tst_16_an,   ay,       4); // 020+
 tst_16!(tst_16_ai,   ay_ai_16, 4+4);
 tst_16!(tst_16_pi,   ay_pi_16, 4+4);
 tst_16!(tst_16_pd,   ay_pd_16, 4+6);
@@ -3556,12 +4464,12 @@ tst_16!(tst_16_di,   ay_di_16, 4+8);
 tst_16!(tst_16_ix,   ay_ix_16, 4+10);
 tst_16!(tst_16_aw,   aw_16,    4+8);
 tst_16!(tst_16_al,   al_16,    4+12);
-// tst_16!(tst_16_pcdi, pcdi_16,  4+8);
-// tst_16!(tst_16_pcix, pcix_16,  4+10);
-// tst_16!(tst_16_imm,  imm_16,   4+4);
+tst_16!(tst_16_pcdi, pcdi_16,  4+8); // 020+
+tst_16!(tst_16_pcix, pcix_16,  4+10); // 020+
+tst_16!(tst_16_imm,  imm_16,   4+4); // 020+
 
 tst_32!(tst_32_dn,   dy,        4);
-// tst_32!(tst_32_an,   ay,        4);
+tst_32!(tst_32_an,   ay,        4); // 020+
 tst_32!(tst_32_ai,   ay_ai_32,  4+8);
 tst_32!(tst_32_pi,   ay_pi_32,  4+8);
 tst_32!(tst_32_pd,   ay_pd_32,  4+10);
@@ -3569,15 +4477,35 @@ tst_32!(tst_32_di,   ay_di_32,  4+12);
 tst_32!(tst_32_ix,   ay_ix_32,  4+14);
 tst_32!(tst_32_aw,   aw_32,     4+12);
 tst_32!(tst_32_al,   al_32,     4+16);
-// tst_32!(tst_32_pcdi, pcdi_32,   4+12);
-// tst_32!(tst_32_pcix, pcix_32,   4+14);
-// tst_32!(tst_32_imm,  imm_32,    4+8);
+tst_32!(tst_32_pcdi, pcdi_32,   4+12); // 020+
+tst_32!(tst_32_pcix, pcix_32,   4+14); // 020+
+tst_32!(tst_32_imm,  imm_32,    4+8); // 020+
 
 // Put implementation of UNLK ops here
 pub fn unlk_32<T: Core>(core: &mut T) -> Result<Cycles> {
     let ay = ay!(core);
     sp!(core) = ay;
-    ay!(core) = core.pop_32();
+    ay!(core) = core.pop_32()?;
 
     Ok(Cycles(12))
 }
+
+// Put implementation of UNPK ops here (020+)
+// The reverse of PACK, the adjustment is added after unpacking
+pub fn unpk_16_rr<T: Core>(core: &mut T) -> Result<Cycles> {
+    let adjustment = u32::from(core.read_imm_u16()?);
+    let src = dy!(core);
+    let res = (((src << 4) & 0x0f00) | low_nibble!(src)).wrapping_add(adjustment);
+    dx!(core) = mask_out_below_16!(dx!(core)) | mask_out_above_16!(res);
+    Ok(Cycles(8))
+}
+pub fn unpk_16_mm<T: Core>(core: &mut T) -> Result<Cycles> {
+    let adjustment = u32::from(core.read_imm_u16()?);
+    let src = operator::ay_pd_8(core)?;
+    let res = (((src << 4) & 0x0f00) | low_nibble!(src)).wrapping_add(adjustment);
+    let ea = effective_address::predecrement_ax_8(core)?;
+    core.write_byte(ea, mask_out_above_8!(res))?;
+    let ea = effective_address::predecrement_ax_8(core)?;
+    core.write_byte(ea, mask_out_above_8!(res >> 8))?;
+    Ok(Cycles(13))
+}
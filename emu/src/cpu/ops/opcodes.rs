@@ -402,6 +402,99 @@ pub const OP_BTST_8_S_PCDI  : u32 = OP_BITOPS | BIT_TST | SRC_IMM | OPER_PCDI;
 pub const OP_BTST_8_S_PCIX  : u32 = OP_BITOPS | BIT_TST | SRC_IMM | OPER_PCIX;
 pub const OP_BTST_8_S_IMM   : u32 = OP_BITOPS | BIT_TST | SRC_IMM | OPER_IMM;
 
+// Put constants for BFCHG, BFCLR, BFEXTS, BFEXTU, BFFFO, BFINS, BFSET and BFTST here (020+)
+pub const OP_BFCHG_32_DN    : u32 = OP_BITFIELD | BF_CHG | OPER_DN;
+pub const OP_BFCHG_32_AI    : u32 = OP_BITFIELD | BF_CHG | OPER_AI;
+pub const OP_BFCHG_32_DI    : u32 = OP_BITFIELD | BF_CHG | OPER_DI;
+pub const OP_BFCHG_32_IX    : u32 = OP_BITFIELD | BF_CHG | OPER_IX;
+pub const OP_BFCHG_32_AW    : u32 = OP_BITFIELD | BF_CHG | OPER_AW;
+pub const OP_BFCHG_32_AL    : u32 = OP_BITFIELD | BF_CHG | OPER_AL;
+
+pub const OP_BFCLR_32_DN    : u32 = OP_BITFIELD | BF_CLR | OPER_DN;
+pub const OP_BFCLR_32_AI    : u32 = OP_BITFIELD | BF_CLR | OPER_AI;
+pub const OP_BFCLR_32_DI    : u32 = OP_BITFIELD | BF_CLR | OPER_DI;
+pub const OP_BFCLR_32_IX    : u32 = OP_BITFIELD | BF_CLR | OPER_IX;
+pub const OP_BFCLR_32_AW    : u32 = OP_BITFIELD | BF_CLR | OPER_AW;
+pub const OP_BFCLR_32_AL    : u32 = OP_BITFIELD | BF_CLR | OPER_AL;
+
+pub const OP_BFEXTS_32_DN   : u32 = OP_BITFIELD | BF_EXTS | OPER_DN;
+pub const OP_BFEXTS_32_AI   : u32 = OP_BITFIELD | BF_EXTS | OPER_AI;
+pub const OP_BFEXTS_32_DI   : u32 = OP_BITFIELD | BF_EXTS | OPER_DI;
+pub const OP_BFEXTS_32_IX   : u32 = OP_BITFIELD | BF_EXTS | OPER_IX;
+pub const OP_BFEXTS_32_AW   : u32 = OP_BITFIELD | BF_EXTS | OPER_AW;
+pub const OP_BFEXTS_32_AL   : u32 = OP_BITFIELD | BF_EXTS | OPER_AL;
+pub const OP_BFEXTS_32_PCDI : u32 = OP_BITFIELD | BF_EXTS | OPER_PCDI;
+pub const OP_BFEXTS_32_PCIX : u32 = OP_BITFIELD | BF_EXTS | OPER_PCIX;
+
+pub const OP_BFEXTU_32_DN   : u32 = OP_BITFIELD | BF_EXTU | OPER_DN;
+pub const OP_BFEXTU_32_AI   : u32 = OP_BITFIELD | BF_EXTU | OPER_AI;
+pub const OP_BFEXTU_32_DI   : u32 = OP_BITFIELD | BF_EXTU | OPER_DI;
+pub const OP_BFEXTU_32_IX   : u32 = OP_BITFIELD | BF_EXTU | OPER_IX;
+pub const OP_BFEXTU_32_AW   : u32 = OP_BITFIELD | BF_EXTU | OPER_AW;
+pub const OP_BFEXTU_32_AL   : u32 = OP_BITFIELD | BF_EXTU | OPER_AL;
+pub const OP_BFEXTU_32_PCDI : u32 = OP_BITFIELD | BF_EXTU | OPER_PCDI;
+pub const OP_BFEXTU_32_PCIX : u32 = OP_BITFIELD | BF_EXTU | OPER_PCIX;
+
+pub const OP_BFFFO_32_DN    : u32 = OP_BITFIELD | BF_FFO | OPER_DN;
+pub const OP_BFFFO_32_AI    : u32 = OP_BITFIELD | BF_FFO | OPER_AI;
+pub const OP_BFFFO_32_DI    : u32 = OP_BITFIELD | BF_FFO | OPER_DI;
+pub const OP_BFFFO_32_IX    : u32 = OP_BITFIELD | BF_FFO | OPER_IX;
+pub const OP_BFFFO_32_AW    : u32 = OP_BITFIELD | BF_FFO | OPER_AW;
+pub const OP_BFFFO_32_AL    : u32 = OP_BITFIELD | BF_FFO | OPER_AL;
+pub const OP_BFFFO_32_PCDI  : u32 = OP_BITFIELD | BF_FFO | OPER_PCDI;
+pub const OP_BFFFO_32_PCIX  : u32 = OP_BITFIELD | BF_FFO | OPER_PCIX;
+
+pub const OP_BFINS_32_DN    : u32 = OP_BITFIELD | BF_INS | OPER_DN;
+pub const OP_BFINS_32_AI    : u32 = OP_BITFIELD | BF_INS | OPER_AI;
+pub const OP_BFINS_32_DI    : u32 = OP_BITFIELD | BF_INS | OPER_DI;
+pub const OP_BFINS_32_IX    : u32 = OP_BITFIELD | BF_INS | OPER_IX;
+pub const OP_BFINS_32_AW    : u32 = OP_BITFIELD | BF_INS | OPER_AW;
+pub const OP_BFINS_32_AL    : u32 = OP_BITFIELD | BF_INS | OPER_AL;
+
+pub const OP_BFSET_32_DN    : u32 = OP_BITFIELD | BF_SET | OPER_DN;
+pub const OP_BFSET_32_AI    : u32 = OP_BITFIELD | BF_SET | OPER_AI;
+pub const OP_BFSET_32_DI    : u32 = OP_BITFIELD | BF_SET | OPER_DI;
+pub const OP_BFSET_32_IX    : u32 = OP_BITFIELD | BF_SET | OPER_IX;
+pub const OP_BFSET_32_AW    : u32 = OP_BITFIELD | BF_SET | OPER_AW;
+pub const OP_BFSET_32_AL    : u32 = OP_BITFIELD | BF_SET | OPER_AL;
+
+pub const OP_BFTST_32_DN    : u32 = OP_BITFIELD | BF_TST | OPER_DN;
+pub const OP_BFTST_32_AI    : u32 = OP_BITFIELD | BF_TST | OPER_AI;
+pub const OP_BFTST_32_DI    : u32 = OP_BITFIELD | BF_TST | OPER_DI;
+pub const OP_BFTST_32_IX    : u32 = OP_BITFIELD | BF_TST | OPER_IX;
+pub const OP_BFTST_32_AW    : u32 = OP_BITFIELD | BF_TST | OPER_AW;
+pub const OP_BFTST_32_AL    : u32 = OP_BITFIELD | BF_TST | OPER_AL;
+pub const OP_BFTST_32_PCDI  : u32 = OP_BITFIELD | BF_TST | OPER_PCDI;
+pub const OP_BFTST_32_PCIX  : u32 = OP_BITFIELD | BF_TST | OPER_PCIX;
+
+// Put constants for CAS, CAS2 here (020+)
+pub const OP_CAS_8_AI   : u32 = OP_CAS | CAS_BYTE | OPER_AI;
+pub const OP_CAS_8_PI   : u32 = OP_CAS | CAS_BYTE | OPER_PI;
+pub const OP_CAS_8_PD   : u32 = OP_CAS | CAS_BYTE | OPER_PD;
+pub const OP_CAS_8_DI   : u32 = OP_CAS | CAS_BYTE | OPER_DI;
+pub const OP_CAS_8_IX   : u32 = OP_CAS | CAS_BYTE | OPER_IX;
+pub const OP_CAS_8_AW   : u32 = OP_CAS | CAS_BYTE | OPER_AW;
+pub const OP_CAS_8_AL   : u32 = OP_CAS | CAS_BYTE | OPER_AL;
+
+pub const OP_CAS_16_AI  : u32 = OP_CAS | CAS_WORD | OPER_AI;
+pub const OP_CAS_16_PI  : u32 = OP_CAS | CAS_WORD | OPER_PI;
+pub const OP_CAS_16_PD  : u32 = OP_CAS | CAS_WORD | OPER_PD;
+pub const OP_CAS_16_DI  : u32 = OP_CAS | CAS_WORD | OPER_DI;
+pub const OP_CAS_16_IX  : u32 = OP_CAS | CAS_WORD | OPER_IX;
+pub const OP_CAS_16_AW  : u32 = OP_CAS | CAS_WORD | OPER_AW;
+pub const OP_CAS_16_AL  : u32 = OP_CAS | CAS_WORD | OPER_AL;
+
+pub const OP_CAS_32_AI  : u32 = OP_CAS | CAS_LONG | OPER_AI;
+pub const OP_CAS_32_PI  : u32 = OP_CAS | CAS_LONG | OPER_PI;
+pub const OP_CAS_32_PD  : u32 = OP_CAS | CAS_LONG | OPER_PD;
+pub const OP_CAS_32_DI  : u32 = OP_CAS | CAS_LONG | OPER_DI;
+pub const OP_CAS_32_IX  : u32 = OP_CAS | CAS_LONG | OPER_IX;
+pub const OP_CAS_32_AW  : u32 = OP_CAS | CAS_LONG | OPER_AW;
+pub const OP_CAS_32_AL  : u32 = OP_CAS | CAS_LONG | OPER_AL;
+
+pub const OP_CAS2_16     : u32 = OP_CAS | CAS_WORD | CAS2;
+pub const OP_CAS2_32     : u32 = OP_CAS | CAS_LONG | CAS2;
+
 pub const OP_CHK_16_DN      : u32 = OP_CHK | WORD_OP | OPER_DN;
 pub const OP_CHK_16_AI      : u32 = OP_CHK | WORD_OP | OPER_AI;
 pub const OP_CHK_16_PI      : u32 = OP_CHK | WORD_OP | OPER_PI;
@@ -414,6 +507,43 @@ pub const OP_CHK_16_PCDI    : u32 = OP_CHK | WORD_OP | OPER_PCDI;
 pub const OP_CHK_16_PCIX    : u32 = OP_CHK | WORD_OP | OPER_PCIX;
 pub const OP_CHK_16_IMM     : u32 = OP_CHK | WORD_OP | OPER_IMM;
 
+pub const OP_CHK_32_DN     : u32 = OP_CHK | LONG_OP | OPER_DN; // 020+
+pub const OP_CHK_32_AI     : u32 = OP_CHK | LONG_OP | OPER_AI; // 020+
+pub const OP_CHK_32_PI     : u32 = OP_CHK | LONG_OP | OPER_PI; // 020+
+pub const OP_CHK_32_PD     : u32 = OP_CHK | LONG_OP | OPER_PD; // 020+
+pub const OP_CHK_32_DI     : u32 = OP_CHK | LONG_OP | OPER_DI; // 020+
+pub const OP_CHK_32_IX     : u32 = OP_CHK | LONG_OP | OPER_IX; // 020+
+pub const OP_CHK_32_AW     : u32 = OP_CHK | LONG_OP | OPER_AW; // 020+
+pub const OP_CHK_32_AL     : u32 = OP_CHK | LONG_OP | OPER_AL; // 020+
+pub const OP_CHK_32_PCDI   : u32 = OP_CHK | LONG_OP | OPER_PCDI; // 020+
+pub const OP_CHK_32_PCIX   : u32 = OP_CHK | LONG_OP | OPER_PCIX; // 020+
+pub const OP_CHK_32_IMM    : u32 = OP_CHK | LONG_OP | OPER_IMM; // 020+
+
+// Put constants for CHK2, CMP2 here (020+)
+pub const OP_CHK2CMP2_8_AI     : u32 = OP_CHK2 | CHK2_BYTE | OPER_AI;
+pub const OP_CHK2CMP2_8_DI     : u32 = OP_CHK2 | CHK2_BYTE | OPER_DI;
+pub const OP_CHK2CMP2_8_IX     : u32 = OP_CHK2 | CHK2_BYTE | OPER_IX;
+pub const OP_CHK2CMP2_8_AW     : u32 = OP_CHK2 | CHK2_BYTE | OPER_AW;
+pub const OP_CHK2CMP2_8_AL     : u32 = OP_CHK2 | CHK2_BYTE | OPER_AL;
+pub const OP_CHK2CMP2_8_PCDI   : u32 = OP_CHK2 | CHK2_BYTE | OPER_PCDI;
+pub const OP_CHK2CMP2_8_PCIX   : u32 = OP_CHK2 | CHK2_BYTE | OPER_PCIX;
+
+pub const OP_CHK2CMP2_16_AI    : u32 = OP_CHK2 | CHK2_WORD | OPER_AI;
+pub const OP_CHK2CMP2_16_DI    : u32 = OP_CHK2 | CHK2_WORD | OPER_DI;
+pub const OP_CHK2CMP2_16_IX    : u32 = OP_CHK2 | CHK2_WORD | OPER_IX;
+pub const OP_CHK2CMP2_16_AW    : u32 = OP_CHK2 | CHK2_WORD | OPER_AW;
+pub const OP_CHK2CMP2_16_AL    : u32 = OP_CHK2 | CHK2_WORD | OPER_AL;
+pub const OP_CHK2CMP2_16_PCDI  : u32 = OP_CHK2 | CHK2_WORD | OPER_PCDI;
+pub const OP_CHK2CMP2_16_PCIX  : u32 = OP_CHK2 | CHK2_WORD | OPER_PCIX;
+
+pub const OP_CHK2CMP2_32_AI    : u32 = OP_CHK2 | CHK2_LONG | OPER_AI;
+pub const OP_CHK2CMP2_32_DI    : u32 = OP_CHK2 | CHK2_LONG | OPER_DI;
+pub const OP_CHK2CMP2_32_IX    : u32 = OP_CHK2 | CHK2_LONG | OPER_IX;
+pub const OP_CHK2CMP2_32_AW    : u32 = OP_CHK2 | CHK2_LONG | OPER_AW;
+pub const OP_CHK2CMP2_32_AL    : u32 = OP_CHK2 | CHK2_LONG | OPER_AL;
+pub const OP_CHK2CMP2_32_PCDI  : u32 = OP_CHK2 | CHK2_LONG | OPER_PCDI;
+pub const OP_CHK2CMP2_32_PCIX  : u32 = OP_CHK2 | CHK2_LONG | OPER_PCIX;
+
 pub const OP_CLR_8_DN      : u32 = OP_CLR | BYTE_SIZED | OPER_DN;
 pub const OP_CLR_8_AI      : u32 = OP_CLR | BYTE_SIZED | OPER_AI;
 pub const OP_CLR_8_PI      : u32 = OP_CLR | BYTE_SIZED | OPER_PI;
@@ -580,6 +710,19 @@ pub const OP_DIVU_16_PCIX  : u32 = OP_DIVU | OPER_PCIX;
 pub const OP_DIVU_16_PD    : u32 = OP_DIVU | OPER_PD;
 pub const OP_DIVU_16_PI    : u32 = OP_DIVU | OPER_PI;
 
+// Put constants for DIVS.L, DIVU.L here (020+)
+pub const OP_DIVL_32_DN    : u32 = OP_DIVL | OPER_DN;
+pub const OP_DIVL_32_AI    : u32 = OP_DIVL | OPER_AI;
+pub const OP_DIVL_32_PI    : u32 = OP_DIVL | OPER_PI;
+pub const OP_DIVL_32_PD    : u32 = OP_DIVL | OPER_PD;
+pub const OP_DIVL_32_DI    : u32 = OP_DIVL | OPER_DI;
+pub const OP_DIVL_32_IX    : u32 = OP_DIVL | OPER_IX;
+pub const OP_DIVL_32_AW    : u32 = OP_DIVL | OPER_AW;
+pub const OP_DIVL_32_AL    : u32 = OP_DIVL | OPER_AL;
+pub const OP_DIVL_32_PCDI  : u32 = OP_DIVL | OPER_PCDI;
+pub const OP_DIVL_32_PCIX  : u32 = OP_DIVL | OPER_PCIX;
+pub const OP_DIVL_32_IMM   : u32 = OP_DIVL | OPER_IMM;
+
 // Put constants for EOR, EORI, EORI to CCR and EORI to SR here
 pub const OP_EOR_8_DN   : u32 = OP_EOR | BYTE_SIZED | DEST_EA | OPER_DN;
 pub const OP_EOR_8_AI   : u32 = OP_EOR | BYTE_SIZED | DEST_EA | OPER_AI;
@@ -644,7 +787,7 @@ pub const OP_EXG_32_DA: u32 = OP_EXG | EXG_DATA_ADDR;
 
 pub const OP_EXT_BW: u32 = OP_EXT | BYTE_TO_WORD;
 pub const OP_EXT_WL: u32 = OP_EXT | WORD_TO_LONG;
-// pub const OP_EXT_BL: u32 = OP_EXT | BYTE_TO_LONG; // 020+
+pub const OP_EXT_BL: u32 = OP_EXT | BYTE_TO_LONG; // 020+
 
 // Put constants for JMP here
 pub const OP_JMP_32_AI   : u32 = OP_JMP | OPER_AI;
@@ -675,6 +818,7 @@ pub const OP_LEA_32_PCIX : u32 = OP_LEA | OPER_PCIX;
 
 // Put constants for LINK here
 pub const OP_LINK_16     : u32 = OP_LINK;
+pub const OP_LINK_32     : u32 = OP_LINK_L; // 020+
 
 // Put constants for LSL, LSR here
 pub const OP_LSL_8_R        : u32 = OP_SHIFT | SHIFT_LEFT  | BYTE_SIZED | LOGI_REG_SHIFT | REG_COUNT;
@@ -1071,6 +1215,16 @@ pub const OP_MOVE_16_FRS_IX   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_IX;
 pub const OP_MOVE_16_FRS_AW   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_AW;
 pub const OP_MOVE_16_FRS_AL   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_AL;
 
+// Put constants for MOVE from CCR here (010+)
+pub const OP_MOVE_16_FRC_DN   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_DN;
+pub const OP_MOVE_16_FRC_AI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AI;
+pub const OP_MOVE_16_FRC_PI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_PI;
+pub const OP_MOVE_16_FRC_PD   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_PD;
+pub const OP_MOVE_16_FRC_DI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_DI;
+pub const OP_MOVE_16_FRC_IX   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_IX;
+pub const OP_MOVE_16_FRC_AW   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AW;
+pub const OP_MOVE_16_FRC_AL   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AL;
+
 // Put constants for MOVE to SR here
 pub const OP_MOVE_16_TOS_DN   : u32 = OP_MOVE2 | MOVE_TO_SR | OPER_DN;
 pub const OP_MOVE_16_TOS_AI   : u32 = OP_MOVE2 | MOVE_TO_SR | OPER_AI;
@@ -1087,6 +1241,10 @@ pub const OP_MOVE_16_TOS_IMM  : u32 = OP_MOVE2 | MOVE_TO_SR | OPER_IMM;
 pub const OP_MOVE_32_TOU : u32 = OP_MOVE2 | MOVE_USP | TO_AN;
 pub const OP_MOVE_32_FRU : u32 = OP_MOVE2 | MOVE_USP | FROM_AN;
 
+// Put constants for MOVEC here (010+)
+pub const OP_MOVEC_32_CR : u32 = OP_MOVEC | CONTROL_TO_REGISTER;
+pub const OP_MOVEC_32_RC : u32 = OP_MOVEC | REGISTER_TO_CONTROL;
+
 pub const OP_MOVEM_16_RE_AI: u32 = OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | OPER_AI;
 pub const OP_MOVEM_16_RE_PD: u32 = OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | OPER_PD;
 pub const OP_MOVEM_16_RE_DI: u32 = OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | OPER_DI;
@@ -1127,6 +1285,31 @@ pub const OP_MOVEP_32_RE: u32 = OP_MOVEP | LONG_TRANSFER | MOVEP_REGISTER_TO_MEM
 // Put constants for MOVEQ here
 pub const OP_MOVEQ_32: u32 = OP_MOVEQ;
 
+// Put constants for MOVES here (010+)
+pub const OP_MOVES_8_AI : u32 = OP_MOVES | BYTE_SIZED | OPER_AI;
+pub const OP_MOVES_8_PI : u32 = OP_MOVES | BYTE_SIZED | OPER_PI;
+pub const OP_MOVES_8_PD : u32 = OP_MOVES | BYTE_SIZED | OPER_PD;
+pub const OP_MOVES_8_DI : u32 = OP_MOVES | BYTE_SIZED | OPER_DI;
+pub const OP_MOVES_8_IX : u32 = OP_MOVES | BYTE_SIZED | OPER_IX;
+pub const OP_MOVES_8_AW : u32 = OP_MOVES | BYTE_SIZED | OPER_AW;
+pub const OP_MOVES_8_AL : u32 = OP_MOVES | BYTE_SIZED | OPER_AL;
+
+pub const OP_MOVES_16_AI: u32 = OP_MOVES | WORD_SIZED | OPER_AI;
+pub const OP_MOVES_16_PI: u32 = OP_MOVES | WORD_SIZED | OPER_PI;
+pub const OP_MOVES_16_PD: u32 = OP_MOVES | WORD_SIZED | OPER_PD;
+pub const OP_MOVES_16_DI: u32 = OP_MOVES | WORD_SIZED | OPER_DI;
+pub const OP_MOVES_16_IX: u32 = OP_MOVES | WORD_SIZED | OPER_IX;
+pub const OP_MOVES_16_AW: u32 = OP_MOVES | WORD_SIZED | OPER_AW;
+pub const OP_MOVES_16_AL: u32 = OP_MOVES | WORD_SIZED | OPER_AL;
+
+pub const OP_MOVES_32_AI: u32 = OP_MOVES | LONG_SIZED | OPER_AI;
+pub const OP_MOVES_32_PI: u32 = OP_MOVES | LONG_SIZED | OPER_PI;
+pub const OP_MOVES_32_PD: u32 = OP_MOVES | LONG_SIZED | OPER_PD;
+pub const OP_MOVES_32_DI: u32 = OP_MOVES | LONG_SIZED | OPER_DI;
+pub const OP_MOVES_32_IX: u32 = OP_MOVES | LONG_SIZED | OPER_IX;
+pub const OP_MOVES_32_AW: u32 = OP_MOVES | LONG_SIZED | OPER_AW;
+pub const OP_MOVES_32_AL: u32 = OP_MOVES | LONG_SIZED | OPER_AL;
+
 // Put constants for MULS here
 pub const OP_MULS_16_DN:   u32 = OP_MULS | OPER_DN;
 pub const OP_MULS_16_AI:   u32 = OP_MULS | OPER_AI;
@@ -1153,6 +1336,19 @@ pub const OP_MULU_16_PCDI: u32 = OP_MULU | OPER_PCDI;
 pub const OP_MULU_16_PCIX: u32 = OP_MULU | OPER_PCIX;
 pub const OP_MULU_16_IMM:  u32 = OP_MULU | OPER_IMM;
 
+// Put constants for MULS.L, MULU.L here (020+)
+pub const OP_MULL_32_DN    : u32 = OP_MULL | OPER_DN;
+pub const OP_MULL_32_AI    : u32 = OP_MULL | OPER_AI;
+pub const OP_MULL_32_PI    : u32 = OP_MULL | OPER_PI;
+pub const OP_MULL_32_PD    : u32 = OP_MULL | OPER_PD;
+pub const OP_MULL_32_DI    : u32 = OP_MULL | OPER_DI;
+pub const OP_MULL_32_IX    : u32 = OP_MULL | OPER_IX;
+pub const OP_MULL_32_AW    : u32 = OP_MULL | OPER_AW;
+pub const OP_MULL_32_AL    : u32 = OP_MULL | OPER_AL;
+pub const OP_MULL_32_PCDI  : u32 = OP_MULL | OPER_PCDI;
+pub const OP_MULL_32_PCIX  : u32 = OP_MULL | OPER_PCIX;
+pub const OP_MULL_32_IMM   : u32 = OP_MULL | OPER_IMM;
+
 // Put constants for NBCD here
 pub const OP_NBCD_8_DN:   u32 = OP_NBCD | OPER_DN;
 pub const OP_NBCD_8_AI:   u32 = OP_NBCD | OPER_AI;
@@ -1343,6 +1539,10 @@ pub const OP_ORI_8_TOC    : u32 = OP_ORI | BYTE_SIZED | DEST_SR;
 // Put constants for ORI to SR here
 pub const OP_ORI_16_TOS   : u32 = OP_ORI | WORD_SIZED | DEST_SR;
 
+// Put constants for PACK here (020+)
+pub const OP_PACK_16_RR  : u32 = OP_PACK | RR_MODE;
+pub const OP_PACK_16_MM  : u32 = OP_PACK | MM_MODE;
+
 // Put constants for PEA here
 pub const OP_PEA_32_AI   : u32 = OP_PEA | OPER_AI;
 pub const OP_PEA_32_DI   : u32 = OP_PEA | OPER_DI;
@@ -1414,6 +1614,7 @@ pub const OP_ROXR_16_IX      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | ROTX_
 pub const OP_ROXR_16_AW      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | ROTX_MEM_SHIFT | OPER_AW;
 pub const OP_ROXR_16_AL      : u32 = OP_SHIFT | SHIFT_RIGHT | WORD_SIZED | ROTX_MEM_SHIFT | OPER_AL;
 
+pub const OP_RTD_32 : u32 = OP_RTD; // 010+
 pub const OP_RTE_32 : u32 = OP_RTE;
 pub const OP_RTR_32 : u32 = OP_RTR;
 pub const OP_RTS_32 : u32 = OP_RTS;
@@ -1730,6 +1931,69 @@ pub const OP_TAS_8_IX    : u32 = OP_TAS | OPER_IX;
 pub const OP_TAS_8_AW    : u32 = OP_TAS | OPER_AW;
 pub const OP_TAS_8_AL    : u32 = OP_TAS | OPER_AL;
 
+// Put constants for TBLS, TBLU, TBLSN and TBLUN here (CPU32)
+// The register form interpolates between Dm (in the opcode) and Dn
+pub const OP_TBL_DN    : u32 = OP_TBL | OPER_DN;
+pub const OP_TBL_AI    : u32 = OP_TBL | OPER_AI;
+pub const OP_TBL_DI    : u32 = OP_TBL | OPER_DI;
+pub const OP_TBL_IX    : u32 = OP_TBL | OPER_IX;
+pub const OP_TBL_AW    : u32 = OP_TBL | OPER_AW;
+pub const OP_TBL_AL    : u32 = OP_TBL | OPER_AL;
+pub const OP_TBL_PCDI  : u32 = OP_TBL | OPER_PCDI;
+pub const OP_TBL_PCIX  : u32 = OP_TBL | OPER_PCIX;
+
+// Put constants for TRAPcc here (020+)
+pub const OP_TRAPT_16   : u32 = OP_TRAP_CC | IF_T  | TRAPCC_WORD;
+pub const OP_TRAPF_16   : u32 = OP_TRAP_CC | IF_F  | TRAPCC_WORD;
+pub const OP_TRAPHI_16  : u32 = OP_TRAP_CC | IF_HI | TRAPCC_WORD;
+pub const OP_TRAPLS_16  : u32 = OP_TRAP_CC | IF_LS | TRAPCC_WORD;
+pub const OP_TRAPCC_16  : u32 = OP_TRAP_CC | IF_CC | TRAPCC_WORD;
+pub const OP_TRAPCS_16  : u32 = OP_TRAP_CC | IF_CS | TRAPCC_WORD;
+pub const OP_TRAPNE_16  : u32 = OP_TRAP_CC | IF_NE | TRAPCC_WORD;
+pub const OP_TRAPEQ_16  : u32 = OP_TRAP_CC | IF_EQ | TRAPCC_WORD;
+pub const OP_TRAPVC_16  : u32 = OP_TRAP_CC | IF_VC | TRAPCC_WORD;
+pub const OP_TRAPVS_16  : u32 = OP_TRAP_CC | IF_VS | TRAPCC_WORD;
+pub const OP_TRAPPL_16  : u32 = OP_TRAP_CC | IF_PL | TRAPCC_WORD;
+pub const OP_TRAPMI_16  : u32 = OP_TRAP_CC | IF_MI | TRAPCC_WORD;
+pub const OP_TRAPGE_16  : u32 = OP_TRAP_CC | IF_GE | TRAPCC_WORD;
+pub const OP_TRAPLT_16  : u32 = OP_TRAP_CC | IF_LT | TRAPCC_WORD;
+pub const OP_TRAPGT_16  : u32 = OP_TRAP_CC | IF_GT | TRAPCC_WORD;
+pub const OP_TRAPLE_16  : u32 = OP_TRAP_CC | IF_LE | TRAPCC_WORD;
+
+pub const OP_TRAPT_32   : u32 = OP_TRAP_CC | IF_T  | TRAPCC_LONG;
+pub const OP_TRAPF_32   : u32 = OP_TRAP_CC | IF_F  | TRAPCC_LONG;
+pub const OP_TRAPHI_32  : u32 = OP_TRAP_CC | IF_HI | TRAPCC_LONG;
+pub const OP_TRAPLS_32  : u32 = OP_TRAP_CC | IF_LS | TRAPCC_LONG;
+pub const OP_TRAPCC_32  : u32 = OP_TRAP_CC | IF_CC | TRAPCC_LONG;
+pub const OP_TRAPCS_32  : u32 = OP_TRAP_CC | IF_CS | TRAPCC_LONG;
+pub const OP_TRAPNE_32  : u32 = OP_TRAP_CC | IF_NE | TRAPCC_LONG;
+pub const OP_TRAPEQ_32  : u32 = OP_TRAP_CC | IF_EQ | TRAPCC_LONG;
+pub const OP_TRAPVC_32  : u32 = OP_TRAP_CC | IF_VC | TRAPCC_LONG;
+pub const OP_TRAPVS_32  : u32 = OP_TRAP_CC | IF_VS | TRAPCC_LONG;
+pub const OP_TRAPPL_32  : u32 = OP_TRAP_CC | IF_PL | TRAPCC_LONG;
+pub const OP_TRAPMI_32  : u32 = OP_TRAP_CC | IF_MI | TRAPCC_LONG;
+pub const OP_TRAPGE_32  : u32 = OP_TRAP_CC | IF_GE | TRAPCC_LONG;
+pub const OP_TRAPLT_32  : u32 = OP_TRAP_CC | IF_LT | TRAPCC_LONG;
+pub const OP_TRAPGT_32  : u32 = OP_TRAP_CC | IF_GT | TRAPCC_LONG;
+pub const OP_TRAPLE_32  : u32 = OP_TRAP_CC | IF_LE | TRAPCC_LONG;
+
+pub const OP_TRAPT      : u32 = OP_TRAP_CC | IF_T  | TRAPCC_NONE;
+pub const OP_TRAPF      : u32 = OP_TRAP_CC | IF_F  | TRAPCC_NONE;
+pub const OP_TRAPHI     : u32 = OP_TRAP_CC | IF_HI | TRAPCC_NONE;
+pub const OP_TRAPLS     : u32 = OP_TRAP_CC | IF_LS | TRAPCC_NONE;
+pub const OP_TRAPCC     : u32 = OP_TRAP_CC | IF_CC | TRAPCC_NONE;
+pub const OP_TRAPCS     : u32 = OP_TRAP_CC | IF_CS | TRAPCC_NONE;
+pub const OP_TRAPNE     : u32 = OP_TRAP_CC | IF_NE | TRAPCC_NONE;
+pub const OP_TRAPEQ     : u32 = OP_TRAP_CC | IF_EQ | TRAPCC_NONE;
+pub const OP_TRAPVC     : u32 = OP_TRAP_CC | IF_VC | TRAPCC_NONE;
+pub const OP_TRAPVS     : u32 = OP_TRAP_CC | IF_VS | TRAPCC_NONE;
+pub const OP_TRAPPL     : u32 = OP_TRAP_CC | IF_PL | TRAPCC_NONE;
+pub const OP_TRAPMI     : u32 = OP_TRAP_CC | IF_MI | TRAPCC_NONE;
+pub const OP_TRAPGE     : u32 = OP_TRAP_CC | IF_GE | TRAPCC_NONE;
+pub const OP_TRAPLT     : u32 = OP_TRAP_CC | IF_LT | TRAPCC_NONE;
+pub const OP_TRAPGT     : u32 = OP_TRAP_CC | IF_GT | TRAPCC_NONE;
+pub const OP_TRAPLE     : u32 = OP_TRAP_CC | IF_LE | TRAPCC_NONE;
+
 // Put constants for TST here
 pub const OP_TST_8_DN   : u32 = OP_TST | BYTE_SIZED | OPER_DN;
 pub const OP_TST_8_AI   : u32 = OP_TST | BYTE_SIZED | OPER_AI;
@@ -1772,6 +2036,10 @@ pub const OP_TST_32_IMM  : u32 = OP_TST | LONG_SIZED | OPER_IMM;  // NOT MC68000
 // Put constants for UNLK here
 pub const OP_UNLK_32     : u32 = OP_UNLK;
 
+// Put constants for UNPK here (020+)
+pub const OP_UNPK_16_RR  : u32 = OP_UNPK | RR_MODE;
+pub const OP_UNPK_16_MM  : u32 = OP_UNPK | MM_MODE;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2309,6 +2577,26 @@ mod tests {
         assert_eq!(0x4e72, OP_STOP);
     }
     #[test]
+    fn correctly_defined_op_rtd_32() {
+        assert_eq!(0x4e74, OP_RTD_32);
+    }
+    #[test]
+    fn correctly_defined_op_movec_32() {
+        assert_eq!(0x4e7a, OP_MOVEC_32_CR);
+        assert_eq!(0x4e7b, OP_MOVEC_32_RC);
+    }
+    #[test]
+    fn correctly_defined_op_moves() {
+        assert_eq!(0x0e10, OP_MOVES_8_AI);
+        assert_eq!(0x0e68, OP_MOVES_16_DI);
+        assert_eq!(0x0eb9, OP_MOVES_32_AL);
+    }
+    #[test]
+    fn correctly_defined_op_move_16_frc() {
+        assert_eq!(0x42c0, OP_MOVE_16_FRC_DN);
+        assert_eq!(0x42f9, OP_MOVE_16_FRC_AL);
+    }
+    #[test]
     fn correctly_defined_op_rte_32() {
         assert_eq!(0x4e73, OP_RTE_32);
     }
@@ -2364,4 +2652,70 @@ mod tests {
     fn correctly_defined_op_tst_32_ix() {
         assert_eq!(0x4ab0, OP_TST_32_IX);
     }
+    #[test]
+    fn correctly_defined_op_bfextu_32_dn() {
+        assert_eq!(0xe9c0, OP_BFEXTU_32_DN);
+    }
+    #[test]
+    fn correctly_defined_op_bfins_32_al() {
+        assert_eq!(0xeff9, OP_BFINS_32_AL);
+    }
+    #[test]
+    fn correctly_defined_op_cas_8_ai() {
+        assert_eq!(0x0ad0, OP_CAS_8_AI);
+    }
+    #[test]
+    fn correctly_defined_op_cas2_32() {
+        assert_eq!(0x0efc, OP_CAS2_32);
+    }
+    #[test]
+    fn correctly_defined_op_chk_32_dn() {
+        assert_eq!(0x4100, OP_CHK_32_DN);
+    }
+    #[test]
+    fn correctly_defined_op_chk2cmp2_16_pcdi() {
+        assert_eq!(0x02fa, OP_CHK2CMP2_16_PCDI);
+    }
+    #[test]
+    fn correctly_defined_op_divl_32_dn() {
+        assert_eq!(0x4c40, OP_DIVL_32_DN);
+    }
+    #[test]
+    fn correctly_defined_op_ext_bl() {
+        assert_eq!(0x49c0, OP_EXT_BL);
+    }
+    #[test]
+    fn correctly_defined_op_link_32() {
+        assert_eq!(0x4808, OP_LINK_32);
+    }
+    #[test]
+    fn correctly_defined_op_mull_32_imm() {
+        assert_eq!(0x4c3c, OP_MULL_32_IMM);
+    }
+    #[test]
+    fn correctly_defined_op_pack_16_mm() {
+        assert_eq!(0x8148, OP_PACK_16_MM);
+    }
+    #[test]
+    fn correctly_defined_op_trapne_16() {
+        assert_eq!(0x56fa, OP_TRAPNE_16);
+    }
+    #[test]
+    fn correctly_defined_op_trapf() {
+        assert_eq!(0x51fc, OP_TRAPF);
+    }
+    #[test]
+    fn correctly_defined_op_tbl() {
+        assert_eq!(0xf800, OP_TBL_DN);
+        assert_eq!(0xf828, OP_TBL_DI);
+        assert_eq!(0xf83b, OP_TBL_PCIX);
+    }
+    #[test]
+    fn correctly_defined_op_bgnd() {
+        assert_eq!(0x4afa, OP_BGND);
+    }
+    #[test]
+    fn correctly_defined_op_unpk_16_rr() {
+        assert_eq!(0x8180, OP_UNPK_16_RR);
+    }
 }
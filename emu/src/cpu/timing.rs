@@ -0,0 +1,359 @@
+// The cycle exact timing mode (with the "cycles" feature). Instructions
+// and exceptions take the clock periods of the MC68000UM tables (those
+// Musashi uses), each bus cycle of which takes four, stretched by the
+// wait states the bus asks for before asserting DTACK. The bus is asked
+// about every bus cycle the core runs, with the clock period it starts
+// at, so that it can also hold the processor off while another master
+// (such as a video chip) has the bus.
+//
+// The clock period a bus cycle starts at is approximate: the bus cycles
+// of an instruction are placed back to back from its start, with its
+// internal operations after them, rather than in the order the MC68000UM
+// gives for each instruction. With fixed wait states per region the
+// instruction lengths are exact, but a peripheral stealing cycles on a
+// schedule may see a cycle a few clock periods before it happens.
+use ram::{AddressBus, AddressSpace};
+use savestate::{self, Snapshot, StateReader, StateWriter};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BusTiming {
+    clock: u64, // clock periods before the current instruction
+    bus_cycles: u32, // run by the current instruction so far
+    wait_states: u32, // inserted into them
+}
+
+impl BusTiming {
+    pub fn new() -> BusTiming {
+        BusTiming { clock: 0, bus_cycles: 0, wait_states: 0 }
+    }
+    // clock periods since the timing was enabled, up to the current
+    // instruction
+    pub fn clock(&self) -> u64 {
+        self.clock
+    }
+    // Approximate: bus cycles run back to back from the start of the
+    // instruction, as the clock periods of internal operations aren't
+    // known until it ends
+    fn next_cycle(&self) -> u64 {
+        self.clock + u64::from(4 * self.bus_cycles + self.wait_states)
+    }
+    pub(super) fn bus_cycles<A: AddressBus>(&mut self, mem: &mut A, address_space: AddressSpace, address: u32, size: u32) {
+        // the 68000 moves a long word in two bus cycles
+        for word in 0..size.div_ceil(2) {
+            let clock = self.next_cycle();
+            self.wait_states += mem.wait_states(address_space, address.wrapping_add(2 * word), clock);
+            self.bus_cycles += 1;
+        }
+    }
+    // Table 8-14 assumes four clock periods for the interrupt acknowledge
+    // cycle. An autovectored one is terminated by VPA, and transfers the
+    // vector in step with the E clock, a tenth of the processor clock.
+    pub(super) fn interrupt_acknowledge(&mut self, autovectored: bool) {
+        if autovectored {
+            let clock = self.next_cycle();
+            self.wait_states += vpa_wait_states(clock);
+        }
+        self.bus_cycles += 1;
+    }
+    // Ends the instruction (or exception) that took the given clock
+    // periods with no wait states, and returns those it took with them
+    pub(super) fn finish(&mut self, cycles: i32) -> i32 {
+        let cycles = cycles + self.wait_states as i32;
+        self.clock += cycles as u64;
+        self.bus_cycles = 0;
+        self.wait_states = 0;
+        cycles
+    }
+    // the processor sat stopped, or halted, for the given clock periods
+    #[cfg(feature = "cycles")]
+    pub(super) fn idle(&mut self, cycles: i32) {
+        self.clock += cycles as u64;
+    }
+}
+
+// A VPA terminated cycle waits from S4 for the next E period (which are
+// taken to start with the timing), and ends with it. It takes 14 to 23
+// clock periods rather than four.
+fn vpa_wait_states(clock: u64) -> u32 {
+    let e_period = (clock + 4).div_ceil(10) * 10;
+    (e_period + 10 - clock - 4) as u32
+}
+
+// The clock periods of DIVU without those of the effective address, at
+// most 136 (the MC68000UM gives less than 140). After a quick check for
+// overflow, each of the 15 steps of the division takes longer for a
+// quotient bit that comes out zero.
+pub fn divu_cycles(dividend: u32, divisor: u16) -> i32 {
+    let divisor = u32::from(divisor) << 16;
+    if dividend >= divisor {
+        return 10;
+    }
+    let mut dividend = dividend;
+    let mut cycles = 76;
+    for _ in 0..15 {
+        let carry = dividend & 0x8000_0000 != 0;
+        dividend <<= 1;
+        if carry {
+            dividend = dividend.wrapping_sub(divisor);
+        } else {
+            cycles += 4;
+            if dividend >= divisor {
+                dividend -= divisor;
+                cycles -= 2;
+            }
+        }
+    }
+    cycles
+}
+
+// The clock periods of DIVS without those of the effective address, at
+// most 156 (the MC68000UM gives less than 158). Negative operands take
+// longer, as do zeros among the 15 high bits of the absolute quotient.
+pub fn divs_cycles(dividend: i32, divisor: i16) -> i32 {
+    let mut cycles = if dividend < 0 { 14 } else { 12 };
+    let abs_dividend = dividend.unsigned_abs();
+    let abs_divisor = u32::from(divisor.unsigned_abs());
+    if abs_dividend >> 16 >= abs_divisor {
+        return cycles + 4;
+    }
+    cycles += 110;
+    if divisor >= 0 {
+        cycles = if dividend >= 0 { cycles - 2 } else { cycles + 2 };
+    }
+    let quotient = abs_dividend / abs_divisor;
+    for bit in 0..15 {
+        if quotient & (0x8000 >> bit) == 0 {
+            cycles += 2;
+        }
+    }
+    cycles
+}
+
+impl Snapshot for BusTiming {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u32((self.clock >> 32) as u32);
+        writer.put_u32(self.clock as u32);
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        let high = u64::from(reader.get_u32()?);
+        self.clock = high << 32 | u64::from(reader.get_u32()?);
+        self.bus_cycles = 0;
+        self.wait_states = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{divs_cycles, divu_cycles, vpa_wait_states};
+
+    #[test]
+    fn divu_takes_longest_for_zero_quotient_bits() {
+        assert_eq!(136, divu_cycles(0, 1));
+        assert_eq!(136, divu_cycles(5, 7));
+        assert!(divu_cycles(0xfffe_ffff, 0xffff) < 136);
+    }
+    #[test]
+    fn divu_stops_early_on_overflow() {
+        assert_eq!(10, divu_cycles(0x0001_0000, 1));
+    }
+    #[test]
+    fn divu_and_divs_stay_within_the_published_bounds() {
+        for &(dividend, divisor) in &[(0u32, 1u16), (1, 1), (0x1234_5678, 0x5678), (0x7fff_ffff, 0xffff), (0x8000_0000, 0x8001), (1000, 3), (0xffff, 0x100)] {
+            let divu = divu_cycles(dividend, divisor);
+            assert!(divu == 10 || (76..140).contains(&divu), "DIVU {:08x}/{:04x}: {}", dividend, divisor, divu);
+            let divs = divs_cycles(dividend as i32, divisor as i16);
+            assert!(divs <= 18 || (120..158).contains(&divs), "DIVS {:08x}/{:04x}: {}", dividend, divisor, divs);
+            assert_eq!(0, divu % 2);
+            assert_eq!(0, divs % 2);
+        }
+    }
+    #[test]
+    fn divs_takes_longer_for_negative_operands() {
+        assert_eq!(150, divs_cycles(1, 2));
+        assert_eq!(152, divs_cycles(1, -2));
+        assert_eq!(156, divs_cycles(-1, 2));
+        assert_eq!(120, divs_cycles(0xfffe, 1));
+        assert_eq!(18, divs_cycles(-0x10000, 1));
+    }
+    #[test]
+    fn vpa_cycles_wait_for_the_e_clock() {
+        let waits: Vec<u32> = (0..10).map(vpa_wait_states).collect();
+        assert_eq!(vec![16, 15, 14, 13, 12, 11, 10, 19, 18, 17], waits);
+    }
+
+    #[cfg(feature = "cycles")]
+    mod core {
+        use cpu::{ConfiguredCore, Cycles, ProcessingState};
+        use interrupts::AutoInterruptController;
+        use ram::{MappedBus, AddressBus, SUPERVISOR_DATA};
+        use ram::mappedbus::{Device, Size};
+        use ram::BusResult;
+        use ram::AddressSpace;
+        use super::super::vpa_wait_states;
+        use std::sync::{Arc, Mutex};
+
+        type BusCore = ConfiguredCore<AutoInterruptController, MappedBus>;
+
+        // RAM from 0, with the stack at $8000, code at $1000, A0 at $2000
+        // and A1 at $2100
+        fn core_with(code: &[u16], wait_states: u32) -> BusCore {
+            let mut bus = MappedBus::new();
+            bus.map_ram(0, 0x10000);
+            bus.set_wait_states(0, wait_states);
+            bus.write_long(SUPERVISOR_DATA, 0, 0x8000);
+            bus.write_long(SUPERVISOR_DATA, 4, 0x1000);
+            for (i, &word) in code.iter().enumerate() {
+                bus.write_word(SUPERVISOR_DATA, 0x1000 + 2 * i as u32, u32::from(word));
+            }
+            let mut core = ConfiguredCore::new_with(0, AutoInterruptController::new(), bus);
+            core.enable_bus_timing();
+            core.reset();
+            core.dar[8] = 0x2000;
+            core.dar[9] = 0x2100;
+            core
+        }
+
+        #[test]
+        fn reset_takes_40_clock_periods() {
+            let core = core_with(&[0x4e71], 0);
+            assert_eq!(ProcessingState::Normal, core.processing_state);
+            assert_eq!(40, core.bus_timing.as_ref().unwrap().clock());
+        }
+
+        // clock periods (with reads and writes) from MC68000UM, chapter 8
+        const PUBLISHED: &[(&str, &[u16], i32, i32)] = &[
+            ("NOP", &[0x4e71], 4, 1),
+            ("MOVE.L D0,D1", &[0x2200], 4, 1),
+            ("MOVE.W (A0),(A1)", &[0x3290], 12, 3),
+            ("MOVE.L D0,(A1)+", &[0x22c0], 12, 3),
+            ("ADDI.L #$12345678,D0", &[0x0680, 0x1234, 0x5678], 16, 3),
+            ("LEA (A0),A2", &[0x45d0], 4, 1),
+            ("MOVEM.W (A0),D0-D3", &[0x4c90, 0x000f], 12 + 4 * 4, 7),
+            ("MOVEM.L (A0)+,D0-D1", &[0x4cd8, 0x0003], 12 + 8 * 2, 7),
+            ("MOVEM.L D0-D3,-(A7)", &[0x48e7, 0xf000], 8 + 8 * 4, 10),
+        ];
+
+        #[test]
+        fn instructions_take_the_published_clock_periods() {
+            for &(asm, code, cycles, _) in PUBLISHED {
+                let mut core = core_with(code, 0);
+                assert_eq!(Cycles(cycles), core.execute1(), "{}", asm);
+            }
+        }
+        #[test]
+        fn wait_states_stretch_every_bus_cycle() {
+            for &(asm, code, cycles, bus_cycles) in PUBLISHED {
+                let mut core = core_with(code, 2);
+                assert_eq!(Cycles(cycles + 2 * bus_cycles), core.execute1(), "{}", asm);
+            }
+        }
+        #[test]
+        fn the_clock_keeps_counting() {
+            // reset reads four words too
+            let mut core = core_with(&[0x4e71, 0x3290, 0x4e71], 1);
+            assert_eq!(40 + 4, core.bus_timing.as_ref().unwrap().clock());
+            core.execute1();
+            core.execute1();
+            assert_eq!(44 + 5 + 15, core.bus_timing.as_ref().unwrap().clock());
+            assert_eq!(Cycles(5), core.execute1());
+            assert_eq!(44 + 5 + 15 + 5, core.bus_timing.as_ref().unwrap().clock());
+        }
+        #[test]
+        fn a_stopped_processor_keeps_time() {
+            // STOP #$2700
+            let mut core = core_with(&[0x4e72, 0x2700], 0);
+            let (Cycles(used), _) = core.run(1000);
+            assert_eq!(1000, used);
+            assert_eq!(40 + 1000, core.bus_timing.as_ref().unwrap().clock());
+        }
+        #[test]
+        fn divu_takes_data_dependent_time() {
+            // DIVU.W D1,D0
+            let mut core = core_with(&[0x80c1], 0);
+            core.dar[0] = 5;
+            core.dar[1] = 7;
+            assert_eq!(Cycles(136), core.execute1());
+            let mut core = core_with(&[0x80c1], 0);
+            core.dar[0] = 0x0001_0000;
+            core.dar[1] = 1;
+            assert_eq!(Cycles(10), core.execute1());
+        }
+        #[test]
+        fn divs_takes_data_dependent_time() {
+            // DIVS.W (A0),D0, with the EA adding four clock periods
+            let mut core = core_with(&[0x81d0], 0);
+            core.dar[0] = 0xffff_ffff;
+            core.mem.write_word(SUPERVISOR_DATA, 0x2000, 2);
+            assert_eq!(Cycles(156 + 4), core.execute1());
+        }
+        #[test]
+        fn chk_and_zero_divide_traps_include_the_effective_address() {
+            // CHK.W (A0),D0 and DIVU.W (A0),D0 trapping through vectors
+            // pointing at a NOP
+            let mut core = core_with(&[0x4190], 0);
+            core.mem.write_long(SUPERVISOR_DATA, 6 * 4, 0x1100);
+            core.dar[0] = 0xffff;
+            assert_eq!(Cycles(40 + 4), core.execute1());
+            let mut core = core_with(&[0x80d0], 0);
+            core.mem.write_long(SUPERVISOR_DATA, 5 * 4, 0x1100);
+            assert_eq!(Cycles(38 + 4), core.execute1());
+        }
+        #[test]
+        fn autovectored_interrupts_wait_for_the_e_clock() {
+            let mut seen = Vec::new();
+            for nops in 0..5 {
+                let mut core = core_with(&[0x4e71; 8], 0);
+                core.mem.write_long(SUPERVISOR_DATA, 25 * 4, 0x1100);
+                core.execute(4 * nops);
+                core.sr_to_flags(0x2000);
+                core.int_ctrl.request_interrupt(1);
+                let clock = core.bus_timing.as_ref().unwrap().clock();
+                let Cycles(cycles) = core.execute1();
+                assert_eq!(44 + vpa_wait_states(clock) as i32, cycles);
+                assert!((54..64).contains(&cycles));
+                seen.push(cycles);
+            }
+            seen.dedup();
+            assert!(seen.len() > 1);
+        }
+
+        // a video chip that has the bus for the first 100 clock periods
+        struct Video(Arc<Mutex<Vec<u64>>>);
+
+        impl Device for Video {
+            fn read(&mut self, _: AddressSpace, _: u32, _: Size) -> BusResult<u32> {
+                Ok(0)
+            }
+            fn write(&mut self, _: AddressSpace, _: u32, _: Size, _: u32) -> BusResult<()> {
+                Ok(())
+            }
+            fn wait_states(&mut self, _offset: u32, clock: u64) -> u32 {
+                self.0.lock().unwrap().push(clock);
+                if clock < 100 { (100 - clock) as u32 } else { 0 }
+            }
+        }
+
+        #[test]
+        fn a_device_sees_each_bus_cycle_and_can_steal_clock_periods() {
+            // MOVE.W D0,$ff0000, 16(3/1)
+            let mut core = core_with(&[0x33c0, 0x00ff, 0x0000, 0x33c0, 0x00ff, 0x0000], 0);
+            let clocks = Arc::new(Mutex::new(Vec::new()));
+            core.mem.map_device(0xff0000, 2, Box::new(Video(clocks.clone())));
+            // opcode and address at 40, 44 and 48, then the write
+            assert_eq!(Cycles(16 + 48), core.execute1());
+            assert_eq!(vec![52], *clocks.lock().unwrap());
+            assert_eq!(Cycles(16), core.execute1());
+            assert_eq!(vec![52, 116], *clocks.lock().unwrap());
+        }
+        #[test]
+        fn state_includes_the_clock() {
+            let mut core = core_with(&[0x4e71, 0x4e71], 0);
+            core.execute1();
+            let state = core.save_state();
+            core.execute1();
+            core.load_state(&state).unwrap();
+            assert_eq!(44, core.bus_timing.as_ref().unwrap().clock());
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// Per-instruction hooks (see Callbacks::before_instruction) and a trace
+// logger built on them, writing one line per instruction in the format of
+// Musashi's example cpu_instr_callback:
+//
+//     E 400: 303c 0010           : MOVE.W #$0010,D0
+use std::io::{self, Write};
+use cpu::{Callbacks, ConfiguredCore, Core, Cycles, Exception, Result};
+use cpu::debugger::Register;
+use interrupts::InterruptController;
+use ram::{AddressBus, SUPERVISOR_PROGRAM, USER_PROGRAM};
+use r68k_tools::disassembler::Disassembler;
+use r68k_tools::memory::{Memory, MemoryVec};
+use r68k_tools::PC;
+
+// the longest 68000 instruction: an opcode and four extension words
+const MAX_INSTRUCTION_BYTES: u32 = 10;
+// and on the 68020, with full format extension words on both operands
+const MAX_INSTRUCTION_BYTES_020: u32 = 22;
+
+const REGISTERS: [Register; 18] = [
+    Register::D(0), Register::D(1), Register::D(2), Register::D(3),
+    Register::D(4), Register::D(5), Register::D(6), Register::D(7),
+    Register::A(0), Register::A(1), Register::A(2), Register::A(3),
+    Register::A(4), Register::A(5), Register::A(6), Register::A(7),
+    Register::Pc, Register::Sr,
+];
+
+// The registers visible to a program, as of some point in time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Registers {
+    pub dar: [u32; 16],
+    pub pc: u32,
+    pub sr: u16,
+}
+
+impl Registers {
+    pub fn get(&self, register: Register) -> u32 {
+        match register {
+            Register::D(reg) => self.dar[reg & 7],
+            Register::A(reg) => self.dar[8 + (reg & 7)],
+            Register::Pc => self.pc,
+            Register::Sr => u32::from(self.sr),
+        }
+    }
+    // the registers that differ in after, as (register, before, after)
+    pub fn changes<'a>(&'a self, after: &'a Registers) -> impl Iterator<Item=(Register, u32, u32)> + 'a {
+        REGISTERS.iter()
+            .map(move |&register| (register, self.get(register), after.get(register)))
+            .filter(|&(_, before, after)| before != after)
+    }
+}
+
+pub struct TraceLogger<W: Write> {
+    out: W,
+    disassembler: Disassembler<'static>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TraceLogger<W> {
+    pub fn new(out: W) -> TraceLogger<W> {
+        TraceLogger { out, disassembler: Disassembler::new(), error: None }
+    }
+    // Logging stops at the first write error, which is returned here
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.out),
+        }
+    }
+    fn trace_line<T: InterruptController, A: AddressBus>(&self, core: &mut ConfiguredCore<T, A>, pc: u32, ir: u16) -> String {
+        let address_space = if core.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        let max_bytes = if core.cpu_type().is_020() {MAX_INSTRUCTION_BYTES_020} else {MAX_INSTRUCTION_BYTES};
+        // peeked through the PMMU, so that tracing changes nothing, up to
+        // the first byte that can't be read
+        let bytes = (0..max_bytes).map_while(|offset| core.peek_byte(address_space, pc.wrapping_add(offset)).ok().map(|byte| byte as u8)).collect();
+        let mem = MemoryVec::new8(PC(pc), bytes);
+        let (words, instruction) = match self.disassembler.disassemble(PC(pc), &mem) {
+            Ok((next, instruction)) => {
+                let words: Vec<String> = mem.data()[..(next.0 - pc) as usize].chunks(2).map(|word| format!("{:02x}{:02x}", word[0], word[1])).collect();
+                (words.join(" "), instruction.to_string().replace('\t', " "))
+            },
+            // like Musashi's disassembler
+            Err(_) => (format!("{:04x}", ir), format!("dc.w ${:04x}; ILLEGAL", ir)),
+        };
+        format!("E {:03x}: {:<20}: {}", pc, words, instruction)
+    }
+}
+
+impl<W: Write> Callbacks for TraceLogger<W> {
+    const INSTRUCTION_HOOKS: bool = true;
+
+    fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+        Err(ex)
+    }
+    fn before_instruction<T: InterruptController, A: AddressBus>(&mut self, core: &mut ConfiguredCore<T, A>, pc: u32, ir: u16) {
+        if self.error.is_none() {
+            let line = self.trace_line(core, pc, ir);
+            if let Err(error) = writeln!(self.out, "{}", line) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Registers, TraceLogger};
+    use cpu::{Callbacks, ConfiguredCore, Core, Cycles, Exception, Result, TestCore};
+    use cpu::debugger::Register;
+    use interrupts::InterruptController;
+    use ram::AddressBus;
+
+    #[test]
+    fn logs_like_musashi() {
+        // 303c,0010 is MOVE.W #$10, D0, 4e71 is NOP and 4afc is ILLEGAL
+        let mut cpu = TestCore::new_mem(0x400, &[0x30, 0x3c, 0x00, 0x10, 0x4e, 0x71, 0x4a, 0xfc]);
+        let mut logger = TraceLogger::new(Vec::new());
+        for _ in 0..3 {
+            cpu.execute_with_state(1, &mut logger);
+        }
+        let log = String::from_utf8(logger.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!("E 400: 303c 0010           : MOVE.W #$0010,D0", lines[0]);
+        assert_eq!("E 404: 4e71                : NOP", lines[1]);
+        assert_eq!("E 406: 4afc                : ILLEGAL", lines[2]);
+    }
+
+    #[test]
+    fn tracing_leaves_memory_alone() {
+        // 2038,1234 is MOVE.L ($1234).W, D0
+        let program = [0x20, 0x38, 0x12, 0x34];
+        let mut untraced = TestCore::new_mem(0x400, &program);
+        untraced.execute1();
+        let mut traced = TestCore::new_mem(0x400, &program);
+        traced.execute_with_state(1, &mut TraceLogger::new(Vec::new()));
+        assert_eq!(untraced.mem.logger.ops(), traced.mem.logger.ops());
+    }
+
+    struct ChangeRecorder {
+        changes: Vec<(Register, u32, u32)>,
+        before: Vec<(u32, u16)>,
+    }
+    impl Callbacks for ChangeRecorder {
+        const INSTRUCTION_HOOKS: bool = true;
+
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn before_instruction<T: InterruptController, A: AddressBus>(&mut self, _: &mut ConfiguredCore<T, A>, pc: u32, ir: u16) {
+            self.before.push((pc, ir));
+        }
+        fn after_instruction<T: InterruptController, A: AddressBus>(&mut self, _: &mut ConfiguredCore<T, A>, before: &Registers, after: &Registers, _: Cycles) {
+            self.changes.extend(before.changes(after));
+        }
+    }
+
+    #[test]
+    fn hooks_see_each_instruction_and_its_changes() {
+        // 7005 is MOVEQ #5, D0 and 5281 is ADDQ.L #1, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0x70, 0x05, 0x52, 0x81]);
+        let mut recorder = ChangeRecorder { changes: Vec::new(), before: Vec::new() };
+        cpu.execute_with_state(1, &mut recorder);
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![(0x40, 0x7005), (0x42, 0x5281)], recorder.before);
+        assert_eq!(vec![
+            (Register::D(0), 0, 5), (Register::Pc, 0x40, 0x42),
+            (Register::D(1), 0, 1), (Register::Pc, 0x42, 0x44),
+        ], recorder.changes);
+    }
+}
@@ -0,0 +1,470 @@
+// The simulator I/O of EASy68K, for the teaching and test programs written
+// for it. Such a program calls TRAP #15 with a task number in D0 for text
+// and number I/O on the console, for file I/O and for the time, and ends
+// with task 9 or SIMHALT. A Simulator services those as Callbacks, and
+// run executes a program, as load reads it from the S-records EASy68K
+// assembles, to completion.
+//
+// The console is a HostPort, so input is line buffered by the terminal:
+// task 5 gets a key once its line is entered, and the keyboard echo of
+// task 12 is left to the terminal. Graphics, sound, the mouse and the
+// dialogs of the Windows simulator are not supported, nor is the printer.
+use cpu::{Callbacks, Core, Cycles, Exception, Result};
+use cpu::{EXCEPTION_TRAP_BASE, EXCEPTION_UNIMPLEMENTED_1111};
+use hosted::{self, bytes_at, in_memory, string_at, write_bytes, Ending, Host, HostedCore};
+use peripherals::serial::HostPort;
+use r68k_tools::srecords::read_s68;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use hosted::{run, Termination};
+
+const SIMHALT: u16 = 0xffff; // the two words of it
+const MAX_STRING: usize = 80; // read by task 2
+const MAX_TEXT: u32 = 0x1_0000; // of a NUL terminated string to display
+
+// results of the file tasks, in D0.W
+const SUCCESS: u32 = 0;
+const END_OF_FILE: u32 = 1;
+const ERROR: u32 = 2;
+const READ_ONLY: u32 = 3;
+
+pub struct Simulator {
+    console: HostPort,
+    directory: PathBuf,
+    files: Vec<Option<File>>, // indexed by file ID
+    ending: Ending,
+}
+
+impl Simulator {
+    pub fn new(console: HostPort) -> Simulator {
+        Simulator { console, directory: PathBuf::from("."), files: Vec::new(), ending: Ending::default() }
+    }
+
+    // Relative file names are taken from here, the current directory
+    // unless set
+    pub fn set_directory(&mut self, directory: &Path) {
+        self.directory = directory.to_path_buf();
+    }
+
+    fn print(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.console.send(byte);
+        }
+    }
+
+    // a line of input, without its line ending, or what there was of it
+    // when the input ended
+    fn read_line(&mut self) -> Vec<u8> {
+        let mut line = Vec::new();
+        while let Some(byte) = self.console.receive_waiting() {
+            if byte == b'\n' {
+                break;
+            }
+            line.push(byte);
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        line
+    }
+
+    // a number, as task 4 reads it; anything else reads as 0
+    fn read_number(&mut self) -> u32 {
+        let line = self.read_line();
+        String::from_utf8_lossy(&line).trim().parse::<i32>().unwrap_or(0) as u32
+    }
+
+    fn open(&mut self, file: File) -> u32 {
+        let free = self.files.iter().position(|f| f.is_none());
+        let id = free.unwrap_or(self.files.len());
+        if id == self.files.len() {
+            self.files.push(None);
+        }
+        self.files[id] = Some(file);
+        id as u32
+    }
+
+    fn file(&mut self, id: u32) -> Option<&mut File> {
+        self.files.get_mut(id as usize).and_then(|f| f.as_mut())
+    }
+
+    fn path(&self, core: &mut impl Core) -> Result<PathBuf> {
+        let a1 = core.dar()[9];
+        let name = string_at(core, a1, MAX_TEXT)?;
+        Ok(self.directory.join(String::from_utf8_lossy(&name).as_ref()))
+    }
+
+    // Services a TRAP #15 task, returning whether it is supported
+    fn task(&mut self, core: &mut impl Core, task: u8) -> Result<bool> {
+        let d1 = core.dar()[1];
+        let d2 = core.dar()[2];
+        let a1 = core.dar()[9];
+        match task {
+            // display a string of D1.W bytes, with and without a new line
+            0 | 1 => {
+                let len = d1 & 0xffff;
+                let text = bytes_at(core, a1, len.min(255))?;
+                self.print(&text);
+                if task == 0 {
+                    self.print(b"\r\n");
+                }
+            },
+            // read a string, NUL terminated, and its length into D1.W
+            2 => {
+                let mut line = self.read_line();
+                line.truncate(MAX_STRING);
+                let len = line.len() as u32;
+                line.push(0);
+                write_bytes(core, a1, &line)?;
+                core.dar()[1] = d1 & 0xffff_0000 | len;
+            },
+            3 => self.print(format!("{}", d1 as i32).as_bytes()),
+            4 => core.dar()[1] = self.read_number(),
+            // read a character into D1.B
+            5 => {
+                let key = self.console.receive_waiting().unwrap_or(0);
+                core.dar()[1] = d1 & 0xffff_ff00 | u32::from(key);
+            },
+            6 => self.print(&[d1 as u8]),
+            // whether a key is waiting, in D1.B
+            7 => {
+                let pending = self.console.has_input();
+                core.dar()[1] = d1 & 0xffff_ff00 | u32::from(pending);
+            },
+            // hundredths of a second since midnight, UTC
+            8 => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                core.dar()[1] = ((now.as_millis() / 10) % 8_640_000) as u32;
+            },
+            9 => self.ending.terminate(core, Termination::Exited(0)),
+            // clear the screen, or put the cursor at the column and row
+            // in the high and low bytes of D1.W
+            11 => match d1 & 0xffff {
+                0xff00 => self.print(b"\x1b[2J\x1b[H"),
+                position if position & 0xff00 != 0xff00 && position & 0xff != 0xff => {
+                    let (column, row) = (position >> 8, position & 0xff);
+                    self.print(format!("\x1b[{};{}H", row + 1, column + 1).as_bytes());
+                },
+                _ => return Ok(false),
+            },
+            12 => (),
+            // display a NUL terminated string, with and without a new line
+            13 | 14 => {
+                let text = string_at(core, a1, MAX_TEXT)?;
+                self.print(&text);
+                if task == 13 {
+                    self.print(b"\r\n");
+                }
+            },
+            // display D1.L unsigned, in the base in D2.B
+            15 => {
+                let base = d2 & 0xff;
+                if (2..=36).contains(&base) {
+                    self.print(in_base(d1, base).as_bytes());
+                }
+            },
+            // a string, then task 3 or 4
+            17 | 18 => {
+                let text = string_at(core, a1, MAX_TEXT)?;
+                self.print(&text);
+                if task == 17 {
+                    self.print(format!("{}", d1 as i32).as_bytes());
+                } else {
+                    core.dar()[1] = self.read_number();
+                }
+            },
+            // display D1.L signed, right justified in D2.B columns
+            20 => self.print(format!("{:>1$}", d1 as i32, (d2 & 0xff) as usize).as_bytes()),
+            // wait for D1.L hundredths of a second
+            23 => thread::sleep(Duration::from_millis(10 * u64::from(d1))),
+            50 => {
+                self.files.clear();
+                set_result(core, SUCCESS);
+            },
+            // open a file, or create a new one, and its ID into D1.L
+            51 | 52 => {
+                let path = self.path(core)?;
+                let opened = if task == 51 {
+                    match OpenOptions::new().read(true).write(true).open(&path) {
+                        Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied =>
+                            File::open(&path).map(|file| (file, READ_ONLY)),
+                        opened => opened.map(|file| (file, SUCCESS)),
+                    }
+                } else {
+                    OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)
+                        .map(|file| (file, SUCCESS))
+                };
+                let result = match opened {
+                    Ok((file, result)) => {
+                        core.dar()[1] = self.open(file);
+                        result
+                    },
+                    Err(_) => ERROR,
+                };
+                set_result(core, result);
+            },
+            // read D2.L bytes, and how many were read into D2.L
+            53 if !in_memory(a1, d2) => {
+                core.dar()[2] = 0;
+                set_result(core, ERROR);
+            },
+            53 => {
+                let mut bytes = Vec::new();
+                let read = match self.file(d1) {
+                    Some(file) => file.take(u64::from(d2)).read_to_end(&mut bytes).is_ok(),
+                    None => false,
+                };
+                write_bytes(core, a1, &bytes)?;
+                core.dar()[2] = bytes.len() as u32;
+                let result = if !read { ERROR } else if bytes.len() < d2 as usize { END_OF_FILE } else { SUCCESS };
+                set_result(core, result);
+            },
+            // write D2.L bytes
+            54 if !in_memory(a1, d2) => set_result(core, ERROR),
+            54 => {
+                let bytes = bytes_at(core, a1, d2)?;
+                let written = self.file(d1).is_some_and(|file| file.write_all(&bytes).is_ok());
+                set_result(core, if written { SUCCESS } else { ERROR });
+            },
+            // move to the position in D2.L
+            55 => {
+                let moved = self.file(d1).is_some_and(|file| file.seek(SeekFrom::Start(u64::from(d2))).is_ok());
+                set_result(core, if moved { SUCCESS } else { ERROR });
+            },
+            56 => {
+                let closed = self.files.get_mut(d1 as usize).and_then(|f| f.take()).is_some();
+                set_result(core, if closed { SUCCESS } else { ERROR });
+            },
+            57 => {
+                let path = self.path(core)?;
+                set_result(core, if fs::remove_file(path).is_ok() { SUCCESS } else { ERROR });
+            },
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+fn set_result(core: &mut impl Core, result: u32) {
+    let d0 = core.dar()[0];
+    core.dar()[0] = d0 & 0xffff_0000 | result;
+}
+
+fn in_base(value: u32, base: u32) -> String {
+    let mut digits = Vec::new();
+    let mut value = value;
+    loop {
+        digits.push(std::char::from_digit(value % base, base).unwrap().to_ascii_uppercase());
+        value /= base;
+        if value == 0 {
+            return digits.iter().rev().collect();
+        }
+    }
+}
+
+impl Callbacks for Simulator {
+    fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+        match ex {
+            Exception::Trap(num, cycles) if num == EXCEPTION_TRAP_BASE + 15 => {
+                let task = core.dar()[0] as u8;
+                if !self.task(core, task)? {
+                    self.ending.terminate(core, Termination::Unsupported(u32::from(task)));
+                }
+                Ok(Cycles(cycles))
+            },
+            Exception::UnimplementedInstruction(SIMHALT, pc, EXCEPTION_UNIMPLEMENTED_1111)
+                if matches!(core.read_program_word(pc.wrapping_add(2)), Ok(word) if word == u32::from(SIMHALT)) => {
+                self.ending.terminate(core, Termination::Exited(0));
+                Ok(Cycles(0))
+            },
+            // EASy68K leaves the vectors to the program
+            _ => self.ending.unhandled(core, ex),
+        }
+    }
+}
+
+impl Host for Simulator {
+    fn termination(&self) -> Option<Termination> {
+        self.ending.termination()
+    }
+}
+
+// Reads a program from S-records into a core of EASy68K's 16MB, which
+// starts it in supervisor mode, at the address of the termination record
+// (or the first record, without one), with the stack at the top of memory
+pub fn load(reader: &mut dyn BufRead) -> io::Result<HostedCore> {
+    let (segments, entrypoint) = read_s68(reader)?;
+    hosted::load(&segments, entrypoint, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use peripherals::serial::SharedBuffer;
+    use ram::{AddressBus, SUPERVISOR_DATA};
+    use r68k_tools::memory::MemoryVec;
+    use r68k_tools::srecords::write_s68;
+    use r68k_tools::PC;
+    use std::env;
+
+    const CODE: u32 = 0x1000;
+    const DATA: u32 = 0x2000;
+
+    // assembles the words at CODE, with the data at DATA, into S-records
+    // and runs them
+    fn simulate_in(directory: &Path, code: &[u16], data: &[u8], input: &[u8]) -> (Termination, String, HostedCore) {
+        let code = MemoryVec::new16(PC(CODE), code.to_vec());
+        let data = MemoryVec::new8(PC(DATA), data.to_vec());
+        let mut records = Vec::new();
+        write_s68(&mut records, vec![&code, &data], CODE).unwrap();
+        let mut core = load(&mut &records[..]).unwrap();
+        assert_eq!((CODE, hosted::MEMORY_TOP, 0x2000), (core.pc, core.dar[15], core.status_register()));
+
+        let output = SharedBuffer::new();
+        let mut simulator = Simulator::new(HostPort::with_input(input, output.clone()));
+        simulator.set_directory(directory);
+        let termination = run(&mut core, &mut simulator);
+        (termination, String::from_utf8(output.contents()).unwrap(), core)
+    }
+
+    fn byte(core: &mut HostedCore, address: u32) -> u8 {
+        core.mem.read_byte(SUPERVISOR_DATA, address) as u8
+    }
+
+    fn simulate(code: &[u16], data: &[u8], input: &[u8]) -> (Termination, String, HostedCore) {
+        simulate_in(Path::new("."), code, data, input)
+    }
+
+    #[test]
+    fn displays_strings_and_exits() {
+        // MOVEA.L #DATA,A1; MOVEQ #13,D0; TRAP #15; MOVEQ #5,D1; MOVEQ #1,D0;
+        // TRAP #15; MOVEQ #0,D0; TRAP #15; MOVEQ #'!',D1; MOVEQ #6,D0;
+        // TRAP #15; MOVEQ #9,D0; TRAP #15
+        let code = [0x227c, 0x0000, 0x2000, 0x700d, 0x4e4f, 0x7205, 0x7001, 0x4e4f,
+            0x7000, 0x4e4f, 0x7221, 0x7006, 0x4e4f, 0x7009, 0x4e4f];
+        let (termination, output, core) = simulate(&code, b"Hello, world\0", b"");
+        assert!(matches!(termination, Termination::Exited(0)));
+        assert_eq!("Hello, world\r\nHelloHello\r\n!", output);
+        assert_eq!(CODE + 2 * code.len() as u32, core.pc);
+    }
+
+    #[test]
+    fn displays_and_reads_numbers() {
+        // MOVE.L #-42,D1; MOVEQ #3,D0; TRAP #15;
+        // MOVE.L #255,D1; MOVEQ #16,D2; MOVEQ #15,D0; TRAP #15;
+        // MOVEQ #6,D2; MOVEQ #20,D0; TRAP #15;
+        // MOVEQ #4,D0; TRAP #15; MOVE.L D1,D3;
+        // MOVEQ #5,D0; TRAP #15; MOVE.L D1,D4;
+        // MOVEQ #7,D0; TRAP #15; SIMHALT
+        let code = [0x223c, 0xffff, 0xffd6, 0x7003, 0x4e4f,
+            0x223c, 0x0000, 0x00ff, 0x7410, 0x700f, 0x4e4f,
+            0x7406, 0x7014, 0x4e4f,
+            0x7004, 0x4e4f, 0x2601,
+            0x7005, 0x4e4f, 0x2801,
+            0x7007, 0x4e4f, 0xffff, 0xffff];
+        let (termination, output, core) = simulate(&code, b"", b" 123\r\nx");
+        assert!(matches!(termination, Termination::Exited(0)));
+        assert_eq!("-42FF   255", output);
+        assert_eq!((123, u32::from(b'x'), 0), (core.dar[3], core.dar[4] & 0xff, core.dar[1] & 0xff));
+    }
+
+    #[test]
+    fn reads_strings_and_prompts() {
+        // MOVEA.L #DATA,A1; MOVEQ #2,D0; TRAP #15; MOVEQ #14,D0; TRAP #15;
+        // MOVE.W D1,D3; MOVEA.L #DATA+$100,A1; MOVEQ #18,D0; TRAP #15;
+        // MOVEQ #17,D0; TRAP #15; MOVE.W #$0503,D1; MOVEQ #11,D0;
+        // TRAP #15; MOVEQ #9,D0; TRAP #15
+        let code = [0x227c, 0x0000, 0x2000, 0x7002, 0x4e4f, 0x700e, 0x4e4f,
+            0x3601, 0x227c, 0x0000, 0x2100, 0x7012, 0x4e4f,
+            0x7011, 0x4e4f, 0x323c, 0x0503, 0x700b,
+            0x4e4f, 0x7009, 0x4e4f];
+        let mut data = vec![0; 0x100];
+        data.extend_from_slice(b"n=\0");
+        let (termination, output, mut core) = simulate(&code, &data, b"abc\n-7\n");
+        assert!(matches!(termination, Termination::Exited(0)));
+        assert_eq!("abcn=n=-7\x1b[4;6H", output);
+        assert_eq!(3, core.dar[3] & 0xffff);
+        assert_eq!(0, byte(&mut core, DATA + 3));
+    }
+
+    #[test]
+    fn reads_and_writes_files() {
+        let directory = env::temp_dir().join(format!("r68k-easy68k-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        // MOVEQ #50,D0; TRAP #15;
+        // MOVEA.L #DATA,A1; MOVEQ #52,D0; TRAP #15;
+        // MOVEA.L #DATA+$10,A1; MOVEQ #5,D2; MOVEQ #54,D0; TRAP #15;
+        // MOVEQ #56,D0; TRAP #15;
+        // MOVEA.L #DATA,A1; MOVEQ #51,D0; TRAP #15;
+        // MOVEA.L #DATA+$20,A1; MOVEQ #8,D2; MOVEQ #53,D0; TRAP #15;
+        // MOVE.W D0,D3; MOVE.L D2,D5;
+        // MOVEQ #1,D2; MOVEQ #55,D0; TRAP #15;
+        // MOVEA.L #DATA+$30,A1; MOVEQ #2,D2; MOVEQ #53,D0; TRAP #15;
+        // MOVE.W D0,D4; MOVEQ #56,D0; TRAP #15;
+        // MOVEA.L #DATA,A1; MOVEQ #57,D0; TRAP #15; MOVE.W D0,D6;
+        // MOVEQ #51,D0; TRAP #15; MOVE.W D0,D7; MOVEQ #9,D0; TRAP #15
+        let code = [0x7032, 0x4e4f,
+            0x227c, 0x0000, 0x2000, 0x7034, 0x4e4f,
+            0x227c, 0x0000, 0x2010, 0x7405, 0x7036, 0x4e4f,
+            0x7038, 0x4e4f,
+            0x227c, 0x0000, 0x2000, 0x7033, 0x4e4f,
+            0x227c, 0x0000, 0x2020, 0x7408, 0x7035, 0x4e4f,
+            0x3600, 0x2a02,
+            0x7401, 0x7037, 0x4e4f,
+            0x227c, 0x0000, 0x2030, 0x7402, 0x7035, 0x4e4f,
+            0x3800, 0x7038, 0x4e4f,
+            0x227c, 0x0000, 0x2000, 0x7039, 0x4e4f, 0x3c00,
+            0x7033, 0x4e4f, 0x3e00, 0x7009, 0x4e4f];
+        let mut data = b"test.txt".to_vec();
+        data.resize(0x10, 0);
+        data.extend_from_slice(b"hello");
+        let (termination, _, mut core) = simulate_in(&directory, &code, &data, b"");
+        let written = fs::read(directory.join("test.txt"));
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(termination, Termination::Exited(0)));
+        let read: Vec<u8> = (0..5).map(|i| byte(&mut core, DATA + 0x20 + i)).collect();
+        assert_eq!(b"hello".to_vec(), read);
+        assert_eq!(b"el".to_vec(), vec![byte(&mut core, DATA + 0x30), byte(&mut core, DATA + 0x31)]);
+        // five bytes read of eight is the end of the file, then the file
+        // is deleted and can't be opened
+        assert_eq!((END_OF_FILE, 5, SUCCESS), (core.dar[3] & 0xffff, core.dar[5], core.dar[4] & 0xffff));
+        assert_eq!((SUCCESS, ERROR), (core.dar[6] & 0xffff, core.dar[7] & 0xffff));
+        assert!(written.is_err());
+    }
+
+    #[test]
+    fn transfers_are_bounded() {
+        // MOVEA.L #DATA,A1; MOVEQ #14,D0; TRAP #15;
+        // MOVEQ #-1,D2; MOVEQ #54,D0; TRAP #15; MOVE.W D0,D3;
+        // MOVEQ #-1,D2; MOVEQ #53,D0; TRAP #15; MOVE.W D0,D4;
+        // MOVEQ #9,D0; TRAP #15
+        let code = [0x227c, 0x0000, 0x2000, 0x700e, 0x4e4f,
+            0x74ff, 0x7036, 0x4e4f, 0x3600,
+            0x74ff, 0x7035, 0x4e4f, 0x3800, 0x7009, 0x4e4f];
+        let data = vec![b'x'; MAX_TEXT as usize + 0x10];
+        let (termination, output, core) = simulate(&code, &data, b"");
+        assert!(matches!(termination, Termination::Exited(0)));
+        assert_eq!(MAX_TEXT as usize, output.len());
+        assert_eq!((ERROR, ERROR, 0), (core.dar[3] & 0xffff, core.dar[4] & 0xffff, core.dar[2]));
+    }
+
+    #[test]
+    fn programs_end_on_what_they_cannot_handle() {
+        // MOVEQ #99,D0; TRAP #15
+        let (termination, _, _) = simulate(&[0x7063, 0x4e4f], b"", b"");
+        assert!(matches!(termination, Termination::Unsupported(99)));
+        // ILLEGAL
+        let (termination, _, _) = simulate(&[0x4afc], b"", b"");
+        assert!(matches!(termination, Termination::Unhandled(Exception::IllegalInstruction(0x4afc, CODE))));
+        // MOVE.L #handler,$10.W; ILLEGAL; handler: MOVEQ #9,D0; TRAP #15
+        let (termination, _, _) = simulate(&[0x21fc, 0x0000, 0x100a, 0x0010, 0x4afc, 0x7009, 0x4e4f], b"", b"");
+        assert!(matches!(termination, Termination::Exited(0)));
+        // STOP #$2700
+        let (termination, _, _) = simulate(&[0x4e72, 0x2700], b"", b"");
+        assert!(matches!(termination, Termination::Stopped));
+    }
+}
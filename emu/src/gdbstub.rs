@@ -0,0 +1,696 @@
+// A stub for the GDB remote serial protocol, letting m68k-elf-gdb debug
+// programs running on a ConfiguredCore:
+//
+//     (gdb) target remote localhost:1234
+//
+// The protocol handling (GdbStub, PacketDecoder) is independent of the
+// transport, and serve() runs a session over a TCP connection.
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use cpu::{AccessType, ConfiguredCore};
+use cpu::debugger::{StopReason, Watch, Watchpoint};
+use interrupts::InterruptController;
+use ram::{AddressBus, AddressSpace};
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// d0-d7, a0-a7, sr and pc, as in the org.gnu.gdb.m68k.core feature
+const NUM_REGS: usize = 18;
+const SR_REG: usize = 16;
+const PC_REG: usize = 17;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>m68k</architecture>
+  <feature name="org.gnu.gdb.m68k.core">
+    <reg name="d0" bitsize="32"/>
+    <reg name="d1" bitsize="32"/>
+    <reg name="d2" bitsize="32"/>
+    <reg name="d3" bitsize="32"/>
+    <reg name="d4" bitsize="32"/>
+    <reg name="d5" bitsize="32"/>
+    <reg name="d6" bitsize="32"/>
+    <reg name="d7" bitsize="32"/>
+    <reg name="a0" bitsize="32" type="data_ptr"/>
+    <reg name="a1" bitsize="32" type="data_ptr"/>
+    <reg name="a2" bitsize="32" type="data_ptr"/>
+    <reg name="a3" bitsize="32" type="data_ptr"/>
+    <reg name="a4" bitsize="32" type="data_ptr"/>
+    <reg name="a5" bitsize="32" type="data_ptr"/>
+    <reg name="fp" bitsize="32" type="data_ptr"/>
+    <reg name="sp" bitsize="32" type="data_ptr"/>
+    <reg name="ps" bitsize="32"/>
+    <reg name="pc" bitsize="32" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+// the longest packet we accept, and so the most memory gdb reads at once
+// (as two hex digits a byte)
+const PACKET_SIZE: usize = 0x1000;
+
+// how many cycles to run between checks for Ctrl-C
+const INTERRUPT_POLL_INTERVAL: i32 = 0x4000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resume {
+    Step,
+    Continue,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    Reply(String),
+    Resume(Resume),
+    Detach,
+    Kill,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    Packet(String),
+    BadChecksum,
+    Interrupt, // Ctrl-C
+}
+
+enum DecoderState {
+    Idle,
+    Payload,
+    Escape,
+    Checksum(Option<u8>),
+}
+
+// Splits the incoming byte stream into packets and out-of-band Ctrl-C
+pub struct PacketDecoder {
+    state: DecoderState,
+    payload: Vec<u8>,
+}
+
+impl Default for PacketDecoder {
+    fn default() -> PacketDecoder {
+        PacketDecoder::new()
+    }
+}
+
+impl PacketDecoder {
+    pub fn new() -> PacketDecoder {
+        PacketDecoder { state: DecoderState::Idle, payload: Vec::new() }
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<Event> {
+        match self.state {
+            DecoderState::Idle => match byte {
+                b'$' => {
+                    self.payload.clear();
+                    self.state = DecoderState::Payload;
+                    None
+                },
+                0x03 => Some(Event::Interrupt),
+                _ => None, // acks, or noise
+            },
+            DecoderState::Payload => {
+                match byte {
+                    b'#' => self.state = DecoderState::Checksum(None),
+                    b'}' => self.state = DecoderState::Escape,
+                    _ => self.payload.push(byte),
+                }
+                None
+            },
+            DecoderState::Escape => {
+                self.payload.push(byte ^ 0x20);
+                self.state = DecoderState::Payload;
+                None
+            },
+            DecoderState::Checksum(None) => {
+                self.state = DecoderState::Checksum(Some(byte));
+                None
+            },
+            DecoderState::Checksum(Some(high)) => {
+                self.state = DecoderState::Idle;
+                let expected = parse_hex(&[high, byte]).map(|sum| sum as u8);
+                // the checksum covers the escaped payload, so escape it again
+                let escaped = escape(&self.payload);
+                if expected == Some(checksum(&escaped)) {
+                    Some(Event::Packet(String::from_utf8_lossy(&self.payload).into_owned()))
+                } else {
+                    Some(Event::BadChecksum)
+                }
+            },
+        }
+    }
+}
+
+fn checksum(payload: &[u8]) -> u8 {
+    payload.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn escape(payload: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(payload.len());
+    for &byte in payload {
+        match byte {
+            b'#' | b'$' | b'}' | b'*' => {
+                escaped.push(b'}');
+                escaped.push(byte ^ 0x20);
+            },
+            _ => escaped.push(byte),
+        }
+    }
+    escaped
+}
+
+// wraps a payload as $payload#checksum
+pub fn frame(payload: &str) -> Vec<u8> {
+    let escaped = escape(payload.as_bytes());
+    let mut packet = Vec::with_capacity(escaped.len() + 4);
+    packet.push(b'$');
+    packet.extend_from_slice(&escaped);
+    packet.extend_from_slice(format!("#{:02x}", checksum(&escaped)).as_bytes());
+    packet
+}
+
+fn parse_hex(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() || digits.len() > 8 {
+        return None;
+    }
+    ::std::str::from_utf8(digits).ok().and_then(|s| u32::from_str_radix(s, 16).ok())
+}
+
+fn parse_hex_str(digits: &str) -> Option<u32> {
+    parse_hex(digits.as_bytes())
+}
+
+fn decode_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.as_bytes().chunks(2).map(|pair| parse_hex(pair).map(|byte| byte as u8)).collect()
+}
+
+// "addr,length" as used by m, M and Z packets
+fn parse_address_length(args: &str) -> Option<(u32, u32)> {
+    let mut parts = args.splitn(2, ',');
+    let address = parts.next().and_then(parse_hex_str)?;
+    let length = parts.next().and_then(parse_hex_str)?;
+    Some((address, length))
+}
+
+fn error(code: u8) -> Action {
+    Action::Reply(format!("E{:02x}", code))
+}
+
+fn ok() -> Action {
+    Action::Reply("OK".to_string())
+}
+
+fn unsupported() -> Action {
+    Action::Reply(String::new())
+}
+
+pub struct GdbStub {
+    no_ack: bool,
+}
+
+impl Default for GdbStub {
+    fn default() -> GdbStub {
+        GdbStub::new()
+    }
+}
+
+impl GdbStub {
+    pub fn new() -> GdbStub {
+        GdbStub { no_ack: false }
+    }
+
+    // once GDB has requested no-ack mode, packets are no longer acked
+    pub fn no_ack(&self) -> bool {
+        self.no_ack
+    }
+
+    fn read_register<T: InterruptController, A: AddressBus>(core: &ConfiguredCore<T, A>, reg: usize) -> Option<u32> {
+        match reg {
+            0..=15 => Some(core.dar[reg]),
+            SR_REG => Some(u32::from(core.status_register())),
+            PC_REG => Some(core.pc),
+            _ => None,
+        }
+    }
+
+    fn write_register<T: InterruptController, A: AddressBus>(core: &mut ConfiguredCore<T, A>, reg: usize, value: u32) -> bool {
+        match reg {
+            0..=15 => core.dar[reg] = value,
+            SR_REG => core.sr_to_flags(value as u16),
+            PC_REG => core.jump(value),
+            _ => return false,
+        }
+        true
+    }
+
+    // memory is accessed as data, in the current mode
+    fn address_space<T: InterruptController, A: AddressBus>(core: &ConfiguredCore<T, A>) -> AddressSpace {
+        AddressSpace::from_flags(core.s_flag != 0, true)
+    }
+
+    pub fn handle_packet<T: InterruptController, A: AddressBus>(&mut self, core: &mut ConfiguredCore<T, A>, packet: &str) -> Action {
+        let (command, args) = packet.split_at(if packet.is_empty() { 0 } else { 1 });
+        match command {
+            "?" => Action::Reply(format!("S{:02x}", SIGTRAP)),
+            "g" => {
+                let regs: String = (0..NUM_REGS)
+                    .filter_map(|reg| Self::read_register(core, reg))
+                    .map(|value| format!("{:08x}", value))
+                    .collect();
+                Action::Reply(regs)
+            },
+            "G" => {
+                if args.len() != NUM_REGS * 8 {
+                    return error(0);
+                }
+                let values: Option<Vec<u32>> = args.as_bytes().chunks(8).map(parse_hex).collect();
+                match values {
+                    Some(values) => {
+                        for (reg, value) in values.into_iter().enumerate() {
+                            Self::write_register(core, reg, value);
+                        }
+                        ok()
+                    },
+                    None => error(0),
+                }
+            },
+            "p" => match parse_hex_str(args).and_then(|reg| Self::read_register(core, reg as usize)) {
+                Some(value) => Action::Reply(format!("{:08x}", value)),
+                None => error(0),
+            },
+            "P" => {
+                let mut parts = args.splitn(2, '=');
+                let reg = parts.next().and_then(parse_hex_str);
+                let value = parts.next().and_then(parse_hex_str);
+                match (reg, value) {
+                    (Some(reg), Some(value)) if Self::write_register(core, reg as usize, value) => ok(),
+                    _ => error(0),
+                }
+            },
+            "m" => match parse_address_length(args) {
+                Some((_, length)) if length as usize > PACKET_SIZE / 2 => error(0),
+                Some((address, length)) => {
+                    let address_space = Self::address_space(core);
+                    let mut bytes = String::with_capacity(length as usize * 2);
+                    for offset in 0..length {
+                        match core.peek_byte(address_space, address.wrapping_add(offset)) {
+                            Ok(byte) => bytes.push_str(&format!("{:02x}", byte)),
+                            Err(_) if offset > 0 => break, // a partial read is fine
+                            Err(_) => return error(1),
+                        }
+                    }
+                    Action::Reply(bytes)
+                },
+                None => error(0),
+            },
+            "M" => {
+                let mut parts = args.splitn(2, ':');
+                let target = parts.next().and_then(parse_address_length);
+                let data = parts.next().and_then(decode_hex_bytes);
+                match (target, data) {
+                    (Some((address, length)), Some(ref data)) if data.len() == length as usize => {
+                        let address_space = Self::address_space(core);
+                        for (offset, &byte) in data.iter().enumerate() {
+                            if core.poke_byte(address_space, address.wrapping_add(offset as u32), u32::from(byte)).is_err() {
+                                return error(1);
+                            }
+                        }
+                        ok()
+                    },
+                    _ => error(0),
+                }
+            },
+            // software and hardware breakpoints are the same thing here,
+            // and watchpoints watch all address spaces
+            "Z" | "z" => {
+                let mut parts = args.splitn(2, ',');
+                let kind = parts.next();
+                let target = parts.next().and_then(parse_address_length);
+                let insert = command == "Z";
+                match (kind, target) {
+                    (Some("0"), Some((address, _))) | (Some("1"), Some((address, _))) => {
+                        if insert {
+                            core.debugger.add_breakpoint(address);
+                        } else {
+                            core.debugger.remove_breakpoint(address);
+                        }
+                        ok()
+                    },
+                    (Some(kind), Some((address, length))) => {
+                        let watch = match kind {
+                            "2" => Watch::Write,
+                            "3" => Watch::Read,
+                            "4" => Watch::Access,
+                            _ => return unsupported(),
+                        };
+                        // gdb watches length bytes, at least one and not past the top of memory
+                        let last = match length.checked_sub(1).and_then(|len| address.checked_add(len)) {
+                            Some(last) => last,
+                            None => return error(0),
+                        };
+                        let watchpoint = Watchpoint::new(address..=last, watch);
+                        if insert {
+                            core.debugger.add_watchpoint(watchpoint);
+                        } else {
+                            core.debugger.remove_watchpoint(&watchpoint);
+                        }
+                        ok()
+                    },
+                    _ => error(0),
+                }
+            },
+            "s" | "c" => {
+                if !args.is_empty() {
+                    match parse_hex_str(args) {
+                        Some(address) => core.jump(address),
+                        None => return error(0),
+                    }
+                }
+                Action::Resume(if command == "s" { Resume::Step } else { Resume::Continue })
+            },
+            "H" => ok(), // there is only the one thread
+            "D" => Action::Detach,
+            "k" => Action::Kill,
+            "q" | "Q" => self.handle_query(packet),
+            _ => unsupported(),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Action {
+        if packet.starts_with("qSupported") {
+            Action::Reply(format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", PACKET_SIZE))
+        } else if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            match parse_address_length(args) {
+                Some((offset, length)) => {
+                    let offset = (offset as usize).min(TARGET_XML.len());
+                    let end = (offset + length as usize).min(TARGET_XML.len());
+                    let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                    Action::Reply(format!("{}{}", more, &TARGET_XML[offset..end]))
+                },
+                None => error(0),
+            }
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            ok()
+        } else if packet == "qAttached" {
+            Action::Reply("1".to_string())
+        } else if packet == "qC" {
+            Action::Reply("QC1".to_string())
+        } else if packet == "qfThreadInfo" {
+            Action::Reply("m1".to_string())
+        } else if packet == "qsThreadInfo" {
+            Action::Reply("l".to_string())
+        } else {
+            unsupported()
+        }
+    }
+
+    // Runs the core as requested, and returns the stop reply. A continue
+    // runs until the core's debugger stops it, or interrupted() reports
+    // Ctrl-C. A halted core can't make progress, and is reported as a
+    // segfault.
+    pub fn resume<T: InterruptController, A: AddressBus, F: FnMut() -> bool>(&mut self, core: &mut ConfiguredCore<T, A>, resume: Resume, mut interrupted: F) -> String {
+        let reason = match resume {
+            Resume::Step => core.run(1).1,
+            Resume::Continue => loop {
+                match core.run(INTERRUPT_POLL_INTERVAL).1 {
+                    StopReason::CyclesExhausted | StopReason::Stopped => if interrupted() {
+                        return format!("S{:02x}", SIGINT);
+                    },
+                    reason => break reason,
+                }
+            },
+        };
+        match reason {
+            StopReason::Halted => format!("S{:02x}", SIGSEGV),
+            StopReason::Watchpoint(hit) => {
+                let kind = if hit.access_type == AccessType::Write { "watch" } else { "rwatch" };
+                format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.address)
+            },
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+}
+
+fn poll_interrupt(stream: &mut TcpStream) -> bool {
+    let mut byte = [0u8];
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let interrupted = match stream.read(&mut byte) {
+        Ok(1) => byte[0] == 0x03,
+        _ => false,
+    };
+    let _ = stream.set_nonblocking(false);
+    interrupted
+}
+
+// Waits for GDB to connect, then serves it until it detaches
+pub fn listen<T: InterruptController, A: AddressBus, S: ToSocketAddrs>(core: &mut ConfiguredCore<T, A>, address: S) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let (stream, _) = listener.accept()?;
+    serve(core, stream)
+}
+
+pub fn serve<T: InterruptController, A: AddressBus>(core: &mut ConfiguredCore<T, A>, mut stream: TcpStream) -> io::Result<()> {
+    let mut stub = GdbStub::new();
+    let mut decoder = PacketDecoder::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let len = stream.read(&mut buf)?;
+        if len == 0 {
+            return Ok(()); // GDB went away
+        }
+        for &byte in &buf[..len] {
+            let reply = match decoder.feed(byte) {
+                None => continue,
+                Some(Event::BadChecksum) => {
+                    stream.write_all(b"-")?;
+                    continue;
+                },
+                Some(Event::Interrupt) => format!("S{:02x}", SIGINT),
+                Some(Event::Packet(packet)) => {
+                    if !stub.no_ack() {
+                        stream.write_all(b"+")?;
+                    }
+                    match stub.handle_packet(core, &packet) {
+                        Action::Reply(reply) => reply,
+                        Action::Resume(resume) => {
+                            stream.flush()?;
+                            stub.resume(core, resume, || poll_interrupt(&mut stream))
+                        },
+                        Action::Detach => {
+                            stream.write_all(&frame("OK"))?;
+                            return Ok(());
+                        },
+                        Action::Kill => return Ok(()),
+                    }
+                },
+            };
+            stream.write_all(&frame(&reply))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, Action, Event, GdbStub, PacketDecoder, Resume};
+    use cpu::{ConfiguredCore, TestCore};
+    use interrupts::{AutoInterruptController, InterruptController};
+    use ram::{AddressBus, AddressSpace, BusResult, SUPERVISOR_DATA};
+    use ram::mappedbus::{Device, MappedBus, Size};
+
+    // a device register that counts how often it is read
+    struct ReadCounter(u32);
+
+    impl Device for ReadCounter {
+        fn read(&mut self, _: AddressSpace, _: u32, _: Size) -> BusResult<u32> {
+            self.0 += 1;
+            Ok(self.0)
+        }
+        fn write(&mut self, _: AddressSpace, _: u32, _: Size, _: u32) -> BusResult<()> {
+            Ok(())
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Vec<Event> {
+        let mut decoder = PacketDecoder::new();
+        bytes.iter().filter_map(|&byte| decoder.feed(byte)).collect()
+    }
+
+    fn reply<T: InterruptController, A: AddressBus>(stub: &mut GdbStub, core: &mut ConfiguredCore<T, A>, packet: &str) -> String {
+        match stub.handle_packet(core, packet) {
+            Action::Reply(reply) => reply,
+            action => panic!("expected a reply, got {:?}", action),
+        }
+    }
+
+    #[test]
+    fn frames_and_decodes_packets() {
+        assert_eq!(b"$OK#9a".to_vec(), frame("OK"));
+        assert_eq!(vec![Event::Packet("OK".to_string())], decode(&frame("OK")));
+        assert_eq!(vec![Event::Packet("a#b".to_string())], decode(&frame("a#b")));
+        assert_eq!(vec![Event::BadChecksum], decode(b"$OK#00"));
+        assert_eq!(vec![Event::Interrupt, Event::Packet("?".to_string())], decode(b"+\x03$?#3f"));
+    }
+
+    #[test]
+    fn reads_and_writes_registers() {
+        let mut core = TestCore::new(0x1000);
+        let mut stub = GdbStub::new();
+        core.dar[1] = 0x12345678;
+        core.dar[15] = 0x2000;
+        let regs = reply(&mut stub, &mut core, "g");
+        assert_eq!(18 * 8, regs.len());
+        assert_eq!("12345678", &regs[8..16]);
+        assert_eq!("00002000", &regs[15*8..16*8]);
+        assert_eq!("00002700", &regs[16*8..17*8]);
+        assert_eq!("00001000", &regs[17*8..]);
+
+        assert_eq!("OK", reply(&mut stub, &mut core, "P11=00002000"));
+        assert_eq!(0x2000, core.pc);
+        assert_eq!("OK", reply(&mut stub, &mut core, "P10=00000700")); // user mode
+        assert_eq!(0, core.s_flag);
+        assert_eq!("00002000", reply(&mut stub, &mut core, "p11"));
+        assert_eq!("E00", reply(&mut stub, &mut core, "p12"));
+
+        let mut regs = regs;
+        regs.replace_range(0..8, "cafebabe");
+        assert_eq!("OK", reply(&mut stub, &mut core, &format!("G{}", regs)));
+        assert_eq!(0xcafebabe, core.dar[0]);
+        assert_eq!(0x1000, core.pc);
+    }
+
+    #[test]
+    fn reads_and_writes_memory() {
+        let mut core = TestCore::new_mem(0x40, &[0x4e, 0x71]);
+        let mut stub = GdbStub::new();
+        assert_eq!("4e71", reply(&mut stub, &mut core, "m40,2"));
+        assert_eq!("OK", reply(&mut stub, &mut core, "M100,4:91929394"));
+        assert_eq!(0x91929394, core.mem.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!("E00", reply(&mut stub, &mut core, "M100,4:9192"));
+        core.mem.unmap(0x200..0x300);
+        assert_eq!("E01", reply(&mut stub, &mut core, "m200,4"));
+        assert_eq!("aa", reply(&mut stub, &mut core, "m1ff,4")); // a partial read
+        assert_eq!("E00", reply(&mut stub, &mut core, "m0,ffffffff"));
+    }
+
+    #[test]
+    fn patched_code_is_decoded_again() {
+        let mut core = TestCore::new_mem(0x40, &[0x4e, 0x71]);
+        core.enable_decode_cache();
+        core.execute1();
+        let mut stub = GdbStub::new();
+        // 7001 is MOVEQ #1,D0
+        assert_eq!("OK", reply(&mut stub, &mut core, "M40,2:7001"));
+        core.pc = 0x40;
+        core.execute1();
+        assert_eq!(1, core.dar[0]);
+    }
+
+    #[test]
+    fn reads_memory_without_touching_devices() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0, 0x1000);
+        bus.map_device(0x1000, 2, Box::new(ReadCounter(0)));
+        let mut core = ConfiguredCore::new_with(0, AutoInterruptController::new(), bus);
+        let mut stub = GdbStub::new();
+        assert_eq!("0000", reply(&mut stub, &mut core, "mffe,4"));
+        assert_eq!("E01", reply(&mut stub, &mut core, "m1000,2"));
+        assert_eq!(1, core.mem.read_byte(SUPERVISOR_DATA, 0x1000));
+    }
+
+    #[test]
+    fn serves_target_description() {
+        let mut core = TestCore::new(0x40);
+        let mut stub = GdbStub::new();
+        assert!(reply(&mut stub, &mut core, "qSupported:swbreak+").contains("qXfer:features:read+"));
+        let first = reply(&mut stub, &mut core, "qXfer:features:read:target.xml:0,10");
+        assert_eq!("m<?xml version=\"1", first);
+        let rest = reply(&mut stub, &mut core, "qXfer:features:read:target.xml:10,1000");
+        assert!(rest.starts_with('l'));
+        assert!(rest.contains("org.gnu.gdb.m68k.core"));
+    }
+
+    #[test]
+    fn steps_and_continues_to_breakpoints() {
+        // three NOPs, then BRA.S back to the first
+        let mut core = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x71, 0x60, 0xf8]);
+        let mut stub = GdbStub::new();
+        assert_eq!(Action::Resume(Resume::Step), stub.handle_packet(&mut core, "s"));
+        assert_eq!("S05", stub.resume(&mut core, Resume::Step, || false));
+        assert_eq!(0x42, core.pc);
+
+        assert_eq!("OK", reply(&mut stub, &mut core, "Z0,46,2"));
+        assert_eq!("OK", reply(&mut stub, &mut core, "Z1,44,2"));
+        assert_eq!("S05", stub.resume(&mut core, Resume::Continue, || false));
+        assert_eq!(0x44, core.pc);
+        // continuing from a breakpoint does not stop right away
+        assert_eq!("S05", stub.resume(&mut core, Resume::Continue, || false));
+        assert_eq!(0x46, core.pc);
+
+        assert_eq!("OK", reply(&mut stub, &mut core, "z0,46,2"));
+        assert_eq!(Action::Resume(Resume::Continue), stub.handle_packet(&mut core, "c42"));
+        assert_eq!(0x42, core.pc);
+        assert_eq!("S05", stub.resume(&mut core, Resume::Continue, || false));
+        assert_eq!(0x44, core.pc); // looped back around
+    }
+
+    #[test]
+    fn continues_to_watchpoints() {
+        // MOVE.L D0,($0100).W, then BRA.S *
+        let mut core = TestCore::new_mem(0x40, &[0x21, 0xc0, 0x01, 0x00, 0x60, 0xfe]);
+        let mut stub = GdbStub::new();
+        assert_eq!("OK", reply(&mut stub, &mut core, "Z3,100,4"));
+        assert_eq!("OK", reply(&mut stub, &mut core, "Z2,102,2"));
+        assert_eq!("T05watch:100;", stub.resume(&mut core, Resume::Continue, || false));
+        assert_eq!(0x44, core.pc);
+        assert_eq!("OK", reply(&mut stub, &mut core, "z2,102,2"));
+        assert_eq!(1, core.debugger.watchpoints().len());
+        // up to the top of memory, but not past it, and of a byte at least
+        assert_eq!("OK", reply(&mut stub, &mut core, "Z2,fffffffc,4"));
+        assert_eq!(Some(&(0xffff_fffc..=0xffff_ffff)), core.debugger.watchpoints().last().map(|w| &w.range));
+        assert_eq!("E00", reply(&mut stub, &mut core, "Z2,fffffffc,5"));
+        assert_eq!("E00", reply(&mut stub, &mut core, "Z2,100,0"));
+    }
+
+    #[test]
+    fn continue_can_be_interrupted() {
+        // 60fe is BRA.S *
+        let mut core = TestCore::new_mem(0x40, &[0x60, 0xfe]);
+        let mut stub = GdbStub::new();
+        assert_eq!("S02", stub.resume(&mut core, Resume::Continue, || true));
+        assert_eq!(0x40, core.pc);
+    }
+
+    #[test]
+    fn serves_a_tcp_connection() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"$?#3f").unwrap();
+            let mut reply = [0u8; 8];
+            stream.read_exact(&mut reply).unwrap();
+            stream.write_all(b"+$D#44").unwrap();
+            let mut detach = Vec::new();
+            stream.read_to_end(&mut detach).unwrap();
+            (reply, detach)
+        });
+        let mut core = TestCore::new(0x40);
+        let (stream, _) = listener.accept().unwrap();
+        super::serve(&mut core, stream).unwrap();
+        let (reply, detach) = client.join().unwrap();
+        assert_eq!(b"+$S05#b8", &reply);
+        assert_eq!(b"+$OK#9a".to_vec(), detach);
+    }
+}
@@ -0,0 +1,140 @@
+// What the EASy68K simulator and semihosting share: a program loaded into
+// 16MB of memory, whose system calls a host services as Callbacks and
+// which runs, without an OS or vectors of its own, until it ends. The
+// host keeps why in an Ending, and run returns it.
+use cpu::{Callbacks, ConfiguredCore, Core, Cycles, Exception, Result};
+use cpu::debugger::StopReason;
+use interrupts::{AutoInterruptController, InterruptController};
+use r68k_tools::memory::{Memory, MemoryVec};
+use ram::{AddressBus, FlatMem, SUPERVISOR_DATA};
+use std::io;
+
+pub type HostedCore = ConfiguredCore<AutoInterruptController, FlatMem>;
+
+// the size of the memory, where the stack starts
+pub const MEMORY_TOP: u32 = 0x0100_0000;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Termination {
+    Exited(i32),          // with a status, 0 where the program gives none
+    Unsupported(u32),     // by a system call not serviced here
+    Unhandled(Exception), // an exception the program has no vector for
+    Halted,               // by a double fault
+    Stopped,              // by STOP, with no interrupt to wake the core
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ending(Option<Termination>);
+
+impl Ending {
+    pub fn termination(&self) -> Option<Termination> {
+        self.0
+    }
+
+    // Ends the program, at once
+    pub fn terminate(&mut self, core: &mut impl Core, termination: Termination) {
+        self.0 = Some(termination);
+        core.stop_instruction_processing();
+    }
+
+    // Ends the program at an exception it hasn't set the vector of, as a
+    // bare-metal program mostly doesn't, rather than jump to 0; any other
+    // exception is processed as usual
+    pub fn unhandled(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+        if let Some(vector) = ex.vector() {
+            let address = core.vbr().wrapping_add(u32::from(vector) << 2);
+            if matches!(core.read_data_long(address), Ok(0)) {
+                self.terminate(core, Termination::Unhandled(ex));
+                return Ok(Cycles(0));
+            }
+        }
+        Err(ex)
+    }
+}
+
+pub trait Host: Callbacks {
+    // why the program ended, once it has
+    fn termination(&self) -> Option<Termination>;
+}
+
+// Loads the segments into a new core, which starts the program in
+// supervisor mode at the entry point (or the first segment, without one),
+// with the stack at the top of memory and the interrupt mask given
+pub fn load(segments: &[MemoryVec], entry: Option<u32>, int_mask: u8) -> io::Result<HostedCore> {
+    let mut mem = FlatMem::new(0);
+    for segment in segments {
+        if u64::from(segment.offset()) + segment.data().len() as u64 > u64::from(MEMORY_TOP) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the program doesn't fit in memory"));
+        }
+        for (i, &byte) in segment.data().iter().enumerate() {
+            mem.write_byte(SUPERVISOR_DATA, segment.offset() + i as u32, u32::from(byte));
+        }
+    }
+    let start = entry.or_else(|| segments.first().map(|segment| segment.offset()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no program"))?;
+    let mut core = ConfiguredCore::new_with(start, AutoInterruptController::new(), mem);
+    core.start_at(start, MEMORY_TOP, int_mask);
+    Ok(core)
+}
+
+// Runs the program until it ends, and returns why it did
+pub fn run<T: InterruptController, A: AddressBus, H: Host>(core: &mut ConfiguredCore<T, A>, host: &mut H) -> Termination {
+    loop {
+        let (_, reason) = core.run_with_state(100_000, host);
+        if let Some(termination) = host.termination() {
+            return termination;
+        }
+        match reason {
+            StopReason::Halted => return Termination::Halted,
+            StopReason::Stopped => return Termination::Stopped,
+            _ => (),
+        }
+    }
+}
+
+// Whether the bytes at the address are within the memory, so that a
+// program can't have the host take in more. Callers check this before
+// moving a length the program gives.
+pub fn in_memory(address: u32, len: u32) -> bool {
+    u64::from(address) + u64::from(len) <= u64::from(MEMORY_TOP)
+}
+
+pub fn bytes_at(core: &mut impl Core, address: u32, len: u32) -> Result<Vec<u8>> {
+    (0..len).map(|i| core.read_data_byte(address.wrapping_add(i)).map(|byte| byte as u8)).collect()
+}
+
+pub fn write_bytes(core: &mut impl Core, address: u32, bytes: &[u8]) -> Result<()> {
+    for (i, &byte) in bytes.iter().enumerate() {
+        core.write_data_byte(address.wrapping_add(i as u32), u32::from(byte))?;
+    }
+    Ok(())
+}
+
+// up to the NUL, or max bytes without one
+pub fn string_at(core: &mut impl Core, address: u32, max: u32) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for i in 0..max {
+        let byte = core.read_data_byte(address.wrapping_add(i))? as u8;
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use r68k_tools::PC;
+
+    #[test]
+    fn programs_must_fit_in_memory() {
+        let code = MemoryVec::new16(PC(0x1000), vec![0x4e71]);
+        let core = load(&[code], None, 7).unwrap();
+        assert_eq!((0x1000, MEMORY_TOP, 0x2700), (core.pc, core.dar[15], core.status_register()));
+        let past = MemoryVec::new8(PC(MEMORY_TOP - 1), vec![0, 0]);
+        assert_eq!(Some(io::ErrorKind::InvalidData), load(&[past], Some(0x1000), 0).err().map(|e| e.kind()));
+        assert!(in_memory(MEMORY_TOP - 2, 2) && !in_memory(MEMORY_TOP - 2, 3) && !in_memory(u32::MAX, 2));
+    }
+}
@@ -1,3 +1,5 @@
+use savestate::{self, Snapshot, StateError, StateReader, StateWriter};
+
 pub trait InterruptController
 {
     fn reset_external_devices(&mut self); // triggered by RESET instruction
@@ -5,10 +7,9 @@ pub trait InterruptController
     fn acknowledge_interrupt(&mut self, priority: u8) -> Option<u8>;
 }
 
-#[allow(dead_code)]
 pub const UNINITIALIZED_INTERRUPT: u8 = 0x0F;
 pub const SPURIOUS_INTERRUPT: u8 = 0x18;
-const AUTOVECTOR_BASE: u8 = 0x18;
+pub const AUTOVECTOR_BASE: u8 = 0x18;
 
 #[derive(Default)]
 pub struct AutoInterruptController {
@@ -45,11 +46,216 @@ impl InterruptController for AutoInterruptController {
     }
 }
 
+impl Snapshot for AutoInterruptController {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u8(self.level);
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.level = reader.get_u8()?;
+        Ok(())
+    }
+}
+
+// What a device answers the interrupt acknowledge cycle with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Vector {
+    Auto, // asserts VPA, for the autovector of its level
+    Number(u8), // puts its vector number on the data bus
+    Uninitialized, // a vectored device whose vector register wasn't written
+    Spurious, // doesn't answer, so the cycle ends in a bus error
+}
+
+// A device that works out its requests, and the vector it answers the
+// acknowledge cycle with, by itself, like an MFP with its own interrupt
+// registers. It is asked at every instruction boundary.
+pub trait Responder: Send {
+    fn requesting(&self) -> bool;
+    fn acknowledge(&mut self) -> Vector;
+    // triggered by the RESET instruction
+    fn reset(&mut self) {}
+}
+
+struct Device {
+    level: u8,
+    vector: Vector,
+    pulsed: bool, // latched until acknowledged
+    line: bool, // held until deasserted
+    responder: Option<Box<dyn Responder>>,
+}
+
+impl Device {
+    fn requesting(&self) -> bool {
+        self.pulsed || self.line || self.responder.as_ref().is_some_and(|r| r.requesting())
+    }
+}
+
+// Devices, such as an MFP or a DUART, supplying their own vectors. Each
+// requests an interrupt at the level it was added with, either pulsed
+// (latched until acknowledged, like AutoInterruptController) or by
+// holding its line asserted, which the acknowledge cycle leaves alone;
+// a real device deasserts it once the handler reads its status. The
+// levels requested are encoded into the highest one, as on the IPL
+// pins, and devices at the same level are daisy chained in the order
+// they were added: the first one that is requesting answers the
+// acknowledge cycle.
+#[derive(Default)]
+pub struct VectoredInterruptController {
+    devices: Vec<Device>,
+}
+impl VectoredInterruptController {
+    pub fn new() -> VectoredInterruptController {
+        VectoredInterruptController { devices: Vec::new() }
+    }
+
+    // adds a device at the end of the chain of its level, and returns its
+    // index
+    pub fn add_device(&mut self, level: u8, vector: Vector) -> usize {
+        assert!(level > 0 && level < 8);
+        self.devices.push(Device { level, vector, pulsed: false, line: false, responder: None });
+        self.devices.len() - 1
+    }
+
+    // adds a device that answers for itself (its vector is only used for
+    // requests made through the controller)
+    pub fn add_responder(&mut self, level: u8, responder: Box<dyn Responder>) -> usize {
+        let device = self.add_device(level, Vector::Auto);
+        self.devices[device].responder = Some(responder);
+        device
+    }
+
+    pub fn devices(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn level(&self, device: usize) -> u8 {
+        self.devices[device].level
+    }
+
+    pub fn vector(&self, device: usize) -> Vector {
+        self.devices[device].vector
+    }
+
+    // for devices whose vector register is written by the program
+    pub fn set_vector(&mut self, device: usize, vector: Vector) {
+        self.devices[device].vector = vector;
+    }
+
+    // a pulsed request
+    pub fn request_interrupt(&mut self, device: usize) {
+        self.devices[device].pulsed = true;
+    }
+
+    // withdraws a pulsed request that hasn't been acknowledged yet
+    pub fn cancel_interrupt(&mut self, device: usize) {
+        self.devices[device].pulsed = false;
+    }
+
+    pub fn assert_line(&mut self, device: usize) {
+        self.devices[device].line = true;
+    }
+
+    pub fn deassert_line(&mut self, device: usize) {
+        self.devices[device].line = false;
+    }
+
+    pub fn set_line(&mut self, device: usize, asserted: bool) {
+        self.devices[device].line = asserted;
+    }
+
+    pub fn is_line_asserted(&self, device: usize) -> bool {
+        self.devices[device].line
+    }
+
+    pub fn is_requesting(&self, device: usize) -> bool {
+        self.devices[device].requesting()
+    }
+}
+impl InterruptController for VectoredInterruptController {
+    fn reset_external_devices(&mut self) {
+        for device in &mut self.devices {
+            device.pulsed = false;
+            device.line = false;
+            if let Some(ref mut responder) = device.responder {
+                responder.reset();
+            }
+        }
+    }
+
+    fn highest_priority(&self) -> u8 {
+        self.devices.iter().filter(|d| d.requesting()).map(|d| d.level).max().unwrap_or(0)
+    }
+
+    fn acknowledge_interrupt(&mut self, priority: u8) -> Option<u8> {
+        let device = self.devices.iter_mut().find(|d| d.requesting() && d.level == priority);
+        let vector = match device {
+            Some(device) => match device.responder {
+                Some(ref mut responder) if responder.requesting() => responder.acknowledge(),
+                _ => {
+                    device.pulsed = false;
+                    device.vector
+                },
+            },
+            // nobody at that level answers either
+            None => Vector::Spurious,
+        };
+        Some(match vector {
+            Vector::Auto => AUTOVECTOR_BASE + priority,
+            Vector::Number(number) => number,
+            Vector::Uninitialized => UNINITIALIZED_INTERRUPT,
+            Vector::Spurious => SPURIOUS_INTERRUPT,
+        })
+    }
+}
+
+// The devices are part of the state, so a state only loads into a
+// controller with the same devices at the same levels. Responders keep
+// their state elsewhere, typically with the bus they are mapped into.
+impl Snapshot for VectoredInterruptController {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u16(self.devices.len() as u16);
+        for device in &self.devices {
+            writer.put_u8(device.level);
+            let (kind, number) = match device.vector {
+                Vector::Auto => (0, 0),
+                Vector::Number(number) => (1, number),
+                Vector::Uninitialized => (2, 0),
+                Vector::Spurious => (3, 0),
+            };
+            writer.put_u8(kind);
+            writer.put_u8(number);
+            writer.put_bool(device.pulsed);
+            writer.put_bool(device.line);
+        }
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        if reader.get_u16()? as usize != self.devices.len() {
+            return Err(StateError::Invalid("interrupt devices"));
+        }
+        for device in &mut self.devices {
+            if reader.get_u8()? != device.level {
+                return Err(StateError::Invalid("interrupt devices"));
+            }
+            device.vector = match (reader.get_u8()?, reader.get_u8()?) {
+                (0, _) => Vector::Auto,
+                (1, number) => Vector::Number(number),
+                (2, _) => Vector::Uninitialized,
+                (3, _) => Vector::Spurious,
+                _ => return Err(StateError::Invalid("interrupt vector")),
+            };
+            device.pulsed = reader.get_bool()?;
+            device.line = reader.get_bool()?;
+        }
+        Ok(())
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{InterruptController, AutoInterruptController,
-        AUTOVECTOR_BASE};
+    use super::{InterruptController, AutoInterruptController, VectoredInterruptController, Vector,
+        AUTOVECTOR_BASE, SPURIOUS_INTERRUPT, UNINITIALIZED_INTERRUPT};
+    use cpu::{ConfiguredCore, Core, ProcessingState};
+    use ram::{AddressBus, PagedMem, SUPERVISOR_DATA};
+    use savestate::{Snapshot, StateError, StateReader, StateWriter};
 
     #[test]
     fn keeps_track_of_priority() {
@@ -74,4 +280,236 @@ mod tests {
         ctrl.reset_external_devices();
         assert_eq!(0, ctrl.highest_priority());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn vectored_devices_supply_their_vectors() {
+        let mut ctrl = VectoredInterruptController::new();
+        let mfp = ctrl.add_device(6, Vector::Number(0x40));
+        let timer = ctrl.add_device(5, Vector::Auto);
+        let duart = ctrl.add_device(4, Vector::Uninitialized);
+        ctrl.request_interrupt(duart);
+        ctrl.request_interrupt(timer);
+        ctrl.request_interrupt(mfp);
+        assert_eq!(6, ctrl.highest_priority());
+        assert_eq!(Some(0x40), ctrl.acknowledge_interrupt(6));
+        assert_eq!(5, ctrl.highest_priority());
+        assert_eq!(Some(AUTOVECTOR_BASE + 5), ctrl.acknowledge_interrupt(5));
+        assert_eq!(Some(UNINITIALIZED_INTERRUPT), ctrl.acknowledge_interrupt(4));
+        assert_eq!(0, ctrl.highest_priority());
+        // once the program initializes it
+        ctrl.set_vector(duart, Vector::Number(0x50));
+        ctrl.request_interrupt(duart);
+        assert_eq!(Some(0x50), ctrl.acknowledge_interrupt(4));
+    }
+    #[test]
+    fn devices_at_a_level_are_daisy_chained() {
+        let mut ctrl = VectoredInterruptController::new();
+        let first = ctrl.add_device(5, Vector::Number(0x40));
+        let second = ctrl.add_device(5, Vector::Number(0x41));
+        ctrl.request_interrupt(second);
+        ctrl.request_interrupt(first);
+        assert_eq!(Some(0x40), ctrl.acknowledge_interrupt(5));
+        assert!(ctrl.is_requesting(second));
+        assert_eq!(5, ctrl.highest_priority());
+        assert_eq!(Some(0x41), ctrl.acknowledge_interrupt(5));
+        assert_eq!(0, ctrl.highest_priority());
+    }
+    #[test]
+    fn held_lines_survive_the_acknowledge() {
+        let mut ctrl = VectoredInterruptController::new();
+        let held = ctrl.add_device(5, Vector::Number(0x40));
+        let pulsed = ctrl.add_device(3, Vector::Auto);
+        ctrl.assert_line(held);
+        ctrl.request_interrupt(pulsed);
+        assert_eq!(Some(0x40), ctrl.acknowledge_interrupt(5));
+        assert_eq!(Some(0x40), ctrl.acknowledge_interrupt(5));
+        assert_eq!(5, ctrl.highest_priority());
+        ctrl.deassert_line(held);
+        assert_eq!(3, ctrl.highest_priority());
+        assert_eq!(Some(AUTOVECTOR_BASE + 3), ctrl.acknowledge_interrupt(3));
+        assert_eq!(0, ctrl.highest_priority());
+        // a line asserted, and pulsed, stays requesting once acknowledged
+        ctrl.set_line(held, true);
+        ctrl.request_interrupt(held);
+        ctrl.acknowledge_interrupt(5);
+        assert!(ctrl.is_requesting(held));
+        ctrl.set_line(held, false);
+        assert!(!ctrl.is_requesting(held));
+    }
+    #[test]
+    fn unanswered_acknowledges_are_spurious() {
+        let mut ctrl = VectoredInterruptController::new();
+        let device = ctrl.add_device(3, Vector::Spurious);
+        ctrl.request_interrupt(device);
+        assert_eq!(Some(SPURIOUS_INTERRUPT), ctrl.acknowledge_interrupt(3));
+        // a request withdrawn before the acknowledge
+        ctrl.set_vector(device, Vector::Number(0x40));
+        ctrl.request_interrupt(device);
+        ctrl.cancel_interrupt(device);
+        assert_eq!(Some(SPURIOUS_INTERRUPT), ctrl.acknowledge_interrupt(3));
+    }
+    #[test]
+    fn vectored_requests_are_cleared_on_external_device_reset() {
+        let mut ctrl = VectoredInterruptController::new();
+        let device = ctrl.add_device(2, Vector::Auto);
+        ctrl.request_interrupt(device);
+        ctrl.reset_external_devices();
+        assert_eq!(0, ctrl.highest_priority());
+        assert_eq!(Vector::Auto, ctrl.vector(device));
+    }
+    #[test]
+    fn vectored_state_is_saved() {
+        let mut ctrl = VectoredInterruptController::new();
+        let line = ctrl.add_device(6, Vector::Number(0x40));
+        let device = ctrl.add_device(2, Vector::Uninitialized);
+        ctrl.assert_line(line);
+        ctrl.request_interrupt(device);
+        ctrl.set_vector(device, Vector::Number(0x60));
+        let mut writer = StateWriter::new();
+        ctrl.save(&mut writer);
+        let bytes = writer.into_bytes();
+
+        let mut restored = VectoredInterruptController::new();
+        restored.add_device(6, Vector::Auto);
+        restored.add_device(2, Vector::Auto);
+        restored.load(&mut StateReader::new(&bytes).unwrap()).unwrap();
+        assert_eq!(Vector::Number(0x40), restored.vector(0));
+        assert!(restored.is_line_asserted(0));
+        restored.deassert_line(0);
+        assert_eq!(2, restored.highest_priority());
+        assert_eq!(Some(0x60), restored.acknowledge_interrupt(2));
+
+        let mut other = VectoredInterruptController::new();
+        other.add_device(6, Vector::Auto);
+        assert_eq!(Err(StateError::Invalid("interrupt devices")), other.load(&mut StateReader::new(&bytes).unwrap()));
+    }
+    #[test]
+    fn the_core_takes_the_supplied_vector() {
+        let mut mem = PagedMem::new(0);
+        // STOP #$2000, with vector $40 going to $2000 and the uninitialized
+        // interrupt vector to $3000
+        mem.write_long(SUPERVISOR_DATA, 0, 0x8000);
+        mem.write_long(SUPERVISOR_DATA, 4, 0x1000);
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x4e72);
+        mem.write_word(SUPERVISOR_DATA, 0x1002, 0x2000);
+        mem.write_long(SUPERVISOR_DATA, 0x40 * 4, 0x2000);
+        mem.write_long(SUPERVISOR_DATA, u32::from(UNINITIALIZED_INTERRUPT) * 4, 0x3000);
+        let mut ctrl = VectoredInterruptController::new();
+        let mfp = ctrl.add_device(6, Vector::Number(0x40));
+        let duart = ctrl.add_device(4, Vector::Uninitialized);
+        let mut core = ConfiguredCore::new_with(0, ctrl, mem);
+        core.reset();
+        core.execute1();
+        core.int_ctrl.request_interrupt(duart);
+        core.int_ctrl.request_interrupt(mfp);
+        core.execute1();
+        assert_eq!(0x2000, core.pc);
+        assert_eq!(0x2600, core.status_register());
+        assert!(core.int_ctrl.is_requesting(duart));
+    }
+
+    // ADDQ.L #1,D7; BRA.S *
+    const COUNT: &[u16] = &[0x5287, 0x60fe];
+    // ADDQ.L #1,D7; RTE
+    const COUNT_AND_RETURN: &[u16] = &[0x5287, 0x4e73];
+
+    // runs the code at $1000, with the autovectored interrupts going to
+    // the handler at $2000
+    fn vectored_core(code: &[u16], handler: &[u16]) -> ConfiguredCore<VectoredInterruptController, PagedMem> {
+        let mut mem = PagedMem::new(0);
+        mem.write_long(SUPERVISOR_DATA, 0, 0x8000);
+        mem.write_long(SUPERVISOR_DATA, 4, 0x1000);
+        for level in 1..8 {
+            mem.write_long(SUPERVISOR_DATA, u32::from(AUTOVECTOR_BASE + level) * 4, 0x2000);
+        }
+        for (i, &word) in code.iter().enumerate() {
+            mem.write_word(SUPERVISOR_DATA, 0x1000 + 2 * i as u32, u32::from(word));
+        }
+        for (i, &word) in handler.iter().enumerate() {
+            mem.write_word(SUPERVISOR_DATA, 0x2000 + 2 * i as u32, u32::from(word));
+        }
+        let mut core = ConfiguredCore::new_with(0, VectoredInterruptController::new(), mem);
+        core.reset();
+        core
+    }
+
+    fn run(core: &mut ConfiguredCore<VectoredInterruptController, PagedMem>, instructions: usize) {
+        for _ in 0..instructions {
+            core.execute1();
+        }
+    }
+
+    #[test]
+    fn a_held_level_7_line_interrupts_once_per_edge() {
+        // BRA.S *
+        let mut core = vectored_core(&[0x60fe], COUNT);
+        let nmi = core.int_ctrl.add_device(7, Vector::Auto);
+        core.int_ctrl.assert_line(nmi);
+        run(&mut core, 10);
+        // the handler runs at level 7, so only a new edge interrupts it
+        assert_eq!(1, core.dar[7]);
+        core.int_ctrl.deassert_line(nmi);
+        run(&mut core, 1);
+        core.int_ctrl.assert_line(nmi);
+        run(&mut core, 10);
+        assert_eq!(2, core.dar[7]);
+        // nor does a drop the core doesn't get to see
+        core.int_ctrl.deassert_line(nmi);
+        core.int_ctrl.assert_line(nmi);
+        run(&mut core, 10);
+        assert_eq!(2, core.dar[7]);
+    }
+
+    #[test]
+    fn every_pulse_on_level_7_interrupts() {
+        let mut core = vectored_core(&[0x60fe], COUNT);
+        let nmi = core.int_ctrl.add_device(7, Vector::Auto);
+        for count in 1..4 {
+            core.int_ctrl.request_interrupt(nmi);
+            run(&mut core, 3);
+            assert_eq!(count, core.dar[7]);
+        }
+    }
+
+    #[test]
+    fn held_lines_interrupt_until_deasserted() {
+        let mut core = vectored_core(&[0x60fe], COUNT_AND_RETURN);
+        let device = core.int_ctrl.add_device(3, Vector::Auto);
+        core.sr_to_flags(0x2000);
+        core.int_ctrl.assert_line(device);
+        run(&mut core, 10);
+        let count = core.dar[7];
+        assert!(count > 1, "{}", count);
+        core.int_ctrl.deassert_line(device);
+        run(&mut core, 10);
+        assert!(core.dar[7] <= count + 1);
+        assert_eq!(0x1000, core.pc);
+    }
+
+    #[test]
+    fn masked_pending_interrupts_wake_stop_once_unmasked() {
+        // STOP #$2700, with a level 3 interrupt pending
+        let mut core = vectored_core(&[0x4e72, 0x2700], COUNT);
+        let device = core.int_ctrl.add_device(3, Vector::Auto);
+        let nmi = core.int_ctrl.add_device(7, Vector::Auto);
+        core.int_ctrl.assert_line(device);
+        run(&mut core, 5);
+        assert_eq!(ProcessingState::Stopped, core.processing_state);
+        assert_eq!(0, core.dar[7]);
+        // though level 7 isn't masked
+        core.int_ctrl.request_interrupt(nmi);
+        run(&mut core, 2);
+        assert_eq!(1, core.dar[7]);
+        assert_eq!(0x2700, core.status_register());
+
+        // STOP #$2000, unmasking the interrupt pending since the reset
+        let mut core = vectored_core(&[0x4e72, 0x2000], COUNT);
+        let device = core.int_ctrl.add_device(3, Vector::Auto);
+        core.int_ctrl.assert_line(device);
+        run(&mut core, 1);
+        run(&mut core, 2);
+        assert_eq!(1, core.dar[7]);
+        assert_eq!(0x2300, core.status_register());
+        assert!(core.processing_state.running());
+    }
+}
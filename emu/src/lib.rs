@@ -2,13 +2,24 @@
 #[cfg(test)]
 extern crate itertools;
 extern crate r68k_common;
-#[cfg(test)]
 extern crate r68k_tools;
+#[cfg(feature = "jit")]
+extern crate libc;
 
 pub mod cpu;
 #[macro_use]
 pub mod ram;
 pub mod interrupts;
+pub mod peripherals;
+pub mod batch;
+#[cfg(feature = "cycles")]
+pub mod machine;
+pub mod savestate;
+pub mod gdbstub;
+pub mod hosted;
+pub mod easy68k;
+pub mod semihosting;
+pub mod linea;
 pub mod musashi;
 
 
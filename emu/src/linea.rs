@@ -0,0 +1,201 @@
+// Host-side handlers for Line-A traps, the unimplemented $Axxx opcodes
+// that classic Mac OS and Atari's Line-A graphics use as system calls, for
+// emulating such an OS's APIs in Rust rather than running its ROM. Each
+// handler gets the core, to read and change registers and memory, and the
+// opcode, and either completes the trap or leaves it to the program's
+// vector 10 handler after all:
+//
+//     let mut traps = LineATraps::new();
+//     traps.register(0xa9c8, |core, _| {   // _SysBeep
+//         core.dar()[8 + 7] += 2;
+//         Ok(Trap::Return(Cycles(34)))
+//     });
+//     core.execute_with_state(cycles, &mut traps);
+use cpu::{Callbacks, Core, Cycles, Exception, Result, EXCEPTION_UNIMPLEMENTED_1010};
+
+pub const FIRST_LINE_A: u16 = 0xa000;
+pub const LAST_LINE_A: u16 = 0xafff;
+
+// What a handler did with a trap
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trap {
+    // it completed, taking these cycles, and the program continues
+    Return(Cycles),
+    // the line 1010 emulator exception happens anyway
+    Exception,
+}
+
+pub type TrapHandler = Box<dyn FnMut(&mut dyn Core, u16) -> Result<Trap>>;
+
+struct Registration {
+    first: u16,
+    last: u16, // inclusive
+    handler: TrapHandler,
+}
+
+#[derive(Default)]
+pub struct LineATraps {
+    registrations: Vec<Registration>,
+}
+
+impl LineATraps {
+    pub fn new() -> LineATraps {
+        LineATraps { registrations: Vec::new() }
+    }
+
+    pub fn register<F>(&mut self, opcode: u16, handler: F)
+        where F: FnMut(&mut dyn Core, u16) -> Result<Trap> + 'static
+    {
+        self.register_range(opcode, opcode, handler);
+    }
+
+    // Handles the opcodes first to last. Where registrations overlap, the
+    // latest one handles the opcode, so that a catch-all for a range can
+    // be registered before the traps that are implemented.
+    pub fn register_range<F>(&mut self, first: u16, last: u16, handler: F)
+        where F: FnMut(&mut dyn Core, u16) -> Result<Trap> + 'static
+    {
+        assert!(FIRST_LINE_A <= first && first <= last && last <= LAST_LINE_A, "{:04x}-{:04x} aren't Line-A opcodes", first, last);
+        self.registrations.push(Registration { first, last, handler: Box::new(handler) });
+    }
+
+    pub fn is_registered(&self, opcode: u16) -> bool {
+        self.registrations.iter().any(|r| r.first <= opcode && opcode <= r.last)
+    }
+
+    // Handles a Line-A exception whose opcode has a handler, and returns
+    // any other exception to be processed as usual, so that other
+    // Callbacks can dispatch to these traps too. The handler runs with the
+    // pc past the trap instruction, and may jump elsewhere.
+    pub fn dispatch(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+        let (opcode, pc) = match ex {
+            Exception::UnimplementedInstruction(opcode, pc, EXCEPTION_UNIMPLEMENTED_1010) => (opcode, pc),
+            _ => return Err(ex),
+        };
+        let registration = match self.registrations.iter_mut().rev().find(|r| r.first <= opcode && opcode <= r.last) {
+            Some(registration) => registration,
+            None => return Err(ex),
+        };
+        core.jump(pc.wrapping_add(2));
+        match (registration.handler)(core, opcode)? {
+            Trap::Return(cycles) => Ok(cycles),
+            Trap::Exception => Err(ex),
+        }
+    }
+}
+
+impl Callbacks for LineATraps {
+    fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+        self.dispatch(core, ex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::{TestCore, EXCEPTION_ILLEGAL_INSTRUCTION};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    const CODE: u32 = 0x40;
+    const LINE_A_HANDLER: u32 = 0x1000;
+    const ILLEGAL_HANDLER: u32 = 0x2000;
+    const STACK: u32 = 0x4000;
+
+    fn with_code(code: &[u16]) -> TestCore {
+        let bytes: Vec<u8> = code.iter().flat_map(|word| word.to_be_bytes().to_vec()).collect();
+        let mut core = TestCore::new_mem(CODE, &bytes);
+        core.dar[15] = STACK;
+        core.write_data_long(u32::from(EXCEPTION_UNIMPLEMENTED_1010) * 4, LINE_A_HANDLER).unwrap();
+        core.write_data_long(u32::from(EXCEPTION_ILLEGAL_INSTRUCTION) * 4, ILLEGAL_HANDLER).unwrap();
+        core
+    }
+
+    #[test]
+    fn handlers_complete_traps() {
+        // _BlockMove-alike: copies D0 bytes from A0 to A1, then MOVEQ #1,D1
+        let mut core = with_code(&[0xa02e, 0x7201]);
+        core.write_data_long(0x3000, 0x1234_5678).unwrap();
+        core.dar[0] = 4;
+        core.dar[8] = 0x3000;
+        core.dar[9] = 0x3100;
+        let mut traps = LineATraps::new();
+        traps.register(0xa02e, |core, opcode| {
+            assert_eq!(0xa02e, opcode);
+            let (count, from, to) = (core.dar()[0], core.dar()[8], core.dar()[9]);
+            for i in 0..count {
+                let byte = core.read_data_byte(from + i)?;
+                core.write_data_byte(to + i, byte)?;
+            }
+            core.dar()[0] = 0; // noErr
+            Ok(Trap::Return(Cycles(100)))
+        });
+        let cycles = core.execute_with_state(1, &mut traps);
+        assert_eq!(Cycles(100), cycles);
+        assert_eq!(CODE + 2, core.pc);
+        assert_eq!(0x1234_5678, core.read_data_long(0x3100).unwrap());
+        assert_eq!(0, core.dar[0]);
+        core.execute_with_state(1, &mut traps);
+        assert_eq!(1, core.dar[1]);
+    }
+
+    #[test]
+    fn handlers_fall_back_to_the_exception() {
+        let mut core = with_code(&[0xa9f4]);
+        let mut traps = LineATraps::new();
+        traps.register(0xa9f4, |_, _| Ok(Trap::Exception));
+        core.execute_with_state(1, &mut traps);
+        assert_eq!(LINE_A_HANDLER, core.pc);
+        // which returns to the trap instruction
+        assert_eq!(CODE, core.read_data_long(core.dar[15] + 2).unwrap());
+    }
+
+    #[test]
+    fn unregistered_opcodes_and_other_exceptions_are_processed_as_usual() {
+        let mut traps = LineATraps::new();
+        traps.register(0xa000, |_, _| Ok(Trap::Return(Cycles(0))));
+        let mut core = with_code(&[0xa001]);
+        core.execute_with_state(1, &mut traps);
+        assert_eq!(LINE_A_HANDLER, core.pc);
+        let mut core = with_code(&[0x4afc]); // ILLEGAL
+        core.execute_with_state(1, &mut traps);
+        assert_eq!(ILLEGAL_HANDLER, core.pc);
+    }
+
+    #[test]
+    fn the_latest_registration_handles_an_opcode() {
+        let handled = Rc::new(Cell::new(0));
+        let mut traps = LineATraps::new();
+        let catch_all = handled.clone();
+        traps.register_range(0xa800, LAST_LINE_A, move |_, opcode| {
+            catch_all.set(opcode);
+            Ok(Trap::Return(Cycles(34)))
+        });
+        // a toolbox trap popping its return address, as with the auto-pop
+        // bit set
+        traps.register(0xace0, |core, _| {
+            let sp = core.dar()[15];
+            let to = core.read_data_long(sp)?;
+            core.dar()[15] = sp + 4;
+            core.jump(to);
+            Ok(Trap::Return(Cycles(34)))
+        });
+        assert!(traps.is_registered(0xa9ff) && !traps.is_registered(0xa7ff));
+
+        let mut core = with_code(&[0xa9ff, 0xace0]);
+        core.execute_with_state(1, &mut traps);
+        assert_eq!((0xa9ff, CODE + 2), (handled.get(), core.pc));
+        core.dar[15] -= 4;
+        let sp = core.dar[15];
+        core.write_data_long(sp, 0x500).unwrap();
+        core.execute_with_state(1, &mut traps);
+        assert_eq!(0xa9ff, handled.get());
+        assert_eq!((0x500, sp + 4), (core.pc, core.dar[15]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn only_line_a_opcodes_can_be_registered() {
+        LineATraps::new().register_range(0xa000, 0xb000, |_, _| Ok(Trap::Exception));
+    }
+}
@@ -0,0 +1,292 @@
+// A machine of several cores (say a main CPU and a sound CPU) run in
+// lockstep on a global clock. Each core runs a timeslice of cycles at a
+// time, and timed events, scheduled by the host or by devices through a
+// Scheduler, run between timeslices. A timeslice ends early at the next
+// event, so an event at cycle N happens at the first instruction boundary
+// of each core from cycle N on. Cores share memory by mapping the same
+// SharedRam into their buses.
+use cpu::{ConfiguredCore, Cycles};
+use interrupts::{AutoInterruptController, InterruptController};
+use ram::AddressBus;
+use std::sync::{Arc, Mutex};
+
+pub type Action<T, A> = Box<dyn FnOnce(&mut Machine<T, A>) + Send>;
+
+struct Event<T: InterruptController, A: AddressBus> {
+    clock: u64,
+    action: Action<T, A>,
+}
+
+struct Queue<T: InterruptController, A: AddressBus> {
+    now: u64,
+    events: Vec<Event<T, A>>, // ordered by clock, then by when scheduled
+}
+
+// A handle to the event queue of a machine. Clones share the queue, so
+// devices can keep one to schedule events of their own.
+pub struct Scheduler<T: InterruptController, A: AddressBus> {
+    queue: Arc<Mutex<Queue<T, A>>>,
+}
+
+impl<T: InterruptController, A: AddressBus> Clone for Scheduler<T, A> {
+    fn clone(&self) -> Self {
+        Scheduler { queue: self.queue.clone() }
+    }
+}
+
+impl<T: InterruptController, A: AddressBus> Scheduler<T, A> {
+    fn new() -> Scheduler<T, A> {
+        Scheduler { queue: Arc::new(Mutex::new(Queue { now: 0, events: Vec::new() })) }
+    }
+
+    // the global clock, in cycles
+    pub fn now(&self) -> u64 {
+        self.queue.lock().unwrap().now
+    }
+
+    // Runs the action at the given cycle, or as soon as possible if that
+    // has already passed
+    pub fn at<F>(&self, clock: u64, action: F) where F: FnOnce(&mut Machine<T, A>) + Send + 'static {
+        let mut queue = self.queue.lock().unwrap();
+        let index = queue.events.iter().position(|e| e.clock > clock).unwrap_or(queue.events.len());
+        queue.events.insert(index, Event { clock, action: Box::new(action) });
+    }
+
+    pub fn after<F>(&self, cycles: u64, action: F) where F: FnOnce(&mut Machine<T, A>) + Send + 'static {
+        let now = self.now();
+        self.at(now + cycles, action);
+    }
+
+    fn next_event(&self) -> Option<u64> {
+        self.queue.lock().unwrap().events.first().map(|e| e.clock)
+    }
+
+    fn due_action(&self) -> Option<Action<T, A>> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.events.first().is_some_and(|e| e.clock <= queue.now) {
+            Some(queue.events.remove(0).action)
+        } else {
+            None
+        }
+    }
+
+    fn advance_to(&self, clock: u64) {
+        self.queue.lock().unwrap().now = clock;
+    }
+}
+
+impl<A: AddressBus> Scheduler<AutoInterruptController, A> {
+    // raises an interrupt of the given level on a core, at a cycle
+    pub fn interrupt_at(&self, clock: u64, core: usize, level: u8) {
+        self.at(clock, move |machine| {
+            machine.core_mut(core).int_ctrl.request_interrupt(level);
+        });
+    }
+}
+
+struct Slot<T: InterruptController, A: AddressBus> {
+    core: ConfiguredCore<T, A>,
+    clock: u64, // may run past the global clock by part of an instruction
+}
+
+pub struct Machine<T: InterruptController, A: AddressBus> {
+    slots: Vec<Slot<T, A>>,
+    timeslice: u64,
+    scheduler: Scheduler<T, A>,
+}
+
+impl<T: InterruptController, A: AddressBus> Machine<T, A> {
+    // Shorter timeslices keep the cores closer together, at the cost of
+    // some speed
+    pub fn new(timeslice: u32) -> Machine<T, A> {
+        assert!(timeslice > 0);
+        Machine { slots: Vec::new(), timeslice: u64::from(timeslice), scheduler: Scheduler::new() }
+    }
+
+    // adds a core that starts at the current clock, and returns its index
+    pub fn add_core(&mut self, core: ConfiguredCore<T, A>) -> usize {
+        let clock = self.clock();
+        self.slots.push(Slot { core, clock });
+        self.slots.len() - 1
+    }
+
+    pub fn cores(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn core(&self, index: usize) -> &ConfiguredCore<T, A> {
+        &self.slots[index].core
+    }
+
+    pub fn core_mut(&mut self, index: usize) -> &mut ConfiguredCore<T, A> {
+        &mut self.slots[index].core
+    }
+
+    pub fn scheduler(&self) -> Scheduler<T, A> {
+        self.scheduler.clone()
+    }
+
+    pub fn clock(&self) -> u64 {
+        self.scheduler.now()
+    }
+
+    // Runs all cores for the given number of cycles of the global clock,
+    // along with the events that fall due meanwhile
+    pub fn run(&mut self, cycles: u64) {
+        let end = self.clock() + cycles;
+        loop {
+            self.run_due_events();
+            let now = self.clock();
+            if now >= end {
+                break;
+            }
+            let mut until = (now + self.timeslice).min(end);
+            if let Some(next) = self.scheduler.next_event() {
+                until = until.min(next);
+            }
+            for slot in &mut self.slots {
+                if slot.clock < until {
+                    let Cycles(used) = slot.core.execute((until - slot.clock) as i32);
+                    slot.clock += used as u64;
+                }
+            }
+            self.scheduler.advance_to(until);
+        }
+    }
+
+    fn run_due_events(&mut self) {
+        while let Some(action) = self.scheduler.due_action() {
+            action(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Machine, Scheduler};
+    use cpu::{ConfiguredCore, ProcessingState};
+    use interrupts::AutoInterruptController;
+    use ram::{AddressBus, AddressSpace, BusResult, MappedBus, SUPERVISOR_DATA};
+    use ram::mappedbus::{Device, SharedRam, Size};
+    use std::sync::{Arc, Mutex};
+
+    type BusMachine = Machine<AutoInterruptController, MappedBus>;
+
+    // RAM from 0, with the stack at $8000, code at $1000 and level 4
+    // interrupts going to $1100
+    fn core_with(code: &[u16], setup: impl FnOnce(&mut MappedBus)) -> ConfiguredCore<AutoInterruptController, MappedBus> {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0, 0x10000);
+        bus.write_long(SUPERVISOR_DATA, 0, 0x8000);
+        bus.write_long(SUPERVISOR_DATA, 4, 0x1000);
+        bus.write_long(SUPERVISOR_DATA, 28 * 4, 0x1100);
+        // MOVEQ #1,D7; STOP #$2000
+        for (i, &word) in [0x7e01, 0x4e72, 0x2000].iter().enumerate() {
+            bus.write_word(SUPERVISOR_DATA, 0x1100 + 2 * i as u32, word);
+        }
+        for (i, &word) in code.iter().enumerate() {
+            bus.write_word(SUPERVISOR_DATA, 0x1000 + 2 * i as u32, u32::from(word));
+        }
+        setup(&mut bus);
+        let mut core = ConfiguredCore::new_with(0, AutoInterruptController::new(), bus);
+        core.reset();
+        core
+    }
+
+    // BRA.S *, 10 cycles
+    const LOOP: &[u16] = &[0x60fe];
+    // STOP #$2000
+    const STOP: &[u16] = &[0x4e72, 0x2000];
+
+    #[test]
+    fn cores_run_in_lockstep() {
+        let mut machine = BusMachine::new(100);
+        machine.add_core(core_with(LOOP, |_| ()));
+        machine.add_core(core_with(STOP, |_| ()));
+        machine.run(1005);
+        assert_eq!(1005, machine.clock());
+        for slot in &machine.slots {
+            assert!(slot.clock >= 1005 && slot.clock < 1015, "{}", slot.clock);
+        }
+        assert_eq!(ProcessingState::Stopped, machine.core(1).processing_state);
+    }
+
+    #[test]
+    fn events_run_at_their_cycle_in_order() {
+        let mut machine = BusMachine::new(100);
+        machine.add_core(core_with(LOOP, |_| ()));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let scheduler = machine.scheduler();
+        for &(clock, name) in &[(250, "b"), (30, "a"), (250, "c")] {
+            let seen = seen.clone();
+            scheduler.at(clock, move |machine| seen.lock().unwrap().push((machine.clock(), name)));
+        }
+        {
+            let seen = seen.clone();
+            scheduler.at(400, move |machine| {
+                // an event can schedule the next one
+                machine.scheduler().after(5, move |machine| seen.lock().unwrap().push((machine.clock(), "d")));
+            });
+        }
+        machine.run(1000);
+        assert_eq!(vec![(30, "a"), (250, "b"), (250, "c"), (405, "d")], *seen.lock().unwrap());
+        assert!(machine.slots[0].clock >= 1000);
+    }
+
+    #[test]
+    fn interrupts_can_be_raised_at_a_cycle() {
+        let mut machine = BusMachine::new(1000);
+        machine.add_core(core_with(STOP, |_| ()));
+        machine.add_core(core_with(STOP, |_| ()));
+        machine.scheduler().interrupt_at(500, 1, 4);
+        machine.run(499);
+        assert_eq!(0, machine.core(1).dar[7]);
+        machine.run(200);
+        assert_eq!(0, machine.core(0).dar[7]);
+        assert_eq!(1, machine.core(1).dar[7]);
+    }
+
+    // a timer that interrupts its core as many cycles after it is
+    // written as the value written
+    struct Timer {
+        scheduler: Scheduler<AutoInterruptController, MappedBus>,
+        core: usize,
+    }
+
+    impl Device for Timer {
+        fn read(&mut self, _: AddressSpace, _: u32, _: Size) -> BusResult<u32> {
+            Ok(0)
+        }
+        fn write(&mut self, _: AddressSpace, _: u32, _: Size, value: u32) -> BusResult<()> {
+            let clock = self.scheduler.now() + u64::from(value);
+            self.scheduler.interrupt_at(clock, self.core, 4);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn devices_can_schedule_events() {
+        let mut machine = BusMachine::new(50);
+        let scheduler = machine.scheduler();
+        // MOVE.W #2000,$ff0000; STOP #$2000
+        let code = &[0x33fc, 2000, 0x00ff, 0x0000, 0x4e72, 0x2000];
+        let core = core_with(code, |bus| bus.map_device(0xff0000, 2, Box::new(Timer { scheduler, core: 0 })));
+        machine.add_core(core);
+        machine.run(1500);
+        assert_eq!(0, machine.core(0).dar[7]);
+        machine.run(1000);
+        assert_eq!(1, machine.core(0).dar[7]);
+    }
+
+    #[test]
+    fn cores_can_share_memory() {
+        let ram = SharedRam::new(0x1000);
+        let mut machine = BusMachine::new(100);
+        // MOVE.W #$1234,$10000; STOP #$2000
+        machine.add_core(core_with(&[0x33fc, 0x1234, 0x0001, 0x0000, 0x4e72, 0x2000], |bus| bus.map_shared(0x10000, &ram)));
+        // wait: TST.W $20000; BEQ.S wait; MOVE.W $20000,D0; STOP #$2000
+        machine.add_core(core_with(&[0x4a79, 0x0002, 0x0000, 0x67f8, 0x3039, 0x0002, 0x0000, 0x4e72, 0x2000], |bus| bus.map_shared(0x20000, &ram)));
+        machine.run(1000);
+        assert_eq!(0x1234, machine.core(1).dar[0]);
+    }
+}
@@ -300,6 +300,17 @@ pub fn roundtrip_register(reg: Register, value: u32) -> u32 {
 
 use cpu::{Cycles, TestCore};
 
+fn musashi_cpu_type(cpu_type: ::cpu::CpuType) -> CpuType {
+    match cpu_type {
+        ::cpu::CpuType::M68000 => CpuType::M68000,
+        ::cpu::CpuType::M68010 => CpuType::M68010,
+        ::cpu::CpuType::M68EC020 => CpuType::M68EC020,
+        ::cpu::CpuType::M68020 => CpuType::M68020,
+        ::cpu::CpuType::M68030 => CpuType::M68030,
+        ::cpu::CpuType::Cpu32 => panic!("Musashi doesn't emulate the CPU32"),
+    }
+}
+
 static REGS: [Register; 16] = [
     Register::D0,
     Register::D1,
@@ -335,7 +346,7 @@ pub fn initialize_musashi(core: &mut TestCore, memory_initializer: u32) {
         m68ki_address_space = 5;
         initialize_musashi_memory(memory_initializer);
         m68k_init();
-        m68k_set_cpu_type(CpuType::M68000);
+        m68k_set_cpu_type(musashi_cpu_type(core.cpu_type()));
         m68k_write_memory_32(0, core.ssp());
         m68k_write_memory_32(4, core.pc);
         m68k_pulse_reset();
@@ -350,6 +361,9 @@ pub fn initialize_musashi(core: &mut TestCore, memory_initializer: u32) {
         m68k_set_reg(Register::SR, core.status_register() as u32);
         m68k_set_reg(Register::USP, core.usp());
         m68k_set_reg(Register::ISP, core.ssp());
+        m68k_set_reg(Register::VBR, core.vbr);
+        m68k_set_reg(Register::SFC, core.sfc);
+        m68k_set_reg(Register::DFC, core.dfc);
         for (i, &reg) in REGS.iter().enumerate() {
             if i != 15 {
                 m68k_set_reg(reg, core.dar[i]);
@@ -395,6 +409,9 @@ pub fn execute(core: &mut TestCore, num_cycles: i32) -> Cycles {
             core.dar[15] = m68k_get_reg(ptr::null_mut(), Register::USP);
             core.inactive_ssp = m68k_get_reg(ptr::null_mut(), Register::ISP);
         }
+        core.vbr = m68k_get_reg(ptr::null_mut(), Register::VBR);
+        core.sfc = m68k_get_reg(ptr::null_mut(), Register::SFC);
+        core.dfc = m68k_get_reg(ptr::null_mut(), Register::DFC);
 
         Cycles(cycle_count)
     }
@@ -580,6 +597,7 @@ mod tests {
     }
 
     static mut OPCODE_UNDER_TEST: u16 = 0;
+    static mut CPU_TYPE_UNDER_TEST: ::cpu::CpuType = ::cpu::CpuType::M68000;
     extern crate once_cell;
     static mut MUSASHI_CORE: Option<TestCore> = None;
 
@@ -609,6 +627,7 @@ mod tests {
         allow_exception: bool,
     ) -> TestResult {
         let pc = 0x140;
+        let cpu_type = unsafe { CPU_TYPE_UNDER_TEST };
         let mem = unsafe {
             [
                 ((OPCODE_UNDER_TEST >> 8) & 0xff) as u8,
@@ -625,6 +644,9 @@ mod tests {
                 const STACK_MASK: u32 = 1024 - 16; // keep even
                 musashi.inactive_ssp = 0x128;
                 musashi.inactive_usp = 0x256;
+                // MOVES moves between the supervisor and the user spaces
+                musashi.sfc = USER_DATA.fc();
+                musashi.dfc = USER_DATA.fc();
                 for r in 0..8 {
                     musashi.dar[r] = 0;
                     musashi.dar[8 + r] = 0x128;
@@ -686,8 +708,11 @@ mod tests {
                 mem_mask: u32,
                 memory_initializer: u32,
                 allow_exception: bool,
+                cpu_type: ::cpu::CpuType,
             ) -> TestResult {
                 let mut r68k = musashi.clone(); // so very self-aware!
+                #[cfg(feature = "jit")]
+                compile_first_instruction(&mut r68k);
 
                 let musashi_cycles = reset_and_execute1(musashi, memory_initializer & mem_mask);
                 let r68k_cycles = r68k.execute(super::EXEC_CYCLES);
@@ -697,7 +722,8 @@ mod tests {
                 // the same spot) and we allow exceptions (or we would discard
                 // all results for those instructions that always result  in
                 // exceptions such as illegal/unimplemented or traps)
-                let can_compare_cycles =
+                // r68k keeps 68000 timings for the 020, Musashi does not
+                let can_compare_cycles = !cpu_type.is_020() &&
                     if let Some(vector) = memory_accesses_equal_unless_exception(&r68k) {
                         if musashi.pc != r68k.pc || !allow_exception {
                             true
@@ -722,13 +748,15 @@ mod tests {
 
             if let Some(ref mut core) = &mut MUSASHI_CORE {
                 core.reset_mem(pc, &mem, memory_initializer & mem_mask);
+                core.set_cpu_type(cpu_type);
                 setup_musashi_core(core, mem_mask, rs);
-                test_cores(core, mem_mask, memory_initializer, allow_exception)
+                test_cores(core, mem_mask, memory_initializer, allow_exception, cpu_type)
             } else {
                 let mut core =
                     TestCore::new_mem_init_for_tests(pc, &mem, memory_initializer & mem_mask);
+                core.set_cpu_type(cpu_type);
                 setup_musashi_core(&mut core, mem_mask, rs);
-                let ret = test_cores(&mut core, mem_mask, memory_initializer, allow_exception);
+                let ret = test_cores(&mut core, mem_mask, memory_initializer, allow_exception, cpu_type);
                 MUSASHI_CORE = Some(core.clone());
                 ret
             }
@@ -745,11 +773,31 @@ mod tests {
             qc!($opmask, $opcode, $fn_name, hammer_cores_allow_exception);
         };
     }
+    // the 68000 legal opcode list doesn't have the 68010 additions, so these
+    // try every opcode matching the mask
+    macro_rules! qc_010 {
+        ($opmask:ident, $opcode:ident, $fn_name:ident) => {
+            qc!($opmask, $opcode, $fn_name, hammer_cores_allow_exception, opcodes!($opmask, $opcode),
+                [::cpu::CpuType::M68010]);
+        };
+    }
+    macro_rules! qc_020 {
+        ($opmask:ident, $opcode:ident, $fn_name:ident) => {
+            qc!($opmask, $opcode, $fn_name, hammer_cores_allow_exception, opcodes!($opmask, $opcode),
+                [::cpu::CpuType::M68EC020]);
+        };
+    }
     macro_rules! qc {
         ($opmask:ident, $opcode:ident, $fn_name:ident) => {
             qc!($opmask, $opcode, $fn_name, hammer_cores_even_addresses);
         };
+        // the 68000 instructions are hammered on the 68010 too, which has
+        // its own exception frames and timings
         ($opmask:ident, $opcode:ident, $fn_name:ident, $hammer:ident) => {
+            qc!($opmask, $opcode, $fn_name, $hammer, opcodes($opmask, $opcode),
+                [::cpu::CpuType::M68000, ::cpu::CpuType::M68010]);
+        };
+        ($opmask:ident, $opcode:ident, $fn_name:ident, $hammer:ident, $opcodes:expr, $cpu_types:expr) => {
             #[test]
             // #[ignore]
             fn $fn_name() {
@@ -762,20 +810,23 @@ mod tests {
                 const QC_ROUNDS: usize = 16;
 
                 // for opcode in $opcode..($opcode + BLOCK_SIZE)
-                for opcode in opcodes($opmask, $opcode) {
-                    println!("Hammering {:016b} {} times", opcode, QC_ROUNDS);
-                    unsafe {
-                        // this is because I don't know how to make
-                        // hammer_cores take the opcode as a parameter and
-                        // we cannot simply use a closure either; see
-                        // https://github.com/BurntSushi/quickcheck/issues/56
-                        OPCODE_UNDER_TEST = opcode as u16;
+                for &cpu_type in $cpu_types.iter() {
+                    for opcode in $opcodes {
+                        println!("Hammering {:016b} on the {:?} {} times", opcode, cpu_type, QC_ROUNDS);
+                        unsafe {
+                            // this is because I don't know how to make
+                            // hammer_cores take the opcode as a parameter and
+                            // we cannot simply use a closure either; see
+                            // https://github.com/BurntSushi/quickcheck/issues/56
+                            OPCODE_UNDER_TEST = opcode as u16;
+                            CPU_TYPE_UNDER_TEST = cpu_type;
+                        }
+                        QuickCheck::new()
+                            .gen(Gen::new(QC_ROUNDS))
+                            .tests(QC_ROUNDS as u64)
+                            .min_tests_passed(QC_ROUNDS as u64)
+                            .quickcheck($hammer as fn(_, _) -> _);
                     }
-                    QuickCheck::new()
-                        .gen(Gen::new(QC_ROUNDS))
-                        .tests(QC_ROUNDS as u64)
-                        .min_tests_passed(QC_ROUNDS as u64)
-                        .quickcheck($hammer as fn(_, _) -> _);
                 }
 
                 ::release_lock!(MUSASHI_LOCK);
@@ -2210,6 +2261,105 @@ mod tests {
     // Put qc for RTE here
     qc8!(MASK_EXACT, OP_RTE_32, qc_rte_32);
 
+    qc_010!(MASK_EXACT, OP_RTE_32, qc_rte_32_010);
+    qc_010!(MASK_EXACT, OP_RTD_32, qc_rtd_32_010);
+    qc_010!(MASK_EXACT, OP_MOVEC_32_CR, qc_movec_32_cr_010);
+    qc_010!(MASK_EXACT, OP_MOVEC_32_RC, qc_movec_32_rc_010);
+
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRC_DN, qc_move_16_frc_dn_010);
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRC_AI, qc_move_16_frc_ai_010);
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRC_PI, qc_move_16_frc_pi_010);
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRC_PD, qc_move_16_frc_pd_010);
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRC_DI, qc_move_16_frc_di_010);
+    qc_010!(MASK_EXACT, OP_MOVE_16_FRC_AW, qc_move_16_frc_aw_010);
+    qc_010!(MASK_EXACT, OP_MOVE_16_FRC_AL, qc_move_16_frc_al_010);
+
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRS_DN, qc_move_16_frs_dn_010);
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRS_AI, qc_move_16_frs_ai_010);
+    qc_010!(MASK_OUT_Y, OP_MOVE_16_FRS_PD, qc_move_16_frs_pd_010);
+    qc_010!(MASK_EXACT, OP_MOVE_16_FRS_AW, qc_move_16_frs_aw_010);
+
+    qc_010!(MASK_OUT_Y, OP_MOVES_8_AI, qc_moves_8_ai_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_8_PI, qc_moves_8_pi_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_8_PD, qc_moves_8_pd_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_8_DI, qc_moves_8_di_010);
+    qc_010!(MASK_EXACT, OP_MOVES_8_AW, qc_moves_8_aw_010);
+    qc_010!(MASK_EXACT, OP_MOVES_8_AL, qc_moves_8_al_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_16_AI, qc_moves_16_ai_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_16_PI, qc_moves_16_pi_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_16_PD, qc_moves_16_pd_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_16_DI, qc_moves_16_di_010);
+    qc_010!(MASK_EXACT, OP_MOVES_16_AW, qc_moves_16_aw_010);
+    qc_010!(MASK_EXACT, OP_MOVES_16_AL, qc_moves_16_al_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_32_AI, qc_moves_32_ai_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_32_PI, qc_moves_32_pi_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_32_PD, qc_moves_32_pd_010);
+    qc_010!(MASK_OUT_Y, OP_MOVES_32_DI, qc_moves_32_di_010);
+    qc_010!(MASK_EXACT, OP_MOVES_32_AW, qc_moves_32_aw_010);
+    qc_010!(MASK_EXACT, OP_MOVES_32_AL, qc_moves_32_al_010);
+
+    qc_010!(MASK_LONIB, OP_TRAP, qc_trap_010);
+
+    // 020 additions, except for those where Musashi differs from the PRM
+    // (CAS2, CHK2, PACK, UNPK and TRAPcc with an operand)
+    qc_020!(MASK_EXACT, OP_RTE_32, qc_rte_32_020);
+    qc_020!(MASK_EXACT, OP_MOVEC_32_CR, qc_movec_32_cr_020);
+    qc_020!(MASK_EXACT, OP_MOVEC_32_RC, qc_movec_32_rc_020);
+
+    qc_020!(MASK_EXACT, OP_BRA_32, qc_bra_32_020);
+    qc_020!(MASK_EXACT, OP_BSR_32, qc_bsr_32_020);
+    qc_020!(MASK_EXACT, OP_BHI_32, qc_bhi_32_020);
+    qc_020!(MASK_EXACT, OP_BEQ_32, qc_beq_32_020);
+    qc_020!(MASK_EXACT, OP_BLT_32, qc_blt_32_020);
+
+    qc_020!(MASK_OUT_Y, OP_BFCHG_32_DN, qc_bfchg_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFCHG_32_AI, qc_bfchg_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFCLR_32_DN, qc_bfclr_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFCLR_32_AI, qc_bfclr_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFEXTS_32_DN, qc_bfexts_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFEXTS_32_AI, qc_bfexts_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFEXTU_32_DN, qc_bfextu_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFEXTU_32_AI, qc_bfextu_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFFFO_32_DN, qc_bfffo_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFFFO_32_AI, qc_bfffo_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFINS_32_DN, qc_bfins_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFINS_32_AI, qc_bfins_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFSET_32_DN, qc_bfset_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFSET_32_AI, qc_bfset_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_BFTST_32_DN, qc_bftst_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_BFTST_32_AI, qc_bftst_32_ai_020);
+
+    qc_020!(MASK_OUT_Y, OP_CAS_8_AI, qc_cas_8_ai_020);
+    qc_020!(MASK_OUT_Y, OP_CAS_16_AI, qc_cas_16_ai_020);
+    qc_020!(MASK_OUT_Y, OP_CAS_32_AI, qc_cas_32_ai_020);
+    qc_020!(MASK_OUT_Y, OP_CAS_32_PD, qc_cas_32_pd_020);
+
+    qc_020!(MASK_OUT_X_Y, OP_CHK_32_DN, qc_chk_32_dn_020);
+    qc_020!(MASK_OUT_X_Y, OP_CHK_32_AI, qc_chk_32_ai_020);
+    qc_020!(MASK_OUT_X, OP_CHK_32_IMM, qc_chk_32_imm_020);
+
+    qc_020!(MASK_OUT_Y, OP_DIVL_32_DN, qc_divl_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_DIVL_32_AI, qc_divl_32_ai_020);
+    qc_020!(MASK_EXACT, OP_DIVL_32_IMM, qc_divl_32_imm_020);
+
+    qc_020!(MASK_OUT_Y, OP_EXT_BL, qc_ext_bl_020);
+    qc_020!(MASK_OUT_Y, OP_LINK_32, qc_link_32_020);
+
+    qc_020!(MASK_OUT_Y, OP_MULL_32_DN, qc_mull_32_dn_020);
+    qc_020!(MASK_OUT_Y, OP_MULL_32_AI, qc_mull_32_ai_020);
+    qc_020!(MASK_EXACT, OP_MULL_32_IMM, qc_mull_32_imm_020);
+
+    qc_020!(MASK_EXACT, OP_TRAPT, qc_trapt_020);
+    qc_020!(MASK_EXACT, OP_TRAPF, qc_trapf_020);
+    qc_020!(MASK_EXACT, OP_TRAPHI, qc_traphi_020);
+    qc_020!(MASK_EXACT, OP_TRAPVS, qc_trapvs_020);
+
+    qc_020!(MASK_EXACT, OP_TST_8_IMM, qc_tst_8_imm_020);
+    qc_020!(MASK_OUT_Y, OP_TST_16_AN, qc_tst_16_an_020);
+    qc_020!(MASK_EXACT, OP_TST_16_PCDI, qc_tst_16_pcdi_020);
+    qc_020!(MASK_OUT_Y, OP_TST_32_AN, qc_tst_32_an_020);
+    qc_020!(MASK_EXACT, OP_TST_32_IMM, qc_tst_32_imm_020);
+
     // Put qc for RTR here
     qc8!(MASK_EXACT, OP_RTR_32, qc_rtr_32);
 
@@ -2778,17 +2928,44 @@ mod tests {
             }
         }};
     }
+    // In JIT mode r68k runs the instruction under test from compiled code,
+    // which has it fetched already (on a copy of the core, with the decode
+    // cache keeping the words)
+    #[cfg(feature = "jit")]
+    fn compile_first_instruction(r68k: &mut TestCore) {
+        let mut copy = r68k.clone();
+        copy.enable_decode_cache();
+        copy.execute(super::EXEC_CYCLES);
+        r68k.enable_jit();
+        r68k.jit.as_mut().unwrap().set_threshold(0);
+        r68k.decode_cache = copy.decode_cache.take();
+    }
+    // which also means r68k reads no program words of its own, so only
+    // the other accesses are compared
+    fn compared_ops(ops: Vec<Operation>) -> Vec<Operation> {
+        if cfg!(feature = "jit") {
+            ops.into_iter().filter(|op| match *op {
+                Operation::ReadWord(space, _, _) | Operation::ReadLong(space, _, _) =>
+                    space != SUPERVISOR_PROGRAM && space != USER_PROGRAM,
+                _ => true,
+            }).collect()
+        } else {
+            ops
+        }
+    }
     fn assert_all_memory_accesses_equal(r68k: &TestCore) {
-        if !equal(get_ops(), r68k.mem.logger.ops()) {
+        let musashi_ops = compared_ops(get_ops());
+        let r68k_ops = compared_ops(r68k.mem.logger.ops());
+        if !equal(&musashi_ops, &r68k_ops) {
             println!("musashi ops: ");
-            for op in get_ops() {
+            for op in &musashi_ops {
                 println!("{:?}", op);
             }
             println!("r68k ops: ");
-            for op in r68k.mem.logger.ops() {
+            for op in &r68k_ops {
                 println!("{:?}", op);
             }
-            assert_equal(get_ops(), r68k.mem.logger.ops());
+            assert_equal(&musashi_ops, &r68k_ops);
         }
     }
     fn memory_accesses_equal_unless_exception(r68k: &TestCore) -> Option<u8> {
@@ -2802,29 +2979,31 @@ mod tests {
         // If an exception occurred, do not compare beyond which vector
         // was taken as Mushashi during address errors, in some cases
         // also executed some instructions from the handler (now fixed)
-        if let Some(vector_read_index) = r68k.mem.logger.ops().iter().position(is_reading_vector) {
+        let musashi_ops = compared_ops(get_ops());
+        let r68k_ops = compared_ops(r68k.mem.logger.ops());
+        if let Some(vector_read_index) = r68k_ops.iter().position(is_reading_vector) {
             if !equal(
-                get_ops().iter().take(vector_read_index + 1),
-                r68k.mem.logger.ops().iter().take(vector_read_index + 1),
+                musashi_ops.iter().take(vector_read_index + 1),
+                r68k_ops.iter().take(vector_read_index + 1),
             ) {
                 println!("musashi ops: ");
-                for op in get_ops() {
+                for op in &musashi_ops {
                     println!("{:?}", op);
                 }
                 println!("r68k ops: ");
-                for op in r68k.mem.logger.ops() {
+                for op in &r68k_ops {
                     println!("{:?}", op);
                 }
                 assert_equal(
-                    get_ops().iter().take(vector_read_index + 1),
-                    r68k.mem.logger.ops().iter().take(vector_read_index + 1),
+                    musashi_ops.iter().take(vector_read_index + 1),
+                    r68k_ops.iter().take(vector_read_index + 1),
                 );
             }
 
             // If we got this far, the memory accesses up to, and
             // including the vector read match up, but we cannot
             // compare further
-            let vector = match r68k.mem.logger.ops()[vector_read_index] {
+            let vector = match r68k_ops[vector_read_index] {
                 Operation::ReadLong(SUPERVISOR_DATA, addr, _) => addr / 4,
                 x => panic!("Unexpectedly got {:?}", x),
             };
@@ -2840,6 +3019,9 @@ mod tests {
         core_eq!(musashi, r68k.status_register());
         core_eq!(musashi, r68k.ssp());
         core_eq!(musashi, r68k.usp());
+        core_eq!(musashi, r68k.vbr);
+        core_eq!(musashi, r68k.sfc);
+        core_eq!(musashi, r68k.dfc);
         for i in (0..16).rev() {
             core_eq!(musashi, r68k.dar[i]);
         }
@@ -2991,6 +3173,119 @@ mod tests {
         ::release_lock!(MUSASHI_LOCK);
     }
 
+    #[test]
+    fn compare_trace_exception_actions() {
+        ::set_lock!(MUSASHI_LOCK);
+
+        // d200 is ADD.B D0,D1
+        let mut musashi = TestCore::new_mem(0x4000, &[0xd2, 0x00]);
+        let vec9handler = 0x2F0000;
+        musashi
+            .mem
+            .write_long(SUPERVISOR_PROGRAM, 9 * 4, vec9handler);
+        musashi
+            .mem
+            .write_long(SUPERVISOR_PROGRAM, vec9handler, 0xd2780108);
+        let trace_bit = 1 << 15;
+        let supervisor_bit = 1 << 13;
+        musashi.sr_to_flags(trace_bit | supervisor_bit);
+        musashi.dar[15] = 0x100;
+        let mut r68k = musashi.clone(); // so very self-aware!
+        initialize_musashi(&mut musashi, 0xaaaaaaaa);
+        execute1(&mut musashi);
+        let musashi_cycles = execute1(&mut musashi);
+        let r68k_cycles = r68k.execute1();
+
+        assert_eq!(musashi_cycles, r68k_cycles);
+        assert_cores_equal(&musashi, &r68k);
+
+        ::release_lock!(MUSASHI_LOCK);
+    }
+
+    // Runs the instruction at 0x4000 traced, in supervisor mode, on both
+    // cores; every vector but the reset ones leads to a handler at 0x2F0000
+    fn compare_traced(code: &[u8], setup: fn(&mut TestCore)) -> (Cycles, Cycles) {
+        let mut musashi = TestCore::new_mem(0x4000, code);
+        let handler = 0x2F0000;
+        for vector in 2..64 {
+            musashi
+                .mem
+                .write_long(SUPERVISOR_PROGRAM, vector * 4, handler);
+        }
+        musashi
+            .mem
+            .write_long(SUPERVISOR_PROGRAM, handler, 0xd2780108);
+        let trace_bit = 1 << 15;
+        let supervisor_bit = 1 << 13;
+        musashi.sr_to_flags(trace_bit | supervisor_bit);
+        musashi.dar[15] = 0x100;
+        setup(&mut musashi);
+        let mut r68k = musashi.clone();
+        initialize_musashi(&mut musashi, 0xaaaaaaaa);
+        execute1(&mut musashi);
+        let musashi_cycles = execute1(&mut musashi);
+        let r68k_cycles = r68k.execute1();
+
+        assert_cores_equal(&musashi, &r68k);
+        (musashi_cycles, r68k_cycles)
+    }
+
+    #[test]
+    fn compare_traced_stop() {
+        ::set_lock!(MUSASHI_LOCK);
+
+        // 4e72,a700 is STOP #$a700, which keeps the trace bit
+        let (musashi_cycles, r68k_cycles) = compare_traced(&[0x4e, 0x72, 0xa7, 0x00], |_| ());
+        assert_eq!(musashi_cycles, r68k_cycles);
+
+        ::release_lock!(MUSASHI_LOCK);
+    }
+
+    #[test]
+    fn compare_traced_trap() {
+        ::set_lock!(MUSASHI_LOCK);
+
+        // 4e40 is TRAP #0, whose exception isn't traced
+        let (musashi_cycles, r68k_cycles) = compare_traced(&[0x4e, 0x40], |_| ());
+        assert_eq!(musashi_cycles, r68k_cycles);
+
+        ::release_lock!(MUSASHI_LOCK);
+    }
+
+    #[test]
+    fn compare_traced_chk() {
+        ::set_lock!(MUSASHI_LOCK);
+
+        // 4181 is CHK D1,D0, out of bounds with D0 negative; Musashi counts
+        // the cycles of a CHK exception differently, so only the state is
+        // compared
+        compare_traced(&[0x41, 0x81], |core| core.dar[0] = 0xffff);
+
+        ::release_lock!(MUSASHI_LOCK);
+    }
+
+    #[test]
+    fn compare_traced_illegal_instruction() {
+        ::set_lock!(MUSASHI_LOCK);
+
+        // 4afc is ILLEGAL
+        let (musashi_cycles, r68k_cycles) = compare_traced(&[0x4a, 0xfc], |_| ());
+        assert_eq!(musashi_cycles, r68k_cycles);
+
+        ::release_lock!(MUSASHI_LOCK);
+    }
+
+    #[test]
+    fn compare_traced_address_error() {
+        ::set_lock!(MUSASHI_LOCK);
+
+        // d278,0107 is ADD.W $0107,D1, from an odd address
+        let (musashi_cycles, r68k_cycles) = compare_traced(&[0xd2, 0x78, 0x01, 0x07], |_| ());
+        assert_eq!(musashi_cycles, r68k_cycles);
+
+        ::release_lock!(MUSASHI_LOCK);
+    }
+
     use super::m68k_get_reg;
     use std::ptr;
 
@@ -3332,7 +3627,21 @@ mod tests {
         test_interrupts(7, 7); // same as mask!
     }
 
+    #[test]
+    fn traced_instruction_defers_to_pending_interrupt() {
+        test_traced_interrupts(0, 5, true);
+    }
+
+    #[test]
+    fn masked_interrupt_leaves_instruction_traced() {
+        test_traced_interrupts(5, 5, true);
+    }
+
     fn test_interrupts(mask: u16, irq: u32) {
+        test_traced_interrupts(mask, irq, false);
+    }
+
+    fn test_traced_interrupts(mask: u16, irq: u32, traced: bool) {
         ::set_lock!(MUSASHI_LOCK);
 
         // opcodes d278,0108 is ADD.W    $0108, D1
@@ -3343,13 +3652,19 @@ mod tests {
             ],
         );
         let supervisor_bit = 1 << 13;
+        let trace_bit = if traced { 1 << 15 } else { 0 };
         let irq_mask = mask << 8;
-        musashi.sr_to_flags(supervisor_bit | irq_mask);
+        musashi.sr_to_flags(trace_bit | supervisor_bit | irq_mask);
         let vec4handler = 0x2F0000;
         let autovector_base = 24;
         musashi
             .mem
             .write_long(SUPERVISOR_PROGRAM, (autovector_base + irq) * 4, vec4handler);
+        if traced {
+            musashi
+                .mem
+                .write_long(SUPERVISOR_PROGRAM, 9 * 4, vec4handler);
+        }
         // opcodes d278,0108 is ADD.W    $0108, D1
         musashi
             .mem
@@ -3370,7 +3685,7 @@ mod tests {
         assert_eq!(musashi_cycles, r68k_cycles);
         if (mask as u32) < irq || irq == 7 {
             assert!(r68k_cycles > Cycles(40));
-        } else {
+        } else if !traced {
             assert!(r68k_cycles < Cycles(40));
         }
         assert_cores_equal(&musashi, &r68k);
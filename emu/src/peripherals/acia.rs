@@ -0,0 +1,192 @@
+// The MC6850 ACIA, the serial port of many single board computers. It
+// has a control/status register and a data register, selected by RS
+// (wired to A1 on most 68000 boards, so the registers are two bytes
+// apart), and stays in master reset until the program writes a control
+// word taking it out. Its interrupt has no vector of its own, so boards
+// autovector it.
+//
+// Characters move whole and as fast as the program takes them, so the
+// clock divide and word select bits are kept but ignored. Characters
+// arrive when the program reads the status register, or when the host
+// calls poll, which it should now and then (say after each execute) for
+// receive interrupts to happen.
+use interrupts::Vector;
+use peripherals::Chip;
+use peripherals::serial::HostPort;
+use savestate::{self, StateReader, StateWriter};
+
+pub const STATUS: u8 = 0; // and control, when written
+pub const DATA: u8 = 1;
+
+// status bits
+pub const RECEIVE_FULL: u8 = 0x01;
+pub const TRANSMIT_EMPTY: u8 = 0x02;
+pub const INTERRUPT: u8 = 0x80;
+
+// control bits
+const MASTER_RESET: u8 = 0x03;
+const TRANSMIT_CONTROL: u8 = 0x60;
+const TRANSMIT_INTERRUPT: u8 = 0x20;
+const RECEIVE_INTERRUPT: u8 = 0x80;
+
+pub struct Acia {
+    port: HostPort,
+    control: u8,
+    data: u8,
+    full: bool,
+}
+
+impl Acia {
+    pub fn new(port: HostPort) -> Acia {
+        Acia { port, control: MASTER_RESET, data: 0, full: false }
+    }
+
+    fn in_reset(&self) -> bool {
+        self.control & MASTER_RESET == MASTER_RESET
+    }
+
+    // takes the next character from the host, if the data register is free
+    pub fn poll(&mut self) {
+        if !self.in_reset() && !self.full {
+            if let Some(byte) = self.port.receive() {
+                self.data = byte;
+                self.full = true;
+            }
+        }
+    }
+
+    fn status(&self) -> u8 {
+        if self.in_reset() {
+            return 0;
+        }
+        let mut status = TRANSMIT_EMPTY;
+        if self.full {
+            status |= RECEIVE_FULL;
+        }
+        if self.requesting() {
+            status |= INTERRUPT;
+        }
+        status
+    }
+}
+
+impl Chip for Acia {
+    fn read_register(&mut self, register: u8) -> u8 {
+        if register & 1 == STATUS {
+            self.poll();
+            self.status()
+        } else {
+            self.full = false;
+            self.data
+        }
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) {
+        if register & 1 == STATUS {
+            self.control = value;
+            if self.in_reset() {
+                self.full = false;
+            }
+        } else if !self.in_reset() {
+            self.port.send(value);
+        }
+    }
+
+    fn requesting(&self) -> bool {
+        !self.in_reset() && (self.control & RECEIVE_INTERRUPT != 0 && self.full
+            || self.control & TRANSMIT_CONTROL == TRANSMIT_INTERRUPT)
+    }
+
+    fn acknowledge(&mut self) -> Vector {
+        Vector::Auto
+    }
+
+    // the ACIA has no reset pin, only the master reset of its control word
+    fn reset(&mut self) {}
+
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u8(self.control);
+        writer.put_u8(self.data);
+        writer.put_bool(self.full);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.control = reader.get_u8()?;
+        self.data = reader.get_u8()?;
+        self.full = reader.get_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::ConfiguredCore;
+    use interrupts::{AutoInterruptController, Responder};
+    use peripherals::{Lane, Shared};
+    use peripherals::serial::SharedBuffer;
+    use ram::{AddressBus, MappedBus, SUPERVISOR_DATA};
+
+    fn acia(input: &[u8]) -> (Shared<Acia>, SharedBuffer) {
+        let output = SharedBuffer::new();
+        (Shared::new(Acia::new(HostPort::with_input(input, output.clone())), Lane::Upper), output)
+    }
+
+    #[test]
+    fn nothing_moves_until_out_of_master_reset() {
+        let (acia, output) = acia(b"ab");
+        let mut chip = acia.lock();
+        assert_eq!(0, chip.read_register(STATUS));
+        chip.write_register(DATA, b'x');
+        // divide by 16, 8 bits and a stop bit
+        chip.write_register(STATUS, 0x15);
+        assert_eq!(TRANSMIT_EMPTY | RECEIVE_FULL, chip.read_register(STATUS));
+        assert_eq!(b'a', chip.read_register(DATA));
+        assert_eq!(TRANSMIT_EMPTY, chip.status());
+        chip.write_register(DATA, b'y');
+        assert_eq!(b"y".to_vec(), output.contents());
+        chip.poll();
+        chip.write_register(STATUS, MASTER_RESET);
+        assert_eq!(0, chip.read_register(STATUS));
+    }
+
+    #[test]
+    fn interrupts_are_autovectored() {
+        let (mut acia, _) = acia(b"a");
+        // receive interrupts
+        acia.lock().write_register(STATUS, 0x95);
+        assert!(!acia.requesting());
+        acia.lock().poll();
+        assert!(acia.requesting());
+        assert_eq!(INTERRUPT, acia.lock().status() & INTERRUPT);
+        assert_eq!(Vector::Auto, acia.acknowledge());
+        assert_eq!(b'a', acia.lock().read_register(DATA));
+        assert!(!acia.requesting());
+        // transmit interrupts, as long as the transmitter is empty
+        acia.lock().write_register(STATUS, 0x35);
+        assert!(acia.requesting());
+    }
+
+    #[test]
+    fn a_program_can_echo_characters() {
+        let (acia, output) = acia(b"HELLO");
+        let mut bus = MappedBus::new();
+        bus.map_ram(0, 0x10000);
+        bus.map_device(0xff0000, 4, Box::new(acia.clone()));
+        bus.write_long(SUPERVISOR_DATA, 0, 0x8000);
+        bus.write_long(SUPERVISOR_DATA, 4, 0x1000);
+        // MOVE.B #3,$ff0000; MOVE.B #$15,$ff0000;
+        // loop: BTST #0,$ff0000; BEQ.S loop; MOVE.B $ff0002,D0;
+        // wait: BTST #1,$ff0000; BEQ.S wait; MOVE.B D0,$ff0002; BRA.S loop
+        let code = [0x13fc, 0x0003, 0x00ff, 0x0000, 0x13fc, 0x0015, 0x00ff, 0x0000,
+            0x0839, 0x0000, 0x00ff, 0x0000, 0x67f6, 0x1039, 0x00ff, 0x0002,
+            0x0839, 0x0001, 0x00ff, 0x0000, 0x67f6, 0x13c0, 0x00ff, 0x0002, 0x60de];
+        for (i, &word) in code.iter().enumerate() {
+            bus.write_word(SUPERVISOR_DATA, 0x1000 + 2 * i as u32, word);
+        }
+        let mut core = ConfiguredCore::new_with(0, AutoInterruptController::new(), bus);
+        core.reset();
+        core.execute(10_000);
+        assert_eq!(b"HELLO".to_vec(), output.contents());
+    }
+}
@@ -0,0 +1,600 @@
+// The MC68681 DUART: two serial channels with three character receive
+// FIFOs, a counter/timer, an input port whose lines 0-3 can interrupt on
+// a change, an output port, and an interrupt vector register answering
+// the acknowledge cycle (with the uninitialized interrupt vector, $0f,
+// until written). On 68000 boards it usually sits on the lower half of
+// the data bus.
+//
+// Characters move whole and as fast as the program takes them, so the
+// mode and clock select registers are kept but ignored. The counter/timer
+// runs off the X1 clock (3.6864MHz on most boards) in the modes counting
+// X1 or X1/16; those counting IP2 or a transmitter clock don't run. Both
+// are driven by the host calling advance with the cycles it ran the core
+// for, which also takes characters from the host ports.
+use interrupts::Vector;
+use peripherals::{Chip, Clock};
+use peripherals::serial::HostPort;
+use savestate::{self, StateError, StateReader, StateWriter};
+use std::collections::VecDeque;
+
+// registers, when read
+pub const MRA: u8 = 0x0;
+pub const SRA: u8 = 0x1;
+pub const RBA: u8 = 0x3;
+pub const IPCR: u8 = 0x4;
+pub const ISR: u8 = 0x5;
+pub const CUR: u8 = 0x6;
+pub const CLR: u8 = 0x7;
+pub const MRB: u8 = 0x8;
+pub const SRB: u8 = 0x9;
+pub const RBB: u8 = 0xb;
+pub const IVR: u8 = 0xc;
+pub const IP: u8 = 0xd;
+pub const START_COUNTER: u8 = 0xe;
+pub const STOP_COUNTER: u8 = 0xf;
+// and when written
+pub const CSRA: u8 = 0x1;
+pub const CRA: u8 = 0x2;
+pub const TBA: u8 = 0x3;
+pub const ACR: u8 = 0x4;
+pub const IMR: u8 = 0x5;
+pub const CTUR: u8 = 0x6;
+pub const CTLR: u8 = 0x7;
+pub const CSRB: u8 = 0x9;
+pub const CRB: u8 = 0xa;
+pub const TBB: u8 = 0xb;
+pub const OPCR: u8 = 0xd;
+pub const SET_OUTPUTS: u8 = 0xe;
+pub const RESET_OUTPUTS: u8 = 0xf;
+
+// status register bits
+pub const RECEIVER_READY: u8 = 0x01;
+pub const FIFO_FULL: u8 = 0x02;
+pub const TRANSMITTER_READY: u8 = 0x04;
+pub const TRANSMITTER_EMPTY: u8 = 0x08;
+
+// interrupt status register bits
+pub const TRANSMITTER_READY_A: u8 = 0x01;
+pub const RECEIVER_READY_A: u8 = 0x02;
+pub const COUNTER_READY: u8 = 0x08;
+pub const TRANSMITTER_READY_B: u8 = 0x10;
+pub const RECEIVER_READY_B: u8 = 0x20;
+pub const INPUT_CHANGE: u8 = 0x80;
+
+const FIFO: usize = 3;
+const RECEIVER_INTERRUPTS_ON_FULL: u8 = 0x40; // in MR1
+
+struct Channel {
+    port: HostPort,
+    mr1: u8,
+    mr2: u8,
+    mr2_next: bool, // the mode register pointer
+    csr: u8,
+    receiving: bool,
+    transmitting: bool,
+    fifo: VecDeque<u8>,
+}
+
+impl Channel {
+    fn new(port: HostPort) -> Channel {
+        Channel { port, mr1: 0, mr2: 0, mr2_next: false, csr: 0, receiving: false, transmitting: false, fifo: VecDeque::new() }
+    }
+
+    fn reset(&mut self) {
+        self.mr2_next = false;
+        self.receiving = false;
+        self.transmitting = false;
+        self.fifo.clear();
+    }
+
+    fn poll(&mut self) {
+        while self.receiving && self.fifo.len() < FIFO {
+            match self.port.receive() {
+                Some(byte) => self.fifo.push_back(byte),
+                None => break,
+            }
+        }
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if !self.fifo.is_empty() {
+            status |= RECEIVER_READY;
+        }
+        if self.fifo.len() == FIFO {
+            status |= FIFO_FULL;
+        }
+        if self.transmitting {
+            status |= TRANSMITTER_READY | TRANSMITTER_EMPTY;
+        }
+        status
+    }
+
+    // the receiver's interrupt status, which MR1 picks
+    fn receiver_ready(&self) -> bool {
+        let full = if self.mr1 & RECEIVER_INTERRUPTS_ON_FULL != 0 { FIFO_FULL } else { RECEIVER_READY };
+        self.status() & full != 0
+    }
+
+    fn read_mode(&mut self) -> u8 {
+        if self.mr2_next {
+            self.mr2
+        } else {
+            self.mr2_next = true;
+            self.mr1
+        }
+    }
+
+    fn write_mode(&mut self, value: u8) {
+        if self.mr2_next {
+            self.mr2 = value;
+        } else {
+            self.mr2_next = true;
+            self.mr1 = value;
+        }
+    }
+
+    fn command(&mut self, command: u8) {
+        match command & 0x03 {
+            1 => self.receiving = true,
+            2 => self.receiving = false,
+            _ => (),
+        }
+        match command >> 2 & 0x03 {
+            1 => self.transmitting = true,
+            2 => self.transmitting = false,
+            _ => (),
+        }
+        match command >> 4 & 0x07 {
+            1 => self.mr2_next = false,
+            2 => {
+                self.receiving = false;
+                self.fifo.clear();
+            },
+            3 => self.transmitting = false,
+            // no errors are modelled to reset, nor breaks
+            _ => (),
+        }
+    }
+
+    fn save(&self, writer: &mut StateWriter) {
+        for &register in &[self.mr1, self.mr2, self.csr] {
+            writer.put_u8(register);
+        }
+        for &flag in &[self.mr2_next, self.receiving, self.transmitting] {
+            writer.put_bool(flag);
+        }
+        writer.put_u8(self.fifo.len() as u8);
+        for &byte in &self.fifo {
+            writer.put_u8(byte);
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        for register in &mut [&mut self.mr1, &mut self.mr2, &mut self.csr] {
+            **register = reader.get_u8()?;
+        }
+        for flag in &mut [&mut self.mr2_next, &mut self.receiving, &mut self.transmitting] {
+            **flag = reader.get_bool()?;
+        }
+        let len = reader.get_u8()? as usize;
+        if len > FIFO {
+            return Err(StateError::Invalid("DUART FIFO"));
+        }
+        self.fifo = reader.get_bytes(len)?.iter().cloned().collect();
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CounterMode {
+    Stopped, // or counting a source that isn't modelled
+    Counter(u64), // counting down once, from a start command, by X1 divided by this
+    Timer(u64), // a square wave, of twice the preload, of X1 divided by this
+}
+
+pub struct Duart {
+    channels: [Channel; 2],
+    clock: Clock, // X1
+    acr: u8,
+    imr: u8,
+    ivr: u8,
+    opcr: u8,
+    outputs: u8,
+    inputs: u8,
+    changes: u8, // of input lines 0-3, since IPCR was read
+    input_changed: bool,
+    preload: u16,
+    counter: u16,
+    counting: bool, // in counter mode
+    counter_ready: bool,
+    prescaled: u64, // X1 periods into the current count
+    second_half: bool, // of the timer's square wave
+}
+
+fn count_of(value: u16) -> u64 {
+    if value == 0 { 0x10000 } else { u64::from(value) }
+}
+
+impl Duart {
+    // the clocks of the core and of X1, in Hz, and the host ends of
+    // channels A and B
+    pub fn new(cpu_clock: u32, x1_clock: u32, a: HostPort, b: HostPort) -> Duart {
+        let mut duart = Duart {
+            channels: [Channel::new(a), Channel::new(b)],
+            clock: Clock::new(cpu_clock, x1_clock),
+            acr: 0, imr: 0, ivr: 0, opcr: 0, outputs: 0,
+            inputs: 0, changes: 0, input_changed: false,
+            preload: 0, counter: 0, counting: false, counter_ready: false,
+            prescaled: 0, second_half: false,
+        };
+        duart.reset();
+        duart
+    }
+
+    pub fn counter_mode(&self) -> CounterMode {
+        match self.acr >> 4 & 0x07 {
+            0b011 => CounterMode::Counter(16),
+            0b110 => CounterMode::Timer(1),
+            0b111 => CounterMode::Timer(16),
+            _ => CounterMode::Stopped,
+        }
+    }
+
+    // runs the counter/timer for as long as the core took the given
+    // cycles, and takes the characters that arrived meanwhile
+    pub fn advance(&mut self, cycles: u64) {
+        let periods = self.clock.periods(cycles);
+        match self.counter_mode() {
+            CounterMode::Counter(divider) if self.counting => {
+                let counts = self.prescale(periods, divider);
+                if counts >= count_of(self.counter) {
+                    self.counter_ready = true;
+                }
+                self.counter = self.counter.wrapping_sub(counts as u16);
+            },
+            CounterMode::Timer(divider) => {
+                let counts = self.prescale(periods, divider);
+                let counter = count_of(self.counter);
+                if counts < counter {
+                    self.counter = (counter - counts) as u16;
+                } else {
+                    let reload = count_of(self.preload);
+                    let halves = u64::from(self.second_half) + 1 + (counts - counter) / reload;
+                    self.counter = (reload - (counts - counter) % reload) as u16;
+                    self.counter_ready |= halves >= 2;
+                    self.second_half = halves % 2 == 1;
+                }
+            },
+            _ => (),
+        }
+        for channel in &mut self.channels {
+            channel.poll();
+        }
+    }
+
+    fn prescale(&mut self, periods: u64, divider: u64) -> u64 {
+        let periods = self.prescaled + periods;
+        self.prescaled = periods % divider;
+        periods / divider
+    }
+
+    // the level on an input port line, of which 0-3 can interrupt on a
+    // change, as ACR enables them
+    pub fn set_input(&mut self, line: u8, level: bool) {
+        let bit = 1 << line;
+        if (self.inputs & bit != 0) != level {
+            self.inputs ^= bit;
+            if line < 4 {
+                self.changes |= bit;
+                self.input_changed |= self.acr & bit != 0;
+            }
+        }
+    }
+
+    // The output port register, whose set bits drive their pins low
+    pub fn outputs(&self) -> u8 {
+        self.outputs
+    }
+
+    fn interrupt_status(&self) -> u8 {
+        let mut status = 0;
+        for (channel, shift) in self.channels.iter().zip(&[0, 4]) {
+            if channel.transmitting {
+                status |= TRANSMITTER_READY_A << shift;
+            }
+            if channel.receiver_ready() {
+                status |= RECEIVER_READY_A << shift;
+            }
+        }
+        if self.counter_ready {
+            status |= COUNTER_READY;
+        }
+        if self.input_changed {
+            status |= INPUT_CHANGE;
+        }
+        status
+    }
+}
+
+impl Chip for Duart {
+    fn read_register(&mut self, register: u8) -> u8 {
+        let channel = (register >> 3 & 1) as usize;
+        match register & 0x0f {
+            MRA | MRB => self.channels[channel].read_mode(),
+            SRA | SRB => {
+                self.channels[channel].poll();
+                self.channels[channel].status()
+            },
+            RBA | RBB => {
+                let byte = self.channels[channel].fifo.pop_front().unwrap_or(0);
+                self.channels[channel].poll();
+                byte
+            },
+            IPCR => {
+                let ipcr = (self.inputs & 0x0f) << 4 | self.changes;
+                self.changes = 0;
+                self.input_changed = false;
+                ipcr
+            },
+            ISR => {
+                for channel in &mut self.channels {
+                    channel.poll();
+                }
+                self.interrupt_status()
+            },
+            CUR => (self.counter >> 8) as u8,
+            CLR => self.counter as u8,
+            IVR => self.ivr,
+            IP => self.inputs | 0xc0,
+            START_COUNTER => {
+                self.counter = self.preload;
+                self.prescaled = 0;
+                self.second_half = false;
+                self.counting = true;
+                0xff
+            },
+            STOP_COUNTER => {
+                if let CounterMode::Counter(_) = self.counter_mode() {
+                    self.counting = false;
+                }
+                self.counter_ready = false;
+                0xff
+            },
+            _ => 0xff,
+        }
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) {
+        let channel = (register >> 3 & 1) as usize;
+        match register & 0x0f {
+            MRA | MRB => self.channels[channel].write_mode(value),
+            CSRA | CSRB => self.channels[channel].csr = value,
+            CRA | CRB => self.channels[channel].command(value),
+            TBA | TBB if self.channels[channel].transmitting => self.channels[channel].port.send(value),
+            ACR => self.acr = value,
+            IMR => self.imr = value,
+            CTUR => self.preload = self.preload & 0x00ff | u16::from(value) << 8,
+            CTLR => self.preload = self.preload & 0xff00 | u16::from(value),
+            IVR => self.ivr = value,
+            OPCR => self.opcr = value,
+            SET_OUTPUTS => self.outputs |= value,
+            RESET_OUTPUTS => self.outputs &= !value,
+            _ => (),
+        }
+    }
+
+    fn requesting(&self) -> bool {
+        self.interrupt_status() & self.imr != 0
+    }
+
+    fn acknowledge(&mut self) -> Vector {
+        Vector::Number(self.ivr)
+    }
+
+    fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+        self.acr = 0;
+        self.imr = 0;
+        self.ivr = 0x0f;
+        self.opcr = 0;
+        self.outputs = 0;
+        self.changes = 0;
+        self.input_changed = false;
+        self.counting = false;
+        self.counter_ready = false;
+    }
+
+    fn save(&self, writer: &mut StateWriter) {
+        self.clock.save(writer);
+        for channel in &self.channels {
+            channel.save(writer);
+        }
+        for &register in &[self.acr, self.imr, self.ivr, self.opcr, self.outputs, self.inputs, self.changes, self.prescaled as u8] {
+            writer.put_u8(register);
+        }
+        writer.put_u16(self.preload);
+        writer.put_u16(self.counter);
+        for &flag in &[self.input_changed, self.counting, self.counter_ready, self.second_half] {
+            writer.put_bool(flag);
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.clock.load(reader)?;
+        for channel in &mut self.channels {
+            channel.load(reader)?;
+        }
+        for register in &mut [&mut self.acr, &mut self.imr, &mut self.ivr, &mut self.opcr, &mut self.outputs, &mut self.inputs, &mut self.changes] {
+            **register = reader.get_u8()?;
+        }
+        self.prescaled = u64::from(reader.get_u8()?);
+        self.preload = reader.get_u16()?;
+        self.counter = reader.get_u16()?;
+        for flag in &mut [&mut self.input_changed, &mut self.counting, &mut self.counter_ready, &mut self.second_half] {
+            **flag = reader.get_bool()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interrupts::Responder;
+    use peripherals::{Lane, Shared};
+    use peripherals::serial::SharedBuffer;
+    use savestate::{StateReader, StateWriter};
+
+    fn duart(input: &[u8]) -> (Shared<Duart>, SharedBuffer) {
+        let output = SharedBuffer::new();
+        let a = HostPort::with_input(input, output.clone());
+        (Shared::new(Duart::new(8_000_000, 3_686_400, a, HostPort::disconnected()), Lane::Lower), output)
+    }
+
+    #[test]
+    fn channels_move_characters_through_their_fifos() {
+        let (duart, output) = duart(b"abcde");
+        let mut chip = duart.lock();
+        assert_eq!(0, chip.read_register(SRA));
+        chip.write_register(TBA, b'x');
+        // enable the receiver and transmitter
+        chip.write_register(CRA, 0x05);
+        assert_eq!(RECEIVER_READY | FIFO_FULL | TRANSMITTER_READY | TRANSMITTER_EMPTY, chip.read_register(SRA));
+        assert_eq!(b'a', chip.read_register(RBA));
+        chip.write_register(TBA, b'o');
+        chip.write_register(TBA, b'k');
+        assert_eq!(b"ok".to_vec(), output.contents());
+        let received: Vec<u8> = (0..4).map(|_| chip.read_register(RBA)).collect();
+        assert_eq!(b"bcde".to_vec(), received);
+        assert_eq!(TRANSMITTER_READY | TRANSMITTER_EMPTY, chip.read_register(SRA));
+        // MR1 then MR2, until the pointer is reset
+        chip.write_register(MRA, 0x13);
+        chip.write_register(MRA, 0x07);
+        assert_eq!(0x07, chip.read_register(MRA));
+        chip.write_register(CRA, 0x10);
+        assert_eq!(0x13, chip.read_register(MRA));
+        assert_eq!(0x07, chip.read_register(MRA));
+        // resetting the receiver disables it
+        chip.write_register(CRA, 0x20);
+        assert_eq!(TRANSMITTER_READY | TRANSMITTER_EMPTY, chip.read_register(SRA));
+        assert_eq!(0, chip.read_register(SRB));
+    }
+
+    #[test]
+    fn the_timer_sets_counter_ready_every_square_wave() {
+        let (mut duart, _) = duart(b"");
+        {
+            let mut chip = duart.lock();
+            // the timer, on X1/16, with a preload of 16
+            chip.write_register(ACR, 0x70);
+            chip.write_register(CTUR, 0);
+            chip.write_register(CTLR, 16);
+            chip.write_register(IMR, COUNTER_READY);
+            assert_eq!(CounterMode::Timer(16), chip.counter_mode());
+            chip.read_register(START_COUNTER);
+            // a half of the wave is 256 periods of X1, and the whole 512 take
+            // 1111.1 cycles
+            chip.advance(278);
+            assert_eq!(8, chip.read_register(CLR));
+            chip.advance(833);
+            assert_eq!(0, chip.read_register(ISR) & COUNTER_READY);
+            chip.advance(1);
+            assert_eq!(COUNTER_READY, chip.read_register(ISR) & COUNTER_READY);
+        }
+        assert!(duart.requesting());
+        // the uninitialized interrupt vector, until IVR is written
+        assert_eq!(Vector::Number(0x0f), duart.acknowledge());
+        duart.lock().write_register(IVR, 0x40);
+        assert_eq!(Vector::Number(0x40), duart.acknowledge());
+        // stopping only clears counter ready in timer mode
+        duart.lock().read_register(STOP_COUNTER);
+        assert!(!duart.requesting());
+        duart.lock().advance(1112);
+        assert!(duart.requesting());
+        duart.reset();
+        assert!(!duart.requesting());
+        assert_eq!(0x0f, duart.lock().read_register(IVR));
+    }
+
+    #[test]
+    fn the_counter_counts_down_once() {
+        let (duart, _) = duart(b"");
+        let mut chip = duart.lock();
+        // the counter, on X1/16, from 100
+        chip.write_register(ACR, 0x30);
+        chip.write_register(CTLR, 100);
+        chip.read_register(START_COUNTER);
+        // 1600 periods of X1 take 3472.2 cycles
+        chip.advance(3472);
+        assert_eq!((0, 1), (chip.counter >> 8, chip.counter & 0xff));
+        assert_eq!(0, chip.interrupt_status() & COUNTER_READY);
+        // and on past the terminal count
+        chip.advance(50);
+        assert_eq!(COUNTER_READY, chip.interrupt_status() & COUNTER_READY);
+        assert_eq!(0xff, chip.read_register(CUR));
+        chip.read_register(STOP_COUNTER);
+        let stopped = chip.read_register(CLR);
+        chip.advance(100_000);
+        assert_eq!(0, chip.interrupt_status() & COUNTER_READY);
+        assert_eq!(stopped, chip.read_register(CLR));
+    }
+
+    #[test]
+    fn input_changes_interrupt() {
+        let (mut duart, _) = duart(b"");
+        duart.lock().write_register(ACR, 0x01);
+        duart.lock().write_register(IMR, INPUT_CHANGE);
+        duart.lock().set_input(1, true);
+        assert!(!duart.requesting());
+        duart.lock().set_input(0, true);
+        assert!(duart.requesting());
+        assert_eq!(0xc3, duart.lock().read_register(IP));
+        assert_eq!(0x33, duart.lock().read_register(IPCR));
+        assert!(!duart.requesting());
+        assert_eq!(0x30, duart.lock().read_register(IPCR));
+        // outputs
+        duart.lock().write_register(SET_OUTPUTS, 0x81);
+        duart.lock().write_register(RESET_OUTPUTS, 0x01);
+        assert_eq!(0x80, duart.lock().outputs());
+        duart.reset();
+    }
+
+    #[test]
+    fn receiver_interrupts_on_ready_or_full() {
+        let (duart, _) = duart(b"abc");
+        let mut chip = duart.lock();
+        chip.write_register(IMR, RECEIVER_READY_A);
+        chip.write_register(CRA, 0x01);
+        chip.write_register(MRA, RECEIVER_INTERRUPTS_ON_FULL);
+        chip.advance(1);
+        assert!(chip.requesting());
+        chip.read_register(RBA);
+        assert!(!chip.requesting());
+    }
+
+    #[test]
+    fn state_is_saved() {
+        let (saved, _) = duart(b"ab");
+        let mut chip = saved.lock();
+        chip.write_register(CRA, 0x01);
+        chip.write_register(ACR, 0x70);
+        chip.write_register(CTLR, 50);
+        chip.read_register(START_COUNTER);
+        chip.advance(1000);
+        let mut writer = StateWriter::new();
+        chip.save(&mut writer);
+        let bytes = writer.into_bytes();
+
+        let (restored, _) = duart(b"");
+        let mut restored = restored.lock();
+        restored.load(&mut StateReader::new(&bytes).unwrap()).unwrap();
+        assert_eq!(b'a', restored.read_register(RBA));
+        assert_eq!(chip.read_register(CLR), restored.read_register(CLR));
+        chip.advance(1000);
+        restored.advance(1000);
+        assert_eq!(chip.read_register(CLR), restored.read_register(CLR));
+    }
+}
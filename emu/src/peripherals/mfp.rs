@@ -0,0 +1,686 @@
+// The MC68901 multi-function peripheral, as in the Atari ST: an 8-bit
+// general purpose I/O port (GPIP) whose input lines interrupt on an edge,
+// four timers, a USART and 16 prioritized interrupt channels supplying
+// their own vectors. It sits on the lower half of the data bus, so it is
+// shared with Lane::Lower to have its registers at the odd addresses.
+//
+// The timers run off a clock of their own, which the host drives by
+// calling advance with the cycles it ran the core for (say, what execute
+// returned), so timer interrupts are only noticed between such calls. The
+// USART moves whole characters rather than bits: what the program sends
+// is queued for the host (take_transmitted), which hands it what the
+// program is to receive (receive).
+use interrupts::Vector;
+use peripherals::{Chip, Clock};
+use savestate::{self, StateReader, StateWriter};
+use std::collections::VecDeque;
+
+// registers
+pub const GPDR: u8 = 0x00;
+pub const AER: u8 = 0x01;
+pub const DDR: u8 = 0x02;
+pub const IERA: u8 = 0x03;
+pub const IERB: u8 = 0x04;
+pub const IPRA: u8 = 0x05;
+pub const IPRB: u8 = 0x06;
+pub const ISRA: u8 = 0x07;
+pub const ISRB: u8 = 0x08;
+pub const IMRA: u8 = 0x09;
+pub const IMRB: u8 = 0x0a;
+pub const VR: u8 = 0x0b;
+pub const TACR: u8 = 0x0c;
+pub const TBCR: u8 = 0x0d;
+pub const TCDCR: u8 = 0x0e;
+pub const TADR: u8 = 0x0f;
+pub const TBDR: u8 = 0x10;
+pub const TCDR: u8 = 0x11;
+pub const TDDR: u8 = 0x12;
+pub const SCR: u8 = 0x13;
+pub const UCR: u8 = 0x14;
+pub const RSR: u8 = 0x15;
+pub const TSR: u8 = 0x16;
+pub const UDR: u8 = 0x17;
+
+// interrupt channels, from the lowest priority to the highest. Channels
+// 8-15 are in the A registers, and 0-7 in the B registers.
+pub const GPIP0: u8 = 0;
+pub const GPIP1: u8 = 1;
+pub const GPIP2: u8 = 2;
+pub const GPIP3: u8 = 3;
+pub const TIMER_D: u8 = 4;
+pub const TIMER_C: u8 = 5;
+pub const GPIP4: u8 = 6;
+pub const GPIP5: u8 = 7;
+pub const TIMER_B: u8 = 8;
+pub const TRANSMIT_ERROR: u8 = 9;
+pub const TRANSMIT_EMPTY: u8 = 10;
+pub const RECEIVE_ERROR: u8 = 11;
+pub const RECEIVE_FULL: u8 = 12;
+pub const TIMER_A: u8 = 13;
+pub const GPIP6: u8 = 14;
+pub const GPIP7: u8 = 15;
+
+pub const TIMER_CHANNELS: [u8; 4] = [TIMER_A, TIMER_B, TIMER_C, TIMER_D];
+const GPIP_CHANNELS: [u8; 8] = [GPIP0, GPIP1, GPIP2, GPIP3, GPIP4, GPIP5, GPIP6, GPIP7];
+// the GPIP lines (and AER bits) shared with the inputs of timers A and B
+const TIMER_INPUTS: [u8; 2] = [4, 3];
+const PRESCALERS: [u64; 8] = [0, 4, 10, 16, 50, 64, 100, 200];
+
+const SOFTWARE_EOI: u8 = 0x08; // the S bit of VR
+
+// RSR and TSR bits
+const BUFFER_FULL: u8 = 0x80;
+const OVERRUN: u8 = 0x40;
+const RECEIVER_ENABLE: u8 = 0x01;
+const BUFFER_EMPTY: u8 = 0x80;
+const TRANSMITTER_ENABLE: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimerMode {
+    Stopped,
+    Delay(u64), // counting timer clocks, divided by the prescaler
+    EventCount, // counting active edges of the timer input
+    PulseWidth(u64), // like Delay, but only while the timer input is active
+}
+
+#[derive(Clone, Copy, Default)]
+struct Timer {
+    control: u8, // the mode bits of the control register
+    data: u8,
+    counter: u8, // 0 counts as 256
+    prescaled: u64, // timer clocks into the current prescaler period
+    input: bool, // TAI or TBI
+}
+
+fn count_of(value: u8) -> u64 {
+    if value == 0 { 256 } else { u64::from(value) }
+}
+
+impl Timer {
+    fn mode(&self) -> TimerMode {
+        match self.control & 0x0f {
+            0 => TimerMode::Stopped,
+            8 => TimerMode::EventCount,
+            control if control < 8 => TimerMode::Delay(PRESCALERS[control as usize]),
+            control => TimerMode::PulseWidth(PRESCALERS[control as usize - 8]),
+        }
+    }
+
+    fn set_control(&mut self, control: u8) {
+        if self.control == 0 {
+            self.prescaled = 0;
+        }
+        self.control = control;
+    }
+
+    // a stopped timer loads its counter too
+    fn set_data(&mut self, data: u8) {
+        self.data = data;
+        if self.mode() == TimerMode::Stopped {
+            self.counter = data;
+        }
+    }
+
+    // Counts down, reloading from the data register when reaching zero,
+    // and tells whether it did
+    fn count(&mut self, n: u64) -> bool {
+        let counter = count_of(self.counter);
+        if n < counter {
+            self.counter = (counter - n) as u8;
+            false
+        } else {
+            let reload = count_of(self.data);
+            self.counter = (reload - (n - counter) % reload) as u8;
+            true
+        }
+    }
+
+    fn run(&mut self, clocks: u64, prescaler: u64) -> bool {
+        let clocks = self.prescaled + clocks;
+        self.prescaled = clocks % prescaler;
+        self.count(clocks / prescaler)
+    }
+}
+
+pub struct Mfp {
+    clock: Clock, // of the timers
+    gpdr: u8,
+    aer: u8,
+    ddr: u8,
+    inputs: u8, // the levels on the GPIP lines
+    ier: u16, // bit n for channel n
+    ipr: u16,
+    isr: u16,
+    imr: u16,
+    vr: u8,
+    timers: [Timer; 4],
+    scr: u8,
+    ucr: u8,
+    rsr: u8,
+    tsr: u8,
+    received: u8,
+    transmitted: VecDeque<u8>,
+}
+
+impl Mfp {
+    // The clocks of the core and of the timers, in Hz: 8_000_000 and
+    // 2_457_600 on an Atari ST
+    pub fn new(cpu_clock: u32, timer_clock: u32) -> Mfp {
+        let mut mfp = Mfp {
+            clock: Clock::new(cpu_clock, timer_clock),
+            gpdr: 0, aer: 0, ddr: 0, inputs: 0,
+            ier: 0, ipr: 0, isr: 0, imr: 0, vr: 0,
+            timers: [Timer::default(); 4],
+            scr: 0, ucr: 0, rsr: 0, tsr: 0,
+            received: 0,
+            transmitted: VecDeque::new(),
+        };
+        mfp.reset();
+        mfp
+    }
+
+    // runs the timers for as long as the core took the given cycles
+    pub fn advance(&mut self, cycles: u64) {
+        let clocks = self.clock.periods(cycles);
+        for (timer, &channel) in TIMER_CHANNELS.iter().enumerate() {
+            let expired = match self.timers[timer].mode() {
+                TimerMode::Delay(prescaler) => self.timers[timer].run(clocks, prescaler),
+                TimerMode::PulseWidth(prescaler) if self.timer_input_active(timer) =>
+                    self.timers[timer].run(clocks, prescaler),
+                _ => false,
+            };
+            if expired {
+                self.interrupt(channel);
+            }
+        }
+    }
+
+    pub fn timer_mode(&self, timer: usize) -> TimerMode {
+        self.timers[timer].mode()
+    }
+
+    fn timer_input_active(&self, timer: usize) -> bool {
+        let active = self.aer & 1 << TIMER_INPUTS[timer] != 0;
+        self.timers[timer].input == active
+    }
+
+    // The level on TAI (timer 0) or TBI (timer 1). The active edge, or
+    // level, is the one AER gives their GPIP line.
+    pub fn set_timer_input(&mut self, timer: usize, level: bool) {
+        assert!(timer < 2);
+        if self.timers[timer].input == level {
+            return;
+        }
+        self.timers[timer].input = level;
+        let active = self.timer_input_active(timer);
+        match self.timers[timer].mode() {
+            TimerMode::EventCount if active && self.timers[timer].count(1) =>
+                self.interrupt(TIMER_CHANNELS[timer]),
+            // the end of a pulse interrupts on the GPIP line
+            TimerMode::PulseWidth(_) if !active =>
+                self.interrupt(GPIP_CHANNELS[TIMER_INPUTS[timer] as usize]),
+            _ => (),
+        }
+    }
+
+    // The level on a GPIP line, which interrupts on the edge AER gives
+    // when the line is an input
+    pub fn set_gpip_input(&mut self, line: u8, level: bool) {
+        let bit = 1 << line;
+        let previous = self.inputs & bit != 0;
+        self.inputs = if level { self.inputs | bit } else { self.inputs & !bit };
+        if previous != level && self.ddr & bit == 0 && (self.aer & bit != 0) == level {
+            self.interrupt(GPIP_CHANNELS[line as usize]);
+        }
+    }
+
+    // the levels the MFP drives on the GPIP lines that are outputs
+    pub fn gpip_outputs(&self) -> u8 {
+        self.gpdr & self.ddr
+    }
+
+    // A character for the receiver, which it drops (flagging an overrun
+    // should it have one already) unless it is enabled and empty
+    pub fn receive(&mut self, byte: u8) -> bool {
+        if self.rsr & RECEIVER_ENABLE == 0 {
+            false
+        } else if self.rsr & BUFFER_FULL != 0 {
+            self.rsr |= OVERRUN;
+            self.interrupt(RECEIVE_ERROR);
+            false
+        } else {
+            self.received = byte;
+            self.rsr |= BUFFER_FULL;
+            self.interrupt(RECEIVE_FULL);
+            true
+        }
+    }
+
+    // the characters the program sent since last asked
+    pub fn take_transmitted(&mut self) -> Vec<u8> {
+        self.transmitted.drain(..).collect()
+    }
+
+    // an interrupt event on the channel, pending if it is enabled
+    pub fn interrupt(&mut self, channel: u8) {
+        let bit = 1 << channel;
+        if self.ier & bit != 0 {
+            self.ipr |= bit;
+        }
+    }
+
+    // the highest pending channel, unless masked or (with software end of
+    // interrupt) not above those in service
+    fn requesting_channel(&self) -> Option<u8> {
+        let active = self.ipr & self.imr;
+        let highest = 16 - active.leading_zeros();
+        let in_service = if self.vr & SOFTWARE_EOI != 0 { 16 - self.isr.leading_zeros() } else { 0 };
+        if highest > in_service { Some(highest as u8 - 1) } else { None }
+    }
+}
+
+impl Chip for Mfp {
+    fn read_register(&mut self, register: u8) -> u8 {
+        match register {
+            GPDR => self.gpdr & self.ddr | self.inputs & !self.ddr,
+            AER => self.aer,
+            DDR => self.ddr,
+            IERA => (self.ier >> 8) as u8,
+            IERB => self.ier as u8,
+            IPRA => (self.ipr >> 8) as u8,
+            IPRB => self.ipr as u8,
+            ISRA => (self.isr >> 8) as u8,
+            ISRB => self.isr as u8,
+            IMRA => (self.imr >> 8) as u8,
+            IMRB => self.imr as u8,
+            VR => self.vr,
+            TACR => self.timers[0].control,
+            TBCR => self.timers[1].control,
+            TCDCR => self.timers[2].control << 4 | self.timers[3].control,
+            TADR..=TDDR => self.timers[(register - TADR) as usize].counter,
+            SCR => self.scr,
+            UCR => self.ucr,
+            RSR => {
+                let rsr = self.rsr;
+                self.rsr &= !OVERRUN;
+                rsr
+            },
+            TSR => self.tsr,
+            UDR => {
+                self.rsr &= !BUFFER_FULL;
+                self.received
+            },
+            _ => 0xff,
+        }
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) {
+        let high = u16::from(value) << 8 | 0xff;
+        let low = 0xff00 | u16::from(value);
+        match register {
+            GPDR => self.gpdr = value,
+            AER => self.aer = value,
+            DDR => self.ddr = value,
+            // disabling a channel clears its pending interrupt
+            IERA => {
+                self.ier = self.ier & 0xff | u16::from(value) << 8;
+                self.ipr &= self.ier;
+            },
+            IERB => {
+                self.ier = self.ier & 0xff00 | u16::from(value);
+                self.ipr &= self.ier;
+            },
+            // writing 0 bits clears them, and 1 bits leaves them alone
+            IPRA => self.ipr &= high,
+            IPRB => self.ipr &= low,
+            ISRA => self.isr &= high,
+            ISRB => self.isr &= low,
+            IMRA => self.imr = self.imr & 0xff | u16::from(value) << 8,
+            IMRB => self.imr = self.imr & 0xff00 | u16::from(value),
+            VR => {
+                self.vr = value;
+                if value & SOFTWARE_EOI == 0 {
+                    self.isr = 0;
+                }
+            },
+            TACR => self.timers[0].set_control(value & 0x0f),
+            TBCR => self.timers[1].set_control(value & 0x0f),
+            TCDCR => {
+                self.timers[2].set_control(value >> 4 & 0x07);
+                self.timers[3].set_control(value & 0x07);
+            },
+            TADR..=TDDR => self.timers[(register - TADR) as usize].set_data(value),
+            SCR => self.scr = value,
+            UCR => self.ucr = value,
+            RSR => self.rsr = self.rsr & 0xfc | value & 0x03,
+            TSR => self.tsr = self.tsr & 0xd0 | value & 0x2f,
+            // sent at once, leaving the buffer empty again
+            UDR if self.tsr & TRANSMITTER_ENABLE != 0 => {
+                self.transmitted.push_back(value);
+                self.interrupt(TRANSMIT_EMPTY);
+            },
+            _ => (),
+        }
+    }
+
+    fn save(&self, writer: &mut StateWriter) {
+        self.clock.save(writer);
+        for &register in &[self.gpdr, self.aer, self.ddr, self.inputs, self.vr, self.scr, self.ucr, self.rsr, self.tsr, self.received] {
+            writer.put_u8(register);
+        }
+        for &register in &[self.ier, self.ipr, self.isr, self.imr] {
+            writer.put_u16(register);
+        }
+        for timer in &self.timers {
+            writer.put_u8(timer.control);
+            writer.put_u8(timer.data);
+            writer.put_u8(timer.counter);
+            writer.put_u32(timer.prescaled as u32);
+            writer.put_bool(timer.input);
+        }
+        writer.put_u32(self.transmitted.len() as u32);
+        for &byte in &self.transmitted {
+            writer.put_u8(byte);
+        }
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.clock.load(reader)?;
+        for register in &mut [&mut self.gpdr, &mut self.aer, &mut self.ddr, &mut self.inputs, &mut self.vr, &mut self.scr, &mut self.ucr, &mut self.rsr, &mut self.tsr, &mut self.received] {
+            **register = reader.get_u8()?;
+        }
+        for register in &mut [&mut self.ier, &mut self.ipr, &mut self.isr, &mut self.imr] {
+            **register = reader.get_u16()?;
+        }
+        for timer in &mut self.timers {
+            timer.control = reader.get_u8()?;
+            timer.data = reader.get_u8()?;
+            timer.counter = reader.get_u8()?;
+            timer.prescaled = u64::from(reader.get_u32()?);
+            timer.input = reader.get_bool()?;
+        }
+        let len = reader.get_u32()? as usize;
+        self.transmitted = reader.get_bytes(len)?.iter().cloned().collect();
+        Ok(())
+    }
+
+    fn requesting(&self) -> bool {
+        self.requesting_channel().is_some()
+    }
+
+    // Clears the pending bit of the channel, and with software end of
+    // interrupt, puts it in service
+    fn acknowledge(&mut self) -> Vector {
+        match self.requesting_channel() {
+            Some(channel) => {
+                let bit = 1 << channel;
+                self.ipr &= !bit;
+                if self.vr & SOFTWARE_EOI != 0 {
+                    self.isr |= bit;
+                }
+                Vector::Number(self.vr & 0xf0 | channel)
+            },
+            None => Vector::Spurious,
+        }
+    }
+
+    // Clears the registers, except for the timer data, the sync character
+    // and the received character, and stops the timers
+    fn reset(&mut self) {
+        self.gpdr = 0;
+        self.aer = 0;
+        self.ddr = 0;
+        self.ier = 0;
+        self.ipr = 0;
+        self.isr = 0;
+        self.imr = 0;
+        self.vr = 0;
+        for timer in &mut self.timers {
+            timer.control = 0;
+        }
+        self.ucr = 0;
+        self.rsr = 0;
+        self.tsr = BUFFER_EMPTY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interrupts::{Responder, Vector};
+    use peripherals::{Chip, Lane, Shared};
+    use ram::SUPERVISOR_DATA;
+    use ram::mappedbus::{Device, Size};
+
+    // an Atari ST's
+    fn mfp() -> Shared<Mfp> {
+        Shared::new(Mfp::new(8_000_000, 2_457_600), Lane::Lower)
+    }
+
+    fn enable(mfp: &Shared<Mfp>, channel: u8) {
+        let mut mfp = mfp.lock();
+        let (enabled, mask) = (mfp.read_register(IERA), mfp.read_register(IMRA));
+        let (enabled_b, mask_b) = (mfp.read_register(IERB), mfp.read_register(IMRB));
+        let bit = 1u16 << channel;
+        mfp.write_register(IERA, enabled | (bit >> 8) as u8);
+        mfp.write_register(IMRA, mask | (bit >> 8) as u8);
+        mfp.write_register(IERB, enabled_b | bit as u8);
+        mfp.write_register(IMRB, mask_b | bit as u8);
+    }
+
+    #[test]
+    fn timer_a_interrupts_after_its_delay() {
+        let mut mfp = mfp();
+        enable(&mfp, TIMER_A);
+        {
+            let mut mfp = mfp.lock();
+            mfp.write_register(VR, 0x40);
+            mfp.write_register(TADR, 10);
+            // delay mode, dividing by 4
+            mfp.write_register(TACR, 0x01);
+            assert_eq!(TimerMode::Delay(4), mfp.timer_mode(0));
+            // 40 timer clocks take 130.2 cycles
+            mfp.advance(33);
+            assert_eq!(8, mfp.read_register(TADR));
+            mfp.advance(97);
+            assert_eq!(0, mfp.read_register(IPRA));
+            mfp.advance(1);
+            assert_eq!(0x20, mfp.read_register(IPRA));
+            assert_eq!(10, mfp.read_register(TADR));
+        }
+        assert!(mfp.requesting());
+        assert_eq!(Vector::Number(0x40 | TIMER_A), mfp.acknowledge());
+        assert!(!mfp.requesting());
+        {
+            let mut mfp = mfp.lock();
+            // the next one comes at 260.4 cycles
+            mfp.advance(129);
+            assert_eq!(0, mfp.read_register(IPRA));
+            mfp.advance(1);
+            assert_eq!(0x20, mfp.read_register(IPRA));
+            // data written while running only reloads the counter
+            mfp.write_register(TADR, 100);
+            assert_eq!(10, mfp.read_register(TADR));
+            mfp.advance(130);
+            assert_eq!(100, mfp.read_register(TADR));
+            // stopped, it keeps its count
+            mfp.write_register(TACR, 0);
+            mfp.advance(1000);
+            assert_eq!(100, mfp.read_register(TADR));
+        }
+    }
+
+    #[test]
+    fn timers_count_events_and_pulses() {
+        let mfp = mfp();
+        enable(&mfp, TIMER_B);
+        enable(&mfp, GPIP3);
+        let mut mfp = mfp.lock();
+        // counting rising edges of TBI
+        mfp.write_register(AER, 0x08);
+        mfp.write_register(TBDR, 2);
+        mfp.write_register(TBCR, 0x08);
+        for _ in 0..2 {
+            mfp.set_timer_input(1, true);
+            mfp.set_timer_input(1, false);
+        }
+        assert_eq!(0x01, mfp.read_register(IPRA));
+        mfp.write_register(IPRA, 0);
+        // measuring a high pulse, dividing by 10
+        mfp.write_register(TBCR, 0);
+        mfp.write_register(TBDR, 0);
+        mfp.write_register(TBCR, 0x0a);
+        mfp.advance(1000);
+        assert_eq!(0, mfp.read_register(TBDR));
+        mfp.set_timer_input(1, true);
+        // 307 timer clocks
+        mfp.advance(1000);
+        mfp.set_timer_input(1, false);
+        assert_eq!((256 - 30) as u8, mfp.read_register(TBDR));
+        // the end of the pulse interrupts on GPIP3
+        assert_eq!(0, mfp.read_register(IPRA));
+        assert_eq!(0x08, mfp.read_register(IPRB));
+    }
+
+    #[test]
+    fn pending_and_in_service_registers() {
+        let mut mfp = mfp();
+        for &channel in &[TIMER_D, GPIP4, TIMER_A] {
+            enable(&mfp, channel);
+        }
+        // software end of interrupt
+        mfp.lock().write_register(VR, 0x48);
+        mfp.lock().interrupt(GPIP4);
+        mfp.lock().interrupt(GPIP7); // not enabled
+        assert_eq!(0x40, mfp.lock().read_register(IPRB));
+        assert_eq!(0, mfp.lock().read_register(IPRA));
+        assert_eq!(Vector::Number(0x40 | GPIP4), mfp.acknowledge());
+        assert_eq!(0, mfp.lock().read_register(IPRB));
+        assert_eq!(0x40, mfp.lock().read_register(ISRB));
+        // a lower priority waits for the end of the interrupt
+        mfp.lock().interrupt(TIMER_D);
+        assert!(!mfp.requesting());
+        // while a higher one nests
+        mfp.lock().interrupt(TIMER_A);
+        assert_eq!(Vector::Number(0x40 | TIMER_A), mfp.acknowledge());
+        assert_eq!(0x20, mfp.lock().read_register(ISRA));
+        assert_eq!(0x40, mfp.lock().read_register(ISRB));
+        mfp.lock().write_register(ISRA, 0xdf);
+        assert!(!mfp.requesting());
+        mfp.lock().write_register(ISRB, 0xbf);
+        assert!(mfp.requesting());
+        // masked, it stays pending
+        mfp.lock().write_register(IMRB, 0);
+        assert!(!mfp.requesting());
+        assert_eq!(0x10, mfp.lock().read_register(IPRB));
+        mfp.lock().write_register(IMRB, 0x10);
+        // writing 0 clears it
+        mfp.lock().write_register(IPRB, 0xef);
+        assert!(!mfp.requesting());
+        // as does disabling it
+        mfp.lock().interrupt(TIMER_D);
+        mfp.lock().write_register(IERB, 0x40);
+        assert_eq!(0, mfp.lock().read_register(IPRB));
+        // automatic end of interrupt leaves nothing in service
+        mfp.lock().write_register(VR, 0x40);
+        mfp.lock().write_register(IMRB, 0x50);
+        mfp.lock().interrupt(GPIP4);
+        assert_eq!(Vector::Number(0x40 | GPIP4), mfp.acknowledge());
+        assert_eq!(0, mfp.lock().read_register(ISRB));
+    }
+
+    #[test]
+    fn gpip_lines_interrupt_on_their_edge() {
+        let mfp = mfp();
+        enable(&mfp, GPIP5);
+        enable(&mfp, GPIP7);
+        let mut mfp = mfp.lock();
+        // GPIP5 on a falling edge, GPIP7 on a rising one, GPIP0 an output
+        mfp.write_register(AER, 0x80);
+        mfp.write_register(DDR, 0x01);
+        mfp.set_gpip_input(5, true);
+        mfp.set_gpip_input(7, true);
+        assert_eq!(0x80, mfp.read_register(IPRA));
+        assert_eq!(0, mfp.read_register(IPRB));
+        mfp.set_gpip_input(5, false);
+        assert_eq!(0x80, mfp.read_register(IPRB));
+        mfp.write_register(GPDR, 0xff);
+        assert_eq!(0x81, mfp.read_register(GPDR));
+        assert_eq!(0x01, mfp.gpip_outputs());
+    }
+
+    #[test]
+    fn the_usart_moves_characters() {
+        let mfp = mfp();
+        enable(&mfp, RECEIVE_FULL);
+        enable(&mfp, RECEIVE_ERROR);
+        let mut mfp = mfp.lock();
+        mfp.write_register(UDR, b'x');
+        assert!(!mfp.receive(b'a'));
+        mfp.write_register(TSR, TRANSMITTER_ENABLE);
+        mfp.write_register(RSR, RECEIVER_ENABLE);
+        mfp.write_register(UDR, b'o');
+        mfp.write_register(UDR, b'k');
+        assert_eq!(b"ok".to_vec(), mfp.take_transmitted());
+        assert_eq!(BUFFER_EMPTY, mfp.read_register(TSR) & BUFFER_EMPTY);
+        assert!(mfp.receive(b'a'));
+        assert!(!mfp.receive(b'b'));
+        assert_eq!(0x18, mfp.read_register(IPRA));
+        assert_eq!(BUFFER_FULL | OVERRUN, mfp.read_register(RSR) & 0xc0);
+        assert_eq!(b'a', mfp.read_register(UDR));
+        assert_eq!(0, mfp.read_register(RSR) & 0xc0);
+    }
+
+    #[test]
+    fn registers_are_at_odd_addresses() {
+        let mut mfp = mfp();
+        mfp.write(SUPERVISOR_DATA, 2 * u32::from(VR), Size::Word, 0x1240).unwrap();
+        assert_eq!(0x40, mfp.lock().read_register(VR));
+        assert_eq!(Ok(0xff40), mfp.read(SUPERVISOR_DATA, 2 * u32::from(VR), Size::Word));
+        assert_eq!(Ok(0xff), mfp.read(SUPERVISOR_DATA, 2 * u32::from(VR), Size::Byte));
+        assert_eq!(Ok(0xff40_ff00), mfp.read(SUPERVISOR_DATA, 2 * u32::from(VR), Size::Long));
+        mfp.reset();
+        assert_eq!(0, mfp.lock().read_register(VR));
+    }
+
+    #[cfg(feature = "cycles")]
+    #[test]
+    fn the_core_takes_timer_interrupts() {
+        use cpu::{ConfiguredCore, Cycles};
+        use interrupts::VectoredInterruptController;
+        use ram::{AddressBus, MappedBus};
+
+        let mfp = mfp();
+        let mut bus = MappedBus::new();
+        bus.map_ram(0, 0x10000);
+        bus.map_device(0xfffa00, 0x40, Box::new(mfp.clone()));
+        bus.write_long(SUPERVISOR_DATA, 0, 0x8000);
+        bus.write_long(SUPERVISOR_DATA, 4, 0x1000);
+        bus.write_long(SUPERVISOR_DATA, u32::from(0x40 | TIMER_A) * 4, 0x2000);
+        // MOVE.B #$48,VR; MOVE.B #$20,IERA; MOVE.B #$20,IMRA;
+        // MOVE.B #100,TADR; MOVE.B #7,TACR (dividing by 200);
+        // MOVE #$2000,SR; BRA.S *
+        let code = [0x13fc, 0x0048, 0x00ff, 0xfa17, 0x13fc, 0x0020, 0x00ff, 0xfa07, 0x13fc, 0x0020, 0x00ff, 0xfa13,
+            0x13fc, 0x0064, 0x00ff, 0xfa1f, 0x13fc, 0x0007, 0x00ff, 0xfa19, 0x46fc, 0x2000, 0x60fe];
+        // ADDQ.L #1,D7; MOVE.B #$df,ISRA; RTE
+        let handler = [0x5287, 0x13fc, 0x00df, 0x00ff, 0xfa0f, 0x4e73];
+        for (base, words) in [(0x1000, &code[..]), (0x2000, &handler[..])].iter() {
+            for (i, &word) in words.iter().enumerate() {
+                bus.write_word(SUPERVISOR_DATA, base + 2 * i as u32, word);
+            }
+        }
+        let mut ctrl = VectoredInterruptController::new();
+        ctrl.add_responder(6, Box::new(mfp.clone()));
+        let mut core = ConfiguredCore::new_with(0, ctrl, bus);
+        core.reset();
+        // 20000 timer clocks between interrupts, or 65104 cycles
+        let mut cycles = 0;
+        while cycles < 660_000 {
+            let Cycles(used) = core.execute(1000);
+            mfp.lock().advance(used as u64);
+            cycles += used;
+        }
+        assert_eq!(10, core.dar[7]);
+        assert_eq!(0, mfp.lock().read_register(ISRA));
+    }
+}
@@ -0,0 +1,194 @@
+// Models of 8-bit peripheral chips, to map into a MappedBus. Those that
+// interrupt the core also plug into a VectoredInterruptController, and
+// those with timers are driven by the cycles the host runs the core for.
+use interrupts::{Responder, Vector};
+use ram::{AddressSpace, BusResult};
+use ram::mappedbus::{Device, Size};
+use savestate::{self, StateReader, StateWriter};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub mod acia;
+pub mod duart;
+pub mod mfp;
+pub mod serial;
+
+// The half of the data bus a chip sits on, which puts its registers at
+// either the even (upper) or the odd (lower) addresses of the region it
+// is mapped at. The other half reads as $ff.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Lane {
+    Upper,
+    Lower,
+}
+
+// A chip with byte wide registers, which may interrupt the core
+pub trait Chip: Send {
+    fn read_register(&mut self, register: u8) -> u8;
+    fn write_register(&mut self, register: u8, value: u8);
+
+    // the interrupt request output
+    fn requesting(&self) -> bool {
+        false
+    }
+    // the interrupt acknowledge cycle
+    fn acknowledge(&mut self) -> Vector {
+        Vector::Spurious
+    }
+
+    fn reset(&mut self);
+
+    fn save(&self, writer: &mut StateWriter);
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()>;
+}
+
+// A chip to map into a MappedBus, and to add to a
+// VectoredInterruptController as a Responder. Clones share the chip, so
+// the host keeps one to drive it.
+pub struct Shared<C: Chip> {
+    chip: Arc<Mutex<C>>,
+    lane: Lane,
+}
+
+impl<C: Chip> Clone for Shared<C> {
+    fn clone(&self) -> Self {
+        Shared { chip: self.chip.clone(), lane: self.lane }
+    }
+}
+
+impl<C: Chip> Shared<C> {
+    pub fn new(chip: C, lane: Lane) -> Shared<C> {
+        Shared { chip: Arc::new(Mutex::new(chip)), lane }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, C> {
+        self.chip.lock().unwrap()
+    }
+
+    // the register at the offset, if the chip is on its half of the bus
+    fn register(&self, offset: u32) -> Option<u8> {
+        let odd = offset & 1 == 1;
+        if odd == (self.lane == Lane::Lower) { Some((offset >> 1) as u8) } else { None }
+    }
+}
+
+impl<C: Chip> Device for Shared<C> {
+    fn read(&mut self, _: AddressSpace, offset: u32, size: Size) -> BusResult<u32> {
+        let mut chip = self.lock();
+        Ok((offset..offset + size.bytes()).fold(0, |value, address| {
+            let byte = self.register(address).map_or(0xff, |register| chip.read_register(register));
+            value << 8 | u32::from(byte)
+        }))
+    }
+
+    fn write(&mut self, _: AddressSpace, offset: u32, size: Size, value: u32) -> BusResult<()> {
+        let mut chip = self.lock();
+        let last = offset + size.bytes() - 1;
+        for address in offset..=last {
+            if let Some(register) = self.register(address) {
+                chip.write_register(register, (value >> (8 * (last - address))) as u8);
+            }
+        }
+        Ok(())
+    }
+
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.lock().save(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.lock().load(reader)
+    }
+}
+
+impl<C: Chip> Responder for Shared<C> {
+    fn requesting(&self) -> bool {
+        self.lock().requesting()
+    }
+
+    fn acknowledge(&mut self) -> Vector {
+        self.lock().acknowledge()
+    }
+
+    fn reset(&mut self) {
+        self.lock().reset();
+    }
+}
+
+// The clock of a chip, counted in the cycles of the core
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    cpu_clock: u64,
+    clock: u64,
+    fraction: u64, // cycles times the clock, short of a period of it
+}
+
+impl Clock {
+    // both in Hz
+    pub fn new(cpu_clock: u32, clock: u32) -> Clock {
+        assert!(cpu_clock > 0 && clock > 0);
+        Clock { cpu_clock: u64::from(cpu_clock), clock: u64::from(clock), fraction: 0 }
+    }
+
+    // the periods of the clock that the cycles of the core took
+    pub fn periods(&mut self, cycles: u64) -> u64 {
+        let total = self.fraction + cycles * self.clock;
+        self.fraction = total % self.cpu_clock;
+        total / self.cpu_clock
+    }
+
+    fn save(&self, writer: &mut StateWriter) {
+        writer.put_u32(self.fraction as u32);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        self.fraction = u64::from(reader.get_u32()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chip, Clock, Lane, Shared};
+    use ram::SUPERVISOR_DATA;
+    use ram::mappedbus::{Device, Size};
+    use savestate::{self, StateReader, StateWriter};
+
+    struct Latches([u8; 4]);
+
+    impl Chip for Latches {
+        fn read_register(&mut self, register: u8) -> u8 {
+            self.0[register as usize]
+        }
+        fn write_register(&mut self, register: u8, value: u8) {
+            self.0[register as usize] = value;
+        }
+        fn reset(&mut self) {}
+        fn save(&self, _: &mut StateWriter) {}
+        fn load(&mut self, _: &mut StateReader) -> savestate::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registers_are_on_one_half_of_the_bus() {
+        let mut lower = Shared::new(Latches([0; 4]), Lane::Lower);
+        lower.write(SUPERVISOR_DATA, 2, Size::Long, 0x1122_3344).unwrap();
+        assert_eq!([0, 0x22, 0x44, 0], lower.lock().0);
+        assert_eq!(Ok(0xff22), lower.read(SUPERVISOR_DATA, 2, Size::Word));
+        assert_eq!(Ok(0xff), lower.read(SUPERVISOR_DATA, 2, Size::Byte));
+        let mut upper = Shared::new(Latches([0; 4]), Lane::Upper);
+        upper.write(SUPERVISOR_DATA, 2, Size::Long, 0x1122_3344).unwrap();
+        assert_eq!([0, 0x11, 0x33, 0], upper.lock().0);
+        assert_eq!(Ok(0x11ff_33ff), upper.read(SUPERVISOR_DATA, 2, Size::Long));
+    }
+
+    #[test]
+    fn clocks_keep_the_fractions_of_their_periods() {
+        // three periods every ten cycles
+        let mut clock = Clock::new(10, 3);
+        assert_eq!(0, clock.periods(3));
+        assert_eq!(1, clock.periods(1));
+        assert_eq!(5, clock.periods(16));
+        assert_eq!(30, clock.periods(100));
+    }
+}
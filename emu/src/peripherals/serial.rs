@@ -0,0 +1,173 @@
+// The host end of a serial line, for the ACIA and the DUART, or of the
+// console of the EASy68K simulator. Characters come from any reader
+// (stdin, a pipe, a socket), which is read on a thread of its own so that
+// waiting for input doesn't hold up the core, and go to any writer as soon
+// as the program sends them. Input given up front, as tests do, needs no
+// thread.
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+pub struct HostPort {
+    pending: VecDeque<u8>,
+    reader: Option<Receiver<u8>>,
+    writer: Box<dyn Write + Send>,
+}
+
+impl HostPort {
+    pub fn new<R, W>(reader: R, writer: W) -> HostPort
+        where R: Read + Send + 'static, W: Write + Send + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut reader = reader;
+        thread::spawn(move || {
+            let mut buf = [0; 256];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) => if buf[..len].iter().any(|&byte| sender.send(byte).is_err()) {
+                        break;
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(_) => break,
+                }
+            }
+        });
+        HostPort { pending: VecDeque::new(), reader: Some(receiver), writer: Box::new(writer) }
+    }
+
+    // the console the emulator runs in
+    pub fn stdio() -> HostPort {
+        HostPort::new(io::stdin(), io::stdout())
+    }
+
+    // a connection, for instance from a terminal to a localhost port
+    pub fn tcp(stream: TcpStream) -> io::Result<HostPort> {
+        Ok(HostPort::new(stream.try_clone()?, stream))
+    }
+
+    pub fn with_input<W: Write + Send + 'static>(input: &[u8], writer: W) -> HostPort {
+        HostPort { pending: input.iter().cloned().collect(), reader: None, writer: Box::new(writer) }
+    }
+
+    // nothing comes in, and what goes out is lost
+    pub fn disconnected() -> HostPort {
+        HostPort::with_input(&[], io::sink())
+    }
+
+    // the next character that arrived, if any
+    pub fn receive(&mut self) -> Option<u8> {
+        if self.pending.is_empty() {
+            if let Some(ref reader) = self.reader {
+                self.pending.extend(reader.try_iter());
+            }
+        }
+        self.pending.pop_front()
+    }
+
+    // waits for the next character, until the input has ended
+    pub fn receive_waiting(&mut self) -> Option<u8> {
+        match self.pending.pop_front() {
+            Some(byte) => Some(byte),
+            None => self.reader.as_ref().and_then(|reader| reader.recv().ok()),
+        }
+    }
+
+    pub fn has_input(&mut self) -> bool {
+        if let Some(ref reader) = self.reader {
+            self.pending.extend(reader.try_iter());
+        }
+        !self.pending.is_empty()
+    }
+
+    // Errors writing are ignored, as the line would just lose the
+    // character
+    pub fn send(&mut self, byte: u8) {
+        let _ = self.writer.write_all(&[byte]).and_then(|_| self.writer.flush());
+    }
+}
+
+// A writer keeping what was written in memory, for clones to look at
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> SharedBuffer {
+        SharedBuffer::default()
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HostPort, SharedBuffer};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // what arrives within a second
+    fn receive(port: &mut HostPort, len: usize) -> Vec<u8> {
+        let start = Instant::now();
+        let mut received = Vec::new();
+        while received.len() < len && start.elapsed() < Duration::from_secs(1) {
+            match port.receive() {
+                Some(byte) => received.push(byte),
+                None => thread::yield_now(),
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn characters_come_from_the_reader_and_go_to_the_writer() {
+        let output = SharedBuffer::new();
+        let mut port = HostPort::new(&b"in"[..], output.clone());
+        assert_eq!(b"in".to_vec(), receive(&mut port, 2));
+        port.send(b'o');
+        port.send(b'k');
+        assert_eq!(b"ok".to_vec(), output.contents());
+        assert_eq!(None, port.receive());
+        assert_eq!(None, port.receive_waiting());
+
+        let mut port = HostPort::with_input(b"xy", output.clone());
+        assert!(port.has_input());
+        assert_eq!(Some(b'x'), port.receive());
+        assert_eq!(Some(b'y'), port.receive_waiting());
+        assert!(!port.has_input());
+        assert_eq!(None, port.receive());
+        assert_eq!(None, port.receive_waiting());
+    }
+
+    #[test]
+    fn ports_can_be_sockets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut terminal = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut port = HostPort::tcp(listener.accept().unwrap().0).unwrap();
+        terminal.write_all(b"ping").unwrap();
+        assert_eq!(b"ping".to_vec(), receive(&mut port, 4));
+        for &byte in b"pong" {
+            port.send(byte);
+        }
+        let mut reply = [0; 4];
+        terminal.read_exact(&mut reply).unwrap();
+        assert_eq!(b"pong", &reply);
+    }
+}
@@ -0,0 +1,333 @@
+use savestate::{self, Snapshot, StateReader, StateWriter};
+use super::{AddressSpace, AddressBus, BusError, BusResult, ADDRBUS_MASK};
+
+const MEM_SIZE: usize = 0x100_0000; // the entire 24-bit address bus
+// dirty tracking uses the same granularity as the pages of PagedMem,
+// so diffs() reports the same bytes for both
+const PAGE_SIZE: usize = 16;
+const PAGE_SHIFT: usize = 4;
+const PAGES: usize = MEM_SIZE / PAGE_SIZE;
+
+// A flat array covering the 24-bit address space, trading 16MB of
+// host memory for a plain index per access (instead of the hash lookup
+// per byte that PagedMem needs). On a 32-bit address bus (a 68020 or
+// 68030), the core's accesses above 16MB end in bus errors rather than
+// alias memory; the infallible accesses still wrap at 16MB.
+pub struct FlatMem {
+    mem: Box<[u8; MEM_SIZE]>,
+    dirty: Vec<u64>, // one bit per page
+    address_mask: u32,
+    pub initializer: u32,
+}
+
+impl FlatMem {
+    pub fn new(initializer: u32) -> FlatMem {
+        let mut mem = FlatMem { mem: ::box_array![0; MEM_SIZE], dirty: vec![0; PAGES / 64], address_mask: ADDRBUS_MASK, initializer };
+        mem.reset(initializer);
+        mem
+    }
+
+    pub fn reset(&mut self, initializer: u32) {
+        self.initializer = initializer;
+        let pattern = initializer.to_be_bytes();
+        for chunk in self.mem.chunks_mut(4) {
+            chunk.copy_from_slice(&pattern);
+        }
+        for bits in &mut self.dirty {
+            *bits = 0;
+        }
+    }
+
+    fn is_dirty(&self, page: usize) -> bool {
+        self.dirty[page / 64] & (1 << (page % 64)) != 0
+    }
+    // Writes bytes that lie within the memory, as one copy. Like PagedMem,
+    // a page only counts as changed once a value that differs from the
+    // initializer has been written to it.
+    fn write_bytes(&mut self, address: usize, bytes: &[u8]) {
+        let split = PAGE_SIZE - address % PAGE_SIZE;
+        if split < bytes.len() {
+            self.write_bytes(address, &bytes[..split]);
+            self.write_bytes(address + split, &bytes[split..]);
+            return;
+        }
+        let initial = self.initializer.rotate_left(8 * (address % 4) as u32).to_be_bytes();
+        if bytes != &initial[..bytes.len()] {
+            let page = address >> PAGE_SHIFT;
+            self.dirty[page / 64] |= 1 << (page % 64);
+        }
+        self.mem[address..address + bytes.len()].copy_from_slice(bytes);
+    }
+    #[cfg(test)]
+    fn dirty_pages(&self) -> usize {
+        self.dirty.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    pub fn read_u8(&self, address: u32) -> u32 {
+        u32::from(self.mem[(address & ADDRBUS_MASK) as usize])
+    }
+
+    pub fn write_u8(&mut self, address: u32, value: u32) {
+        self.write_bytes((address & ADDRBUS_MASK) as usize, &[value as u8]);
+    }
+
+    // Faults an access past the 16MB of memory, which only a core with
+    // more than 24 address lines makes
+    fn check_in_range(&self, address: u32, size: u32) -> BusResult<()> {
+        if self.address_mask != ADDRBUS_MASK && u64::from(address & self.address_mask) + u64::from(size) > MEM_SIZE as u64 {
+            Err(BusError)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn diffs(&self) -> DiffIter<'_> {
+        DiffIter { mem: self, page: 0, index: 0 }
+    }
+}
+
+pub struct DiffIter<'a> {
+    mem: &'a FlatMem,
+    page: usize,
+    index: usize,
+}
+impl<'a> Iterator for DiffIter<'a> {
+    type Item = (u32, u8);
+    fn next(&mut self) -> Option<(u32, u8)> {
+        if self.index == PAGE_SIZE {
+            self.index = 0;
+            self.page += 1;
+        }
+        if self.index == 0 {
+            while self.page < PAGES && !self.mem.is_dirty(self.page) {
+                self.page += 1;
+            }
+        }
+        if self.page >= PAGES {
+            None
+        } else {
+            let address = self.page * PAGE_SIZE + self.index;
+            self.index += 1;
+            Some((address as u32, self.mem.mem[address]))
+        }
+    }
+}
+
+impl AddressBus for FlatMem {
+    fn copy_from(&mut self, other: &Self) {
+        for (addr, byte) in other.diffs() {
+            self.write_u8(addr, u32::from(byte));
+        }
+    }
+
+    fn read_byte(&mut self, _address_space: AddressSpace, address: u32) -> u32 {
+        self.read_u8(address)
+    }
+
+    fn read_word(&mut self, _address_space: AddressSpace, address: u32) -> u32 {
+        let a = (address & ADDRBUS_MASK) as usize;
+        if a < MEM_SIZE - 1 {
+            u32::from(u16::from_be_bytes([self.mem[a], self.mem[a + 1]]))
+        } else {
+            // wraps around the end of the address bus
+            self.read_u8(address) << 8 | self.read_u8(address.wrapping_add(1))
+        }
+    }
+
+    fn read_long(&mut self, _address_space: AddressSpace, address: u32) -> u32 {
+        let a = (address & ADDRBUS_MASK) as usize;
+        if a < MEM_SIZE - 3 {
+            u32::from_be_bytes([self.mem[a], self.mem[a + 1], self.mem[a + 2], self.mem[a + 3]])
+        } else {
+            // wraps around the end of the address bus
+            self.read_u8(address) << 24
+            | self.read_u8(address.wrapping_add(1)) << 16
+            | self.read_u8(address.wrapping_add(2)) <<  8
+            | self.read_u8(address.wrapping_add(3))
+        }
+    }
+
+    fn write_byte(&mut self, _address_space: AddressSpace, address: u32, value: u32) {
+        self.write_u8(address, value);
+    }
+
+    fn write_word(&mut self, _address_space: AddressSpace, address: u32, value: u32) {
+        let a = (address & ADDRBUS_MASK) as usize;
+        if a < MEM_SIZE - 1 {
+            self.write_bytes(a, &(value as u16).to_be_bytes());
+        } else {
+            // wraps around the end of the address bus
+            self.write_u8(address, value >>  8);
+            self.write_u8(address.wrapping_add(1), value);
+        }
+    }
+
+    fn write_long(&mut self, _address_space: AddressSpace, address: u32, value: u32) {
+        let a = (address & ADDRBUS_MASK) as usize;
+        if a < MEM_SIZE - 3 {
+            self.write_bytes(a, &value.to_be_bytes());
+        } else {
+            // wraps around the end of the address bus
+            self.write_u8(address, value >> 24);
+            self.write_u8(address.wrapping_add(1), value >> 16);
+            self.write_u8(address.wrapping_add(2), value >>  8);
+            self.write_u8(address.wrapping_add(3), value);
+        }
+    }
+
+    fn set_address_mask(&mut self, address_mask: u32) {
+        self.address_mask = address_mask;
+    }
+
+    fn try_read_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_in_range(address, 1)?;
+        Ok(self.read_byte(address_space, address))
+    }
+    fn try_read_word(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_in_range(address, 2)?;
+        Ok(self.read_word(address_space, address))
+    }
+    fn try_read_long(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_in_range(address, 4)?;
+        Ok(self.read_long(address_space, address))
+    }
+    fn try_write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_in_range(address, 1)?;
+        self.write_byte(address_space, address, value);
+        Ok(())
+    }
+    fn try_write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_in_range(address, 2)?;
+        self.write_word(address_space, address, value);
+        Ok(())
+    }
+    fn try_write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_in_range(address, 4)?;
+        self.write_long(address_space, address, value);
+        Ok(())
+    }
+}
+
+impl Snapshot for FlatMem {
+    fn save(&self, writer: &mut StateWriter) {
+        savestate::save_memory(writer, self.initializer, self.diffs());
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        let initializer = reader.get_u32()?;
+        self.reset(initializer);
+        savestate::load_memory(reader, |address, byte| self.write_u8(address, u32::from(byte)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressBus, FlatMem, PAGE_SIZE};
+    use ram::{BusError, PagedMem, SUPERVISOR_DATA, USER_PROGRAM, ADDRBUS_MASK};
+
+    const PAGE: u32 = PAGE_SIZE as u32;
+
+    #[test]
+    fn read_initialized_memory() {
+        let mut mem = FlatMem::new(0x01020304);
+        for v in 0..256 {
+            assert_eq!(0x01, mem.read_byte(SUPERVISOR_DATA, 4*v+0));
+            assert_eq!(0x0203, mem.read_word(SUPERVISOR_DATA, 4*v+1));
+            assert_eq!(0x04010203, mem.read_long(SUPERVISOR_DATA, 4*v+3));
+        }
+    }
+
+    #[test]
+    fn read_your_writes() {
+        let mut mem = FlatMem::new(0x01020304);
+        let pattern = 0xAAAA7777;
+        mem.write_long(SUPERVISOR_DATA, 128, pattern);
+        assert_eq!(pattern, mem.read_long(USER_PROGRAM, 128));
+        mem.write_word(SUPERVISOR_DATA, 256, pattern);
+        assert_eq!(pattern & 0xFFFF, mem.read_word(USER_PROGRAM, 256));
+        mem.write_byte(SUPERVISOR_DATA, 512, pattern);
+        assert_eq!(pattern & 0xFF, mem.read_byte(USER_PROGRAM, 512));
+    }
+
+    #[test]
+    fn cross_address_bus_boundary_access() {
+        let mut mem = FlatMem::new(0x01020304);
+        mem.write_long(SUPERVISOR_DATA, ADDRBUS_MASK-1, 0x91929394);
+        assert_eq!(0x91929394, mem.read_long(SUPERVISOR_DATA, ADDRBUS_MASK-1));
+        assert_eq!(0x9394, mem.read_word(SUPERVISOR_DATA, 0));
+        assert_eq!(0x9293, mem.read_word(SUPERVISOR_DATA, ADDRBUS_MASK));
+        let addr = u32::max_value()-1;
+        mem.write_long(SUPERVISOR_DATA, addr, 0x95969798);
+        assert_eq!(0x95969798, mem.read_long(SUPERVISOR_DATA, addr));
+    }
+
+    #[test]
+    fn a_32_bit_address_bus_faults_above_16mb() {
+        let mut mem = FlatMem::new(0x01020304);
+        assert_eq!(Ok(0x01), mem.try_read_byte(SUPERVISOR_DATA, 0x5000_0000));
+        mem.set_address_mask(0xffff_ffff);
+        assert_eq!(Err(BusError), mem.try_read_byte(SUPERVISOR_DATA, 0x5000_0000));
+        assert_eq!(Err(BusError), mem.try_write_word(SUPERVISOR_DATA, ADDRBUS_MASK, 0x9192));
+        assert_eq!(Ok(()), mem.try_write_word(SUPERVISOR_DATA, ADDRBUS_MASK - 1, 0x9192));
+        assert_eq!(Ok(0x9192), mem.try_read_word(SUPERVISOR_DATA, ADDRBUS_MASK - 1));
+    }
+
+    #[test]
+    fn no_diff_initially_or_when_writing_initializer() {
+        let mut mem = FlatMem::new(0x01020304);
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x01020304);
+        assert_eq!(None, mem.diffs().next());
+        assert_eq!(0, mem.dirty_pages());
+    }
+
+    #[test]
+    fn diffs_are_reported_per_page() {
+        let mut mem = FlatMem::new(0x01020304);
+        mem.write_byte(SUPERVISOR_DATA, PAGE * 10, 0x91);
+        mem.write_byte(SUPERVISOR_DATA, PAGE * 20 + 1, 0x92);
+        mem.write_byte(SUPERVISOR_DATA, ADDRBUS_MASK, 0x93);
+        let diffs: Vec<(u32, u8)> = mem.diffs().collect();
+        assert_eq!(3 * PAGE_SIZE, diffs.len());
+        assert_eq!((PAGE * 10, 0x91), diffs[0]);
+        assert_eq!((PAGE * 20 + 1, 0x92), diffs[PAGE_SIZE + 1]);
+        assert_eq!((ADDRBUS_MASK, 0x93), diffs[3 * PAGE_SIZE - 1]);
+    }
+
+    #[test]
+    fn diffs_match_paged_mem() {
+        let mut flat = FlatMem::new(0x01020304);
+        let mut paged = PagedMem::new(0x01020304);
+        for &(address, value) in &[(0x40, 0x4e714e71), (0x1002, 0x01020304), (0x1006, 0x12345678), (0xfffffe, 0x9192)] {
+            flat.write_long(SUPERVISOR_DATA, address, value);
+            paged.write_long(SUPERVISOR_DATA, address, value);
+        }
+        assert_eq!(paged.diffs().collect::<Vec<_>>(), flat.diffs().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn writes_across_pages_only_dirty_the_pages_they_change() {
+        let mut mem = FlatMem::new(0x01020304);
+        // the first half is what the initializer has there
+        mem.write_long(SUPERVISOR_DATA, PAGE - 2, 0x0304_9192);
+        assert_eq!(1, mem.dirty_pages());
+        assert_eq!(Some((PAGE, 0x91)), mem.diffs().next());
+        assert_eq!(0x0304_9192, mem.read_long(SUPERVISOR_DATA, PAGE - 2));
+    }
+
+    #[test]
+    fn reset_clears_memory_and_diffs() {
+        let mut mem = FlatMem::new(0x01020304);
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x91929394);
+        mem.reset(0xAAAAAAAA);
+        assert_eq!(0xAAAAAAAA, mem.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!(None, mem.diffs().next());
+    }
+
+    #[test]
+    fn copy_from_copies_diffs() {
+        let mut mem = FlatMem::new(0x01020304);
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x91929394);
+        let mut copy = FlatMem::new(0x01020304);
+        copy.copy_from(&mem);
+        assert_eq!(0x91929394, copy.read_long(SUPERVISOR_DATA, 0x100));
+    }
+}
@@ -1,6 +1,8 @@
-use super::{AddressBus, AddressSpace, ADDRBUS_MASK};
+use super::{AddressBus, AddressSpace, BusError, BusResult};
+use std::ops::Range;
 use ram::pagedmem::{DiffIter, PagedMem};
-use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use savestate::{self, Snapshot, StateReader, StateWriter};
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Operation {
@@ -45,19 +47,19 @@ pub trait OpsLogging {
 
 #[derive(Default)]
 pub struct OpsLogger {
-    log: RefCell<Vec<Operation>>,
+    log: Vec<Operation>,
     count: usize,
 }
 
 impl OpsLogger {
     pub fn new() -> OpsLogger {
         OpsLogger {
-            log: RefCell::new(Vec::new()),
+            log: Vec::new(),
             count: 0
         }
     }
     pub fn ops(&self) -> Vec<Operation> {
-        self.log.borrow_mut().clone()
+        self.log.clone()
     }
     pub fn len(&self) -> usize {
         self.count
@@ -68,11 +70,41 @@ impl OpsLogger {
 }
 impl OpsLogging for OpsLogger {
     fn log(&mut self, op: Operation) {
-        self.log.borrow_mut().push(op);
+        self.log.push(op);
         self.count += 1;
     }
 }
 
+// An operations log that clones share, even across threads, so that it
+// can be looked at while the core logging to it runs on another thread
+#[derive(Clone, Default)]
+pub struct SharedOpsLogger {
+    log: Arc<Mutex<Vec<Operation>>>,
+}
+
+impl SharedOpsLogger {
+    pub fn new() -> SharedOpsLogger {
+        SharedOpsLogger { log: Arc::new(Mutex::new(Vec::new())) }
+    }
+    pub fn ops(&self) -> Vec<Operation> {
+        self.log.lock().unwrap().clone()
+    }
+    pub fn len(&self) -> usize {
+        self.log.lock().unwrap().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn clear(&self) {
+        self.log.lock().unwrap().clear();
+    }
+}
+impl OpsLogging for SharedOpsLogger {
+    fn log(&mut self, op: Operation) {
+        self.log.lock().unwrap().push(op);
+    }
+}
+
 pub struct LoggingMem<T: OpsLogging> {
     pub logger: T,
     mem: PagedMem,
@@ -101,6 +133,12 @@ impl<T: OpsLogging> LoggingMem<T> {
     pub fn diffs(&self) -> DiffIter {
         self.mem.diffs()
     }
+    pub fn unmap(&mut self, range: Range<u32>) {
+        self.mem.unmap(range)
+    }
+    fn check_mapped(&self, address: u32, size: u32) -> BusResult<()> {
+        if self.mem.is_mapped(address, size) { Ok(()) } else { Err(BusError) }
+    }
 
     pub fn reset(&mut self, initializer: u32) {
         self.initializer = initializer;
@@ -110,16 +148,18 @@ impl<T: OpsLogging> LoggingMem<T> {
 
 impl<T: OpsLogging> AddressBus for LoggingMem<T> {
     fn copy_from(&mut self, other: &Self) {
-        for (addr, byte) in other.diffs() {
-            self.write_u8(addr, u32::from(byte));
-        }
+        self.mem.copy_from(&other.mem);
+    }
+
+    fn set_address_mask(&mut self, address_mask: u32) {
+        self.mem.set_address_mask(address_mask)
     }
 
     fn read_byte(&mut self, address_space: AddressSpace, address: u32) -> u32 {
         let value = self.read_u8(address);
         self.logger.log(Operation::ReadByte(
             address_space,
-            address & ADDRBUS_MASK,
+            address & self.mem.address_mask(),
             value as u8,
         ));
         value
@@ -129,7 +169,7 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
         let value = (self.read_u8(address) << 8 | self.read_u8(address.wrapping_add(1))) as u32;
         self.logger.log(Operation::ReadWord(
             address_space,
-            address & ADDRBUS_MASK,
+            address & self.mem.address_mask(),
             value as u16,
         ));
         value
@@ -142,7 +182,7 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
             | self.read_u8(address.wrapping_add(3))) as u32;
         self.logger.log(Operation::ReadLong(
             address_space,
-            address & ADDRBUS_MASK,
+            address & self.mem.address_mask(),
             value,
         ));
         value
@@ -151,7 +191,7 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
     fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
         self.logger.log(Operation::WriteByte(
             address_space,
-            address & ADDRBUS_MASK,
+            address & self.mem.address_mask(),
             value,
         ));
         self.write_u8(address, value);
@@ -160,7 +200,7 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
     fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
         self.logger.log(Operation::WriteWord(
             address_space,
-            address & ADDRBUS_MASK,
+            address & self.mem.address_mask(),
             value,
         ));
         self.write_u8(address, value >> 8);
@@ -170,7 +210,7 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
     fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
         self.logger.log(Operation::WriteLong(
             address_space,
-            address & ADDRBUS_MASK,
+            address & self.mem.address_mask(),
             value,
         ));
         self.write_u8(address, value >> 24);
@@ -178,12 +218,63 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
         self.write_u8(address.wrapping_add(2), value >> 8);
         self.write_u8(address.wrapping_add(3), value);
     }
+
+    fn peek_byte(&mut self, _address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 1)?;
+        Ok(self.read_u8(address))
+    }
+
+    // faulted bus cycles are not logged
+    fn try_read_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 1)?;
+        Ok(self.read_byte(address_space, address))
+    }
+
+    fn try_read_word(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 2)?;
+        Ok(self.read_word(address_space, address))
+    }
+
+    fn try_read_long(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 4)?;
+        Ok(self.read_long(address_space, address))
+    }
+
+    fn try_write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_mapped(address, 1)?;
+        self.write_byte(address_space, address, value);
+        Ok(())
+    }
+
+    fn try_write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_mapped(address, 2)?;
+        self.write_word(address_space, address, value);
+        Ok(())
+    }
+
+    fn try_write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_mapped(address, 4)?;
+        self.write_long(address_space, address, value);
+        Ok(())
+    }
+}
+
+// the log of operations is not part of the state
+impl<T: OpsLogging> Snapshot for LoggingMem<T> {
+    fn save(&self, writer: &mut StateWriter) {
+        savestate::save_memory(writer, self.initializer, self.diffs());
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        let initializer = reader.get_u32()?;
+        self.reset(initializer);
+        savestate::load_memory(reader, |address, byte| self.write_u8(address, u32::from(byte)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{AddressBus, LoggingMem, Operation, OpsLogger};
-    use ram::{ADDRBUS_MASK, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM};
+    use super::{AddressBus, LoggingMem, Operation, OpsLogger, SharedOpsLogger};
+    use ram::{BusError, ADDRBUS_MASK, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM};
 
     #[test]
     fn read_byte_is_logged() {
@@ -232,6 +323,20 @@ mod tests {
         do_write_long_is_logged(0xFF000180);
     }
 
+    #[test]
+    fn faulted_access_is_not_logged() {
+        let mut mem = LoggingMem::new(0x01020304, OpsLogger::new());
+        mem.unmap(0x1000..0x2000);
+        assert_eq!(Err(BusError), mem.try_read_word(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(Err(BusError), mem.try_write_long(USER_DATA, 0x1ffe, 0xAAAA7777));
+        assert!(mem.logger.is_empty());
+        assert_eq!(Ok(0x0102), mem.try_read_word(SUPERVISOR_DATA, 0x2000));
+        assert_eq!(
+            Operation::ReadWord(SUPERVISOR_DATA, 0x2000, 0x0102),
+            mem.logger.ops()[0]
+        );
+    }
+
     fn do_read_byte_is_logged(address: u32) {
         let mut mem = LoggingMem::new(0x01020304, OpsLogger::new());
         mem.read_byte(SUPERVISOR_DATA, address);
@@ -294,4 +399,17 @@ mod tests {
             mem.logger.ops()[0]
         );
     }
+
+    #[test]
+    fn shared_log_can_be_read_from_another_thread() {
+        let logger = SharedOpsLogger::new();
+        let mut mem = LoggingMem::new(0x01020304, logger.clone());
+        ::std::thread::spawn(move || {
+            mem.write_word(SUPERVISOR_DATA, 0x100, 0xbeef);
+            mem.read_byte(USER_DATA, 0x101);
+        }).join().unwrap();
+        assert_eq!(vec![Operation::WriteWord(SUPERVISOR_DATA, 0x100, 0xbeef), Operation::ReadByte(USER_DATA, 0x101, 0xef)], logger.ops());
+        logger.clear();
+        assert!(logger.is_empty());
+    }
 }
@@ -0,0 +1,561 @@
+use savestate::{self, Snapshot, StateError, StateReader, StateWriter};
+use super::{AddressBus, AddressSpace, BusError, BusResult, ADDRBUS_MASK};
+use std::sync::{Arc, Mutex};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Size {
+    Byte, Word, Long
+}
+
+impl Size {
+    pub fn bytes(self) -> u32 {
+        match self {
+            Size::Byte => 1,
+            Size::Word => 2,
+            Size::Long => 4,
+        }
+    }
+    pub fn mask(self) -> u32 {
+        match self {
+            Size::Byte => 0xff,
+            Size::Word => 0xffff,
+            Size::Long => 0xffff_ffff,
+        }
+    }
+}
+
+// A memory mapped device. The offset is relative to the start of the
+// region the device is mapped at, and values are right aligned (i.e. a
+// byte access reads or writes the lowest 8 bits). Returning an error
+// terminates the bus cycle with a bus error. Devices are Send, so that
+// a core with a MappedBus can run on any thread.
+pub trait Device: Send {
+    fn read(&mut self, address_space: AddressSpace, offset: u32, size: Size) -> BusResult<u32>;
+    fn write(&mut self, address_space: AddressSpace, offset: u32, size: Size, value: u32) -> BusResult<()>;
+
+    // wait states for a bus cycle starting at the given clock period, on
+    // top of those of the region (see AddressBus::wait_states)
+    fn wait_states(&mut self, _offset: u32, _clock: u64) -> u32 {
+        0
+    }
+
+    // devices with registers or buffers of their own should include
+    // them in save states of the bus
+    fn save_state(&self, _writer: &mut StateWriter) {}
+    fn load_state(&mut self, _reader: &mut StateReader) -> savestate::Result<()> {
+        Ok(())
+    }
+}
+
+// RAM that can be mapped into the buses of several cores at once, like
+// the dual-ported RAM between a main and a sound CPU. Clones share the
+// same contents.
+#[derive(Clone)]
+pub struct SharedRam(Arc<Mutex<Vec<u8>>>);
+
+impl SharedRam {
+    pub fn new(size: u32) -> SharedRam {
+        SharedRam(Arc::new(Mutex::new(vec![0; size as usize])))
+    }
+    pub fn len(&self) -> u32 {
+        self.0.lock().unwrap().len() as u32
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+enum Contents {
+    Ram(Vec<u8>),
+    Rom(Vec<u8>),
+    Shared(SharedRam),
+    Device(Box<dyn Device>),
+}
+
+fn read_bytes(bytes: &[u8], offset: u32, size: Size) -> u32 {
+    let offset = offset as usize;
+    bytes[offset..offset + size.bytes() as usize].iter()
+        .fold(0, |value, &byte| value << 8 | u32::from(byte))
+}
+
+fn write_bytes(bytes: &mut [u8], offset: u32, size: Size, value: u32) {
+    let offset = offset as usize;
+    let len = size.bytes() as usize;
+    for (i, byte) in bytes[offset..offset + len].iter_mut().enumerate() {
+        *byte = (value >> (8 * (len - 1 - i))) as u8;
+    }
+}
+
+struct Region {
+    start: u32,
+    end: u64, // exclusive, so that a region can end at the top of memory
+    contents: Contents,
+    wait_states: u32, // before DTACK, in each bus cycle
+}
+
+// An AddressBus routing ranges of the address space to RAM (possibly
+// shared with other buses), ROM or devices. Accesses to unmapped
+// addresses, or straddling the end of a region, cause bus errors. Writes
+// to ROM are ignored. Addresses are 24-bit like the 68000's until the
+// core drives more address lines (see AddressBus::set_address_mask), so
+// that regions above 16MB are only reached by a 68020 or 68030.
+pub struct MappedBus {
+    regions: Vec<Region>,
+    address_mask: u32,
+}
+
+impl Default for MappedBus {
+    fn default() -> MappedBus {
+        MappedBus::new()
+    }
+}
+
+impl MappedBus {
+    pub fn new() -> MappedBus {
+        MappedBus { regions: Vec::new(), address_mask: ADDRBUS_MASK }
+    }
+
+    pub fn map_ram(&mut self, start: u32, size: u32) {
+        self.map(start, size, Contents::Ram(vec![0; size as usize]));
+    }
+
+    pub fn map_rom(&mut self, start: u32, contents: Vec<u8>) {
+        let size = contents.len() as u32;
+        self.map(start, size, Contents::Rom(contents));
+    }
+
+    pub fn map_shared(&mut self, start: u32, ram: &SharedRam) {
+        self.map(start, ram.len(), Contents::Shared(ram.clone()));
+    }
+
+    pub fn map_device(&mut self, start: u32, size: u32, device: Box<dyn Device>) {
+        self.map(start, size, Contents::Device(device));
+    }
+
+    fn map(&mut self, start: u32, size: u32, contents: Contents) {
+        let end = u64::from(start) + u64::from(size);
+        if end > 1 << 32 {
+            panic!("region {:06x}+{:x} is outside the address space", start, size);
+        }
+        if let Some(other) = self.regions.iter().find(|r| u64::from(start) < r.end && u64::from(r.start) < end) {
+            panic!("region {:06x}-{:06x} overlaps {:06x}-{:06x}", start, end, other.start, other.end);
+        }
+        self.regions.push(Region { start, end, contents, wait_states: 0 });
+    }
+
+    // Slows down every bus cycle to the region mapped at the address,
+    // like a DTACK generated late for slow memory or devices
+    pub fn set_wait_states(&mut self, address: u32, wait_states: u32) {
+        let address = address & self.address_mask;
+        match self.regions.iter_mut().find(|r| r.start <= address && u64::from(address) < r.end) {
+            Some(region) => region.wait_states = wait_states,
+            None => panic!("no region mapped at {:06x}", address),
+        }
+    }
+
+    // copies data straight into RAM or ROM, as when loading a program
+    // or ROM image
+    pub fn load_image(&mut self, address: u32, data: &[u8]) -> BusResult<()> {
+        let (region, offset) = self.region(address, data.len() as u32)?;
+        match region.contents {
+            Contents::Ram(ref mut bytes) | Contents::Rom(ref mut bytes) => {
+                let offset = offset as usize;
+                bytes[offset..offset + data.len()].copy_from_slice(data);
+                Ok(())
+            },
+            Contents::Shared(ref ram) => {
+                let offset = offset as usize;
+                ram.0.lock().unwrap()[offset..offset + data.len()].copy_from_slice(data);
+                Ok(())
+            },
+            Contents::Device(_) => Err(BusError),
+        }
+    }
+
+    fn region(&mut self, address: u32, size: u32) -> BusResult<(&mut Region, u32)> {
+        let address = address & self.address_mask;
+        match self.regions.iter_mut().find(|r| r.start <= address && u64::from(address) < r.end) {
+            Some(ref region) if u64::from(address) + u64::from(size) > region.end => Err(BusError),
+            Some(region) => {
+                let offset = address - region.start;
+                Ok((region, offset))
+            },
+            None => Err(BusError),
+        }
+    }
+
+    fn read(&mut self, address_space: AddressSpace, address: u32, size: Size) -> BusResult<u32> {
+        let (region, offset) = self.region(address, size.bytes())?;
+        match region.contents {
+            Contents::Ram(ref bytes) | Contents::Rom(ref bytes) => Ok(read_bytes(bytes, offset, size)),
+            Contents::Shared(ref ram) => Ok(read_bytes(&ram.0.lock().unwrap(), offset, size)),
+            Contents::Device(ref mut device) => device.read(address_space, offset, size),
+        }
+    }
+
+    fn write(&mut self, address_space: AddressSpace, address: u32, size: Size, value: u32) -> BusResult<()> {
+        let (region, offset) = self.region(address, size.bytes())?;
+        let value = value & size.mask();
+        match region.contents {
+            Contents::Ram(ref mut bytes) => {
+                write_bytes(bytes, offset, size, value);
+                Ok(())
+            },
+            Contents::Shared(ref ram) => {
+                write_bytes(&mut ram.0.lock().unwrap(), offset, size, value);
+                Ok(())
+            },
+            Contents::Rom(_) => Ok(()),
+            Contents::Device(ref mut device) => device.write(address_space, offset, size, value),
+        }
+    }
+}
+
+// The infallible accesses can't signal a bus error, so unmapped reads
+// return all ones (like an undriven, pulled-up data bus) and unmapped
+// writes are lost.
+impl AddressBus for MappedBus {
+    fn copy_from(&mut self, other: &Self) {
+        for region in &other.regions {
+            if let Contents::Ram(ref bytes) = region.contents {
+                let _ = self.load_image(region.start, bytes);
+            }
+        }
+    }
+
+    fn set_address_mask(&mut self, address_mask: u32) {
+        self.address_mask = address_mask;
+    }
+
+    fn read_byte(&mut self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, Size::Byte).unwrap_or(0xff)
+    }
+
+    fn read_word(&mut self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, Size::Word).unwrap_or(0xffff)
+    }
+
+    fn read_long(&mut self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, Size::Long).unwrap_or(0xffff_ffff)
+    }
+
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        let _ = self.write(address_space, address, Size::Byte, value);
+    }
+
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        let _ = self.write(address_space, address, Size::Word, value);
+    }
+
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        let _ = self.write(address_space, address, Size::Long, value);
+    }
+
+    fn try_read_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.read(address_space, address, Size::Byte)
+    }
+
+    fn try_read_word(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.read(address_space, address, Size::Word)
+    }
+
+    fn try_read_long(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.read(address_space, address, Size::Long)
+    }
+
+    fn try_write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.write(address_space, address, Size::Byte, value)
+    }
+
+    fn try_write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.write(address_space, address, Size::Word, value)
+    }
+
+    fn try_write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.write(address_space, address, Size::Long, value)
+    }
+
+    // reading a device register may change it, so devices can't be peeked
+    fn peek_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        if let Contents::Device(_) = self.region(address, 1)?.0.contents {
+            return Err(BusError);
+        }
+        self.read(address_space, address, Size::Byte)
+    }
+
+    // a cycle to an unmapped address ends in a bus error without waiting
+    fn wait_states(&mut self, _address_space: AddressSpace, address: u32, clock: u64) -> u32 {
+        match self.region(address, 1) {
+            Ok((region, offset)) => region.wait_states + match region.contents {
+                Contents::Device(ref mut device) => device.wait_states(offset, clock),
+                _ => 0,
+            },
+            Err(_) => 0,
+        }
+    }
+}
+
+// The layout of the bus is configuration, and so is not part of the
+// state; only RAM contents and device state are. A state must be loaded
+// into a bus with the same layout as the one it was saved from.
+impl Snapshot for MappedBus {
+    fn save(&self, writer: &mut StateWriter) {
+        for region in &self.regions {
+            match region.contents {
+                Contents::Ram(ref bytes) => {
+                    writer.put_u32(bytes.len() as u32);
+                    writer.put_bytes(bytes);
+                },
+                Contents::Shared(ref ram) => {
+                    let bytes = ram.0.lock().unwrap();
+                    writer.put_u32(bytes.len() as u32);
+                    writer.put_bytes(&bytes);
+                },
+                Contents::Rom(_) => {},
+                Contents::Device(ref device) => device.save_state(writer),
+            }
+        }
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        for region in &mut self.regions {
+            match region.contents {
+                Contents::Ram(ref mut bytes) => {
+                    let len = bytes.len();
+                    if reader.get_u32()? as usize != len {
+                        return Err(StateError::Invalid("RAM region size"));
+                    }
+                    bytes.copy_from_slice(reader.get_bytes(len)?);
+                },
+                Contents::Shared(ref ram) => {
+                    let mut bytes = ram.0.lock().unwrap();
+                    let len = bytes.len();
+                    if reader.get_u32()? as usize != len {
+                        return Err(StateError::Invalid("RAM region size"));
+                    }
+                    bytes.copy_from_slice(reader.get_bytes(len)?);
+                },
+                Contents::Rom(_) => {},
+                Contents::Device(ref mut device) => device.load_state(reader)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressBus, Device, MappedBus, SharedRam, Size};
+    use ram::{AddressSpace, BusError, BusResult, ADDRBUS_MASK, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA};
+    use savestate::{self, Snapshot, StateError, StateReader, StateWriter};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Register {
+        value: u32,
+        accesses: Vec<(AddressSpace, u32, Size)>,
+    }
+
+    // a device with a single register, shared with the test
+    struct SharedRegister(Arc<Mutex<Register>>);
+
+    impl Device for SharedRegister {
+        fn read(&mut self, address_space: AddressSpace, offset: u32, size: Size) -> BusResult<u32> {
+            let mut reg = self.0.lock().unwrap();
+            reg.accesses.push((address_space, offset, size));
+            Ok(reg.value & size.mask())
+        }
+        fn write(&mut self, address_space: AddressSpace, offset: u32, size: Size, value: u32) -> BusResult<()> {
+            let mut reg = self.0.lock().unwrap();
+            reg.accesses.push((address_space, offset, size));
+            if offset == 0 { reg.value = value; Ok(()) } else { Err(BusError) }
+        }
+        fn save_state(&self, writer: &mut StateWriter) {
+            writer.put_u32(self.0.lock().unwrap().value);
+        }
+        fn load_state(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+            self.0.lock().unwrap().value = reader.get_u32()?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_your_ram_writes() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        bus.write_long(SUPERVISOR_DATA, 0x1800, 0x91929394);
+        assert_eq!(0x91929394, bus.read_long(SUPERVISOR_DATA, 0x1800));
+        assert_eq!(0x9293, bus.read_word(USER_DATA, 0x1801));
+        assert_eq!(0x94, bus.read_byte(SUPERVISOR_PROGRAM, 0x1803));
+    }
+
+    #[test]
+    fn rom_ignores_writes() {
+        let mut bus = MappedBus::new();
+        bus.map_rom(0x0, vec![0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(Ok(()), bus.try_write_word(SUPERVISOR_DATA, 0x0, 0x9192));
+        assert_eq!(0x01020304, bus.read_long(SUPERVISOR_DATA, 0x0));
+    }
+
+    #[test]
+    fn unmapped_access_is_a_bus_error() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        assert_eq!(Err(BusError), bus.try_read_byte(SUPERVISOR_DATA, 0x0fff));
+        assert_eq!(Err(BusError), bus.try_write_word(SUPERVISOR_DATA, 0x2000, 0));
+        // straddling the end of a region
+        assert_eq!(Err(BusError), bus.try_read_long(SUPERVISOR_DATA, 0x1ffe));
+        // but infallible reads see all ones
+        assert_eq!(0xffff, bus.read_word(SUPERVISOR_DATA, 0x2000));
+    }
+
+    #[test]
+    fn regions_are_subject_to_24_bit_address_bus() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        bus.write_byte(SUPERVISOR_DATA, 0xFF001000, 0x91);
+        assert_eq!(Ok(0x91), bus.try_read_byte(SUPERVISOR_DATA, 0x1000));
+    }
+
+    #[test]
+    fn regions_above_16mb_need_a_32_bit_address_bus() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        bus.map_ram(0x5000_1000, 0x1000);
+        bus.map_ram(0xffff_f000, 0x1000);
+        bus.set_address_mask(0xffff_ffff);
+        bus.write_byte(SUPERVISOR_DATA, 0x5000_1000, 0x91);
+        assert_eq!(Ok(0x91), bus.try_read_byte(SUPERVISOR_DATA, 0x5000_1000));
+        assert_eq!(Ok(0), bus.try_read_byte(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(Ok(()), bus.try_write_long(SUPERVISOR_DATA, 0xffff_fffc, 0x91929394));
+        assert_eq!(Err(BusError), bus.try_read_long(SUPERVISOR_DATA, 0xffff_fffe));
+        // and the 68000's 24 bits alias it to the region at the bottom
+        bus.set_address_mask(ADDRBUS_MASK);
+        assert_eq!(Ok(0), bus.try_read_byte(SUPERVISOR_DATA, 0x5000_1000));
+    }
+
+    #[test]
+    fn wait_states_are_set_per_region() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        bus.map_rom(0x4000, vec![0; 16]);
+        bus.set_wait_states(0x4008, 3);
+        assert_eq!(0, bus.wait_states(SUPERVISOR_DATA, 0x1000, 0));
+        assert_eq!(3, bus.wait_states(SUPERVISOR_PROGRAM, 0x4000, 0));
+        assert_eq!(0, bus.wait_states(SUPERVISOR_DATA, 0x8000, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn wait_states_need_a_region() {
+        MappedBus::new().set_wait_states(0x1000, 1);
+    }
+
+    #[test]
+    fn shared_ram_is_seen_by_every_bus_it_is_mapped_into() {
+        let ram = SharedRam::new(0x100);
+        let mut main = MappedBus::new();
+        main.map_shared(0x8000, &ram);
+        let mut sound = MappedBus::new();
+        sound.map_shared(0x0000, &ram);
+        main.write_word(SUPERVISOR_DATA, 0x8010, 0x1234);
+        assert_eq!(Ok(0x12), sound.try_read_byte(SUPERVISOR_DATA, 0x0010));
+        sound.write_long(SUPERVISOR_DATA, 0x00fc, 0xdeadbeef);
+        assert_eq!(0xbeef, main.read_word(SUPERVISOR_DATA, 0x80fe));
+        assert_eq!(Err(BusError), main.try_read_word(SUPERVISOR_DATA, 0x8100));
+    }
+
+    #[test]
+    fn device_receives_address_space_offset_and_size() {
+        let reg = Arc::new(Mutex::new(Register::default()));
+        let mut bus = MappedBus::new();
+        bus.map_device(0xff0000, 0x10, Box::new(SharedRegister(reg.clone())));
+        assert_eq!(Ok(()), bus.try_write_long(USER_DATA, 0xff0000, 0x91929394));
+        assert_eq!(Ok(0x9394), bus.try_read_word(SUPERVISOR_DATA, 0xff0000));
+        assert_eq!(Err(BusError), bus.try_write_byte(SUPERVISOR_DATA, 0xff0003, 0));
+        assert_eq!(vec![(USER_DATA, 0, Size::Long), (SUPERVISOR_DATA, 0, Size::Word), (SUPERVISOR_DATA, 3, Size::Byte)],
+            reg.lock().unwrap().accesses);
+    }
+
+    #[test]
+    fn load_image_fills_ram_and_rom_but_not_devices() {
+        let reg = Arc::new(Mutex::new(Register::default()));
+        let mut bus = MappedBus::new();
+        bus.map_rom(0x0, vec![0; 8]);
+        bus.map_ram(0x1000, 0x1000);
+        bus.map_device(0xff0000, 0x10, Box::new(SharedRegister(reg)));
+        assert_eq!(Ok(()), bus.load_image(0x4, &[0x91, 0x92]));
+        assert_eq!(Ok(()), bus.load_image(0x1000, &[0x93]));
+        assert_eq!(Err(BusError), bus.load_image(0xff0000, &[0x94]));
+        assert_eq!(0x9192, bus.read_word(SUPERVISOR_DATA, 0x4));
+        assert_eq!(0x93, bus.read_byte(SUPERVISOR_DATA, 0x1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the address space")]
+    fn regions_must_fit_in_the_address_space() {
+        MappedBus::new().map_ram(0xffff_8000, 0x10000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlapping_regions_are_rejected() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        bus.map_ram(0x1ffe, 0x10);
+    }
+
+    #[test]
+    fn core_can_execute_from_mapped_bus() {
+        use cpu::{ConfiguredCore, ProcessingState};
+        use interrupts::AutoInterruptController;
+
+        let reg = Arc::new(Mutex::new(Register::default()));
+        let mut bus = MappedBus::new();
+        // reset vectors: SSP at 0x2000, PC at 0x400
+        // 33fc,abcd,00ff,0000 is MOVE.W #$abcd, $ff0000
+        bus.map_rom(0x0, vec![0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x04, 0x00]);
+        bus.map_ram(0x400, 0x1c00);
+        bus.load_image(0x400, &[0x33, 0xfc, 0xab, 0xcd, 0x00, 0xff, 0x00, 0x00]).unwrap();
+        bus.map_device(0xff0000, 0x10, Box::new(SharedRegister(reg.clone())));
+
+        let mut core = ConfiguredCore::new_with(0, AutoInterruptController::new(), bus);
+        core.reset();
+        assert_eq!(ProcessingState::Normal, core.processing_state);
+        core.execute1();
+        assert_eq!(0xabcd, reg.lock().unwrap().value);
+        assert_eq!(0x408, core.pc);
+    }
+
+    #[test]
+    fn state_includes_ram_and_devices() {
+        let reg = Arc::new(Mutex::new(Register::default()));
+        let mut bus = MappedBus::new();
+        bus.map_rom(0x0, vec![0; 8]);
+        bus.map_ram(0x1000, 0x1000);
+        bus.map_device(0xff0000, 0x10, Box::new(SharedRegister(reg.clone())));
+        bus.write_long(SUPERVISOR_DATA, 0x1800, 0x91929394);
+        bus.write_long(SUPERVISOR_DATA, 0xff0000, 0x95969798);
+        let mut writer = StateWriter::new();
+        bus.save(&mut writer);
+        let state = writer.into_bytes();
+
+        bus.write_long(SUPERVISOR_DATA, 0x1800, 0);
+        bus.write_long(SUPERVISOR_DATA, 0xff0000, 0);
+        bus.load(&mut StateReader::new(&state).unwrap()).unwrap();
+        assert_eq!(0x91929394, bus.read_long(SUPERVISOR_DATA, 0x1800));
+        assert_eq!(0x95969798, reg.lock().unwrap().value);
+    }
+
+    #[test]
+    fn state_requires_same_layout() {
+        let mut bus = MappedBus::new();
+        bus.map_ram(0x1000, 0x1000);
+        let mut writer = StateWriter::new();
+        bus.save(&mut writer);
+        let state = writer.into_bytes();
+
+        let mut other = MappedBus::new();
+        other.map_ram(0x1000, 0x800);
+        assert_eq!(Err(StateError::Invalid("RAM region size")), other.load(&mut StateReader::new(&state).unwrap()));
+    }
+}
@@ -1,5 +1,9 @@
+pub mod flatmem;
 pub mod loggingmem;
+pub mod mappedbus;
 pub mod pagedmem;
+pub use self::flatmem::FlatMem;
+pub use self::mappedbus::MappedBus;
 pub use self::pagedmem::PagedMem;
 // The m68k had a 24 bit external address bus with
 // (2^24 bytes = ) 16 MB addressable space
@@ -25,6 +29,17 @@ impl AddressSpace {
             (false, false) => USER_PROGRAM,
         }
     }
+    // None for the function codes that aren't one of the four spaces,
+    // i.e. the reserved ones and CPU space
+    pub fn from_fc(fc: u32) -> Option<Self> {
+        match fc & 0b111 {
+            1 => Some(USER_DATA),
+            2 => Some(USER_PROGRAM),
+            5 => Some(SUPERVISOR_DATA),
+            6 => Some(SUPERVISOR_PROGRAM),
+            _ => None,
+        }
+    }
     pub fn from_musashi(value: u32) -> Self {
         match value & 0b111 {
             0b001 => USER_DATA,
@@ -58,6 +73,12 @@ pub const SUPERVISOR_DATA: AddressSpace = AddressSpace(Mode::Supervisor, Segment
 pub const USER_PROGRAM: AddressSpace = AddressSpace(Mode::User, Segment::Program);
 pub const USER_DATA: AddressSpace = AddressSpace(Mode::User, Segment::Data);
 
+// A bus cycle terminated by BERR instead of DTACK, i.e. an access to
+// an unmapped or protected address.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BusError;
+pub type BusResult<T> = Result<T, BusError>;
+
 pub trait AddressBus {
     fn copy_from(&mut self, other: &Self);
     fn read_byte(&mut self, address_space: AddressSpace, address: u32) -> u32;
@@ -66,5 +87,50 @@ pub trait AddressBus {
     fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32);
     fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32);
     fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32);
+
+    // The core calls this when its address bus changes width, so that
+    // accesses running past the top of memory can wrap around like the
+    // processor's. Memories of a fixed size may ignore it.
+    fn set_address_mask(&mut self, _address_mask: u32) {}
+
+    // The wait states (clock periods) a bus cycle starting at the given
+    // clock period is stretched by, when the core keeps cycle exact
+    // timing. A bus shared with another master can also hold the
+    // processor off here for as long as the other master has the bus.
+    fn wait_states(&mut self, _address_space: AddressSpace, _address: u32, _clock: u64) -> u32 {
+        0
+    }
+
+    // Reads a byte for a debugger or tracer, without side effects. Buses
+    // with devices, or that log their bus cycles, override this not to
+    // touch them.
+    fn peek_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.try_read_byte(address_space, address)
+    }
+
+    // The CPU core accesses memory through the fallible variants below,
+    // which a bus able to fault should override. By default, every
+    // access succeeds.
+    fn try_read_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        Ok(self.read_byte(address_space, address))
+    }
+    fn try_read_word(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        Ok(self.read_word(address_space, address))
+    }
+    fn try_read_long(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        Ok(self.read_long(address_space, address))
+    }
+    fn try_write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.write_byte(address_space, address, value);
+        Ok(())
+    }
+    fn try_write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.write_word(address_space, address, value);
+        Ok(())
+    }
+    fn try_write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.write_long(address_space, address, value);
+        Ok(())
+    }
 }
 
@@ -1,15 +1,18 @@
 use std::collections::HashMap;
-use super::{AddressSpace, AddressBus, ADDRBUS_MASK, Segment};
+use std::ops::Range;
+use savestate::{self, Snapshot, StateReader, StateWriter};
+use super::{AddressSpace, AddressBus, BusError, BusResult, ADDRBUS_MASK, Segment};
 
 const PAGE_SIZE: u32 = 16; // 16 bytes page size
 const ADDR_MASK: u32 = PAGE_SIZE - 1; // all ones
-const PAGE_MASK: u32 = ADDRBUS_MASK ^ ADDR_MASK;
 
 type Page = Vec<u8>;
 
 pub struct PagedMem {
     pages: HashMap<u32, Page>,
     page_template: Page,
+    unmapped: Vec<Range<u32>>,
+    address_mask: u32,
     pub initializer: u32,
 }
 
@@ -18,8 +21,11 @@ impl PagedMem {
     fn allocated_pages(&self) -> usize {
         self.pages.len()
     }
+    fn page_mask(&self) -> u32 {
+        self.address_mask & !ADDR_MASK
+    }
     fn new_page_is_needed(&self, address: u32, value_to_write: u8) -> bool {
-        let pageno = address & PAGE_MASK;
+        let pageno = address & self.page_mask();
         let write_differs_from_initializer = value_to_write as u8 != Self::read_initializer(address, self.initializer);
         !self.pages.contains_key(&pageno) && write_differs_from_initializer
     }
@@ -27,7 +33,7 @@ impl PagedMem {
     // only if we are going to need to write an interesting value to it
     // i.e. one that differs from the initializer
     fn page_if_needed(&mut self, address: u32, value_to_write: u8) -> Option<&mut Page> {
-        let pageno = address & PAGE_MASK;
+        let pageno = address & self.page_mask();
         if self.new_page_is_needed(address, value_to_write) {
             self.create_initialized_page(pageno);
         }
@@ -47,7 +53,7 @@ impl PagedMem {
         ((initializer >> shift) & 0xFF) as u8
     }
     pub fn read_u8(&self, address: u32) -> u32 {
-        let pageno = address & PAGE_MASK;
+        let pageno = address & self.page_mask();
         if let Some(page) = self.pages.get(&pageno) {
             let index = (address & ADDR_MASK) as usize;
             u32::from(page[index])
@@ -62,6 +68,20 @@ impl PagedMem {
             page[index] = (value & 0xFF) as u8;
         }
     }
+    // accesses touching an unmapped range (of bus addresses, so 24-bit
+    // unless the address mask says otherwise) will cause a bus error
+    pub fn unmap(&mut self, range: Range<u32>) {
+        self.unmapped.push(range);
+    }
+    pub fn is_mapped(&self, address: u32, size: u32) -> bool {
+        (0..size).all(|offset| {
+            let address = address.wrapping_add(offset) & self.address_mask;
+            !self.unmapped.iter().any(|range| range.contains(&address))
+        })
+    }
+    fn check_mapped(&self, address: u32, size: u32) -> BusResult<()> {
+        if self.is_mapped(address, size) { Ok(()) } else { Err(BusError) }
+    }
     pub fn diffs(&self) -> DiffIter {
         let mut keys: Vec<u32> = self.pages.keys().cloned().collect();
         keys.sort();
@@ -74,7 +94,14 @@ impl PagedMem {
         for offset in 0..PAGE_SIZE {
             page_template.push(Self::read_initializer(offset, initializer));
         }
-        PagedMem { pages: HashMap::new(), initializer, page_template }
+        PagedMem { pages: HashMap::new(), initializer, page_template, unmapped: Vec::new(), address_mask: ADDRBUS_MASK }
+    }
+    // 24 bits by default, like the 68000
+    pub fn address_mask(&self) -> u32 {
+        self.address_mask
+    }
+    pub fn set_address_mask(&mut self, address_mask: u32) {
+        self.address_mask = address_mask;
     }
 
     pub fn reset(&mut self, initializer: u32) {
@@ -109,9 +136,15 @@ impl<'a> Iterator for DiffIter<'a> {
 
 impl AddressBus for PagedMem {
     fn copy_from(&mut self, other: &Self) {
+        self.address_mask = other.address_mask;
         for (addr, byte) in other.diffs() {
             self.write_u8(addr, u32::from(byte));
         }
+        self.unmapped = other.unmapped.clone();
+    }
+
+    fn set_address_mask(&mut self, address_mask: u32) {
+        self.set_address_mask(address_mask)
     }
 
     fn read_byte(&mut self, _address_space: AddressSpace, address: u32) -> u32 {
@@ -145,12 +178,56 @@ impl AddressBus for PagedMem {
         self.write_u8(address.wrapping_add(2), value >>  8);
         self.write_u8(address.wrapping_add(3), value);
     }
+
+    fn try_read_byte(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 1)?;
+        Ok(self.read_byte(address_space, address))
+    }
+
+    fn try_read_word(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 2)?;
+        Ok(self.read_word(address_space, address))
+    }
+
+    fn try_read_long(&mut self, address_space: AddressSpace, address: u32) -> BusResult<u32> {
+        self.check_mapped(address, 4)?;
+        Ok(self.read_long(address_space, address))
+    }
+
+    fn try_write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_mapped(address, 1)?;
+        self.write_byte(address_space, address, value);
+        Ok(())
+    }
+
+    fn try_write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_mapped(address, 2)?;
+        self.write_word(address_space, address, value);
+        Ok(())
+    }
+
+    fn try_write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) -> BusResult<()> {
+        self.check_mapped(address, 4)?;
+        self.write_long(address_space, address, value);
+        Ok(())
+    }
+}
+
+impl Snapshot for PagedMem {
+    fn save(&self, writer: &mut StateWriter) {
+        savestate::save_memory(writer, self.initializer, self.diffs());
+    }
+    fn load(&mut self, reader: &mut StateReader) -> savestate::Result<()> {
+        let initializer = reader.get_u32()?;
+        self.reset(initializer);
+        savestate::load_memory(reader, |address, byte| self.write_u8(address, u32::from(byte)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{AddressBus, PagedMem, PAGE_SIZE};
-    use ram::{SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM, ADDRBUS_MASK};
+    use ram::{SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM, ADDRBUS_MASK, BusError};
 
     #[test]
     fn read_initialized_memory() {
@@ -333,6 +410,17 @@ mod tests {
         assert_eq!(0x91929394, mem.read_long(SUPERVISOR_DATA, ADDRBUS_MASK-1));
     }
 
+    #[test]
+    fn wider_address_mask_does_not_wrap() {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.set_address_mask(0xffff_ffff);
+        mem.write_long(SUPERVISOR_DATA, ADDRBUS_MASK-1, 0x91929394);
+        assert_eq!(0x93940304, mem.read_long(SUPERVISOR_DATA, ADDRBUS_MASK+1));
+        assert_eq!(0x01020304, mem.read_long(SUPERVISOR_DATA, 0));
+        mem.write_byte(SUPERVISOR_DATA, 0xffff_ffff, 0x95);
+        assert_eq!(0x95010203, mem.read_long(SUPERVISOR_DATA, 0xffff_ffff));
+    }
+
     #[test]
     fn cross_type_boundary_word_access() {
         let mut mem = PagedMem::new(0x01020304);
@@ -350,4 +438,36 @@ mod tests {
         mem.write_long(SUPERVISOR_DATA, addr, 0x91929394);
         assert_eq!(0x91929394, mem.read_long(SUPERVISOR_DATA, addr));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unmapped_access_is_a_bus_error() {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.unmap(0x1000..0x2000);
+        assert_eq!(Err(BusError), mem.try_read_byte(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(Err(BusError), mem.try_read_word(SUPERVISOR_DATA, 0x1ffe));
+        assert_eq!(Err(BusError), mem.try_write_long(SUPERVISOR_DATA, 0x1800, 0x91929394));
+        assert_eq!(Ok(0x0102), mem.try_read_word(SUPERVISOR_DATA, 0x2000));
+        assert_eq!(Ok(()), mem.try_write_byte(SUPERVISOR_DATA, 0xfff, 0x91));
+        // no page was allocated by the faulted write
+        assert_eq!(1, mem.allocated_pages());
+    }
+
+    #[test]
+    fn access_straddling_an_unmapped_range_is_a_bus_error() {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.unmap(0x1000..0x2000);
+        assert_eq!(Err(BusError), mem.try_read_long(SUPERVISOR_DATA, 0xffe));
+        assert_eq!(Err(BusError), mem.try_write_word(SUPERVISOR_DATA, 0xfff, 0x9192));
+        // unmapped ranges are subject to the 24-bit address bus width
+        assert_eq!(Err(BusError), mem.try_read_byte(SUPERVISOR_DATA, 0xFF001000));
+    }
+
+    #[test]
+    fn copy_from_includes_unmapped_ranges() {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.unmap(0x1000..0x2000);
+        let mut copy = PagedMem::new(0x01020304);
+        copy.copy_from(&mem);
+        assert!(!copy.is_mapped(0x1000, 1));
+    }
+}
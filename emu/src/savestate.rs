@@ -0,0 +1,241 @@
+// Save states are binary blobs, starting with a magic number and a
+// format version, followed by the state of the CPU registers, the
+// interrupt controller and the memory, in that order. All values are
+// stored big-endian, like on the m68k.
+use std::error;
+use std::fmt;
+
+pub const MAGIC: &[u8; 4] = b"r68k";
+// bump whenever the layout of any saved state changes
+pub const VERSION: u16 = 8;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    Invalid(&'static str),
+}
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "Unsupported save state version {} (expected {})", version, VERSION),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::Invalid(what) => write!(f, "Save state has invalid {}", what),
+        }
+    }
+}
+impl error::Error for StateError {
+    fn description(&self) -> &str {
+        match *self {
+            StateError::BadMagic => "BadMagic",
+            StateError::UnsupportedVersion(_) => "UnsupportedVersion",
+            StateError::Truncated => "Truncated",
+            StateError::Invalid(_) => "Invalid",
+        }
+    }
+}
+pub type Result<T> = ::std::result::Result<T, StateError>;
+
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        let mut writer = StateWriter { buf: Vec::new() };
+        writer.put_bytes(MAGIC);
+        writer.put_u16(VERSION);
+        writer
+    }
+    pub fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+    pub fn put_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+    pub fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+    pub fn put_bool(&mut self, value: bool) {
+        self.put_u8(value as u8);
+    }
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub struct StateReader<'a> {
+    buf: &'a [u8],
+}
+impl<'a> StateReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<StateReader<'a>> {
+        let mut reader = StateReader { buf };
+        if reader.get_bytes(MAGIC.len()).map_err(|_| StateError::BadMagic)? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        match reader.get_u16()? {
+            VERSION => Ok(reader),
+            version => Err(StateError::UnsupportedVersion(version)),
+        }
+    }
+    pub fn get_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+    pub fn get_u8(&mut self) -> Result<u8> {
+        Ok(self.get_bytes(1)?[0])
+    }
+    pub fn get_u16(&mut self) -> Result<u16> {
+        let bytes = self.get_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    pub fn get_u32(&mut self) -> Result<u32> {
+        let bytes = self.get_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    pub fn get_bool(&mut self) -> Result<bool> {
+        match self.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("boolean")),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+// Anything that is part of a save state. A load must read back exactly
+// what the corresponding save wrote.
+pub trait Snapshot {
+    fn save(&self, writer: &mut StateWriter);
+    fn load(&mut self, reader: &mut StateReader) -> Result<()>;
+}
+
+// Memory is saved as its initializer, followed by the runs of bytes
+// reported by diffs(), so PagedMem, FlatMem and LoggingMem states are
+// interchangeable.
+pub fn save_memory<I: Iterator<Item=(u32, u8)>>(writer: &mut StateWriter, initializer: u32, diffs: I) {
+    writer.put_u32(initializer);
+    let mut runs: Vec<(u32, Vec<u8>)> = Vec::new();
+    for (address, byte) in diffs {
+        match runs.last_mut() {
+            Some(&mut (start, ref mut bytes)) if start + bytes.len() as u32 == address => bytes.push(byte),
+            _ => runs.push((address, vec![byte])),
+        }
+    }
+    writer.put_u32(runs.len() as u32);
+    for (start, bytes) in runs {
+        writer.put_u32(start);
+        writer.put_u32(bytes.len() as u32);
+        writer.put_bytes(&bytes);
+    }
+}
+pub fn load_memory<F: FnMut(u32, u8)>(reader: &mut StateReader, mut write_u8: F) -> Result<()> {
+    for _ in 0..reader.get_u32()? {
+        let start = reader.get_u32()?;
+        let len = reader.get_u32()? as usize;
+        for (offset, &byte) in reader.get_bytes(len)?.iter().enumerate() {
+            let address = start.checked_add(offset as u32).ok_or(StateError::Invalid("memory run"))?;
+            write_u8(address, byte);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_memory, Snapshot, StateError, StateReader, StateWriter, MAGIC, VERSION};
+    use interrupts::{AutoInterruptController, InterruptController};
+    use ram::{AddressBus, FlatMem, PagedMem, SUPERVISOR_DATA};
+
+    fn roundtrip<S: Snapshot>(from: &S, to: &mut S) {
+        let mut writer = StateWriter::new();
+        from.save(&mut writer);
+        let bytes = writer.into_bytes();
+        let mut reader = StateReader::new(&bytes).unwrap();
+        to.load(&mut reader).unwrap();
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn rejects_blobs_that_are_not_save_states() {
+        assert_eq!(StateError::BadMagic, StateReader::new(b"").err().unwrap());
+        assert_eq!(StateError::BadMagic, StateReader::new(b"m68k\x00\x01").err().unwrap());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut blob = MAGIC.to_vec();
+        blob.extend_from_slice(&(VERSION + 1).to_be_bytes());
+        assert_eq!(StateError::UnsupportedVersion(VERSION + 1), StateReader::new(&blob).err().unwrap());
+    }
+
+    #[test]
+    fn detects_truncation() {
+        let mut writer = StateWriter::new();
+        writer.put_u16(0x1234);
+        let bytes = writer.into_bytes();
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert_eq!(Err(StateError::Truncated), reader.get_u32());
+    }
+
+    #[test]
+    fn rejects_memory_runs_past_the_address_space() {
+        let mut writer = StateWriter::new();
+        writer.put_u32(1);
+        writer.put_u32(0xffff_fffe);
+        writer.put_u32(4);
+        writer.put_bytes(&[1, 2, 3, 4]);
+        let bytes = writer.into_bytes();
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert_eq!(Err(StateError::Invalid("memory run")), load_memory(&mut reader, |_, _| ()));
+    }
+
+    #[test]
+    fn interrupt_controller_roundtrip() {
+        let mut ctrl = AutoInterruptController::new();
+        ctrl.request_interrupt(2);
+        ctrl.request_interrupt(5);
+        let mut restored = AutoInterruptController::new();
+        roundtrip(&ctrl, &mut restored);
+        assert_eq!(5, restored.highest_priority());
+        restored.acknowledge_interrupt(5);
+        assert_eq!(2, restored.highest_priority());
+    }
+
+    #[test]
+    fn memory_roundtrip_replaces_contents() {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x91929394);
+        mem.write_long(SUPERVISOR_DATA, 0x110, 0x95969798);
+        mem.write_byte(SUPERVISOR_DATA, 0xfff000, 0x99);
+        let mut restored = PagedMem::new(0xAAAAAAAA);
+        restored.write_long(SUPERVISOR_DATA, 0x200, 0x12345678);
+        roundtrip(&mem, &mut restored);
+        assert_eq!(mem.diffs().collect::<Vec<_>>(), restored.diffs().collect::<Vec<_>>());
+        assert_eq!(0x01020304, restored.read_long(SUPERVISOR_DATA, 0x200));
+    }
+
+    #[test]
+    fn memory_states_are_interchangeable() {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x91929394);
+        let mut writer = StateWriter::new();
+        mem.save(&mut writer);
+        let bytes = writer.into_bytes();
+        let mut flat = FlatMem::new(0);
+        flat.load(&mut StateReader::new(&bytes).unwrap()).unwrap();
+        assert_eq!(0x91929394, flat.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!(0x01020304, flat.read_long(SUPERVISOR_DATA, 0x200));
+    }
+}
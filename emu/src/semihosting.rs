@@ -0,0 +1,486 @@
+// Semihosting, for bare-metal programs (say unit tests cross-compiled with
+// m68k-elf-gcc) to print, read files and exit with a status without any
+// device drivers. The convention is that of libgloss, which newlib's
+// m68k "hosted" system calls already use, and which QEMU understands:
+//
+//     nop
+//     halt                  ; $4ac8, an illegal instruction on the 68000
+//     .long 0x4e7bf000      ; the sentinel, which is skipped
+//
+// with the operation in D0 and the address of a parameter block in D1.
+// The results go back into the block: a result in its first long and
+// an errno (in GDB's File-I/O numbering) in the second, or for lseek, a
+// 64-bit result and then the errno. A Semihost services the operations
+// below as Callbacks, and run executes a program, as load reads it from
+// an ELF file or S-records, until it exits.
+use cpu::{Callbacks, Core, Cycles, Exception, Result};
+use hosted::{self, bytes_at, in_memory, write_bytes, Ending, Host, HostedCore, MEMORY_TOP};
+use r68k_tools::elf::{is_elf, read_elf};
+use r68k_tools::memory::MemoryVec;
+use r68k_tools::srecords::read_s68;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use hosted::{run, Termination};
+
+pub const NOP: u16 = 0x4e71;
+pub const HALT: u16 = 0x4ac8;
+pub const SENTINEL: u32 = 0x4e7b_f000;
+
+// operations, in D0
+pub const EXIT: u32 = 0; // [status]
+pub const INIT_SIM: u32 = 1; // sets D1 and A7 to the top of memory
+pub const OPEN: u32 = 2; // [path, length including the NUL, flags, mode]
+pub const CLOSE: u32 = 3; // [fd]
+pub const READ: u32 = 4; // [fd, buffer, count]
+pub const WRITE: u32 = 5; // [fd, buffer, count]
+pub const LSEEK: u32 = 6; // [fd, offset high, offset low, whence]
+pub const GETTIMEOFDAY: u32 = 11; // [timeval, timezone], a 32-bit tv_sec and 64-bit tv_usec
+pub const ISATTY: u32 = 12; // [fd]
+
+// open flags
+const O_WRONLY: u32 = 0x1;
+const O_RDWR: u32 = 0x2;
+const O_APPEND: u32 = 0x8;
+const O_CREAT: u32 = 0x200;
+const O_TRUNC: u32 = 0x400;
+const O_EXCL: u32 = 0x800;
+
+// errno values
+pub const ENOENT: u32 = 2;
+pub const EBADF: u32 = 9;
+pub const EACCES: u32 = 13;
+pub const EEXIST: u32 = 17;
+pub const EINVAL: u32 = 22;
+pub const ENAMETOOLONG: u32 = 91;
+pub const EUNKNOWN: u32 = 9999; // also for operations not supported
+
+const FIRST_FILE: u32 = 3; // after stdin, stdout and stderr
+
+// the most a READ or WRITE moves at once, as a short count that the
+// program carries on from
+const MAX_TRANSFER: u32 = 0x1_0000;
+const MAX_PATH: u32 = 0x1000; // including the NUL
+
+fn errno(e: &io::Error) -> u32 {
+    match e.kind() {
+        io::ErrorKind::NotFound => ENOENT,
+        io::ErrorKind::PermissionDenied => EACCES,
+        io::ErrorKind::AlreadyExists => EEXIST,
+        io::ErrorKind::InvalidInput => EINVAL,
+        _ => EUNKNOWN,
+    }
+}
+
+pub struct Semihost {
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    error: Box<dyn Write>,
+    directory: PathBuf,
+    files: Vec<Option<File>>, // from FIRST_FILE on
+    ending: Ending,
+}
+
+impl Semihost {
+    // what the program's stdin, stdout and stderr are
+    pub fn new<I, O, E>(input: I, output: O, error: E) -> Semihost
+        where I: Read + 'static, O: Write + 'static, E: Write + 'static
+    {
+        Semihost {
+            input: Box::new(input), output: Box::new(output), error: Box::new(error),
+            directory: PathBuf::from("."), files: Vec::new(), ending: Ending::default(),
+        }
+    }
+
+    pub fn stdio() -> Semihost {
+        Semihost::new(io::stdin(), io::stdout(), io::stderr())
+    }
+
+    // Relative paths are opened from here, the current directory unless
+    // set
+    pub fn set_directory(&mut self, directory: &Path) {
+        self.directory = directory.to_path_buf();
+    }
+
+    fn file(&mut self, fd: u32) -> Option<&mut File> {
+        fd.checked_sub(FIRST_FILE).and_then(move |index| self.files.get_mut(index as usize)).and_then(|f| f.as_mut())
+    }
+
+    fn open(&mut self, path: &Path, flags: u32) -> io::Result<u32> {
+        let mut options = OpenOptions::new();
+        options.read(flags & (O_WRONLY | O_RDWR) != O_WRONLY)
+            .write(flags & (O_WRONLY | O_RDWR) != 0)
+            .append(flags & O_APPEND != 0)
+            .truncate(flags & O_TRUNC != 0);
+        if flags & O_EXCL != 0 {
+            options.create_new(true);
+        } else {
+            options.create(flags & O_CREAT != 0);
+        }
+        let file = options.open(self.directory.join(path))?;
+        let index = self.files.iter().position(|f| f.is_none()).unwrap_or(self.files.len());
+        if index == self.files.len() {
+            self.files.push(None);
+        }
+        self.files[index] = Some(file);
+        Ok(FIRST_FILE + index as u32)
+    }
+
+    fn read(&mut self, fd: u32, count: u32) -> result::Result<Vec<u8>, u32> {
+        let mut buf = vec![0; count as usize];
+        let read = match fd {
+            0 => self.input.read(&mut buf),
+            _ => self.file(fd).ok_or(EBADF)?.read(&mut buf),
+        };
+        let len = read.map_err(|e| errno(&e))?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    fn write(&mut self, fd: u32, bytes: &[u8]) -> result::Result<u32, u32> {
+        let written = match fd {
+            1 => self.output.write_all(bytes).and_then(|_| self.output.flush()),
+            2 => self.error.write_all(bytes).and_then(|_| self.error.flush()),
+            _ => self.file(fd).ok_or(EBADF)?.write_all(bytes),
+        };
+        written.map(|_| bytes.len() as u32).map_err(|e| errno(&e))
+    }
+
+    // Services an operation, with the parameter block at the address,
+    // returning its result and errno, if it has them
+    fn operation(&mut self, core: &mut impl Core, operation: u32, block: u32) -> Result<Option<result::Result<u32, u32>>> {
+        let result = match operation {
+            EXIT => {
+                let status = param(core, block, 0)? as i32;
+                self.ending.terminate(core, Termination::Exited(status));
+                return Ok(None);
+            },
+            INIT_SIM => {
+                core.dar()[1] = MEMORY_TOP;
+                core.dar()[15] = MEMORY_TOP;
+                return Ok(None);
+            },
+            OPEN => {
+                let (path, len, flags) = (param(core, block, 0)?, param(core, block, 1)?, param(core, block, 2)?);
+                if len > MAX_PATH {
+                    return Ok(Some(Err(ENAMETOOLONG)));
+                }
+                if !in_memory(path, len) {
+                    return Ok(Some(Err(EINVAL)));
+                }
+                let name = bytes_at(core, path, len.saturating_sub(1))?;
+                let name = String::from_utf8_lossy(&name).into_owned();
+                self.open(Path::new(&name), flags).map_err(|e| errno(&e))
+            },
+            CLOSE => {
+                let fd = param(core, block, 0)?;
+                match fd {
+                    0..=2 => Ok(0),
+                    _ if self.file(fd).is_some() => {
+                        self.files[(fd - FIRST_FILE) as usize] = None;
+                        Ok(0)
+                    },
+                    _ => Err(EBADF),
+                }
+            },
+            READ => {
+                let (fd, buffer, count) = (param(core, block, 0)?, param(core, block, 1)?, param(core, block, 2)?);
+                if !in_memory(buffer, count) {
+                    return Ok(Some(Err(EINVAL)));
+                }
+                match self.read(fd, count.min(MAX_TRANSFER)) {
+                    Ok(bytes) => {
+                        write_bytes(core, buffer, &bytes)?;
+                        Ok(bytes.len() as u32)
+                    },
+                    Err(errno) => Err(errno),
+                }
+            },
+            WRITE => {
+                let (fd, buffer, count) = (param(core, block, 0)?, param(core, block, 1)?, param(core, block, 2)?);
+                if !in_memory(buffer, count) {
+                    return Ok(Some(Err(EINVAL)));
+                }
+                let bytes = bytes_at(core, buffer, count.min(MAX_TRANSFER))?;
+                self.write(fd, &bytes)
+            },
+            LSEEK => {
+                let fd = param(core, block, 0)?;
+                let offset = (u64::from(param(core, block, 1)?) << 32 | u64::from(param(core, block, 2)?)) as i64;
+                let whence = param(core, block, 3)?;
+                let position = match whence {
+                    0 if offset >= 0 => Some(SeekFrom::Start(offset as u64)),
+                    1 => Some(SeekFrom::Current(offset)),
+                    2 => Some(SeekFrom::End(offset)),
+                    _ => None,
+                };
+                let seeked = match (self.file(fd), position) {
+                    (None, _) => Err(EBADF),
+                    (_, None) => Err(EINVAL),
+                    (Some(file), Some(position)) => file.seek(position).map_err(|e| errno(&e)),
+                };
+                // a 64-bit result, then the errno
+                let (result, errno) = match seeked {
+                    Ok(position) => (position, 0),
+                    Err(errno) => (u64::MAX, errno),
+                };
+                core.write_data_long(block, (result >> 32) as u32)?;
+                core.write_data_long(block.wrapping_add(4), result as u32)?;
+                core.write_data_long(block.wrapping_add(8), errno)?;
+                return Ok(None);
+            },
+            GETTIMEOFDAY => {
+                let timeval = param(core, block, 0)?;
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                core.write_data_long(timeval, now.as_secs() as u32)?;
+                core.write_data_long(timeval.wrapping_add(4), 0)?;
+                core.write_data_long(timeval.wrapping_add(8), now.subsec_micros())?;
+                Ok(0)
+            },
+            ISATTY => {
+                let fd = param(core, block, 0)?;
+                match fd {
+                    0..=2 => Ok(1),
+                    _ if self.file(fd).is_some() => Ok(0),
+                    _ => Err(EBADF),
+                }
+            },
+            _ => Err(EUNKNOWN),
+        };
+        Ok(Some(result))
+    }
+}
+
+// the long at the index of the parameter block
+fn param(core: &mut impl Core, block: u32, index: u32) -> Result<u32> {
+    core.read_data_long(block.wrapping_add(4 * index))
+}
+
+// whether the HALT at the address is part of the semihosting sequence
+fn is_call(core: &mut impl Core, halt: u32) -> bool {
+    matches!(core.read_program_word(halt.wrapping_sub(2)), Ok(word) if word == u32::from(NOP))
+        && matches!(core.read_program_long(halt.wrapping_add(2)), Ok(SENTINEL))
+}
+
+impl Callbacks for Semihost {
+    fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+        match ex {
+            Exception::IllegalInstruction(HALT, pc) if is_call(core, pc) => {
+                core.jump(pc.wrapping_add(6));
+                let operation = core.dar()[0];
+                let block = core.dar()[1];
+                if let Some(result) = self.operation(core, operation, block)? {
+                    let (result, errno) = match result {
+                        Ok(result) => (result, 0),
+                        Err(errno) => (u32::MAX, errno),
+                    };
+                    core.write_data_long(block, result)?;
+                    core.write_data_long(block.wrapping_add(4), errno)?;
+                }
+                Ok(Cycles(0))
+            },
+            // a bare-metal program may not have set up its vectors
+            _ => self.ending.unhandled(core, ex),
+        }
+    }
+}
+
+impl Host for Semihost {
+    fn termination(&self) -> Option<Termination> {
+        self.ending.termination()
+    }
+}
+
+// Reads a program, from an ELF file or S-records, into a core of 16MB of
+// memory, which starts it in supervisor mode at its entry point, with the
+// stack at the top of memory
+pub fn load(image: &[u8]) -> io::Result<HostedCore> {
+    let (segments, entry): (Vec<MemoryVec>, Option<u32>) = if is_elf(image) {
+        read_elf(image, MEMORY_TOP).map(|(segments, entry)| (segments, Some(entry)))?
+    } else {
+        read_s68(&mut &image[..])?
+    };
+    hosted::load(&segments, entry, 7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use peripherals::serial::SharedBuffer;
+    use ram::{AddressBus, SUPERVISOR_DATA};
+    use r68k_tools::srecords::write_s68;
+    use r68k_tools::PC;
+    use std::env;
+    use std::fs;
+
+    const CODE: u32 = 0x1000;
+    const BLOCKS: u32 = 0x2000; // parameter blocks, 16 bytes apart
+    const STRINGS: u32 = 0x2100;
+    const BUFFER: u32 = 0x2200;
+
+    // MOVEQ #operation,D0; MOVE.L #block,D1; and the call
+    fn call(operation: u32, block: u32) -> Vec<u16> {
+        vec![0x7000 | operation as u16, 0x223c, (block >> 16) as u16, block as u16, NOP, HALT, 0x4e7b, 0xf000]
+    }
+
+    fn program(calls: &[(u32, u32)]) -> Vec<u16> {
+        calls.iter().flat_map(|&(operation, block)| call(operation, block)).collect()
+    }
+
+    fn blocks(params: &[&[u32]]) -> Vec<u8> {
+        let mut data = vec![0; (STRINGS - BLOCKS) as usize];
+        for (i, block) in params.iter().enumerate() {
+            for (j, param) in block.iter().enumerate() {
+                let at = 16 * i + 4 * j;
+                data[at..at + 4].copy_from_slice(&param.to_be_bytes());
+            }
+        }
+        data
+    }
+
+    struct Run {
+        termination: Termination,
+        output: Vec<u8>,
+        error: Vec<u8>,
+        core: HostedCore,
+    }
+
+    impl Run {
+        // the result and errno left in a parameter block
+        fn result(&mut self, block: u32) -> (u32, u32) {
+            let address = BLOCKS + 16 * block;
+            (self.core.mem.read_long(SUPERVISOR_DATA, address), self.core.mem.read_long(SUPERVISOR_DATA, address + 4))
+        }
+    }
+
+    fn semihost_in(directory: &Path, code: &[u16], data: &[u8], input: &[u8]) -> Run {
+        let code = MemoryVec::new16(PC(CODE), code.to_vec());
+        let data = MemoryVec::new8(PC(BLOCKS), data.to_vec());
+        let mut records = Vec::new();
+        write_s68(&mut records, vec![&code, &data], CODE).unwrap();
+        let mut core = load(&records).unwrap();
+        let (output, error) = (SharedBuffer::new(), SharedBuffer::new());
+        let mut semihost = Semihost::new(io::Cursor::new(input.to_vec()), output.clone(), error.clone());
+        semihost.set_directory(directory);
+        let termination = run(&mut core, &mut semihost);
+        Run { termination, output: output.contents(), error: error.contents(), core }
+    }
+
+    fn semihost(code: &[u16], data: &[u8], input: &[u8]) -> Run {
+        semihost_in(Path::new("."), code, data, input)
+    }
+
+    #[test]
+    fn programs_write_and_exit_with_a_status() {
+        let code = program(&[(WRITE, BLOCKS), (WRITE, BLOCKS + 16), (EXIT, BLOCKS + 32)]);
+        let mut data = blocks(&[&[1, STRINGS, 6], &[2, STRINGS + 6, 4], &[3]]);
+        data.extend_from_slice(b"hello\noops");
+        let mut run = semihost(&code, &data, b"");
+        assert!(matches!(run.termination, Termination::Exited(3)));
+        assert_eq!((b"hello\n".to_vec(), b"oops".to_vec()), (run.output.clone(), run.error.clone()));
+        assert_eq!((6, 0), run.result(0));
+        // the EXIT call is the last thing that ran
+        assert_eq!(CODE + 2 * code.len() as u32, run.core.pc);
+    }
+
+    #[test]
+    fn programs_read_and_ask_the_host() {
+        let mut code = program(&[(READ, BLOCKS), (READ, BLOCKS + 16), (WRITE, BLOCKS + 32), (13, BLOCKS + 48),
+            (ISATTY, BLOCKS + 64), (GETTIMEOFDAY, BLOCKS + 80), (INIT_SIM, 0)]);
+        // MOVE.L D1,D2
+        code.push(0x2401);
+        code.extend(call(EXIT, BLOCKS + 96));
+        let data = blocks(&[&[0, BUFFER, 16], &[0, BUFFER, 16], &[0, BUFFER, 1], &[], &[1], &[BUFFER + 16], &[0]]);
+        let mut run = semihost(&code, &data, b"input");
+        assert!(matches!(run.termination, Termination::Exited(0)));
+        let read: Vec<u8> = (0..5).map(|i| run.core.mem.read_byte(SUPERVISOR_DATA, BUFFER + i) as u8).collect();
+        assert_eq!(b"input".to_vec(), read);
+        assert_eq!((5, 0), run.result(0));
+        // then the end of the input
+        assert_eq!((0, 0), run.result(1));
+        assert_eq!((u32::MAX, EBADF), run.result(2));
+        assert_eq!((u32::MAX, EUNKNOWN), run.result(3));
+        assert_eq!((1, 0), run.result(4));
+        assert_eq!((0, 0), run.result(5));
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        let seconds = run.core.mem.read_long(SUPERVISOR_DATA, BUFFER + 16);
+        assert!(now - seconds < 5);
+        assert_eq!((MEMORY_TOP, MEMORY_TOP), (run.core.dar[2], run.core.dar[15]));
+    }
+
+    #[test]
+    fn programs_open_files() {
+        let directory = env::temp_dir().join(format!("r68k-semihosting-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let code = program(&[(OPEN, BLOCKS), (WRITE, BLOCKS + 16), (LSEEK, BLOCKS + 32), (READ, BLOCKS + 48),
+            (CLOSE, BLOCKS + 64), (CLOSE, BLOCKS + 80), (OPEN, BLOCKS + 96), (EXIT, BLOCKS + 112)]);
+        let mut data = blocks(&[&[STRINGS, 8, O_CREAT | O_TRUNC | O_RDWR, 0o644], &[3, STRINGS + 8, 6], &[3, 0, 2, 0],
+            &[3, BUFFER, 10], &[3], &[3], &[STRINGS + 14, 8, 0], &[0]]);
+        data.extend_from_slice(b"out.txt\0abcdefmissing\0");
+        let mut run = semihost_in(&directory, &code, &data, b"");
+        let written = fs::read(directory.join("out.txt")).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(run.termination, Termination::Exited(0)));
+        assert_eq!(b"abcdef".to_vec(), written);
+        assert_eq!((3, 0), run.result(0));
+        assert_eq!((6, 0), run.result(1));
+        // the 64-bit position, then the errno
+        let lseek = (run.result(2), run.core.mem.read_long(SUPERVISOR_DATA, BLOCKS + 40));
+        assert_eq!(((0, 2), 0), lseek);
+        assert_eq!((4, 0), run.result(3));
+        let read: Vec<u8> = (0..4).map(|i| run.core.mem.read_byte(SUPERVISOR_DATA, BUFFER + i) as u8).collect();
+        assert_eq!(b"cdef".to_vec(), read);
+        assert_eq!((0, 0), run.result(4));
+        assert_eq!((u32::MAX, EBADF), run.result(5));
+        assert_eq!((u32::MAX, ENOENT), run.result(6));
+    }
+
+    #[test]
+    fn transfers_are_bounded() {
+        let code = program(&[(WRITE, BLOCKS), (READ, BLOCKS + 16), (OPEN, BLOCKS + 32), (WRITE, BLOCKS + 48), (EXIT, BLOCKS + 64)]);
+        let data = blocks(&[&[1, BUFFER, u32::MAX], &[0, MEMORY_TOP - 4, 5], &[STRINGS, 0x10_0000, 0], &[1, 0, 0x2_0000], &[0]]);
+        let mut run = semihost(&code, &data, b"input");
+        assert!(matches!(run.termination, Termination::Exited(0)));
+        assert_eq!((u32::MAX, EINVAL), run.result(0));
+        assert_eq!((u32::MAX, EINVAL), run.result(1));
+        assert_eq!((u32::MAX, ENAMETOOLONG), run.result(2));
+        // a long write is cut short, for the program to carry on from
+        assert_eq!((MAX_TRANSFER, 0), run.result(3));
+        assert_eq!(MAX_TRANSFER as usize, run.output.len());
+    }
+
+    #[test]
+    fn other_illegal_instructions_are_exceptions() {
+        // HALT without the NOP before it
+        let run = semihost(&[HALT, 0x4e7b, 0xf000], &[], b"");
+        assert!(matches!(run.termination, Termination::Unhandled(Exception::IllegalInstruction(HALT, CODE))));
+        // STOP #$2700
+        let run = semihost(&[0x4e72, 0x2700], &[], b"");
+        assert!(matches!(run.termination, Termination::Stopped));
+    }
+
+    #[test]
+    fn programs_load_from_elf_files() {
+        let code: Vec<u8> = program(&[(EXIT, CODE + 0x20)]).iter().flat_map(|word| word.to_be_bytes().to_vec()).collect();
+        let mut elf = vec![0; 0x54];
+        elf[..6].copy_from_slice(b"\x7fELF\x01\x02");
+        elf[18..20].copy_from_slice(&4u16.to_be_bytes());
+        elf[24..28].copy_from_slice(&CODE.to_be_bytes());
+        elf[28..32].copy_from_slice(&0x34u32.to_be_bytes());
+        elf[42..44].copy_from_slice(&0x20u16.to_be_bytes());
+        elf[44..46].copy_from_slice(&1u16.to_be_bytes());
+        // one loaded segment, of the code and then the status 7
+        for (offset, value) in [(0x34, 1), (0x38, 0x54), (0x3c, CODE), (0x40, CODE), (0x44, 0x24), (0x48, 0x24)].iter() {
+            elf[*offset..*offset + 4].copy_from_slice(&value.to_be_bytes());
+        }
+        elf.extend_from_slice(&code);
+        elf.resize(0x54 + 0x20, 0);
+        elf.extend_from_slice(&7u32.to_be_bytes());
+        let mut core = load(&elf).unwrap();
+        let mut semihost = Semihost::new(io::empty(), io::sink(), io::sink());
+        assert!(matches!(run(&mut core, &mut semihost), Termination::Exited(7)));
+    }
+}
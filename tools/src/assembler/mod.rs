@@ -202,6 +202,38 @@ pub fn encode_just_imm16(op: &OpcodeInstance, template: u16, pc: PC, mem: &mut d
     let pc = mem.write_word(pc, template);
     op.operands[0].add_extension_words(pc, mem)
 }
+pub fn encode_lpstop(op: &OpcodeInstance, template: u16, pc: PC, mem: &mut dyn Memory) -> PC {
+    let pc = mem.write_word(pc, template);
+    let pc = mem.write_word(pc, 0x01c0);
+    op.operands[0].add_extension_words(pc, mem)
+}
+// the TBL extension word: Dx, signed, rounding and size
+fn encode_tbl_extension(op: &OpcodeInstance) -> u16 {
+    let dx = encode_dx(&op.operands[1]) << 3;
+    let signed = if op.mnemonic.starts_with("TBLS") { 0x0800 } else { 0 };
+    let unrounded = if op.mnemonic.ends_with('N') { 0x0400 } else { 0 };
+    let size = match op.size {
+        Size::Byte => 0x0000,
+        Size::Word => 0x0040,
+        Size::Long => 0x0080,
+        Size::Unsized => panic!("unsized {}", op.mnemonic),
+    };
+    dx | signed | unrounded | size
+}
+pub fn encode_tbl_ea(op: &OpcodeInstance, template: u16, pc: PC, mem: &mut dyn Memory) -> PC {
+    let ea = encode_ea(&op.operands[0]);
+    let pc = mem.write_word(pc, template | ea);
+    let pc = mem.write_word(pc, encode_tbl_extension(op) | 0x0100);
+    op.operands[0].add_extension_words(pc, mem)
+}
+pub fn encode_tbl_dn(op: &OpcodeInstance, template: u16, pc: PC, mem: &mut dyn Memory) -> PC {
+    let (regm, regn) = match op.operands[0] {
+        Operand::DataRegisterPair(regm, regn) => (regm as u16, regn as u16),
+        _ => panic!("not a register pair: {:?}", op.operands[0])
+    };
+    let pc = mem.write_word(pc, template | regm);
+    mem.write_word(pc, encode_tbl_extension(op) | regn)
+}
 pub fn encode_quick_ea(op: &OpcodeInstance, template: u16, pc: PC, mem: &mut dyn Memory) -> PC {
     let quick = encode_quick(&op.operands[0]);
     let ea = encode_ea(&op.operands[1]);
@@ -459,6 +491,10 @@ pub fn is_ea_movem(op: &OpcodeInstance) -> bool {
         _ => false,
     }
 }
+pub fn is_drp_dn(op: &OpcodeInstance) -> bool {
+    if op.operands.len() != 2 { return false };
+    matches!((op.operands[0], op.operands[1]), (Operand::DataRegisterPair(_, _), Operand::DataRegisterDirect(_)))
+}
 pub fn is_ea_dn(op: &OpcodeInstance) -> bool {
     if op.operands.len() != 2 { return false };
     match op.operands[1] {
@@ -625,6 +661,8 @@ impl<'b> Assembler<'b> {
         unsizeds.insert("UNLK");
         unsizeds.insert("ILLEGAL");
         unsizeds.insert("STOP");
+        unsizeds.insert("LPSTOP");
+        unsizeds.insert("BGND");
         unsizeds.insert("RESET");
         unsizeds.insert("NOP");
 
@@ -699,6 +737,7 @@ impl<'b> Assembler<'b> {
                 Operand::Immediate(Size::Unsized, x) if op_inst.mnemonic == "BCLR" => Operand::Immediate(Size::Byte, x),
                 Operand::Immediate(Size::Unsized, x) if op_inst.mnemonic == "TRAP" => Operand::Immediate(Size::Byte, x),
                 Operand::Immediate(Size::Unsized, x) if op_inst.mnemonic == "STOP" => Operand::Immediate(Size::Word, x),
+                Operand::Immediate(Size::Unsized, x) if op_inst.mnemonic == "LPSTOP" => Operand::Immediate(Size::Word, x),
                 Operand::Immediate(Size::Unsized, x) => Operand::Immediate(clone.size, x),
                 Operand::Number(Size::Byte, x) => Operand::AbsoluteWord(x as u8 as u16),
                 Operand::Number(Size::Word, x) => Operand::AbsoluteWord(x as u16),
@@ -64,10 +64,11 @@ impl_rdp! {
         operands = { operand ~ (comma ~ operand)* }
         comma = {[","]}
         symbol = _{ name }
-        operand = { reglist | drd | ard | api | apd | ari | pci | pcd | aix | adi | imm | usp | status_reg | condition_reg | abs }
+        operand = { reglist | drp | drd | ard | api | apd | ari | pci | pcd | aix | adi | imm | usp | status_reg | condition_reg | abs }
 
         // addressing modes
         drd = @{ [i"D"] ~ ['0'..'7'] ~ qualifier? ~ !letter}
+        drp = @{ [i"D"] ~ ['0'..'7'] ~ [":"] ~ [i"D"] ~ ['0'..'7'] ~ !letter}
         ard = @{ address_register | stack_pointer }
         address_register = { [i"A"] ~ ['0'..'7'] ~ qualifier? ~ !letter }
         stack_pointer = { [i"SP"] ~ qualifier? ~ !letter }
@@ -186,6 +187,9 @@ impl_rdp! {
             (_: operand, &reg: drd) => {
                 Operand::DataRegisterDirect(reg[1..].parse().unwrap())
             },
+            (_: operand, &regs: drp) => {
+                Operand::DataRegisterPair(regs[1..2].parse().unwrap(), regs[4..5].parse().unwrap())
+            },
             (_: operand, _: ard, address_regno: process_address_register_number()) => {
                 Operand::AddressRegisterDirect(address_regno)
             },
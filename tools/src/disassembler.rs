@@ -211,6 +211,20 @@ pub fn decode_just_imm16(_opcode: u16, _size: Size, pc: PC, mem: &dyn Memory) ->
     let (words, imm) = decode_imm(Size::Word, pc, mem);
     (words, vec![imm])
 }
+pub fn decode_lpstop(_opcode: u16, _size: Size, pc: PC, mem: &dyn Memory) -> (Words, Vec<Operand>) {
+    // skip the $01c0 extension word
+    let (words, imm) = decode_imm(Size::Word, pc + 2, mem);
+    (Words(1) + words, vec![imm])
+}
+pub fn decode_tbl_ea(opcode: u16, size: Size, pc: PC, mem: &dyn Memory) -> (Words, Vec<Operand>) {
+    let extension = mem.read_word(pc + 2);
+    let (words, ea) = decode_ea(opcode, size, pc + 2, mem);
+    (Words(1) + words, vec![ea, Operand::DataRegisterDirect(((extension >> 12) & 7) as u8)])
+}
+pub fn decode_tbl_dn(opcode: u16, _size: Size, pc: PC, mem: &dyn Memory) -> (Words, Vec<Operand>) {
+    let extension = mem.read_word(pc + 2);
+    (Words(1), vec![Operand::DataRegisterPair((opcode & 7) as u8, (extension & 7) as u8), Operand::DataRegisterDirect(((extension >> 12) & 7) as u8)])
+}
 pub fn decode_quick_ea(opcode: u16, size: Size, pc: PC, mem: &dyn Memory) -> (Words, Vec<Operand>) {
     let quick = decode_quick(opcode);
     let (words, ea) = decode_ea(opcode, size, pc, mem);
@@ -361,7 +375,7 @@ impl<'b> Disassembler<'b> {
         for op in &self.optable {
             // check for mask/opcode inconsistency
             assert!(op.mask & op.matching == op.matching, "mask/matching mismatch {:04x} & {:04x} for {}{}", op.mask, op.matching, op.mnemonic, op.size);
-            if ((opcode as u32) & op.mask) == op.matching && (op.validator)(opcode) {
+            if ((opcode as u32) & op.mask) == op.matching && (op.validator)(opcode) && op.matches_extension(pc, mem) {
                 let decoder = op.decoder;
                 let (extension_words, operands) = decoder(opcode, op.size, pc, mem);
                 return Ok((pc + INSTRUCTION_SIZE + extension_words, OpcodeInstance {mnemonic: op.mnemonic, size: op.size, operands: operands }));
@@ -397,6 +411,28 @@ mod tests {
         assert_eq!(pc, PC(0x1002))
     }
     #[test]
+    fn decodes_cpu32_table_lookups() {
+        // TBLS.W (A0),D3 then TBLUN.B D1:D2,D4
+        let mem = MemoryVec::new16(PC(0x1000), vec![0xf810, 0x3940, 0xf801, 0x4402]);
+        let (pc, inst) = disassemble(PC(0x1000), &mem).unwrap();
+        assert_eq!("TBLS.W\t(A0),D3", format!("{}", inst));
+        assert_eq!(pc, PC(0x1004));
+
+        let (pc, inst) = disassemble(pc, &mem).unwrap();
+        assert_eq!(Operand::DataRegisterPair(1, 2), inst.operands[0]);
+        assert_eq!("TBLUN.B\tD1:D2,D4", format!("{}", inst));
+        assert_eq!(pc, PC(0x1008));
+    }
+    #[test]
+    fn decodes_lpstop_but_not_a_bad_extension_word() {
+        let mem = MemoryVec::new16(PC(0x1000), vec![0xf800, 0x01c0, 0x2700, 0xf800, 0x01c1]);
+        let (pc, inst) = disassemble(PC(0x1000), &mem).unwrap();
+        assert_eq!("LPSTOP\t#$2700", format!("{}", inst));
+        assert_eq!(pc, PC(0x1006));
+
+        assert!(disassemble(pc, &mem).is_err());
+    }
+    #[test]
     fn decodes_short_next_jump() {
         // jump forwards (00 == offset in next extension word) + two bytes (to skip the extension word)
         // PRM says "A branch to the immediately following instruction automatically uses the 16-bit
@@ -0,0 +1,122 @@
+// Reads the loadable segments of a 32-bit big-endian m68k ELF executable,
+// as m68k-elf-gcc links them, into memory segments at their physical
+// (load) addresses, with the part of each that isn't in the file (its
+// .bss) zeroed. Sections, symbols and relocations are not looked at.
+use memory::MemoryVec;
+use std::io;
+use PC;
+
+const EM_68K: u16 = 4;
+const PT_LOAD: u32 = 1;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn word(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from(b[0]) << 8 | u16::from(b[1])).ok_or_else(|| invalid("truncated"))
+}
+
+fn long(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    Ok(u32::from(word(bytes, offset)?) << 16 | u32::from(word(bytes, offset + 2)?))
+}
+
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x7fELF")
+}
+
+// the segments, and the entry point, of a program that has to fit below
+// top; the headers aren't trusted with more memory than that
+pub fn read_elf(bytes: &[u8], top: u32) -> io::Result<(Vec<MemoryVec>, u32)> {
+    if !is_elf(bytes) {
+        return Err(invalid("not an ELF file"));
+    }
+    if bytes.get(4..6) != Some(&[1, 2]) || word(bytes, 18)? != EM_68K {
+        return Err(invalid("not a 32-bit big-endian m68k ELF file"));
+    }
+    let entry = long(bytes, 24)?;
+    let phoff = long(bytes, 28)? as usize;
+    let phentsize = word(bytes, 42)? as usize;
+    let phnum = word(bytes, 44)? as usize;
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let header = phoff + i * phentsize;
+        if long(bytes, header)? != PT_LOAD {
+            continue;
+        }
+        let offset = long(bytes, header + 4)? as usize;
+        let paddr = long(bytes, header + 12)?;
+        let filesz = long(bytes, header + 16)? as usize;
+        let memsz = long(bytes, header + 20)? as usize;
+        if memsz == 0 {
+            continue;
+        }
+        // checked before the Vec for the segment is allocated
+        if u64::from(paddr) + memsz.max(filesz) as u64 > u64::from(top) {
+            return Err(invalid("a segment past the end of memory"));
+        }
+        let mut data = bytes.get(offset..offset + filesz).ok_or_else(|| invalid("truncated"))?.to_vec();
+        data.resize(memsz.max(filesz), 0);
+        segments.push(MemoryVec::new8(PC(paddr), data));
+    }
+    Ok((segments, entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_elf, read_elf};
+    use memory::Memory;
+
+    fn put(bytes: &mut [u8], offset: usize, value: u32, len: usize) {
+        for i in 0..len {
+            bytes[offset + i] = (value >> (8 * (len - 1 - i))) as u8;
+        }
+    }
+
+    // an executable with a text segment and a data segment with .bss,
+    // which is loaded elsewhere than it runs
+    fn executable() -> Vec<u8> {
+        let mut elf = vec![0; 0x80];
+        elf[..6].copy_from_slice(b"\x7fELF\x01\x02");
+        put(&mut elf, 16, 2, 2); // ET_EXEC
+        put(&mut elf, 18, 4, 2);
+        put(&mut elf, 24, 0x1000, 4);
+        put(&mut elf, 28, 0x34, 4);
+        put(&mut elf, 42, 0x20, 2);
+        put(&mut elf, 44, 2, 2);
+        for (i, &(offset, vaddr, paddr, filesz, memsz)) in [(0x74, 0x1000, 0x1000, 4, 4), (0x78, 0x8000, 0x1004, 4, 8)].iter().enumerate() {
+            let header = 0x34 + i * 0x20;
+            put(&mut elf, header, 1, 4);
+            put(&mut elf, header + 4, offset, 4);
+            put(&mut elf, header + 8, vaddr, 4);
+            put(&mut elf, header + 12, paddr, 4);
+            put(&mut elf, header + 16, filesz, 4);
+            put(&mut elf, header + 20, memsz, 4);
+        }
+        elf[0x74..0x7c].copy_from_slice(&[0x4e, 0x71, 0x4e, 0x75, 1, 2, 3, 4]);
+        elf
+    }
+
+    #[test]
+    fn loads_segments_at_their_physical_addresses() {
+        let elf = executable();
+        assert!(is_elf(&elf));
+        let (segments, entry) = read_elf(&elf, 0x100c).unwrap();
+        assert_eq!(0x1000, entry);
+        assert_eq!((0x1000, &[0x4e, 0x71, 0x4e, 0x75][..]), (segments[0].offset(), segments[0].data()));
+        assert_eq!((0x1004, &[1, 2, 3, 4, 0, 0, 0, 0][..]), (segments[1].offset(), segments[1].data()));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut elf = executable();
+        elf[5] = 1; // little-endian
+        assert!(read_elf(&elf, u32::MAX).is_err());
+        assert!(read_elf(b"S00600004844521B", u32::MAX).is_err());
+        assert!(read_elf(&executable()[..0x60], u32::MAX).is_err());
+        let mut elf = executable();
+        put(&mut elf, 0x54 + 20, 0xffff_fffc, 4);
+        assert!(read_elf(&elf, u32::MAX).is_err());
+        assert!(read_elf(&executable(), 0x100b).is_err());
+    }
+}
@@ -15,6 +15,7 @@ pub mod memory;
 pub mod assembler;
 pub mod disassembler;
 pub mod srecords;
+pub mod elf;
 
 use memory::Memory;
 
@@ -135,6 +136,16 @@ pub struct OpcodeInfo<'a> {
     synonym: Option<&'a str>,
     encoder: InstructionEncoder,
     selector: InstructionSelector,
+    extension: Option<(u16, u16)>, // mask and matching of the first extension word
+}
+impl<'a> OpcodeInfo<'a> {
+    // the CPU32 TBL and LPSTOP opcodes are only told apart by their extension word
+    fn matches_extension(&self, pc: PC, mem: &dyn Memory) -> bool {
+        match self.extension {
+            Some((mask, matching)) => mem.read_word(pc + 2) & mask == matching,
+            None => true,
+        }
+    }
 }
 #[derive(Clone, Debug, PartialEq)]
 pub struct OpcodeInstance<'a> {
@@ -164,9 +175,10 @@ impl<'a> fmt::Display for OpcodeInstance<'a> {
     }
 }
 macro_rules! instruction {
-    ($mask:expr, $matching:expr, $size:expr, $mnemonic:expr, $validator:ident, $decoder:ident) =>                                  (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: None, validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::nop_encoder, selector: assembler::nop_selector});
-    ($mask:expr, $matching:expr, $size:expr, $mnemonic:expr, $validator:ident, $decoder:ident, $selector:ident, $encoder:ident) => (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: None, validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::$encoder, selector: assembler::$selector});
-    ($mask:expr, $matching:expr, $size:expr, $mnemonic:expr, $synonym:expr, $validator:ident, $decoder:ident, $selector:ident, $encoder:ident) => (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: Some($synonym), validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::$encoder, selector: assembler::$selector});
+    ($mask:expr, $matching:expr, $size:expr, $mnemonic:expr, $validator:ident, $decoder:ident) =>                                  (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: None, validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::nop_encoder, selector: assembler::nop_selector, extension: None});
+    ($mask:expr, $matching:expr, $size:expr, $mnemonic:expr, $validator:ident, $decoder:ident, $selector:ident, $encoder:ident) => (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: None, validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::$encoder, selector: assembler::$selector, extension: None});
+    ($mask:expr, $matching:expr, $size:expr, $mnemonic:expr, $synonym:expr, $validator:ident, $decoder:ident, $selector:ident, $encoder:ident) => (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: Some($synonym), validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::$encoder, selector: assembler::$selector, extension: None});
+    ($mask:expr, $matching:expr, $ext_mask:expr, $ext_matching:expr, $size:expr, $mnemonic:expr, $validator:ident, $decoder:ident, $selector:ident, $encoder:ident) => (OpcodeInfo { mask: $mask, matching: $matching, size: $size, mnemonic: $mnemonic, synonym: None, validator: disassembler::$validator, decoder: disassembler::$decoder, encoder: assembler::$encoder, selector: assembler::$selector, extension: Some(($ext_mask, $ext_matching))});
 }
 fn generate<'a>() -> Vec<OpcodeInfo<'a>> {
     vec![
@@ -493,6 +505,34 @@ fn generate<'a>() -> Vec<OpcodeInfo<'a>> {
         instruction!(MASK_EXACT, OP_RESET, Size::Unsized, "RESET", always, decode_none, is_none, encode_none),
         instruction!(MASK_EXACT, OP_ILLEGAL, Size::Unsized, "ILLEGAL", always, decode_none, is_none, encode_none),
         instruction!(MASK_EXACT, OP_NOP, Size::Unsized, "NOP", always, decode_none, is_none, encode_none),
+
+        // CPU32
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0800, Size::Byte, "TBLS", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0840, Size::Word, "TBLS", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0880, Size::Long, "TBLS", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0c00, Size::Byte, "TBLSN", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0c40, Size::Word, "TBLSN", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0c80, Size::Long, "TBLSN", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0000, Size::Byte, "TBLU", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0040, Size::Word, "TBLU", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0080, Size::Long, "TBLU", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0400, Size::Byte, "TBLUN", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0440, Size::Word, "TBLUN", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_Y, OP_TBL, 0x8ff8, 0x0480, Size::Long, "TBLUN", always, decode_tbl_dn, is_drp_dn, encode_tbl_dn),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0900, Size::Byte, "TBLS", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0940, Size::Word, "TBLS", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0980, Size::Long, "TBLS", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0d00, Size::Byte, "TBLSN", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0d40, Size::Word, "TBLSN", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0d80, Size::Long, "TBLSN", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0100, Size::Byte, "TBLU", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0140, Size::Word, "TBLU", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0180, Size::Long, "TBLU", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0500, Size::Byte, "TBLUN", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0540, Size::Word, "TBLUN", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_OUT_EA, OP_TBL, 0x8fff, 0x0580, Size::Long, "TBLUN", ea_control, decode_tbl_ea, is_ea_dn, encode_tbl_ea),
+        instruction!(MASK_EXACT, OP_LPSTOP, 0xffff, 0x01c0, Size::Unsized, "LPSTOP", always, decode_lpstop, is_imm16, encode_lpstop),
+        instruction!(MASK_EXACT, OP_BGND, Size::Unsized, "BGND", always, decode_none, is_none, encode_none),
     ]
 }
 
@@ -532,6 +572,15 @@ mod tests {
         assert_eq!(asm, format!(" {}", inst));
     }
 
+    #[test]
+    fn roundtrips_cpu32_instructions() {
+        for asm in &[" TBLS.L\t8(A2),D7", " TBLSN.W\tD0:D5,D1", " TBLU.B\t$1234,D0", " LPSTOP\t#$2000", " BGND"] {
+            let mem = assemble_one(asm);
+            let (_, inst) = disassemble_first(&mem);
+            assert_eq!(*asm, format!(" {}", inst));
+        }
+    }
+
     #[test]
     fn synonyms_bcs_blo_byte() {
         synonymous("BCS.B", "BLO.B", "$10")
@@ -22,6 +22,7 @@ pub enum Operand {
     Number(Size, i32),
     Registers(u16, bool), // reglist, reversed
     UserStackPointer,
+    DataRegisterPair(u8, u8), // Dym:Dyn of a register TBL (CPU32)
 }
 
 fn encode_extension_word(xreg_ndx_size: u8, displacement: i8) -> u16 {
@@ -33,6 +34,7 @@ impl Operand {
     pub fn add_extension_words(&self, pc: PC, mem: &mut dyn Memory) -> PC {
         match *self {
             Operand::DataRegisterDirect(_) => pc,
+            Operand::DataRegisterPair(_, _) => pc,
             Operand::AddressRegisterDirect(_) => pc,
             Operand::AddressRegisterIndirect(_) => pc,
             Operand::AddressRegisterIndirectWithPredecrement(_) => pc,
@@ -149,6 +151,7 @@ impl fmt::Display for Operand {
             Operand::Registers(reglist, false) => write_registers(f, reglist),
             Operand::Registers(reglist, true) => write_registers(f, bit_reverse(reglist)),
             Operand::UserStackPointer => write!(f, "USP"),
+            Operand::DataRegisterPair(regm, regn) => write!(f, "D{}:D{}", regm, regn),
          }
     }
 }
@@ -58,8 +58,9 @@ impl<'a> fmt::Display for SRecord<'a> {
 }
 
 use std::io;
-use std::io::Write;
-use memory::Memory;
+use std::io::{BufRead, Write};
+use memory::{Memory, MemoryVec};
+use PC;
 
 pub fn write_s68(writer: &mut dyn Write, segments: Vec<&dyn Memory>, entrypoint: u32) -> io::Result<usize> {
     let mut lines = 1;
@@ -75,9 +76,69 @@ pub fn write_s68(writer: &mut dyn Write, segments: Vec<&dyn Memory>, entrypoint:
     Ok(lines + 1)
 }
 
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+// Reads S-records, as assemblers like EASy68K write them, into segments of
+// contiguous data, and the entrypoint of the termination record, if any.
+// Header and count records are skipped, and checksums are checked.
+pub fn read_s68(reader: &mut dyn BufRead) -> io::Result<(Vec<MemoryVec>, Option<u32>)> {
+    let mut segments: Vec<MemoryVec> = Vec::new();
+    let mut entrypoint = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let kind = line.as_bytes()[0];
+        if kind != b'S' && kind != b's' || line.len() < 4 || line.len() % 2 != 0 {
+            return Err(invalid(i + 1, "not an S-record"));
+        }
+        let bytes = (2..line.len()).step_by(2)
+            .map(|pos| u8::from_str_radix(&line[pos..pos + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid(i + 1, "not hexadecimal"))?;
+        if bytes[0] as usize != bytes.len() - 1 {
+            return Err(invalid(i + 1, "wrong length"));
+        }
+        if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0xff {
+            return Err(invalid(i + 1, "wrong checksum"));
+        }
+        let address_len = match &line[1..2] {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            _ => return Err(invalid(i + 1, "unknown record type")),
+        };
+        if bytes.len() < address_len + 2 {
+            return Err(invalid(i + 1, "too short"));
+        }
+        let address = bytes[1..address_len + 1].iter().fold(0, |address, &b| address << 8 | u32::from(b));
+        let data = &bytes[address_len + 1..bytes.len() - 1];
+        match &line[1..2] {
+            "1" | "2" | "3" => {
+                let follows = segments.last().is_some_and(|mem| mem.offset() + mem.data().len() as u32 == address);
+                if follows {
+                    let mem = segments.last_mut().unwrap();
+                    let end = PC(mem.offset() + mem.data().len() as u32);
+                    mem.write_vec(end, data.to_vec());
+                } else {
+                    segments.push(MemoryVec::new8(PC(address), data.to_vec()));
+                }
+            },
+            "7" | "8" | "9" => entrypoint = Some(address),
+            _ => (),
+        }
+    }
+    Ok((segments, entrypoint))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{write_s68, Checksum, SRecord};
+    use super::{read_s68, write_s68, Checksum, SRecord};
+    use memory::Memory;
     use std::io::LineWriter;
     use memory::MemoryVec;
     use PC;
@@ -143,4 +204,34 @@ mod tests {
 
         assert_eq!(example, generated);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reads_what_was_written() {
+        let mut lw = LineWriter::new(vec![]);
+        let code = MemoryVec::new8(PC(0x1000), (0u8 .. 0x50u8).collect());
+        let data = MemoryVec::new8(PC(0x2001), vec![1, 2, 3]);
+        write_s68(&mut lw, vec![&code, &data], 0x1000).unwrap();
+        let written = lw.into_inner().unwrap();
+        let (segments, entrypoint) = read_s68(&mut &written[..]).unwrap();
+        assert_eq!(Some(0x1000), entrypoint);
+        assert_eq!(2, segments.len());
+        assert_eq!((0x1000, code.data()), (segments[0].offset(), segments[0].data()));
+        assert_eq!((0x2001, data.data()), (segments[1].offset(), segments[1].data()));
+    }
+
+    #[test]
+    fn reads_16_and_32_bit_addresses() {
+        let records = "S00600004844521B\nS1050000FFFFFC\nS3070001000000FFF8\nS5030002FA\nS70500010000F9\n";
+        let (segments, entrypoint) = read_s68(&mut records.as_bytes()).unwrap();
+        assert_eq!(Some(0x10000), entrypoint);
+        assert_eq!((0, &[0xff, 0xff][..]), (segments[0].offset(), segments[0].data()));
+        assert_eq!((0x10000, &[0, 0xff][..]), (segments[1].offset(), segments[1].data()));
+    }
+
+    #[test]
+    fn rejects_bad_records() {
+        for records in &["S1050000FFFFFD", "S1060000FFFFFC", "X1050000FFFFFC", "S1050000FFFGFC", "S4050000FFFFFC"] {
+            assert!(read_s68(&mut records.as_bytes()).is_err(), "{}", records);
+        }
+    }
+}